@@ -0,0 +1,54 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::{block_on, Timer};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/async_pcall.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment, injecting async-aware pcall and xpcall
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+    fns.inject_compat(&lua)?;
+
+    lua.globals().set(
+        "sleep",
+        lua.create_async_function(|_, secs: f64| async move {
+            Timer::after(Duration::from_secs_f64(secs)).await;
+            Ok(secs)
+        })?,
+    )?;
+    lua.globals().set(
+        "fail",
+        lua.create_async_function(|_, message: String| async move {
+            Timer::after(Duration::from_millis(5)).await;
+            Err::<(), _>(LuaError::runtime(message))
+        })?,
+    )?;
+
+    // Load the main script into the scheduler, and run it until completion
+    let id = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    block_on(sched.run());
+
+    // The main script should have completed without errors
+    sched.get_thread_result(id).unwrap()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_async_pcall() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,63 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::{block_on, Timer};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, LuaSchedulerExt, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/awaiting_resume.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    // Keep the original coroutine.resume around, which bypasses the scheduler entirely
+    let coroutine = lua.globals().get::<_, LuaTable>("coroutine")?;
+    lua.globals()
+        .set("rawResume", coroutine.get::<_, LuaFunction>("resume")?)?;
+
+    fns.inject_compat(&lua)?;
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set(
+        "sleep",
+        lua.create_async_function(|_, duration: f64| async move {
+            Timer::after(Duration::from_secs_f64(duration)).await;
+            Ok(())
+        })?,
+    )?;
+    lua.globals().set(
+        "nativeSleep",
+        lua.create_native_async_function(|_, duration: f64| async move {
+            Timer::after(Duration::from_secs_f64(duration)).await;
+            Ok(())
+        })?,
+    )?;
+
+    // Load the main script into the scheduler, and keep track of the thread we spawn
+    let main = lua.load(MAIN_SCRIPT);
+    let id = sched.push_thread_front(main, ())?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // The main script should have completed without errors
+    sched.get_thread_result(id).unwrap()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_awaiting_resume() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,112 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{cell::Cell, rc::Rc, time::Duration};
+
+use async_io::{block_on, Timer};
+use futures_lite::future;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, QueueFull, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/bounded_queues.luau");
+
+const CAPACITY: usize = 8;
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment, with bounded queues
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+    sched.set_queue_capacity(Some(CAPACITY));
+
+    let counts = lua.create_table()?;
+    lua.globals().set("mode", "defer")?;
+    lua.globals().set("counts", counts.clone())?;
+    lua.globals().set("defer", fns.defer)?;
+
+    // Run the main script, which tries to defer far more threads than fit in the queue
+    let main = lua.load(MAIN_SCRIPT);
+    sched.push_thread_front(main, ())?;
+    block_on(sched.run());
+
+    assert_eq!(counts.get::<_, usize>("accepted")?, CAPACITY);
+    assert_eq!(counts.get::<_, usize>("rejected")?, 100 - CAPACITY);
+
+    // Pushing from Rust to a full queue errors the same way, without tracking the rejected thread
+    let resumed = Rc::new(Cell::new(0));
+    let resumed_inner = Rc::clone(&resumed);
+    let noop = lua.create_function(move |_, ()| {
+        resumed_inner.set(resumed_inner.get() + 1);
+        Ok(())
+    })?;
+    let tracked = sched.tracking_stats().tracked;
+    for _ in 0..CAPACITY {
+        sched.push_thread_back(noop.clone(), ())?;
+    }
+    let err = sched.push_thread_back(noop.clone(), ()).unwrap_err();
+    assert_eq!(QueueFull::from_error(&err).unwrap().capacity(), CAPACITY);
+    assert_eq!(sched.tracking_stats().tracked, tracked + CAPACITY);
+
+    // Pushing with lazily created arguments checks the capacity the same way
+    let err = sched
+        .push_thread_back_with(noop.clone(), |lua| ().into_lua_multi(lua))
+        .unwrap_err();
+    assert!(QueueFull::from_error(&err).is_some());
+    assert_eq!(sched.tracking_stats().tracked, tracked + CAPACITY);
+
+    // Pushing asynchronously instead waits for the running scheduler to make space,
+    // keeping the scheduler alive until all pushes are done so that none get stuck
+    let keep_alive = sched.keep_alive();
+    let pushes = async {
+        let _keep_alive = keep_alive;
+        for _ in 0..CAPACITY * 4 {
+            sched.push_thread_back_async(noop.clone(), ()).await?;
+        }
+        Ok::<_, LuaError>(())
+    };
+    let ((), push_result) = block_on(future::zip(sched.run(), pushes));
+    push_result?;
+    assert_eq!(resumed.get(), CAPACITY * 5);
+
+    // Spawning or resuming threads with a full queue should reject them before they start running,
+    // and threads that were already running should always be re-queued and run until completion
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+    sched.set_queue_capacity(Some(1));
+
+    let counts = lua.create_table()?;
+    lua.globals().set("mode", "spawn")?;
+    lua.globals().set("counts", counts.clone())?;
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("defer", fns.defer)?;
+    lua.globals().set("resume", fns.resume)?;
+    lua.globals().set(
+        "sleep",
+        lua.create_async_function(|_, ()| async move {
+            Timer::after(Duration::from_millis(5)).await;
+            Ok(())
+        })?,
+    )?;
+
+    let id = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    block_on(sched.run());
+    sched.get_thread_result(id).unwrap()?;
+
+    assert_eq!(counts.get::<_, usize>("started")?, 3);
+    assert_eq!(counts.get::<_, usize>("done")?, 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_bounded_queues() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,51 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::{Duration, Instant};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/cancel_wait.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("delay", fns.delay)?;
+    lua.globals().set("wait", fns.wait)?;
+    lua.globals().set("cancel", fns.cancel)?;
+
+    // Load the main script into the scheduler
+    let id = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+
+    // Run until completion, which should not wait for the cancelled sleep to finish
+    let start = Instant::now();
+    block_on(sched.run());
+    let elapsed = start.elapsed();
+    println!("Scheduler ran for {elapsed:?}");
+    assert!(elapsed < Duration::from_secs(5));
+
+    // The main script should have waited for at least as long as it asked to
+    let waited = f64::from_lua_multi(sched.get_thread_result(id).unwrap()?, &lua)?;
+    println!("Main script waited for {waited:.3}s");
+    assert!(waited >= 0.05);
+
+    Ok(())
+}
+
+#[test]
+fn test_cancel_wait() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,69 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{thread, time::Duration};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, RuntimeChannel, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/channels.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+
+    // Create a channel, and pass the receiving end of it to Lua
+    let (sender, channel) = RuntimeChannel::<u32>::create();
+    lua.globals().set("channel", channel)?;
+
+    // Send values from other threads, with some delay in between, and then drop
+    // all of the senders, which lets the receiving Lua thread know we are done
+    let workers = (0..3)
+        .map(|worker| {
+            let sender = sender.clone();
+            thread::spawn(move || {
+                for index in 1..=3 {
+                    thread::sleep(Duration::from_millis(10));
+                    sender.send(worker * 10 + index)?;
+                }
+                Ok::<_, LuaError>(())
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(sender);
+
+    // Load the main script into the scheduler, and keep track of the thread we spawn
+    let main = lua.load(MAIN_SCRIPT);
+    let id = sched.push_thread_front(main, ())?;
+
+    // Run until completion
+    block_on(sched.run());
+    for worker in workers {
+        worker.join().unwrap()?;
+    }
+
+    // The main thread should have received every value exactly once
+    let res = sched.get_thread_result(id).unwrap().unwrap();
+    let mut received = Vec::<u32>::from_lua_multi(res, &lua)?;
+    received.sort_unstable();
+    assert_eq!(received, vec![1, 2, 3, 11, 12, 13, 21, 22, 23]);
+
+    Ok(())
+}
+
+#[test]
+fn test_channels() -> LuaResult<()> {
+    main()
+}
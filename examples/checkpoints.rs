@@ -0,0 +1,59 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, RestartOptions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/checkpoints.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("checkpoint", fns.checkpoint)?;
+    sched.remove_error_callback();
+
+    // Load the worker and run it as a supervised service,
+    // it will crash halfway through and resume from its checkpoint
+    let worker: LuaFunction = lua.load(MAIN_SCRIPT).eval()?;
+    sched.supervise("worker", worker, RestartOptions::default())?;
+
+    // Also run a regular thread that saves a checkpoint
+    let regular = lua.load("checkpoint({ step = 'done' })");
+    let regular_id = sched.push_thread_back(regular, ())?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // Verify that the worker resumed from its checkpoint instead of starting over
+    let processed: Vec<u32> = lua.globals().get("processed")?;
+    assert_eq!(processed, vec![1, 2, 3, 4, 5, 6]);
+
+    let worker_checkpoint = sched
+        .get_service_checkpoint("worker")
+        .expect("worker should have saved a checkpoint");
+    assert_eq!(worker_checkpoint.deserialize::<u32>()?, 6);
+
+    let regular_checkpoint = sched
+        .get_thread_checkpoint(regular_id)
+        .expect("regular thread should have saved a checkpoint");
+    let step: String = LuaTable::from_lua(regular_checkpoint.to_lua(&lua)?, &lua)?.get("step")?;
+    assert_eq!(step, "done");
+
+    Ok(())
+}
+
+#[test]
+fn test_checkpoints() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,68 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{ChunkOptions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/chunk_options.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    sched.remove_error_callback();
+
+    // Compile production scripts with full optimizations and no debug info by default
+    let production = ChunkOptions {
+        optimization_level: 2,
+        debug_level: 0,
+        coverage_level: 0,
+        yield_budget: None,
+    };
+    sched.set_chunk_options(production);
+
+    // Push the same script as a production script, as REPL input
+    // with full debug info, and as precompiled production bytecode
+    let repl = ChunkOptions {
+        optimization_level: 0,
+        debug_level: 2,
+        coverage_level: 0,
+        yield_budget: None,
+    };
+    let prod_id = sched.push_chunk("=main", MAIN_SCRIPT, None)?;
+    let repl_id = sched.push_chunk("=repl", MAIN_SCRIPT, Some(repl))?;
+    let bytecode_id = sched.push_bytecode("=precompiled", production.compile(MAIN_SCRIPT))?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // Only the REPL input should have line info in its error message
+    let error_message = |id| {
+        sched
+            .get_thread_result(id)
+            .unwrap()
+            .unwrap_err()
+            .to_string()
+    };
+    let prod_err = error_message(prod_id);
+    let repl_err = error_message(repl_id);
+    let bytecode_err = error_message(bytecode_id);
+    assert!(repl_err.contains("repl:5: something went wrong"));
+    assert!(prod_err.contains("something went wrong") && !prod_err.contains("main:5:"));
+    assert!(bytecode_err.contains("something went wrong") && !bytecode_err.contains(":5:"));
+
+    Ok(())
+}
+
+#[test]
+fn test_chunk_options() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,70 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler, ThreadError, ThreadErrored, ThreadId};
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+    lua.globals().set("error", fns.error)?;
+
+    // Create one thread that completes, and one that errors, before pushing them - note that
+    // errors raised using the scheduler error function keep their value once the thread is
+    // resumed from outside of the scheduler, which lets the scheduler recover the error
+    let completed = lua.create_thread(lua.load("return 'done'").into_function()?)?;
+    completed.resume::<_, String>(())?;
+    let errored = lua.create_thread(lua.load("error({ message = 'oops' })").into_function()?)?;
+    assert!(errored.resume::<_, ()>(()).is_err());
+
+    // Pushing completed threads should fail instantly, without queueing anything
+    let res = sched.push_thread_front(&completed, ());
+    assert!(matches!(res, Err(LuaError::CoroutineInactive)));
+    let err = sched.push_thread_back(&errored, ()).unwrap_err();
+    let errored_err = ThreadErrored::from_error(&err).expect("errored thread should be rejected");
+    let value = ThreadError::from_error(errored_err.error())
+        .expect("errored thread should keep its error value")
+        .value(&lua)?;
+    assert_eq!(
+        value.as_table().unwrap().get::<_, String>("message")?,
+        "oops"
+    );
+
+    // Threads that errored while resumed from Lua keep their error, whatever it was raised with
+    let resumed = lua
+        .load("local co = coroutine.create(function() error('boom') end) coroutine.resume(co) return co")
+        .eval::<LuaThread>()?;
+    let err = sched.push_thread_back(&resumed, ()).unwrap_err();
+    let resumed_err = ThreadErrored::from_error(&err).expect("errored thread should be rejected");
+    assert!(resumed_err.error().to_string().contains("boom"));
+
+    // Rejected threads should never have been recorded as pushed
+    assert_eq!(sched.describe_thread(ThreadId::from(&completed))?, None);
+    assert_eq!(sched.describe_thread(ThreadId::from(&errored))?, None);
+
+    // Both threads should still have an immediate result stored for them
+    let completed_res = sched
+        .get_thread_result(ThreadId::from(&completed))
+        .expect("completed thread should have a result")?;
+    assert!(completed_res.is_empty());
+    let errored_res = sched
+        .get_thread_result(ThreadId::from(&errored))
+        .expect("errored thread should have a result");
+    let errored_res = errored_res.unwrap_err();
+    assert!(ThreadError::from_error(&errored_res).is_some());
+
+    Ok(())
+}
+
+#[test]
+fn test_completed_threads() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,87 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use async_io::{block_on, Timer};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::Scheduler;
+
+const MAIN_SCRIPT: &str = include_str!("./lua/completion_callbacks.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    sched.set_error_callback(|_| {});
+
+    lua.globals().set(
+        "sleep",
+        lua.create_async_function(|_, secs: f64| async move {
+            Timer::after(Duration::from_secs_f64(secs)).await;
+            Ok(())
+        })?,
+    )?;
+
+    let worker: LuaFunction = lua.load(MAIN_SCRIPT).eval()?;
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let record = |prefix: &'static str| {
+        let log = Rc::clone(&log);
+        move |_: &Lua, result: &LuaResult<LuaMultiValue>| {
+            let entry = match result {
+                Ok(values) => format!("{prefix}: {:?}", values.get(0).and_then(LuaValue::as_str)),
+                Err(_) => format!("{prefix}: error"),
+            };
+            log.borrow_mut().push(entry);
+        }
+    };
+
+    // Multiple callbacks may be added for a single thread
+    let ok_id = sched.push_thread_back(lua.create_thread(worker.clone())?, ("ok", false))?;
+    sched.on_thread_complete(ok_id, record("first"));
+    sched.on_thread_complete(ok_id, record("second"));
+
+    // Errors should also be passed to callbacks
+    let err_id = sched.push_thread_back(lua.create_thread(worker.clone())?, ("err", true))?;
+    sched.on_thread_complete(err_id, record("failed"));
+
+    // Cancelled threads should call their callbacks with an error, without ever running
+    let cancelled = lua.create_thread(worker)?;
+    sched.set_thread_tag(&cancelled, "cancelled")?;
+    let cancelled_id = sched.push_thread_back(cancelled, ("cancelled", false))?;
+    sched.on_thread_complete(cancelled_id, record("cancelled"));
+    sched.cancel_by_tag("cancelled")?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // Callbacks added after a thread completed should be called immediately
+    sched.on_thread_complete(ok_id, record("late"));
+
+    let log = log.borrow();
+    println!("Callbacks: {log:?}");
+    assert_eq!(
+        *log,
+        [
+            "cancelled: error",
+            "first: Some(\"ok done\")",
+            "second: Some(\"ok done\")",
+            "failed: error",
+            "late: Some(\"ok done\")",
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_completion_callbacks() -> LuaResult<()> {
+    main()
+}
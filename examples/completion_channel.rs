@@ -0,0 +1,63 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{sync::mpsc, thread, time::Duration};
+
+use async_io::{block_on, Timer};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Scheduler, ThreadCompletion};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/completion_channel.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    sched.remove_error_callback();
+
+    lua.globals().set(
+        "sleep",
+        lua.create_async_function(|_, secs: f64| async move {
+            Timer::after(Duration::from_secs_f64(secs)).await;
+            Ok(())
+        })?,
+    )?;
+
+    // Listen for completions on another thread, which never touches the scheduler
+    let (tx, rx) = mpsc::channel();
+    sched.set_completion_sender(tx);
+    let listener = thread::spawn(move || rx.iter().take(3).collect::<Vec<ThreadCompletion>>());
+
+    // Spawn a few threads that complete at different times
+    let main = lua.load(MAIN_SCRIPT).into_function()?;
+    let slow = sched.push_thread_front(&main, (0.03, false))?;
+    let failing = sched.push_thread_front(&main, (0.02, true))?;
+    let fast = sched.push_thread_front(&main, (0.01, false))?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // The other thread should have been notified about every completion, in order
+    let completions = listener.join().unwrap();
+    let expected = [(fast, true), (failing, false), (slow, true)]
+        .map(|(id, success)| ThreadCompletion { id, success });
+    assert_eq!(completions, expected);
+
+    // Results are still available after notifying
+    assert!(sched.get_thread_result(slow).unwrap().is_ok());
+    assert!(sched.get_thread_result(failing).unwrap().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_completion_channel() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,64 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{cell::RefCell, rc::Rc};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler, ThreadId};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/completion_logging.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+    sched.set_error_callback(|_| {});
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("wait", fns.wait)?;
+
+    // Log every thread that completes, without knowing about any of them up front
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let callback_log = Rc::clone(&log);
+    sched.set_completion_callback(move |_, id, result| {
+        let entry = match result {
+            Ok(values) => Ok(values.get(0).and_then(LuaValue::as_str).map(str::to_string)),
+            Err(e) => Err(e.to_string()),
+        };
+        callback_log.borrow_mut().push((id, entry));
+    });
+
+    // Run the main script until completion
+    let main_id = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    block_on(sched.run());
+
+    // Tracked and untracked threads should both have been logged
+    let child_id = ThreadId::from(&lua.globals().get::<_, LuaThread>("childThread")?);
+    let log = log.borrow();
+    assert_eq!(log.len(), 3);
+    assert!(log.contains(&(main_id, Ok(Some("main".to_string())))));
+    assert!(log.contains(&(child_id, Ok(Some("child".to_string())))));
+    assert!(log
+        .iter()
+        .any(|(_, entry)| entry.as_ref().is_err_and(|e| e.contains("spawned failed"))));
+
+    // The result of the tracked thread should still be available
+    let result = sched.get_thread_result(main_id).unwrap()?;
+    assert_eq!(result.get(0).and_then(LuaValue::as_str), Some("main"));
+
+    Ok(())
+}
+
+#[test]
+fn test_completion_logging() -> LuaResult<()> {
+    main()
+}
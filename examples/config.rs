@@ -0,0 +1,84 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{
+    ChunkOptions, Scheduler, SchedulerConfig, TimerPrecision, TrackingMode, WatchdogPolicy,
+};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/config.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+
+    // A fresh scheduler should have the default configuration
+    let defaults = sched.config();
+    println!("Default config: {defaults:?}");
+    assert_eq!(
+        defaults,
+        SchedulerConfig {
+            deterministic: false,
+            single_threaded: false,
+            max_spawned_per_cycle: None,
+            queue_capacity: None,
+            yield_budget: None,
+            yield_budgets: 0,
+            thread_budget: None,
+            watchdog: None,
+            long_poll_threshold: None,
+            queue_pressure_thresholds: Vec::new(),
+            error_retention: 0,
+            value_log_capacity: 0,
+            respawn_retention: 0,
+            chunk_options: ChunkOptions::default(),
+            timer_precision: TimerPrecision::Coarse,
+            timer_coalescing: Duration::ZERO,
+            tracking_mode: TrackingMode::Default,
+            strict: false,
+        }
+    );
+
+    // Configure the scheduler, and push a thread with a yield budget
+    sched.set_deterministic(true);
+    sched.set_watchdog(Duration::from_secs(5), WatchdogPolicy::Cancel);
+    sched.set_long_poll_callback(Duration::from_millis(50), |_| {});
+    sched.set_queue_pressure_callback([64, 8], |_| {});
+    let id = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    sched.set_thread_yield_budget(id, Duration::from_millis(10));
+    sched.set_thread_budget(Some(Duration::from_secs(1)));
+
+    // The snapshot should reflect all of the above
+    let config = sched.config();
+    println!("Effective config: {config:?}");
+    assert!(config.deterministic);
+    assert_eq!(config.yield_budgets, 1);
+    assert_eq!(config.thread_budget, Some(Duration::from_secs(1)));
+    assert_eq!(
+        config.watchdog,
+        Some((Duration::from_secs(5), WatchdogPolicy::Cancel))
+    );
+    assert_eq!(config.long_poll_threshold, Some(Duration::from_millis(50)));
+    assert_eq!(config.queue_pressure_thresholds, vec![8, 64]);
+
+    // Run until completion
+    block_on(sched.run());
+
+    Ok(())
+}
+
+#[test]
+fn test_config() -> LuaResult<()> {
+    main()
+}
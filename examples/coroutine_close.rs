@@ -0,0 +1,83 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::{Duration, Instant};
+
+use async_io::block_on;
+use futures_lite::future;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Priority, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/coroutine_close.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment, with coroutine.close replaced
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    fns.inject_compat(&lua)?;
+    lua.globals().set("wait", fns.wait)?;
+    lua.globals().set("cancel", fns.cancel)?;
+
+    // Create a thread that waits for a long time, and threads for other queues that should never run
+    let never_run = lua.create_function(|_, ()| {
+        Err::<(), _>(LuaError::runtime("closed thread should never run"))
+    })?;
+    let waiting = lua.create_thread(lua.load("wait(30)").into_function()?)?;
+    let deferred = lua.create_thread(never_run.clone())?;
+    let high = lua.create_thread(never_run.clone())?;
+    let idle = lua.create_thread(never_run.clone())?;
+    let cancelled = lua.create_thread(never_run)?;
+
+    // The waiting thread runs first, so that it is waiting once closed
+    let waiting_id = sched.push_thread_with_priority(waiting.clone(), (), Priority::High)?;
+
+    // Load the main script into the scheduler, with high priority so that it runs before any other
+    // threads, which closes every thread it is given, and cancels the last one instead
+    let main = lua.load(MAIN_SCRIPT);
+    let args = (
+        waiting.clone(),
+        deferred.clone(),
+        high.clone(),
+        idle.clone(),
+        cancelled.clone(),
+    );
+    sched.push_thread_with_priority(main, args, Priority::High)?;
+
+    let deferred_id = sched.push_thread_back(deferred, ())?;
+    let high_id = sched.push_thread_with_priority(high, (), Priority::High)?;
+    let idle_id = sched.push_thread_idle(idle, ())?;
+    let cancelled_id = sched.push_thread_front(cancelled, ())?;
+
+    // Waiting for the closed threads should not hang, and neither should the scheduler
+    let ids = [waiting_id, deferred_id, high_id, idle_id, cancelled_id];
+    let start = Instant::now();
+    block_on(future::zip(sched.run(), async {
+        for id in ids {
+            sched.wait_for_thread(id).await;
+        }
+    }));
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    // Closed and cancelled threads should have an error stored as their result
+    for id in ids {
+        let err = sched.get_thread_result(id).unwrap().unwrap_err();
+        println!("Closed thread result: {err}");
+        assert!(err.to_string().contains("cancelled"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_coroutine_close() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,63 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{cell::RefCell, rc::Rc};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/cycles.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("wait", fns.wait)?;
+    lua.globals().set("cycle", fns.cycle)?;
+
+    // Record every cycle as it starts and ends
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let start_events = Rc::clone(&events);
+    sched.on_cycle_start(move |_, cycle| start_events.borrow_mut().push(("start", cycle)));
+    let end_events = Rc::clone(&events);
+    sched.on_cycle_end(move |_, cycle| end_events.borrow_mut().push(("end", cycle)));
+
+    // Run the main script until completion
+    assert_eq!(sched.current_cycle(), 0);
+    let id = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    block_on(sched.run());
+    sched.get_thread_result(id).unwrap()?;
+
+    // Every cycle should have started and ended exactly once, in order
+    let total = sched.current_cycle();
+    let expected = (1..=total)
+        .flat_map(|cycle| [("start", cycle), ("end", cycle)])
+        .collect::<Vec<_>>();
+    assert_eq!(*events.borrow(), expected);
+
+    // The last cycle that Lua saw should be one of them
+    let last = lua.globals().get::<_, u64>("lastCycle")?;
+    assert!(last >= 6 && last <= total);
+
+    // Resetting the scheduler should also reset the cycle counter
+    sched.reset()?;
+    assert_eq!(sched.current_cycle(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_cycles() -> LuaResult<()> {
+    main()
+}
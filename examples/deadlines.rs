@@ -0,0 +1,75 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::{Duration, Instant};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, LuaSchedulerExt, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/deadlines.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    sched.remove_error_callback();
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("finished", lua.create_table()?)?;
+    lua.globals().set(
+        "sleep",
+        lua.create_async_function(|lua, secs: f64| {
+            let sleep = lua.sleep(Duration::from_secs_f64(secs));
+            async move { Ok(sleep.await.as_secs_f64()) }
+        })?,
+    )?;
+    lua.globals().set(
+        "hasDeadline",
+        lua.create_function(|lua, ()| Ok(lua.current_deadline().is_some()))?,
+    )?;
+
+    // Push one request that completes in time, and one that would take way too long
+    let main = lua.load(MAIN_SCRIPT).into_function()?;
+    let deadline = Instant::now() + Duration::from_millis(100);
+    let fast = sched.push_thread_with_deadline(&main, ("fast", 0.01), deadline)?;
+    let slow = sched.push_thread_with_deadline(&main, ("slow", 10.0), deadline)?;
+
+    // Untrusted scripts that never yield can be timed out too, when given a yield budget
+    sched.set_yield_budget(Some(Duration::from_millis(5)));
+    let looping = lua.load("while true do end");
+    let untrusted = sched.push_thread_with_timeout(looping, (), Duration::from_millis(100))?;
+
+    // Run until completion, which should not have to wait for the slow request
+    let start = Instant::now();
+    block_on(sched.run());
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    // The fast request should have completed, and the slow one cancelled along with its child
+    sched.get_thread_result(fast).unwrap()?;
+    let err = sched.get_thread_result(slow).unwrap().unwrap_err();
+    assert!(err.to_string().contains("deadline"));
+    let err = sched.get_thread_result(untrusted).unwrap().unwrap_err();
+    assert!(err.to_string().contains("deadline"));
+
+    let finished = lua.globals().get::<_, LuaTable>("finished")?;
+    assert!(finished.get::<_, bool>("fast")?);
+    assert!(finished.get::<_, bool>("fast child")?);
+    assert!(!finished.get::<_, bool>("slow")?);
+    assert!(!finished.get::<_, bool>("slow child")?);
+
+    Ok(())
+}
+
+#[test]
+fn test_deadlines() -> LuaResult<()> {
+    main()
+}
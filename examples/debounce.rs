@@ -0,0 +1,48 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/debounce.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("debounce", fns.debounce)?;
+    lua.globals().set("throttle", fns.throttle)?;
+
+    // Load the main script into the scheduler, and keep track of the thread we spawn
+    let main = lua.load(MAIN_SCRIPT);
+    let id = sched.push_thread_front(main, ())?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // Debounced and throttled calls should have been coalesced
+    let res = sched.get_thread_result(id).unwrap().unwrap();
+    let calls = LuaTable::from_lua_multi(res, &lua)?;
+    let debounced = calls.get::<_, Vec<usize>>("debounced")?;
+    let throttled = calls.get::<_, Vec<usize>>("throttled")?;
+    assert_eq!(debounced, vec![5]);
+    assert_eq!(throttled, vec![1, 5]);
+
+    Ok(())
+}
+
+#[test]
+fn test_debounce() -> LuaResult<()> {
+    main()
+}
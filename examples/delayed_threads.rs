@@ -0,0 +1,83 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+#![allow(clippy::cast_precision_loss)]
+
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/delayed_threads.luau");
+
+const COUNT: usize = 100;
+
+// NOTE: Threads are pushed in reverse, so pushing must never stall for longer
+// than this, or threads due later could end up being due before earlier ones
+const SPACING_MS: u64 = 10;
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    let start = Instant::now();
+    let records = Rc::new(RefCell::new(Vec::new()));
+    let record = {
+        let records = Rc::clone(&records);
+        lua.create_function(move |_, (source, index): (String, usize)| {
+            records.borrow_mut().push((source, index, start.elapsed()));
+            Ok(())
+        })?
+    };
+
+    lua.globals().set("delay", fns.delay)?;
+    lua.globals().set("cancel", fns.cancel)?;
+    lua.globals().set("record", record.clone())?;
+
+    // Delay lots of threads from Rust, in reverse order of when they should run
+    for i in (1..=COUNT).rev() {
+        let delay = Duration::from_millis(i as u64 * SPACING_MS);
+        sched.push_thread_delayed(&record, ("rust", i), delay)?;
+    }
+
+    // And then from Lua, which does the same thing
+    sched.push_thread_front(lua.load(MAIN_SCRIPT), (COUNT, SPACING_MS))?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // All threads should have run, in order of when they were due, and never too early
+    let records = records.borrow();
+    assert_eq!(records.len(), COUNT * 2);
+    for source in ["rust", "lua"] {
+        let indices = records
+            .iter()
+            .filter(|(s, _, _)| s == source)
+            .map(|(_, i, _)| *i)
+            .collect::<Vec<_>>();
+        assert_eq!(indices, (1..=COUNT).collect::<Vec<_>>());
+    }
+    for (_, index, elapsed) in records.iter() {
+        assert!(*elapsed >= Duration::from_millis(*index as u64 * SPACING_MS));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_delayed_threads() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,91 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::{Duration, SystemTime};
+
+use async_io::{block_on, Timer};
+use futures_lite::{future, FutureExt};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler, ScopedThreadId, ThreadId};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/describe_threads.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("heartbeat", fns.heartbeat)?;
+
+    // Push a tagged worker, and scope its id the same way we would for logs
+    let before = SystemTime::now();
+    let worker: LuaFunction = lua.load(MAIN_SCRIPT).eval()?;
+    let thread = lua.create_thread(worker)?;
+    sched.set_thread_tag(&thread, "worker")?;
+    let id = sched.push_thread_back(thread.clone(), "a")?;
+    let scoped = sched.scope_thread_id(id);
+    assert_eq!(scoped.epoch, sched.epoch());
+
+    // Ids scoped to some other scheduler should never resolve
+    let foreign = ScopedThreadId {
+        epoch: sched.epoch().wrapping_add(1),
+        id,
+    };
+    assert_eq!(sched.resolve_thread_id(foreign), None);
+
+    // Run until completion, describing the worker while it waits for a tick
+    let host = async {
+        Timer::after(Duration::from_millis(10)).await;
+        let id = sched.resolve_thread_id(scoped).expect("epoch mismatch");
+        let info = sched
+            .describe_thread(id)
+            .expect("failed to describe thread")
+            .expect("missing thread");
+        println!("Described worker: {info:?}");
+        assert_eq!(info.id, scoped);
+        assert_eq!(info.tag.as_deref(), Some("worker"));
+        assert_eq!(info.status, LuaThreadStatus::Resumable);
+        assert!(info.pushed_at.is_some_and(|at| at >= before));
+        sched
+            .fire_tick(Duration::ZERO)
+            .expect("failed to fire tick");
+        future::pending::<()>().await;
+    };
+    block_on(sched.run().or(host));
+
+    // The worker is still referenced here, so it should be describable, but no longer resumable
+    let info = sched.describe_thread(id)?.expect("missing thread");
+    assert_eq!(info.status, LuaThreadStatus::Unresumable);
+    drop(thread);
+
+    // Threads that were only tagged should be describable too, without a push time
+    let tagged = lua.create_thread(lua.create_function(|_, ()| Ok(()))?)?;
+    sched.set_thread_tag(&tagged, "idle")?;
+    let info = sched
+        .describe_thread(ThreadId::from(&tagged))?
+        .expect("missing thread");
+    assert_eq!(info.tag.as_deref(), Some("idle"));
+    assert_eq!(info.pushed_at, None);
+
+    // Once garbage collected, threads should no longer be describable
+    let tagged_id = ThreadId::from(&tagged);
+    drop(tagged);
+    lua.gc_collect()?;
+    lua.gc_collect()?;
+    assert_eq!(sched.describe_thread(tagged_id)?, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_describe_threads() -> LuaResult<()> {
+    main()
+}
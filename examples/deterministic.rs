@@ -0,0 +1,116 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use async_io::block_on;
+use futures_lite::future;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, LuaSchedulerExt, LuaSpawnExt, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/deterministic.luau");
+
+const EXPIRING_THREADS: usize = 8;
+
+/**
+    Runs the same workload in a fresh Lua state, returning the order that things happened in.
+*/
+fn run_workload() -> LuaResult<Vec<String>> {
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    sched.set_deterministic(true);
+    sched.remove_error_callback();
+    let fns = Functions::new(&lua)?;
+
+    let order = Rc::new(RefCell::new(Vec::new()));
+    let order_lua = Rc::clone(&order);
+    lua.globals().set(
+        "log",
+        lua.create_function(move |_, entry: String| {
+            order_lua.borrow_mut().push(entry);
+            Ok(())
+        })?,
+    )?;
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("defer", fns.defer)?;
+    lua.globals().set(
+        "work",
+        lua.create_async_function(|lua, steps: usize| async move {
+            lua.spawn(async move {
+                for _ in 0..steps {
+                    future::yield_now().await;
+                }
+            })
+            .await;
+            Ok(())
+        })?,
+    )?;
+
+    // Threads that expire at the exact same time must be cancelled in the order they were pushed
+    let deadline = Instant::now() + Duration::from_millis(20);
+    let mut expiring = Vec::new();
+    for _ in 0..EXPIRING_THREADS {
+        let thread = lua.create_thread(lua.load("coroutine.yield()").into_function()?)?;
+        expiring.push(sched.push_thread_with_deadline(thread, (), deadline)?);
+    }
+    let order_expired = Rc::clone(&order);
+    sched.set_completion_callback(move |_, id, _| {
+        if let Some(index) = expiring.iter().position(|e| *e == id) {
+            order_expired
+                .borrow_mut()
+                .push(format!("expired {}", index + 1));
+        }
+    });
+
+    // Keep the scheduler running until well after all deadlines have passed
+    sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    sched.push_thread_back(
+        lua.create_async_function(|lua, ()| async move {
+            lua.sleep(Duration::from_millis(100)).await;
+            Ok(())
+        })?,
+        (),
+    )?;
+    block_on(sched.run());
+
+    let order = order.borrow().clone();
+    Ok(order)
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Identical inputs must always result in identical scheduling decisions
+    let first = run_workload()?;
+    println!("Order: {first:?}");
+    for _ in 0..4 {
+        assert_eq!(run_workload()?, first);
+    }
+
+    // Expired threads are cancelled in the order they were pushed
+    let expired = first
+        .iter()
+        .filter(|entry| entry.starts_with("expired"))
+        .cloned()
+        .collect::<Vec<_>>();
+    let expected = (1..=EXPIRING_THREADS)
+        .map(|index| format!("expired {index}"))
+        .collect::<Vec<_>>();
+    assert_eq!(expired, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_deterministic() -> LuaResult<()> {
+    main()
+}
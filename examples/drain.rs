@@ -0,0 +1,65 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::{block_on, Timer};
+use futures_lite::{future, FutureExt};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/drain.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    let results = lua.create_table()?;
+    lua.globals().set("results", results.clone())?;
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("heartbeat", fns.heartbeat)?;
+
+    // Load the main script into the scheduler
+    let main = lua.load(MAIN_SCRIPT);
+    sched.push_thread_front(main, ())?;
+    assert!(sched.drain_status().is_none());
+
+    // Run until completion, draining the scheduler after a short while
+    let host = async {
+        Timer::after(Duration::from_millis(10)).await;
+        sched.begin_drain();
+        let res = sched.push_thread_back(lua.load("error('unreachable')"), ());
+        assert!(res.is_err(), "pushing threads while draining should fail");
+        sched
+            .fire_tick(Duration::ZERO)
+            .expect("failed to fire tick");
+        future::pending::<()>().await;
+    };
+    block_on(sched.run().or(host));
+
+    // All workers should have finished, without being able to spawn new threads
+    let results = results
+        .sequence_values::<String>()
+        .collect::<LuaResult<Vec<_>>>()?;
+    assert_eq!(results, vec!["rejected", "rejected", "rejected"]);
+
+    let status = sched.drain_status().expect("scheduler should be draining");
+    assert_eq!(status.drained, 3);
+    assert_eq!(status.remaining, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_drain() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,62 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_io::{block_on, Timer};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/error_handler.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment, exposing a generic runtime table to scripts
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    let runtime = lua.create_table()?;
+    runtime.set("setErrorHandler", fns.set_error_handler)?;
+    lua.globals().set("__runtime", runtime)?;
+    lua.globals().set("error", fns.error)?;
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set(
+        "wait",
+        lua.create_async_function(|_, secs: f64| async move {
+            Timer::after(Duration::from_secs_f64(secs)).await;
+            Ok(())
+        })?,
+    )?;
+
+    // Collect all errors that reach the host
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let errors_inner = Arc::clone(&errors);
+    sched.set_error_callback(move |e| errors_inner.lock().unwrap().push(e.to_string()));
+
+    // Load the main script into the scheduler, and run it until completion
+    let main = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    block_on(sched.run());
+    assert!(sched.get_thread_result(main).unwrap().is_ok());
+
+    // Only the error that the script did not handle should have reached the host
+    let errors = errors.lock().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("unexpected failure"));
+
+    Ok(())
+}
+
+#[test]
+fn test_error_handler() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,61 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::Scheduler;
+
+const MAIN_SCRIPT: &str = include_str!("./lua/error_history.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment, retaining the two most recent errors
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    sched.set_error_callback(|_| {});
+    sched.set_error_retention(2);
+
+    // Push a few workers that all error
+    let worker: LuaFunction = lua.load(MAIN_SCRIPT).set_name("=workers/failing").eval()?;
+    let mut ids = Vec::new();
+    for name in ["a", "b", "c"] {
+        let thread = lua.create_thread(worker.clone())?;
+        sched.set_thread_tag(&thread, format!("worker-{name}"))?;
+        ids.push(sched.push_thread_back(thread, name)?);
+    }
+
+    // Run until completion, and consume all of the results
+    block_on(sched.run());
+    for id in &ids {
+        assert!(sched.get_thread_result(*id).unwrap().is_err());
+    }
+
+    // Only the two most recent errors should be retained, even after consuming results
+    let errors = sched.recent_errors(50);
+    for error in &errors {
+        println!("Retained error: {error:?}");
+    }
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].thread, ids[2]);
+    assert_eq!(errors[1].thread, ids[1]);
+    assert_eq!(errors[0].tag.as_deref(), Some("worker-c"));
+    assert_eq!(errors[0].chunk_name.as_deref(), Some("workers/failing"));
+    assert!(errors[0].message.ends_with("worker c failed"));
+    assert!(errors[0]
+        .pushed_at
+        .is_some_and(|at| at <= errors[0].failed_at));
+    assert_eq!(sched.recent_errors(1), errors[..1]);
+
+    Ok(())
+}
+
+#[test]
+fn test_error_history() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,59 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+use futures_lite::stream;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Backpressure, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/event_sources.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+
+    let (create_handler, received): (LuaFunction, LuaTable) = lua.load(MAIN_SCRIPT).eval()?;
+
+    // Pipe the same items into Lua using every backpressure strategy,
+    // all items are received at once, before any handler gets to run
+    let strategies = [
+        ("unbounded", Backpressure::Unbounded),
+        ("drop", Backpressure::Drop(2)),
+        ("buffer", Backpressure::Buffer(2)),
+        ("coalesce", Backpressure::Coalesce),
+    ];
+    for (name, backpressure) in strategies {
+        let handler = create_handler.call::<_, LuaFunction>(name)?;
+        sched.spawn_event_source(stream::iter(1..=5), handler, backpressure)?;
+    }
+
+    // Run until all sources have ended
+    block_on(sched.run());
+
+    // Dropping and coalescing should have discarded items, while others received all of them
+    let items = |name: &str| -> LuaResult<Vec<i64>> {
+        received
+            .get::<_, LuaTable>(name)?
+            .sequence_values()
+            .collect()
+    };
+    assert_eq!(items("unbounded")?, vec![1, 2, 3, 4, 5]);
+    assert_eq!(items("drop")?, vec![1, 2]);
+    assert_eq!(items("buffer")?, vec![1, 2, 3, 4, 5]);
+    assert_eq!(items("coalesce")?, vec![5]);
+
+    Ok(())
+}
+
+#[test]
+fn test_event_sources() -> LuaResult<()> {
+    main()
+}
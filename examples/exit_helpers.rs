@@ -0,0 +1,87 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{
+    process::ExitCode,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_io::{block_on, Timer};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/exit_helpers.luau");
+
+fn run(mode: &str) -> LuaResult<(Option<ExitCode>, Vec<String>, Vec<String>)> {
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let errors_inner = Arc::clone(&errors);
+    sched.set_error_callback(move |e| errors_inner.lock().unwrap().push(e.to_string()));
+
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let records_inner = Arc::clone(&records);
+    lua.globals().set("mode", mode)?;
+    lua.globals().set(
+        "record",
+        lua.create_function(move |_, value: String| {
+            records_inner.lock().unwrap().push(value);
+            Ok(())
+        })?,
+    )?;
+    lua.globals().set(
+        "sleep",
+        lua.create_async_function(|_, secs: f64| async move {
+            Timer::after(Duration::from_secs_f64(secs)).await;
+            Ok(())
+        })?,
+    )?;
+    lua.globals()
+        .set("exit_with_cleanup", fns.exit_with_cleanup)?;
+    lua.globals().set("exit_after", fns.exit_after)?;
+
+    // Run until the script exits
+    sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    block_on(sched.run());
+
+    let records = records.lock().unwrap().clone();
+    let errors = errors.lock().unwrap().clone();
+    Ok((sched.get_exit_code(), records, errors))
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Cleanup handlers should all run in order, even if one errors, before exiting
+    let (code, records, errors) = run("cleanup")?;
+    println!("Cleanup exit: {code:?} {records:?} {errors:?}");
+    assert!(format!("{code:?}").contains("(2)"));
+    assert_eq!(records, ["flush", "close"]);
+    assert_eq!(errors.len(), 3);
+    assert!(errors[0].contains("failed to close socket"));
+    assert!(errors[1].contains("failed to close file"));
+    assert!(errors[2].contains("invalid"));
+
+    // Delayed exits should let threads keep working until the duration has passed
+    let (code, records, errors) = run("delayed")?;
+    println!("Delayed exit: {code:?} {records:?} {errors:?}");
+    assert!(format!("{code:?}").contains("(3)"));
+    assert!(records.len() > 1);
+    assert!(errors.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_exit_helpers() -> LuaResult<()> {
+    main()
+}
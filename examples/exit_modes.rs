@@ -0,0 +1,59 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/exit_modes.luau");
+
+fn run_with_mode(mode: &str) -> LuaResult<Vec<String>> {
+    // Set up a fresh Lua environment for each mode
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("defer", fns.defer)?;
+    lua.globals().set("wait", fns.wait)?;
+    lua.globals().set("exit", fns.exit)?;
+
+    // Load the main script into the scheduler
+    sched.push_thread_front(lua.load(MAIN_SCRIPT), mode)?;
+
+    // Run until the main script exits
+    block_on(sched.run());
+
+    // Verify that we got a correct exit code, regardless of the mode
+    let code = sched.get_exit_code().unwrap_or_default();
+    assert!(format!("{code:?}").contains("(2)"));
+
+    let finished = lua.globals().get("finished")?;
+    Ok(finished)
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Graceful exits let running threads finish, but never resume queued threads
+    let graceful = run_with_mode("graceful")?;
+    println!("Finished with graceful exit: {graceful:?}");
+    assert_eq!(graceful, ["running"]);
+
+    // Forced exits stop right away, dropping running threads
+    let force = run_with_mode("force")?;
+    println!("Finished with forced exit: {force:?}");
+    assert!(force.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_exit_modes() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,137 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+use proptest::{collection::vec, prelude::*, test_runner::TestRunner};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/fairness.luau");
+
+const THREADS: usize = 8;
+
+/**
+    A randomized interleaving of pushes to the scheduler queues.
+
+    Each push is given as the index of the thread to push, and if it
+    should be pushed to the front (spawn) or back (defer) of the queue.
+    Threads may be cancelled after all pushes have been made.
+*/
+#[derive(Debug, Clone)]
+struct Interleaving {
+    pushes: Vec<(usize, bool)>,
+    cancelled: Vec<bool>,
+}
+
+fn interleaving() -> impl Strategy<Value = Interleaving> {
+    (
+        vec((0..THREADS, any::<bool>()), 1..48),
+        vec(any::<bool>(), THREADS),
+    )
+        .prop_map(|(pushes, cancelled)| Interleaving { pushes, cancelled })
+}
+
+/**
+    Runs the given interleaving to completion, and returns all resumptions
+    that happened, as pairs of push indices and if that push was to the front.
+*/
+fn run_interleaving(case: &Interleaving) -> LuaResult<Vec<(usize, bool)>> {
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    let resumptions = lua.create_table()?;
+    lua.globals().set("resumptions", resumptions.clone())?;
+
+    let func: LuaFunction = lua.load(MAIN_SCRIPT).eval()?;
+    let threads = (0..THREADS)
+        .map(|_| lua.create_thread(func.clone()))
+        .collect::<LuaResult<Vec<_>>>()?;
+
+    for (index, &(thread, front)) in case.pushes.iter().enumerate() {
+        let thread = threads[thread].clone();
+        if front {
+            sched.push_thread_front(thread, (index, front))?;
+        } else {
+            sched.push_thread_back(thread, (index, front))?;
+        }
+    }
+    for (thread, &cancelled) in threads.iter().zip(&case.cancelled) {
+        if cancelled {
+            fns.cancel.call::<_, ()>(thread.clone())?;
+        }
+    }
+
+    block_on(sched.run());
+
+    resumptions
+        .sequence_values::<LuaTable>()
+        .map(|resumption| {
+            let resumption = resumption?;
+            Ok((resumption.get(1)?, resumption.get(2)?))
+        })
+        .collect::<LuaResult<Vec<_>>>()
+}
+
+/**
+    Checks all scheduler invariants for the given interleaving.
+*/
+fn check_interleaving(case: &Interleaving) -> Result<(), TestCaseError> {
+    let resumptions = run_interleaving(case).map_err(|e| TestCaseError::fail(e.to_string()))?;
+
+    // Every push of a thread that was not cancelled is resumed exactly once,
+    // and threads that were cancelled before running are never resumed at all
+    let mut expected = case
+        .pushes
+        .iter()
+        .enumerate()
+        .filter(|(_, (thread, _))| !case.cancelled[*thread])
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    let mut resumed = resumptions
+        .iter()
+        .map(|(index, _)| *index)
+        .collect::<Vec<_>>();
+    expected.sort_unstable();
+    resumed.sort_unstable();
+    prop_assert_eq!(resumed, expected);
+
+    // Deferred threads never run before threads spawned in the same cycle
+    let first_deferred = resumptions.iter().position(|(_, front)| !front);
+    if let Some(first_deferred) = first_deferred {
+        prop_assert!(resumptions[first_deferred..]
+            .iter()
+            .all(|(_, front)| !front));
+    }
+
+    Ok(())
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Run a bunch of randomized interleavings, shrinking any that fail
+    let config = ProptestConfig {
+        cases: 128,
+        failure_persistence: None,
+        ..ProptestConfig::default()
+    };
+    let mut runner = TestRunner::new(config);
+    runner
+        .run(&interleaving(), |case| check_interleaving(&case))
+        .map_err(|e| LuaError::runtime(e.to_string()))?;
+
+    println!("All scheduler invariants held");
+
+    Ok(())
+}
+
+#[test]
+fn test_fairness() -> LuaResult<()> {
+    main()
+}
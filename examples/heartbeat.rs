@@ -0,0 +1,57 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::{Duration, Instant};
+
+use async_io::{block_on, Timer};
+use futures_lite::FutureExt;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/heartbeat.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("heartbeat", fns.heartbeat)?;
+
+    // Load the main script into the scheduler, and keep track of the thread we spawn
+    let main = lua.load(MAIN_SCRIPT);
+    let id = sched.push_thread_front(main, ())?;
+
+    // Run until completion, while firing ticks at roughly 60 Hz from the host
+    let host = async {
+        let mut last = Instant::now();
+        loop {
+            Timer::after(Duration::from_millis(16)).await;
+            let now = Instant::now();
+            sched.fire_tick(now - last).expect("failed to fire tick");
+            last = now;
+        }
+    };
+    block_on(sched.run().or(host));
+
+    // We should have gotten the number of ticks and their total time
+    let res = sched.get_thread_result(id).unwrap().unwrap();
+    let (ticks, total) = <(usize, f64)>::from_lua_multi(res, &lua)?;
+    assert_eq!(ticks, 10);
+    assert!(total > 0.0);
+
+    Ok(())
+}
+
+#[test]
+fn test_heartbeat() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,52 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/idle.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    let order = lua.create_table()?;
+    lua.globals().set("order", order.clone())?;
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("defer", fns.defer)?;
+
+    // Push some idle work first, it should still run last
+    let idle = lua.load("table.insert(order, 'idle')");
+    sched.push_thread_idle(idle, ())?;
+
+    // Load the main script into the scheduler
+    let main = lua.load(MAIN_SCRIPT);
+    sched.push_thread_front(main, ())?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // Idle work should only have run once everything else was done
+    let order = order
+        .sequence_values::<String>()
+        .collect::<LuaResult<Vec<_>>>()?;
+    assert_eq!(order, vec!["main", "spawned", "deferred", "idle"]);
+    assert_eq!(sched.idle_stats().threads_processed, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_idle() -> LuaResult<()> {
+    main()
+}
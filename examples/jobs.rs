@@ -0,0 +1,59 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{collections::HashMap, time::Duration};
+
+use async_io::{block_on, Timer};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/jobs.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("runJob", fns.run_job)?;
+
+    // Register a couple of jobs, these run on the main executor, not the Lua thread
+    sched.register_job("double", |n: f64| async move {
+        Timer::after(Duration::from_millis(5)).await;
+        Ok(n * 2.0)
+    });
+    sched.register_job("thumbnail", |size: HashMap<String, u32>| async move {
+        Timer::after(Duration::from_millis(5)).await;
+        let thumbnail = size
+            .into_iter()
+            .map(|(key, value)| (key, value / 4))
+            .collect::<HashMap<_, _>>();
+        Ok(thumbnail)
+    });
+
+    // Load the main script into the scheduler, and keep track of the thread we spawn
+    let main = lua.load(MAIN_SCRIPT);
+    let id = sched.push_thread_front(main, ())?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // We should have gotten proper values back from our script
+    let res = sched.get_thread_result(id).unwrap().unwrap();
+    let doubled = f64::from_lua_multi(res, &lua)?;
+    assert!((doubled - 42.0).abs() < f64::EPSILON);
+
+    Ok(())
+}
+
+#[test]
+fn test_jobs() -> LuaResult<()> {
+    main()
+}
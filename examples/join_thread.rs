@@ -0,0 +1,46 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+use futures_lite::future::zip;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/join_thread.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("wait", fns.wait)?;
+
+    // Load the main script into the scheduler
+    let id = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+
+    // Join the thread while the scheduler runs, getting its values in one step
+    let ((), result) = block_on(zip(sched.run(), sched.join_thread(id)));
+    let (a, b) = <(u32, String)>::from_lua_multi(result?, &lua)?;
+    println!("Joined thread returned {a} and {b:?}");
+    assert_eq!((a, b.as_str()), (42, "done"));
+
+    // Joining again errors instead of waiting forever, since the result was already taken
+    let again = block_on(sched.join_thread(id)).unwrap_err();
+    println!("Joining again failed: {again}");
+    assert!(again.to_string().contains("not tracked"));
+
+    Ok(())
+}
+
+#[test]
+fn test_join_thread() -> LuaResult<()> {
+    main()
+}
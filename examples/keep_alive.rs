@@ -0,0 +1,85 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{cell::RefCell, process::ExitCode, rc::Rc, time::Duration};
+
+use async_io::{block_on, Timer};
+use futures_lite::future;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{ExitReason, Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/keep_alive.luau");
+
+const NUM_JOBS: usize = 3;
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    let records = Rc::new(RefCell::new(Vec::new()));
+    let record_records = Rc::clone(&records);
+    lua.globals().set("wait", fns.wait)?;
+    lua.globals().set(
+        "record",
+        lua.create_function(move |_, index: usize| {
+            record_records.borrow_mut().push(index);
+            Ok(())
+        })?,
+    )?;
+    let job = lua.load(MAIN_SCRIPT).into_function()?;
+
+    // A host loop that pushes jobs into the running scheduler, letting it
+    // go idle in between, similar to the tick loop of a game server
+    let host_loop = |keep_alive| {
+        let job = job.clone();
+        let sched = &sched;
+        async move {
+            for index in 1..=NUM_JOBS {
+                Timer::after(Duration::from_millis(20)).await;
+                sched
+                    .push_thread_back(job.clone(), index)
+                    .expect("failed to push job");
+            }
+            Timer::after(Duration::from_millis(20)).await;
+            drop(keep_alive);
+        }
+    };
+
+    // Keep-alive guards should keep the scheduler running until they are dropped
+    let guard = sched.keep_alive();
+    block_on(future::zip(sched.run(), host_loop(Some(guard))));
+    assert_eq!(*records.borrow(), (1..=NUM_JOBS).collect::<Vec<_>>());
+    assert!(matches!(
+        block_on(sched.wait_for_exit()),
+        ExitReason::Completed
+    ));
+
+    // Running forever should only stop once an exit code is set
+    records.borrow_mut().clear();
+    let exit_after_jobs = async {
+        host_loop(None).await;
+        sched.set_exit_code(ExitCode::SUCCESS);
+    };
+    block_on(future::zip(sched.run_forever(), exit_after_jobs));
+    assert_eq!(*records.borrow(), (1..=NUM_JOBS).collect::<Vec<_>>());
+    assert!(matches!(
+        block_on(sched.wait_for_exit()),
+        ExitReason::ExitCode(_)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_keep_alive() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,70 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{cell::Cell, rc::Rc};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::Scheduler;
+
+const MAIN_SCRIPT: &str = include_str!("./lua/lazy_args.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    sched.set_error_callback(|e| println!("Worker errored: {e}"));
+
+    let worker: LuaFunction = lua.load(MAIN_SCRIPT).eval()?;
+    let created = Rc::new(Cell::new(0));
+
+    // Push a worker with arguments that are only created once it is resumed
+    let counter = Rc::clone(&created);
+    let id_lazy = sched.push_thread_front_with(lua.create_thread(worker.clone())?, move |lua| {
+        counter.set(counter.get() + 1);
+        ("lazy", 21).into_lua_multi(lua)
+    })?;
+    assert_eq!(created.get(), 0);
+
+    // Push a worker that gets cancelled, its arguments should never be created
+    let counter = Rc::clone(&created);
+    let cancelled = lua.create_thread(worker.clone())?;
+    sched.set_thread_tag(&cancelled, "cancelled")?;
+    let id_cancelled = sched.push_thread_back_with(cancelled, move |lua| {
+        counter.set(counter.get() + 1);
+        ("cancelled", 0).into_lua_multi(lua)
+    })?;
+    assert_eq!(sched.cancel_by_tag("cancelled")?, 1);
+
+    // Push a worker whose arguments fail to be created
+    let id_failing = sched.push_thread_back_with(lua.create_thread(worker)?, |_| {
+        Err(LuaError::runtime("failed to create arguments"))
+    })?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // Only the arguments for the first worker should have been created
+    assert_eq!(created.get(), 1);
+    let doubled = i64::from_lua_multi(sched.get_thread_result(id_lazy).unwrap()?, &lua)?;
+    assert_eq!(doubled, 42);
+    println!(
+        "Cancelled result: {:?}",
+        sched.get_thread_result(id_cancelled)
+    );
+    assert!(sched.get_thread_result(id_failing).unwrap().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_lazy_args() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,68 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_io::{block_on, Timer};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, LeakReport, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/leaks.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("heartbeat", fns.heartbeat)?;
+
+    // Store any leak report so that we can inspect it after dropping the scheduler
+    let leaks = Arc::new(Mutex::new(None::<LeakReport>));
+    let leaks_inner = Arc::clone(&leaks);
+    sched.set_leak_callback(move |report| {
+        println!("Leaks detected: {report:?}");
+        leaks_inner.lock().unwrap().replace(report);
+    });
+
+    // Push a thread that completes, but whose result we never claim,
+    // and a thread waiting for a tick, which is never fired
+    let completed = sched.push_thread_back(lua.load("return 1"), ())?;
+    let waiting = sched.push_thread_back(lua.load(MAIN_SCRIPT), ())?;
+    let shutdown = async {
+        Timer::after(Duration::from_millis(10)).await;
+    };
+    block_on(sched.run_until(shutdown));
+
+    // Push one more thread after the scheduler has stopped, which is never resumed
+    let queued = sched.push_thread_back(lua.load("return 2"), ())?;
+    drop(sched);
+
+    // Verify that all of the above were reported
+    let report = leaks.lock().unwrap().take().expect("missing leak report");
+    let mut unfinished = report.unfinished_threads.clone();
+    unfinished.sort_by_key(|id| format!("{id:?}"));
+    let mut expected = vec![waiting, queued];
+    expected.sort_by_key(|id| format!("{id:?}"));
+    assert_eq!(unfinished, expected);
+    assert_eq!(report.queued_threads, vec![queued]);
+    assert_eq!(report.registry_values, 3);
+    assert!(!report.unfinished_threads.contains(&completed));
+
+    Ok(())
+}
+
+#[test]
+fn test_leaks() -> LuaResult<()> {
+    main()
+}
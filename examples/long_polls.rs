@@ -0,0 +1,69 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{
+    sync::{Arc, Mutex},
+    thread::sleep,
+    time::Duration,
+};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Scheduler, ThreadId};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/long_polls.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+
+    // Add an accidentally blocking function for our script to use
+    lua.globals().set(
+        "blockingSleep",
+        lua.create_function(|_, ms: u64| {
+            sleep(Duration::from_millis(ms));
+            Ok(())
+        })?,
+    )?;
+
+    // Keep track of any polls that block the scheduler for too long
+    let long_polls = Arc::new(Mutex::new(Vec::new()));
+    let long_polls_inner = Arc::clone(&long_polls);
+    sched.set_long_poll_callback(Duration::from_millis(20), move |poll| {
+        long_polls_inner.lock().unwrap().push(poll);
+    });
+
+    // Spawn one well-behaved thread, and one tagged thread that blocks
+    let main: LuaTable = lua.load(MAIN_SCRIPT).eval()?;
+    sched.push_thread_back(main.get::<_, LuaFunction>("fast")?, ())?;
+    let slow = lua.create_thread(main.get::<_, LuaFunction>("slow")?)?;
+    sched.set_thread_tag(&slow, "slow-plugin")?;
+    sched.push_thread_back(&slow, ())?;
+    assert_eq!(sched.active_tasks(), 0);
+
+    // Run until completion
+    block_on(sched.run());
+
+    // Only the blocking thread should have been reported, and no tasks should be left
+    let long_polls = long_polls.lock().unwrap().clone();
+    assert_eq!(long_polls.len(), 1);
+    assert!(long_polls[0].duration >= Duration::from_millis(20));
+    assert_eq!(long_polls[0].thread, Some(ThreadId::from(&slow)));
+    assert_eq!(long_polls[0].tag.as_deref(), Some("slow-plugin"));
+    assert_eq!(sched.active_tasks(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_long_polls() -> LuaResult<()> {
+    main()
+}
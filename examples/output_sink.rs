@@ -0,0 +1,81 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{cell::RefCell, rc::Rc};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, OutputLevel, OutputRecord, Scheduler, ThreadId};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/output_sink.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn.clone())?;
+    lua.globals().set("wait", fns.wait.clone())?;
+    fns.inject_output(&lua)?;
+
+    // Collect all output instead of writing it to stdout and stderr
+    let records = Rc::new(RefCell::new(Vec::<OutputRecord>::new()));
+    let sink_records = Rc::clone(&records);
+    sched.set_output_sink(move |record| sink_records.borrow_mut().push(record));
+
+    // Errors are written to the sink, so they no longer need to be printed
+    sched.remove_error_callback();
+
+    // Run the main script until completion
+    let main = lua.create_thread(lua.load(MAIN_SCRIPT).into_function()?)?;
+    let main_id = ThreadId::from(&main);
+    sched.push_thread_front(main, ())?;
+    block_on(sched.run());
+
+    let first = ThreadId::from(&lua.globals().get::<_, LuaThread>("firstThread")?);
+    let second = ThreadId::from(&lua.globals().get::<_, LuaThread>("secondThread")?);
+
+    // Every record should be attributed to the thread that wrote it, in order
+    let records = records
+        .borrow()
+        .iter()
+        .map(|r| (r.thread, r.level, r.message.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(records.len(), 5);
+    assert_eq!(
+        records[0],
+        (main_id, OutputLevel::Info, "hello\tfrom\tmain".to_string())
+    );
+    assert_eq!(
+        records[1],
+        (first, OutputLevel::Info, "first\t1\ttrue".to_string())
+    );
+    assert!(records[2..].contains(&(first, OutputLevel::Warn, "first is done".to_string())));
+    assert!(records[2..].contains(&(second, OutputLevel::Info, "second".to_string())));
+
+    // Uncaught errors should also end up in the sink
+    let (thread, level, message) = records.last().unwrap();
+    assert_eq!((*thread, *level), (second, OutputLevel::Error));
+    assert!(message.contains("second failed"));
+
+    // Dropping the functions and scheduler should restore the original globals
+    sched.remove_output_sink();
+    drop(fns);
+    drop(sched);
+    lua.load("print('restored')").exec()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_output_sink() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,69 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use async_io::{block_on, Timer};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, LuaSchedulerExt, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/parking.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set(
+        "sleep",
+        lua.create_async_function(|_, secs: f64| async move {
+            Timer::after(Duration::from_secs_f64(secs)).await;
+            Ok(())
+        })?,
+    )?;
+
+    // Run until completion
+    let start = Instant::now();
+    sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    block_on(sched.run());
+    let elapsed = start.elapsed();
+
+    // All threads spent most of their time sleeping on long timers, so the scheduler
+    // should have parked in the meantime, instead of repeatedly waking up to check
+    let stats = sched.wakeup_stats();
+    println!("Wakeup stats: {stats:?}");
+    assert!(stats.wakeups < 50);
+    assert!(stats.time_parked >= Duration::from_millis(50));
+
+    // One thread spent a while busy, during which the scheduler was not parked
+    assert!(stats.time_parked + Duration::from_millis(150) <= elapsed);
+
+    // Waking up to exit is also a wakeup
+    let exit = lua.create_function(|lua, ()| {
+        lua.set_exit_code(ExitCode::SUCCESS);
+        Ok(())
+    })?;
+    let wakeups = sched.wakeup_stats().wakeups;
+    sched.push_thread_front(exit, ())?;
+    block_on(sched.run());
+    assert!(sched.wakeup_stats().wakeups > wakeups);
+
+    Ok(())
+}
+
+#[test]
+fn test_parking() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,53 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::Scheduler;
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+
+    let ok_id = sched.push_thread_front(lua.load("return 1, { answer = 42 }"), ())?;
+    let err_id = sched.push_thread_front(lua.load("error('oh no')"), ())?;
+
+    // Results are not available until the threads have completed
+    assert!(sched.peek_thread_result(ok_id).is_none());
+    block_on(sched.run());
+
+    // Peeking may happen any number of times, and always sees the same values
+    let first = sched.peek_thread_result(ok_id).unwrap()?.into_vec();
+    let second = sched.peek_thread_result(ok_id).unwrap()?.into_vec();
+    assert_eq!(first[0], LuaValue::Number(1.0));
+    assert_eq!(first, second);
+    let table = LuaTable::from_lua(second[1].clone(), &lua)?;
+    assert_eq!(table.get::<_, u8>("answer")?, 42);
+
+    // Errors may be peeked the same way
+    for _ in 0..2 {
+        let err = sched.peek_thread_result(err_id).unwrap().unwrap_err();
+        assert!(err.to_string().contains("oh no"));
+    }
+
+    // Taking the result afterwards still works, but only once
+    let taken = sched.get_thread_result(ok_id).unwrap()?.into_vec();
+    assert_eq!(taken, first);
+    assert!(sched.peek_thread_result(ok_id).is_none());
+    assert!(sched.get_thread_result(ok_id).is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_peek_results() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,88 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::{block_on, Timer};
+use futures_lite::future;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, LuaSchedulerExt, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/pending_sleeps.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment, with async functions using the scheduler clock
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set(
+        "sleep",
+        lua.create_async_function(|lua, secs: f64| async move {
+            Ok(lua.sleep(Duration::from_secs_f64(secs)).await.as_secs_f64())
+        })?,
+    )?;
+    lua.globals().set(
+        "fetch",
+        lua.create_async_function(|lua, (latency, timeout): (f64, f64)| async move {
+            let response = async move {
+                lua.sleep(Duration::from_secs_f64(latency)).await;
+                "response"
+            };
+            lua.timeout(Duration::from_secs_f64(timeout), response)
+                .await
+        })?,
+    )?;
+
+    // Nothing should be sleeping before the scheduler runs
+    assert_eq!(sched.pending_sleeps(), 0);
+    assert_eq!(sched.next_sleep_wakeup(), None);
+
+    // Observe sleeps from the host while the scheduler is running
+    let id = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    let observe = async {
+        Timer::after(Duration::from_millis(5)).await;
+        (sched.pending_sleeps(), sched.next_sleep_wakeup())
+    };
+    let ((), (pending, next)) = block_on(future::zip(sched.run(), observe));
+    sched.get_thread_result(id).unwrap()?;
+
+    // Three plain sleeps, plus the sleep and timeout of the first fetch
+    assert_eq!(pending, 5);
+    assert!(next.unwrap() <= Duration::from_millis(50));
+
+    // Sleeps that completed or were cancelled by timeouts are no longer pending
+    assert_eq!(sched.pending_sleeps(), 0);
+    assert_eq!(sched.next_sleep_wakeup(), None);
+
+    // Delayed threads are pending sleeps too, but their shared timer is not
+    for _ in 0..100 {
+        sched.push_thread_delayed(lua.load("return"), (), Duration::from_millis(20))?;
+    }
+    assert_eq!(sched.pending_sleeps(), 100);
+    assert!(sched.next_sleep_wakeup().unwrap() <= Duration::from_millis(20));
+    let id = sched.push_thread_front(lua.load("sleep(0.05)"), ())?;
+    let observe = async {
+        Timer::after(Duration::from_millis(5)).await;
+        sched.pending_sleeps()
+    };
+    let ((), pending) = block_on(future::zip(sched.run(), observe));
+    sched.get_thread_result(id).unwrap()?;
+    assert_eq!(pending, 101);
+    assert_eq!(sched.pending_sleeps(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_pending_sleeps() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,101 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{cell::RefCell, rc::Rc};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{
+    unstable::{SchedulerPlugin, ThreadEvent},
+    ExitReason, Functions, Scheduler,
+};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/plugins.luau");
+
+/**
+    A plugin that registers a builtin, and records everything that happens in the scheduler.
+*/
+#[derive(Default, Clone)]
+struct RecorderPlugin {
+    log: Rc<RefCell<Vec<String>>>,
+}
+
+impl SchedulerPlugin for RecorderPlugin {
+    fn on_init(&self, lua: &Lua) -> LuaResult<()> {
+        let fns = Functions::new(lua)?;
+        lua.globals().set("defer", fns.defer)?;
+        lua.globals().set("plugin_version", "1.0")?;
+        self.log.borrow_mut().push("init".to_string());
+        Ok(())
+    }
+
+    fn on_thread_event(&self, _: &Lua, event: &ThreadEvent) {
+        let name = match event {
+            ThreadEvent::Resumed(_) => "resumed",
+            ThreadEvent::Yielded(_) => "yielded",
+            ThreadEvent::Completed(_) => "completed",
+            ThreadEvent::Errored(_, _) => "errored",
+        };
+        self.log.borrow_mut().push(name.to_string());
+    }
+
+    fn on_shutdown(&self, _: &Lua, reason: ExitReason) {
+        self.log.borrow_mut().push(format!("shutdown: {reason:?}"));
+    }
+}
+
+/**
+    A plugin that counts the number of scheduler cycles.
+*/
+#[derive(Default, Clone)]
+struct CyclesPlugin {
+    cycles: Rc<RefCell<usize>>,
+}
+
+impl SchedulerPlugin for CyclesPlugin {
+    fn on_tick(&self, _: &Lua) {
+        *self.cycles.borrow_mut() += 1;
+    }
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment, composing both plugins
+    let recorder = RecorderPlugin::default();
+    let cycles = CyclesPlugin::default();
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua)
+        .with_plugin(recorder.clone())?
+        .with_plugin(cycles.clone())?;
+    sched.set_error_callback(|_| {});
+
+    // Run until completion
+    sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    block_on(sched.run());
+
+    // The main thread and the deferred thread should have
+    // both been reported, and the scheduler should have cycled
+    let log = recorder.log.borrow();
+    println!("Recorded: {log:?}");
+    println!("Cycles: {}", cycles.cycles.borrow());
+    assert_eq!(log.first().map(String::as_str), Some("init"));
+    assert_eq!(log.last().map(String::as_str), Some("shutdown: Completed"));
+    assert_eq!(log.iter().filter(|e| *e == "resumed").count(), 3);
+    assert_eq!(log.iter().filter(|e| *e == "yielded").count(), 1);
+    assert_eq!(log.iter().filter(|e| *e == "completed").count(), 1);
+    assert_eq!(log.iter().filter(|e| *e == "errored").count(), 1);
+    assert!(*cycles.cycles.borrow() > 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_plugins() -> LuaResult<()> {
+    main()
+}
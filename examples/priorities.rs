@@ -0,0 +1,63 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Priority, Scheduler};
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+
+    let order = lua.create_table()?;
+    lua.globals().set("order", order.clone())?;
+    let record = lua
+        .load("local name = ... table.insert(order, name)")
+        .into_function()?;
+
+    // Push threads from least to most important, interleaving priorities
+    for (name, priority) in [
+        ("cosmetic 1", Priority::Low),
+        ("gameplay 1", Priority::Normal),
+        ("network 1", Priority::High),
+        ("cosmetic 2", Priority::Low),
+        ("gameplay 2", Priority::Normal),
+        ("network 2", Priority::High),
+    ] {
+        sched.push_thread_with_priority(&record, name, priority)?;
+    }
+
+    // Run until completion
+    block_on(sched.run());
+
+    // Higher priorities should have run first, keeping push order within each priority
+    let order = order
+        .sequence_values::<String>()
+        .collect::<LuaResult<Vec<_>>>()?;
+    assert_eq!(
+        order,
+        vec![
+            "network 1",
+            "network 2",
+            "gameplay 1",
+            "gameplay 2",
+            "cosmetic 1",
+            "cosmetic 2",
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_priorities() -> LuaResult<()> {
+    main()
+}
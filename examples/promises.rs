@@ -0,0 +1,66 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::{block_on, Timer};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, LuaSchedulerExt, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/promises.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set(
+        "fetch",
+        lua.create_promise_function(|_, (secs, value): (f64, String)| async move {
+            Timer::after(Duration::from_secs_f64(secs)).await;
+            if value.is_empty() {
+                Err(LuaError::runtime("nothing to fetch"))
+            } else {
+                Ok(value)
+            }
+        })?,
+    )?;
+
+    // Load the main script into the scheduler, and keep track of the thread we spawn
+    let main = lua.load(MAIN_SCRIPT);
+    let id = sched.push_thread_front(main, ())?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // The main thread should have gotten the results of all promises, in order
+    let res = sched.get_thread_result(id).unwrap().unwrap();
+    let results = Vec::<String>::from_lua_multi(res, &lua)?;
+    assert_eq!(
+        results,
+        vec![
+            "called",
+            "first",
+            "second",
+            "HELLO",
+            "caught nothing to fetch",
+            "passed through nothing to fetch",
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_promises() -> LuaResult<()> {
+    main()
+}
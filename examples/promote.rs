@@ -0,0 +1,79 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{cell::RefCell, rc::Rc, time::Instant};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/promote.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("defer", fns.defer)?;
+    lua.globals().set("promote", fns.promote)?;
+
+    // Defer a thread from Rust, and then promote it ahead of the main script
+    let urgent = lua.create_function(|_, ()| {
+        println!("Urgent host thread ran");
+        Ok(())
+    })?;
+    let main_id = sched.push_thread_back(lua.load(MAIN_SCRIPT), ())?;
+    let urgent_id = sched.push_thread_back(urgent, ())?;
+    assert!(sched.promote(urgent_id)?);
+    assert!(!sched.promote(urgent_id)?);
+
+    // Run until completion
+    block_on(sched.run());
+
+    // The urgent Lua thread should have run before the other deferred threads
+    let order = LuaFunction::from_lua_multi(sched.get_thread_result(main_id).unwrap()?, &lua)?;
+    let order = order.call::<_, String>(())?;
+    println!("Order: {order}");
+    assert_eq!(order, "urgent,a,b");
+
+    // Promoting threads out of a long deferred queue should keep everything else in order
+    let ran = Rc::new(RefCell::new(Vec::new()));
+    let ids = (0..10_000)
+        .map(|index| {
+            let ran = Rc::clone(&ran);
+            let func = lua.create_function(move |_, ()| {
+                ran.borrow_mut().push(index);
+                Ok(())
+            })?;
+            sched.push_thread_back(func, ())
+        })
+        .collect::<LuaResult<Vec<_>>>()?;
+    let start = Instant::now();
+    for id in ids.iter().rev().take(1_000) {
+        assert!(sched.promote(*id)?);
+    }
+    println!(
+        "Promoted 1000 of 10000 deferred threads in {:?}",
+        start.elapsed()
+    );
+    block_on(sched.run());
+
+    // Each promoted thread is pushed to the front, so the last one promoted runs first
+    let expected = (9_000..10_000).chain(0..9_000).collect::<Vec<_>>();
+    assert_eq!(*ran.borrow(), expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_promote() -> LuaResult<()> {
+    main()
+}
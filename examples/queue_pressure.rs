@@ -0,0 +1,59 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::sync::{Arc, Mutex};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{QueuePressure, Scheduler};
+
+const NUM_THREADS: usize = 2_500;
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+
+    // Keep track of all pressure changes
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let changes_inner = Arc::clone(&changes);
+    sched.set_queue_pressure_callback([1_000, 2_000], move |pressure| {
+        changes_inner.lock().unwrap().push(pressure);
+    });
+
+    // Flood the scheduler with threads, exceeding both thresholds
+    let noop = lua.create_function(|_, ()| Ok(()))?;
+    for _ in 0..NUM_THREADS {
+        sched.push_thread_back(noop.clone(), ())?;
+    }
+
+    // Run until completion
+    block_on(sched.run());
+
+    // Pressure should have risen above both thresholds, and then dropped back down once drained
+    let changes = changes.lock().unwrap().clone();
+    assert_eq!(
+        changes,
+        vec![
+            QueuePressure {
+                level: 2,
+                depth: NUM_THREADS
+            },
+            QueuePressure { level: 0, depth: 0 },
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_queue_pressure() -> LuaResult<()> {
+    main()
+}
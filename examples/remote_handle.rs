@@ -0,0 +1,103 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{ChunkOptions, Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/remote_handle.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let callback_errors = Arc::clone(&errors);
+    sched.set_error_callback(move |e| callback_errors.lock().unwrap().push(e.to_string()));
+
+    let records = Rc::new(RefCell::new(Vec::new()));
+    let record_records = Rc::clone(&records);
+    let record = lua.create_function(move |_, (source, value): (String, LuaValue)| {
+        record_records
+            .borrow_mut()
+            .push((source, value.as_i64().unwrap_or_default()));
+        Ok(())
+    })?;
+    lua.globals().set("record", record.clone())?;
+
+    // Register some functions that other threads may call by name
+    sched.register_function("record", record)?;
+    sched.register_function("stop", fns.exit)?;
+
+    // Push work from other threads, which are free to compile bytecode on their own
+    let handle = sched.handle();
+    let workers = (1..=3)
+        .map(|index| {
+            let handle = handle.clone();
+            thread::spawn(move || {
+                let bytecode = ChunkOptions::default().compile(MAIN_SCRIPT);
+                handle.push_bytecode("worker", bytecode, ("bytecode", index))?;
+                handle.push_registered("record", ("registered", index * 10))?;
+                handle.push_registered("missing", ())
+            })
+        })
+        .collect::<Vec<_>>();
+    for worker in workers {
+        worker.join().unwrap()?;
+    }
+
+    // Once all work has been pushed, stop the scheduler from yet another thread
+    let stopper = handle.clone();
+    thread::spawn(move || stopper.push_registered("stop", ()))
+        .join()
+        .unwrap()?;
+
+    // Run until stopped, the scheduler would otherwise run forever waiting for more work
+    block_on(sched.run_forever());
+
+    // All of the work should have run, in the order it was pushed per thread
+    let records = records.borrow();
+    assert_eq!(records.len(), 6);
+    for index in 1..=3 {
+        let bytecode = records
+            .iter()
+            .position(|r| *r == ("bytecode".into(), index));
+        let registered = records
+            .iter()
+            .position(|r| *r == ("registered".into(), index * 10));
+        assert!(bytecode.unwrap() < registered.unwrap());
+    }
+
+    // Calling functions that were never registered should error
+    let errors = errors.lock().unwrap();
+    assert_eq!(errors.len(), 3);
+    assert!(errors.iter().all(|e| e.contains("'missing'")));
+
+    // Once the scheduler is dropped, no more work can be pushed
+    drop(sched);
+    assert!(handle.is_closed());
+    assert!(handle.push_registered("record", ()).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_remote_handle() -> LuaResult<()> {
+    main()
+}
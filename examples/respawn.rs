@@ -0,0 +1,53 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::Scheduler;
+
+const MAIN_SCRIPT: &str = include_str!("./lua/respawn.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment, retaining origins of pushed jobs
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    sched.remove_error_callback();
+    sched.set_respawn_retention(16);
+
+    lua.globals().set("attempts", lua.create_table()?)?;
+
+    // Push a job as a chunk, without keeping a copy of it around
+    let mut id = sched.push_thread_back(lua.load(MAIN_SCRIPT), "download")?;
+
+    // Retry the job until it succeeds, respawning it from the retained chunk and arguments
+    let mut attempts = 0;
+    let result = loop {
+        attempts += 1;
+        block_on(sched.run());
+        match sched.get_thread_result(id).unwrap() {
+            Ok(values) => break String::from_lua_multi(values, &lua)?,
+            Err(_) => id = sched.respawn(id)?,
+        }
+    };
+    assert_eq!(attempts, 3);
+    assert_eq!(result, "download succeeded");
+
+    // Threads pushed directly as threads have no origin, and can not be respawned
+    let thread = lua.create_thread(lua.load(MAIN_SCRIPT).into_function()?)?;
+    let id = sched.push_thread_back(thread, "upload")?;
+    assert!(sched.respawn(id).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_respawn() -> LuaResult<()> {
+    main()
+}
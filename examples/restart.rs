@@ -0,0 +1,76 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/restart.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment, with an error callback that is kept across runs
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    let errors = Arc::new(AtomicUsize::new(0));
+    let errors_inner = Arc::clone(&errors);
+    sched.set_error_callback(move |e| {
+        println!("Thread errored: {e}");
+        errors_inner.fetch_add(1, Ordering::Relaxed);
+    });
+
+    lua.globals().set("defer", fns.defer)?;
+    lua.globals().set("exit", fns.exit)?;
+
+    // Run the first batch, which exits before its deferred work gets to run
+    let quick = sched.push_thread_front(lua.create_function(|_, ()| Ok(7))?, ())?;
+    sched.push_thread_front(lua.load(MAIN_SCRIPT), 1)?;
+    block_on(sched.run());
+    assert_eq!(sched.get_exit_code(), Some(ExitCode::from(1)));
+
+    // Running again right away would only exit again, so restart first
+    sched.restart();
+    assert_eq!(sched.get_exit_code(), None);
+
+    // Run the second batch, which also resumes the work left over from the first batch
+    let second = sched.push_thread_front(lua.load(MAIN_SCRIPT), 2)?;
+    block_on(sched.run());
+    assert_eq!(sched.get_exit_code(), None);
+
+    // Results from both batches are kept, and the error callback was called for both
+    let quick = i64::from_lua_multi(sched.get_thread_result(quick).unwrap()?, &lua)?;
+    assert_eq!(quick, 7);
+    assert!(sched.get_thread_result(second).unwrap().is_ok());
+    assert_eq!(errors.load(Ordering::Relaxed), 2);
+
+    let ran = lua
+        .globals()
+        .get::<_, LuaTable>("ran")?
+        .sequence_values::<i64>()
+        .collect::<LuaResult<Vec<_>>>()?;
+    println!("Deferred work ran for batches {ran:?}");
+    assert_eq!(ran, vec![1, 2]);
+
+    Ok(())
+}
+
+#[test]
+fn test_restart() -> LuaResult<()> {
+    main()
+}
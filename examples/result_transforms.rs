@@ -0,0 +1,57 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::Scheduler;
+
+const MAIN_SCRIPT: &str = include_str!("./lua/result_transforms.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    sched.remove_error_callback();
+
+    // Turn all errors into plain string values, for every thread
+    sched.set_result_transform(|lua, res| match res {
+        Ok(v) => Ok(v),
+        Err(e) => format!("transformed: {e}").into_lua_multi(lua),
+    });
+
+    // Load the main script into the scheduler twice, and keep track of the threads we spawn
+    let main = lua.load(MAIN_SCRIPT).into_function()?;
+    let id_erroring = sched.push_thread_front(&main, true)?;
+    let id_counting = sched.push_thread_front(&main, false)?;
+
+    // Only keep the amount of values for the second thread
+    sched.set_thread_result_transform(id_counting, |lua, res| {
+        res.and_then(|v| v.len().into_lua_multi(lua))
+    });
+
+    // Run until completion
+    block_on(sched.run());
+
+    // We should have gotten transformed values back from our script
+    let res = sched.get_thread_result(id_erroring).unwrap()?;
+    let message = String::from_lua_multi(res, &lua)?;
+    assert!(message.starts_with("transformed: "));
+
+    let res = sched.get_thread_result(id_counting).unwrap()?;
+    let count = usize::from_lua_multi(res, &lua)?;
+    assert_eq!(count, 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_result_transforms() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,83 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{cell::RefCell, collections::HashMap, pin::pin};
+
+use async_io::block_on;
+use futures_lite::{future, StreamExt};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/results_stream.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+    sched.set_error_callback(|_| {});
+
+    lua.globals().set("wait", fns.wait)?;
+    let job = lua.load(MAIN_SCRIPT).into_function()?;
+
+    // Push some jobs, finishing in reverse order, and one that fails
+    let jobs = RefCell::new(HashMap::new());
+    for index in 1..=5 {
+        let secs = f64::from(6 - index) * 0.02;
+        let id = sched.push_thread_back(job.clone(), (index, secs, index == 3))?;
+        jobs.borrow_mut().insert(id, index);
+    }
+
+    // Consume results as they complete, pushing another job while running
+    let mut stream = pin!(sched.results_stream());
+    let received = RefCell::new(Vec::new());
+    let consume = async {
+        while let Some((id, result)) = stream.next().await {
+            let index = jobs.borrow()[&id];
+            received
+                .borrow_mut()
+                .push((index, result.map(LuaMultiValue::into_vec)));
+            if index == 5 {
+                let id = sched
+                    .push_thread_back(job.clone(), (6, 0.0, false))
+                    .expect("failed to push job");
+                jobs.borrow_mut().insert(id, 6);
+            }
+            if received.borrow().len() == 6 {
+                break;
+            }
+        }
+    };
+    block_on(future::zip(sched.run(), consume));
+
+    // Results should have arrived in the order that jobs completed
+    let received = received.into_inner();
+    let order = received.iter().map(|(index, _)| *index).collect::<Vec<_>>();
+    assert_eq!(order, vec![5, 6, 4, 3, 2, 1]);
+    for (index, result) in received {
+        if index == 3 {
+            assert!(result.unwrap_err().to_string().contains("job 3 failed"));
+        } else {
+            assert_eq!(result?, vec![LuaValue::Integer(index * 10)]);
+        }
+    }
+
+    // Streamed results are taken out of the scheduler
+    for id in jobs.borrow().keys() {
+        assert!(sched.get_thread_result(*id).is_none());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_results_stream() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,90 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler, ThreadId};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/resume_hooks.luau");
+
+/**
+    Accumulated time and resumptions for a single thread.
+*/
+#[derive(Debug, Default)]
+struct Usage {
+    resumptions: usize,
+    errors: usize,
+    time: Duration,
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("wait", fns.wait)?;
+    lua.globals().set("wrap", fns.wrap)?;
+
+    // Account for time spent in every thread, using a stack
+    // since threads may resume other threads while running
+    let started = Rc::new(RefCell::new(Vec::<Instant>::new()));
+    let usage = Rc::new(RefCell::new(HashMap::<ThreadId, Usage>::new()));
+    let started_before = Rc::clone(&started);
+    let usage_after = Rc::clone(&usage);
+    sched.set_resume_hooks(
+        move |_| started_before.borrow_mut().push(Instant::now()),
+        move |id, result| {
+            let start = started.borrow_mut().pop().expect("resumed before");
+            let mut usage = usage_after.borrow_mut();
+            let usage = usage.entry(id).or_default();
+            usage.resumptions += 1;
+            usage.time += start.elapsed();
+            if result.is_err() {
+                usage.errors += 1;
+            }
+        },
+    );
+
+    // Run the script, which spawns and wraps some threads
+    let id = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    block_on(sched.run());
+    sched.get_thread_result(id).unwrap()?;
+
+    let usage = usage.borrow();
+    for (id, usage) in usage.iter() {
+        println!("{id:?} {usage:?}");
+    }
+
+    // Main thread, the spawned thread, and the wrapped thread were all resumed
+    assert_eq!(usage.len(), 3);
+    assert_eq!(usage[&id].resumptions, 1);
+    let mut resumptions = usage.values().map(|u| u.resumptions).collect::<Vec<_>>();
+    resumptions.sort_unstable();
+    assert_eq!(resumptions, [1, 2, 2]);
+    assert!(usage
+        .values()
+        .all(|u| u.errors == 0 && u.time > Duration::ZERO));
+
+    Ok(())
+}
+
+#[test]
+fn test_resume_hooks() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,95 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{cell::RefCell, rc::Rc};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const CORPUS: &str = include_str!("./lua/roblox_compat.luau");
+
+struct Divergence {
+    name: String,
+    expected: Vec<String>,
+    observed: Vec<String>,
+}
+
+/**
+    Runs a single case from the corpus on a fresh [`Lua`] instance
+    and [`Scheduler`], returning its name, expected and observed order.
+*/
+fn run_case(index: usize) -> LuaResult<(String, Vec<String>, Vec<String>)> {
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("task", fns.task_lib(&lua)?)?;
+
+    let observed = Rc::new(RefCell::new(Vec::new()));
+    let observed_inner = Rc::clone(&observed);
+    let log = lua.create_function(move |_, event: String| {
+        observed_inner.borrow_mut().push(event);
+        Ok(())
+    })?;
+
+    let cases: LuaTable = lua.load(CORPUS).set_name("roblox_compat").eval()?;
+    let case: LuaTable = cases.get(index)?;
+    let name: String = case.get("name")?;
+    let expected: Vec<String> = case.get("expected")?;
+    let run: LuaFunction = case.get("run")?;
+
+    let id = sched.push_thread_back(run, log)?;
+    block_on(sched.run());
+    sched.get_thread_result(id).unwrap()?;
+
+    let observed = observed.borrow().clone();
+    Ok((name, expected, observed))
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Count the cases in the corpus, then run each one in isolation
+    let count = Lua::new().load(CORPUS).eval::<LuaTable>()?.raw_len();
+    assert!(count > 0, "corpus should contain at least one case");
+
+    let mut divergences = Vec::new();
+    for index in 1..=count {
+        let (name, expected, observed) = run_case(index)?;
+        if expected == observed {
+            println!("ok   {name}");
+        } else {
+            println!("diff {name}");
+            divergences.push(Divergence {
+                name,
+                expected,
+                observed,
+            });
+        }
+    }
+
+    // Report every divergence before failing, not just the first one
+    for d in &divergences {
+        println!("\n{}", d.name);
+        println!("  expected: {}", d.expected.join(", "));
+        println!("  observed: {}", d.observed.join(", "));
+    }
+    assert!(
+        divergences.is_empty(),
+        "{} of {count} cases diverged from Roblox task library ordering",
+        divergences.len()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_roblox_compat() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,157 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+//! A minimal script runner, wiring scheduler functions and async builtins together into
+//! a small runtime, which may be used as a template when embedding the scheduler.
+//!
+//! Pass a path to a Luau script to run it, otherwise a builtin demo script is run.
+//!
+//! Note that signal handling (such as exiting on ctrl+c) is not included, since it requires
+//! a platform-specific crate - once a signal is received, `Scheduler::set_exit_code` may
+//! be used from Rust to exit, which will also skip any Lua exit hooks.
+
+use std::{io::ErrorKind, io::Write, process::ExitCode};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, LuaSpawnExt, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/runtime.luau");
+
+const PROCESS_LUA: &str = r"
+local exitWithCleanup, tempDir = ...
+local hooks = {}
+return {
+    tempDir = tempDir,
+    onExit = function(f)
+        table.insert(hooks, f)
+    end,
+    exit = function(code)
+        exitWithCleanup(hooks, code)
+    end,
+}
+";
+
+/**
+    Injects the `task`, `fs`, `stdio` and `process` globals into the given Lua state.
+*/
+fn inject_globals(lua: &Lua) -> LuaResult<()> {
+    let fns = Functions::new(lua)?;
+    let globals = lua.globals();
+
+    globals.set("task", fns.task_lib(lua)?)?;
+
+    let fs = lua.create_table()?;
+    fs.set(
+        "readFile",
+        lua.create_async_function(|lua, path: String| async move {
+            let task = lua.spawn(async move {
+                match async_fs::read_to_string(path).await {
+                    Ok(s) => Ok(Some(s)),
+                    Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+                    Err(e) => Err(e),
+                }
+            });
+            task.await.into_lua_err()
+        })?,
+    )?;
+    fs.set(
+        "writeFile",
+        lua.create_async_function(|lua, (path, contents): (String, LuaString)| {
+            let contents = contents.as_bytes().to_vec();
+            async move {
+                lua.spawn(async_fs::write(path, contents))
+                    .await
+                    .into_lua_err()
+            }
+        })?,
+    )?;
+    fs.set(
+        "removeFile",
+        lua.create_async_function(|lua, path: String| async move {
+            lua.spawn(async_fs::remove_file(path)).await.into_lua_err()
+        })?,
+    )?;
+    globals.set("fs", fs)?;
+
+    let stdio = lua.create_table()?;
+    stdio.set(
+        "write",
+        lua.create_async_function(|lua, s: LuaString| {
+            let bytes = s.as_bytes().to_vec();
+            async move {
+                lua.spawn_blocking(move || {
+                    let mut stdout = std::io::stdout();
+                    stdout.write_all(&bytes)?;
+                    stdout.flush()
+                })
+                .await
+                .into_lua_err()
+            }
+        })?,
+    )?;
+    globals.set("stdio", stdio)?;
+
+    let temp_dir = std::env::temp_dir().to_string_lossy().to_string();
+    let process: LuaTable = lua
+        .load(PROCESS_LUA)
+        .set_name("=process")
+        .call((fns.exit_with_cleanup, temp_dir))?;
+    globals.set("process", process)?;
+
+    Ok(())
+}
+
+/**
+    Runs the given script to completion in a fresh runtime, returning its exit code.
+*/
+fn run(name: &str, source: &str) -> LuaResult<(Lua, Option<ExitCode>)> {
+    let lua = Lua::new();
+    let code = {
+        let sched = Scheduler::new(&lua);
+        inject_globals(&lua)?;
+
+        sched.push_thread_front(lua.load(source).set_name(name), ())?;
+        block_on(sched.run());
+
+        sched.get_exit_code()
+    };
+    Ok((lua, code))
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Run the script given as an argument, or the demo script
+    let path = if cfg!(test) {
+        None
+    } else {
+        std::env::args().nth(1)
+    };
+    let code = if let Some(path) = path {
+        let source = std::fs::read_to_string(&path).into_lua_err()?;
+        run(&format!("@{path}"), &source)?.1
+    } else {
+        let (lua, code) = run("@runtime.luau", MAIN_SCRIPT)?;
+        // The demo script should have run its exit hook and exited cleanly
+        assert_eq!(code, Some(ExitCode::SUCCESS));
+        assert!(lua.globals().get::<_, bool>("exited")?);
+        code
+    };
+
+    if let Some(code) = code {
+        println!("Exited with code {code:?}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_runtime() -> LuaResult<()> {
+    main()
+}
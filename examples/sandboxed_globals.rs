@@ -0,0 +1,58 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/sandboxed_globals.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+
+    // Sandbox away globals that the scheduler functions rely on,
+    // the scheduler should have captured its own copies of these
+    lua.load("coroutine = nil; unpack = nil; select = nil; error = nil; table = nil")
+        .exec()?;
+
+    let fns = Functions::new(&lua)?;
+
+    let results = lua.create_table()?;
+    lua.globals().set("results", results.clone())?;
+    lua.globals().set("wrap", fns.wrap)?;
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("cancel", fns.cancel)?;
+    lua.globals().set("exit", fns.exit)?;
+
+    // Load the main script into the scheduler
+    let main = lua.load(MAIN_SCRIPT);
+    sched.push_thread_front(main, ())?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // Verify that all of the scheduler functions kept working
+    let results = results
+        .sequence_values::<String>()
+        .collect::<LuaResult<Vec<_>>>()?;
+    assert_eq!(results, vec!["spawned", "wrapped", "exiting"]);
+
+    let code = sched.get_exit_code().unwrap_or_default();
+    assert!(format!("{code:?}").contains("(2)"));
+
+    Ok(())
+}
+
+#[test]
+fn test_sandboxed_globals() -> LuaResult<()> {
+    main()
+}
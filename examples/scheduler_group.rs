@@ -0,0 +1,78 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_io::{block_on, Timer};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{unstable::SchedulerGroup, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/scheduler_group.luau");
+
+fn create_tenant(name: &str, steps: &Arc<Mutex<Vec<String>>>) -> LuaResult<Lua> {
+    let lua = Lua::new();
+    lua.globals().set("tenant", name)?;
+    lua.globals().set(
+        "sleep",
+        lua.create_async_function(|_, duration: f64| async move {
+            Timer::after(Duration::from_secs_f64(duration)).await;
+            Ok(())
+        })?,
+    )?;
+
+    // Each tenant has its own Lua state, but they all report steps to the host
+    let steps = Arc::clone(steps);
+    lua.globals().set(
+        "record",
+        lua.create_function(move |_, step: String| {
+            steps.lock().unwrap().push(step);
+            Ok(())
+        })?,
+    )?;
+
+    Ok(lua)
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up two isolated tenants, each with their own Lua state and scheduler
+    let steps = Arc::new(Mutex::new(Vec::new()));
+    let lua_a = create_tenant("a", &steps)?;
+    let lua_b = create_tenant("b", &steps)?;
+    let sched_a = Scheduler::new(&lua_a);
+    let sched_b = Scheduler::new(&lua_b);
+    sched_a.push_thread_front(lua_a.load(MAIN_SCRIPT), ())?;
+    sched_b.push_thread_front(lua_b.load(MAIN_SCRIPT), ())?;
+
+    // Run both schedulers interleaved on this thread, until both complete
+    let mut group = SchedulerGroup::new();
+    group.add(&sched_a, Duration::from_millis(1));
+    group.add(&sched_b, Duration::from_millis(1));
+    block_on(group.run_all());
+
+    // Both tenants should have completed all of their steps, interleaved with each other
+    let steps = steps.lock().unwrap().clone();
+    assert_eq!(steps.len(), 6);
+    let a_done = steps
+        .iter()
+        .rposition(|step| step.starts_with('a'))
+        .unwrap();
+    let b_first = steps.iter().position(|step| step.starts_with('b')).unwrap();
+    assert!(b_first < a_done, "tenants did not run interleaved");
+
+    Ok(())
+}
+
+#[test]
+fn test_scheduler_group() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,110 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::collections::HashSet;
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{
+    unstable::{PoolStrategy, SchedulerPool},
+    ChunkOptions, Functions, Scheduler,
+};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/scheduler_pool.luau");
+
+const WORKERS: usize = 4;
+const JOBS: u32 = 16;
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up a pool of Lua states, each one with its own task library and registered functions
+    let init = |lua: &Lua, sched: &Scheduler| {
+        let fns = Functions::new(lua)?;
+        lua.globals().set("task", fns.task_lib(lua)?)?;
+        sched.set_error_callback(|_| {});
+        sched.register_function(
+            "worker",
+            lua.create_function(|_, ()| {
+                let name = std::thread::current()
+                    .name()
+                    .unwrap_or_default()
+                    .to_string();
+                Ok(name)
+            })?,
+        )?;
+        sched.register_function(
+            "fail",
+            lua.create_function(|_, ()| Err::<(), _>(LuaError::runtime("job failed")))?,
+        )?;
+        Ok(())
+    };
+    let pool = SchedulerPool::new(WORKERS, init)?;
+    assert_eq!(pool.size(), WORKERS);
+    assert_eq!(pool.strategy(), PoolStrategy::RoundRobin);
+
+    // Round-robin distributes jobs evenly across all workers
+    let workers = (0..WORKERS)
+        .map(|_| pool.push_registered("worker", ()))
+        .collect::<LuaResult<Vec<_>>>()?;
+    let names = workers
+        .into_iter()
+        .map(|id| block_on(pool.wait_for_result::<String>(id)))
+        .collect::<LuaResult<HashSet<_>>>()?;
+    assert_eq!(names.len(), WORKERS, "every worker should have run one job");
+
+    // Bytecode jobs run on all workers, and results are keyed by their pool-wide job id
+    let bytecode = ChunkOptions::default().compile(MAIN_SCRIPT);
+    let ids = (0..JOBS)
+        .map(|n| pool.push_bytecode("fib", bytecode.clone(), n + 10))
+        .collect::<LuaResult<Vec<_>>>()?;
+    for (n, id) in (0..JOBS).zip(ids) {
+        let (input, output): (u32, u64) = block_on(pool.wait_for_result(id))?;
+        assert_eq!(input, n + 10);
+        assert_eq!(output, fib(u64::from(input)));
+    }
+    assert!(pool.loads().iter().all(|load| *load == 0));
+
+    // Errors are kept as results, and results may only be taken once
+    let failed = pool.push_registered("fail", ())?;
+    let missing = pool.push_registered("missing", ())?;
+    let failed_err = block_on(pool.wait_for_result::<()>(failed)).unwrap_err();
+    let missing_err = block_on(pool.wait_for_result::<()>(missing)).unwrap_err();
+    assert!(failed_err.to_string().contains("job failed"));
+    assert!(missing_err.to_string().contains("'missing'"));
+    assert!(pool.take_result::<()>(failed).is_none());
+
+    // Least-loaded hands jobs to whichever workers are the least busy
+    let pool = pool.with_strategy(PoolStrategy::LeastLoaded);
+    let ids = (0..JOBS)
+        .map(|n| pool.push_bytecode("fib", bytecode.clone(), n))
+        .collect::<LuaResult<Vec<_>>>()?;
+    for id in ids {
+        block_on(pool.wait_for_result::<(u32, u64)>(id))?;
+    }
+    assert!(pool.loads().iter().all(|load| *load == 0));
+
+    // Init errors are returned when creating the pool
+    let err = SchedulerPool::new(2, |_, _| Err(LuaError::runtime("bad init"))).unwrap_err();
+    assert!(err.to_string().contains("bad init"));
+
+    Ok(())
+}
+
+fn fib(n: u64) -> u64 {
+    if n < 2 {
+        n
+    } else {
+        fib(n - 1) + fib(n - 2)
+    }
+}
+
+#[test]
+fn test_scheduler_pool() -> LuaResult<()> {
+    main()
+}
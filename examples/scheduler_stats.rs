@@ -0,0 +1,110 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::{block_on, Timer};
+use futures_lite::future;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, LuaSpawnExt, Scheduler, SchedulerStats};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/scheduler_stats.luau");
+
+/**
+    Prints the given stats using the Prometheus text exposition format.
+*/
+fn print_prometheus(stats: &SchedulerStats) {
+    let metrics = [
+        ("threads_spawned_total", "counter", stats.threads_spawned),
+        ("threads_deferred_total", "counter", stats.threads_deferred),
+        ("threads_resumed_total", "counter", stats.threads_resumed),
+        ("threads_errored_total", "counter", stats.threads_errored),
+        (
+            "threads_cancelled_total",
+            "counter",
+            stats.threads_cancelled,
+        ),
+        ("threads_alive", "gauge", stats.threads_alive as u64),
+        ("futures_spawned_total", "counter", stats.futures_spawned),
+        ("cycles", "gauge", stats.cycles),
+    ];
+    for (name, kind, value) in metrics {
+        println!("# TYPE luau_scheduler_{name} {kind}");
+        println!("luau_scheduler_{name} {value}");
+    }
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("defer", fns.defer)?;
+    lua.globals().set("cancel", fns.cancel)?;
+    lua.globals().set("wait", fns.wait)?;
+    lua.globals().set(
+        "background",
+        lua.create_function(|lua, ()| {
+            lua.spawn_local(async {
+                Timer::after(Duration::from_millis(1)).await;
+            });
+            Ok(())
+        })?,
+    )?;
+
+    // The error in the script is expected, don't print it
+    sched.set_error_callback(|_| {});
+
+    // Nothing has happened before the scheduler runs
+    assert_eq!(sched.stats(), SchedulerStats::default());
+
+    // Observe the stats from the host while the scheduler is running
+    let id = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    let observe = async {
+        Timer::after(Duration::from_millis(10)).await;
+        sched.stats()
+    };
+    let ((), during) = block_on(future::zip(sched.run(), observe));
+    sched.get_thread_result(id).unwrap()?;
+
+    // Only the waiting thread is still alive halfway through
+    assert_eq!(during.threads_alive, 1);
+
+    // Once done, the stats should be ready to export as metrics
+    let stats = sched.stats();
+    print_prometheus(&stats);
+    assert_eq!(stats.threads_spawned, 3);
+    assert_eq!(stats.threads_deferred, 2);
+    assert_eq!(stats.threads_resumed, 5);
+    assert_eq!(stats.threads_errored, 1);
+    assert_eq!(stats.threads_cancelled, 1);
+    assert_eq!(stats.threads_alive, 0);
+    assert_eq!(stats.futures_spawned, 1);
+    assert!(stats.cycles > 0);
+
+    // Threads pushed with lazy arguments or to the idle queue are counted the same way
+    let noop = lua.create_function(|_, ()| Ok(()))?;
+    sched.push_thread_front_with(noop.clone(), |lua| ().into_lua_multi(lua))?;
+    sched.push_thread_back_with(noop.clone(), |lua| ().into_lua_multi(lua))?;
+    sched.push_thread_idle(noop, ())?;
+    block_on(sched.run());
+    let stats = sched.stats();
+    assert_eq!(stats.threads_spawned, 4);
+    assert_eq!(stats.threads_deferred, 4);
+
+    Ok(())
+}
+
+#[test]
+fn test_scheduler_stats() -> LuaResult<()> {
+    main()
+}
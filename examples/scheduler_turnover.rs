@@ -0,0 +1,64 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/scheduler_turnover.luau");
+
+fn original_functions(lua: &Lua) -> LuaResult<(LuaFunction<'_>, LuaFunction<'_>)> {
+    let co = lua.globals().get::<_, LuaTable>("coroutine")?;
+    Ok((co.get("resume")?, co.get("wrap")?))
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up a persistent Lua environment, which will outlive many schedulers
+    let lua = Lua::new();
+    let originals = original_functions(&lua)?;
+
+    for round in 1..=3 {
+        // Create a new scheduler for every round, injecting its functions
+        let sched = Scheduler::new(&lua);
+        let fns = Functions::new(&lua)?;
+        fns.inject_compat(&lua)?;
+        assert_ne!(original_functions(&lua)?, originals);
+
+        // Dropping a clone of the scheduler should leave it attached, with everything injected
+        drop(sched.clone());
+        assert_ne!(original_functions(&lua)?, originals);
+
+        // Run until completion
+        lua.globals().set("round", round)?;
+        let id = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+        block_on(sched.run());
+        sched.get_thread_result(id).unwrap()?;
+
+        // Dropping the scheduler should restore the original functions
+        drop(fns);
+        drop(sched);
+        assert_eq!(original_functions(&lua)?, originals);
+    }
+
+    // Injected functions may also be restored manually, without dropping the scheduler
+    let _sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+    fns.inject_compat(&lua)?;
+    fns.inject_compat(&lua)?;
+    Functions::uninject(&lua)?;
+    assert_eq!(original_functions(&lua)?, originals);
+
+    Ok(())
+}
+
+#[test]
+fn test_scheduler_turnover() -> LuaResult<()> {
+    main()
+}
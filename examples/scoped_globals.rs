@@ -0,0 +1,68 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/scoped_globals.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+    sched.set_error_callback(|_| {});
+
+    lua.globals().set("wait", fns.wait)?;
+    lua.globals().set("request", "original")?;
+
+    let func = lua.load(MAIN_SCRIPT).into_function()?;
+    let globals_for =
+        |request: &str| -> LuaResult<LuaTable> { lua.create_table_from([("request", request)]) };
+
+    // Overrides should not be installed until the thread is first resumed
+    let first = sched.push_thread_with_globals(func.clone(), (0.01, false), &globals_for("a")?)?;
+    let second = sched.push_thread_with_globals(func.clone(), (0.02, false), &globals_for("b")?)?;
+    let failing = sched.push_thread_with_globals(func.clone(), (0.0, true), &globals_for("c")?)?;
+    assert_eq!(lua.globals().get::<_, String>("request")?, "original");
+
+    // Cancelled threads should never install their overrides
+    let cancelled = lua.create_thread(func)?;
+    sched.set_thread_tag(&cancelled, "cancelled")?;
+    sched.push_thread_with_globals(cancelled, (0.0, false), &globals_for("d")?)?;
+    sched.cancel_by_tag("cancelled")?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // Each thread should have seen its own override when it started, and the most
+    // recently installed override that remained while it was running afterwards
+    let result = |id| -> LuaResult<(String, String)> {
+        let values = sched.get_thread_result(id).unwrap()?;
+        lua.unpack_multi(values)
+    };
+    assert_eq!(result(first)?, ("a".to_string(), "b".to_string()));
+    assert_eq!(result(second)?, ("b".to_string(), "b".to_string()));
+
+    // Overrides should be removed even when a thread errors
+    let err = sched.get_thread_result(failing).unwrap().unwrap_err();
+    assert!(err.to_string().contains("request c failed"));
+
+    // Once every thread has completed, the original global should be back
+    assert_eq!(lua.globals().get::<_, String>("request")?, "original");
+
+    Ok(())
+}
+
+#[test]
+fn test_scoped_globals() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,65 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Instant;
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, LuaSpawnExt, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/single_threaded.luau");
+
+fn run_script(single_threaded: bool) -> LuaResult<u64> {
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    sched.set_single_threaded(single_threaded);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set(
+        "double",
+        lua.create_async_function(|lua, n: u64| async move {
+            // Spawned futures run on the main executor, which is
+            // a local executor when in single-threaded mode
+            let doubled = lua.spawn(async move { n * 2 }).await;
+            let blocking = lua.spawn_blocking(move || n * 2).await;
+            assert_eq!(doubled, blocking);
+            Ok(doubled)
+        })?,
+    )?;
+
+    // Load the main script into the scheduler, and run until completion
+    sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    let start = Instant::now();
+    block_on(sched.run());
+    println!(
+        "Ran script in {:?} (single-threaded: {single_threaded})",
+        start.elapsed()
+    );
+
+    let result = lua.globals().get("result")?;
+    Ok(result)
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Both modes should behave exactly the same, only the executor differs
+    let multi = run_script(false)?;
+    let single = run_script(true)?;
+    assert_eq!(multi, single);
+    assert_eq!(single, 10_000 * 10_001);
+
+    Ok(())
+}
+
+#[test]
+fn test_single_threaded() -> LuaResult<()> {
+    main()
+}
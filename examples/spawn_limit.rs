@@ -0,0 +1,64 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::Scheduler;
+
+const SPAWNED: usize = 100;
+const LIMIT: usize = 10;
+
+/**
+    Floods the spawned queue, pushes a single deferred thread, and
+    returns the position that the deferred thread was resumed at.
+*/
+fn deferred_position(max_spawned: Option<usize>) -> LuaResult<usize> {
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    sched.set_max_spawned_per_cycle(max_spawned);
+
+    let order = lua.create_table()?;
+    lua.globals().set("order", order.clone())?;
+
+    let record = lua
+        .load("return function(name) table.insert(order, name) end")
+        .eval::<LuaFunction>()?;
+    for _ in 0..SPAWNED {
+        sched.push_thread_front(record.clone(), "spawned")?;
+    }
+    sched.push_thread_back(record, "deferred")?;
+
+    block_on(sched.run());
+
+    let order = order
+        .sequence_values::<String>()
+        .collect::<LuaResult<Vec<_>>>()?;
+    assert_eq!(order.len(), SPAWNED + 1);
+    Ok(order.iter().position(|name| name == "deferred").unwrap())
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // By default, the deferred thread has to wait for every spawned thread
+    let unlimited = deferred_position(None)?;
+    println!("Deferred thread resumed at position {unlimited} without a limit");
+    assert_eq!(unlimited, SPAWNED);
+
+    // With a limit, the deferred thread gets to run during the very first cycle
+    let limited = deferred_position(Some(LIMIT))?;
+    println!("Deferred thread resumed at position {limited} with a limit of {LIMIT}");
+    assert_eq!(limited, LIMIT);
+
+    Ok(())
+}
+
+#[test]
+fn test_spawn_limit() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,54 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::{Duration, Instant};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const ITERATIONS: u32 = 250;
+
+fn create_and_run() -> LuaResult<Duration> {
+    let start = Instant::now();
+
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+    lua.globals().set("spawn", fns.spawn)?;
+
+    let main = lua.load("spawn(function() end)");
+    sched.push_thread_front(main, ())?;
+    block_on(sched.run());
+
+    Ok(start.elapsed())
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // The first scheduler compiles all of the internal trampoline
+    // functions, every scheduler after it should reuse those instead
+    let first = create_and_run()?;
+
+    let mut total = Duration::ZERO;
+    for _ in 0..ITERATIONS {
+        total += create_and_run()?;
+    }
+    let average = total / ITERATIONS;
+
+    println!("First scheduler startup: {first:?}");
+    println!("Average scheduler startup: {average:?} ({ITERATIONS} iterations)");
+
+    Ok(())
+}
+
+#[test]
+fn test_startup() -> LuaResult<()> {
+    main()
+}
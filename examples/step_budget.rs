@@ -0,0 +1,59 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/step_budget.luau");
+
+const FRAME_BUDGET: Duration = Duration::from_millis(5);
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("completed", 0)?;
+    lua.globals().set("defer", fns.defer)?;
+
+    // Load the main script into the scheduler
+    let main = lua.load(MAIN_SCRIPT);
+    sched.push_thread_front(main, ())?;
+
+    // Give the scheduler a small budget per frame, leaving the rest of the work for later frames
+    let mut stepper = sched.stepper();
+    let mut per_frame = Vec::new();
+    loop {
+        let before = lua.globals().get::<_, usize>("completed")?;
+        let more = block_on(stepper.step_budgeted(FRAME_BUDGET));
+        let after = lua.globals().get::<_, usize>("completed")?;
+        per_frame.push(after - before);
+        if !more {
+            break;
+        }
+    }
+
+    // No single frame should have run all of the deferred work
+    println!("Completed work per frame: {per_frame:?}");
+    assert_eq!(per_frame.iter().sum::<usize>(), 50);
+    assert!(per_frame.iter().all(|&completed| completed < 50));
+    assert!(per_frame.iter().filter(|&&completed| completed > 0).count() > 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_step_budget() -> LuaResult<()> {
+    main()
+}
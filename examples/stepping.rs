@@ -0,0 +1,90 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::{block_on, Timer};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{ExitReason, Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/stepping.luau");
+
+const FRAME_TIME: Duration = Duration::from_millis(16);
+
+fn order(lua: &Lua) -> LuaResult<Vec<String>> {
+    lua.globals()
+        .get::<_, LuaTable>("order")?
+        .sequence_values::<String>()
+        .collect()
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("order", lua.create_table()?)?;
+    lua.globals().set("defer", fns.defer)?;
+    lua.globals().set("wait", fns.wait)?;
+
+    // Load the main script into the scheduler
+    let main = lua.load(MAIN_SCRIPT);
+    sched.push_thread_front(main, ())?;
+
+    // Each step performs a single iteration, resuming the threads that the previous step
+    // picked up from the queues, so the main thread runs during the second step, and
+    // each deferred thread runs one step after the thread that deferred it
+    let mut stepper = sched.stepper();
+    assert!(block_on(stepper.step()));
+    assert!(block_on(stepper.step()));
+    assert!(order(&lua)?.is_empty());
+    assert!(block_on(stepper.step()));
+    assert_eq!(order(&lua)?, vec!["first"]);
+    assert!(block_on(stepper.step()));
+    assert_eq!(order(&lua)?, vec!["first", "second"]);
+
+    // Drive the rest of the scheduler from a frame loop, until it has no more work to do
+    let mut frames = 0;
+    while block_on(stepper.step()) {
+        frames += 1;
+        block_on(Timer::after(FRAME_TIME));
+    }
+    assert!(stepper.is_done());
+    assert!(!block_on(stepper.step()));
+    drop(stepper);
+
+    // The sleeping thread should have spanned multiple frames
+    println!("Stepped through {frames} frames");
+    assert!(frames > 1);
+    assert_eq!(order(&lua)?, vec!["first", "second", "woke"]);
+    assert!(sched.status().is_completed());
+
+    // Dropping a stepper early stops the scheduler, so that it may be run or stepped again
+    sched.push_thread_front(lua.load("wait(10)"), ())?;
+    let mut stepper = sched.stepper();
+    assert!(block_on(stepper.step()));
+    drop(stepper);
+    assert!(matches!(
+        block_on(sched.wait_for_exit()),
+        ExitReason::Stopped
+    ));
+
+    sched.push_thread_front(lua.load("table.insert(order, 'again')"), ())?;
+    block_on(sched.run());
+    assert_eq!(order(&lua)?, vec!["first", "second", "woke", "again"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_stepping() -> LuaResult<()> {
+    main()
+}
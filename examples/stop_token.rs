@@ -0,0 +1,47 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::{block_on, Timer};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler, Status};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/stop_token.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("heartbeat", fns.heartbeat)?;
+
+    // Load the main script into the scheduler, it will never complete on its own
+    let main = lua.load(MAIN_SCRIPT);
+    sched.push_thread_front(main, ())?;
+
+    // Run until our shutdown signal fires
+    let shutdown = async {
+        Timer::after(Duration::from_millis(25)).await;
+    };
+    block_on(sched.run_until(shutdown));
+
+    // Verify that the scheduler stopped without an exit code
+    assert_eq!(sched.status(), Status::Completed);
+    assert!(sched.get_exit_code().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_stop_token() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,66 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler, ThreadId};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/strict_mode.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment, in strict mode
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    sched.set_strict(true);
+    let fns = Functions::new(&lua)?;
+    fns.inject_compat(&lua)?;
+
+    lua.globals().set("cancel", fns.cancel)?;
+    lua.globals().set("defer", fns.defer)?;
+
+    // Starting async work while the scheduler is not running should error
+    let err = fns.exit_after.call::<_, ()>(1.0).unwrap_err();
+    println!("Calling exit_after failed with: {err}");
+    assert!(err.to_string().contains("scheduler misuse"));
+
+    // Pushing a thread that has already completed should error
+    let completed = lua.create_thread(lua.create_function(|_, ()| Ok(()))?)?;
+    completed.resume::<_, ()>(())?;
+    let err = sched.push_thread_back(completed, ()).unwrap_err();
+    println!("Pushing a completed thread failed with: {err}");
+    assert!(err.to_string().contains("hint:"));
+
+    // Pushing a thread created by a different Lua state should error
+    let other = Lua::new();
+    let foreign = other.create_thread(other.create_function(|_, ()| Ok(()))?)?;
+    let err = sched.push_thread_back(foreign, ()).unwrap_err();
+    println!("Pushing a foreign thread failed with: {err}");
+    assert!(err.to_string().contains("different Lua state"));
+
+    // Getting the result for a thread that is not tracked should error
+    let untracked = lua.create_thread(lua.create_function(|_, ()| Ok(()))?)?;
+    let result = sched.get_thread_result(ThreadId::from(&untracked));
+    assert!(result.is_some_and(|r| r.is_err()));
+
+    // Misuse from Lua should also be descriptive, run until completion to check
+    let id = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    block_on(sched.run());
+    sched.get_thread_result(id).unwrap()?;
+
+    // Results may only be taken once
+    assert!(sched.get_thread_result(id).is_some_and(|r| r.is_err()));
+
+    Ok(())
+}
+
+#[test]
+fn test_strict_mode() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,68 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::sync::{Arc, Mutex};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler, ThreadError};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/structured_errors.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment, preserving non-string error values
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("error", fns.error)?;
+
+    // Collect all errors passed to the error callback
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let errors_inner = Arc::clone(&errors);
+    sched.set_error_callback(move |e| errors_inner.lock().unwrap().push(e));
+
+    // Load the main script into the scheduler, along with a thread that errors with a string
+    let main = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    let plain = sched.push_thread_front(lua.load("error('plain')"), ())?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // The error callback should have gotten the original table value
+    let errors = errors.lock().unwrap();
+    assert_eq!(errors.len(), 2);
+    let structured = errors
+        .iter()
+        .find_map(|e| ThreadError::from_error(&e.error))
+        .unwrap();
+    let value = LuaTable::from_lua(structured.value(&lua)?, &lua)?;
+    assert_eq!(value.get::<_, i64>("code")?, 404);
+    assert_eq!(value.get::<_, String>("message")?, "not found");
+
+    // And so should the result map, while string errors stay the same as before
+    let main_err = sched.get_thread_result(main).unwrap().unwrap_err();
+    let main_value = ThreadError::from_error(&main_err).unwrap().value(&lua)?;
+    assert_eq!(
+        LuaTable::from_lua(main_value, &lua)?.get::<_, i64>("code")?,
+        404
+    );
+
+    let plain_err = sched.get_thread_result(plain).unwrap().unwrap_err();
+    assert!(ThreadError::from_error(&plain_err).is_none());
+    assert!(plain_err.to_string().contains("plain"));
+
+    Ok(())
+}
+
+#[test]
+fn test_structured_errors() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,88 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{RestartOptions, RestartPolicy, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/supervisor.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+
+    // Load the services from the main script
+    let services: LuaTable = lua.load(MAIN_SCRIPT).eval()?;
+
+    // Keep track of all restarts, and don't print errors from crashing services
+    let restarts = Arc::new(Mutex::new(Vec::new()));
+    let restarts_inner = Arc::clone(&restarts);
+    sched.set_restart_callback(move |event| {
+        let errored = event.error.is_some();
+        restarts_inner
+            .lock()
+            .unwrap()
+            .push((event.name, event.restarts, errored));
+    });
+    sched.remove_error_callback();
+
+    // A flaky service that crashes twice before completing successfully
+    sched.supervise(
+        "flaky",
+        services.get("flaky")?,
+        RestartOptions {
+            policy: RestartPolicy::OnFailure,
+            backoff: Duration::from_millis(10),
+            max_restarts: Some(5),
+        },
+    )?;
+
+    // A service that completes successfully, but should always be restarted
+    sched.supervise(
+        "ticker",
+        services.get("ticker")?,
+        RestartOptions {
+            policy: RestartPolicy::Always,
+            backoff: Duration::ZERO,
+            max_restarts: Some(2),
+        },
+    )?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // Verify that services were restarted according to their policies
+    let mut restarts = restarts.lock().unwrap().clone();
+    restarts.sort();
+    assert_eq!(
+        restarts,
+        vec![
+            ("flaky".to_string(), 1, true),
+            ("flaky".to_string(), 2, true),
+            ("ticker".to_string(), 1, false),
+            ("ticker".to_string(), 2, false),
+        ]
+    );
+    assert_eq!(services.get::<_, u32>("flakyRuns")?, 3);
+    assert_eq!(services.get::<_, u32>("tickerRuns")?, 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_supervisor() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,68 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::{block_on, Timer};
+use futures_lite::future::zip;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::Scheduler;
+
+const MAIN_SCRIPT: &str = include_str!("./lua/suspend_threads.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+
+    lua.globals().set(
+        "sleep",
+        lua.create_async_function(|_, secs: f64| async move {
+            Timer::after(Duration::from_secs_f64(secs)).await;
+            Ok(())
+        })?,
+    )?;
+
+    let ran = lua.create_table()?;
+    lua.globals().set("ran", ran.clone())?;
+
+    // Push two threads, suspending the first one before it ever runs
+    let main = lua.load(MAIN_SCRIPT).into_function()?;
+    let a = sched.push_thread_back(&main, "a")?;
+    let b = sched.push_thread_back(&main, "b")?;
+    assert!(sched.suspend_thread(a));
+    assert!(!sched.suspend_thread(a));
+
+    // Run the scheduler, and resume the suspended thread once the other has finished
+    let resume_later = async {
+        Timer::after(Duration::from_millis(50)).await;
+        assert!(sched.is_thread_suspended(a));
+        assert!(sched.resume_suspended_thread(a));
+    };
+    block_on(zip(sched.run(), resume_later));
+
+    // The suspended thread should not have run until it was resumed
+    let ran = ran
+        .sequence_values::<String>()
+        .collect::<LuaResult<Vec<_>>>()?;
+    assert_eq!(
+        ran,
+        vec!["b started", "b finished", "a started", "a finished"]
+    );
+    assert!(!sched.is_thread_suspended(a));
+    assert!(sched.get_thread_result(b).is_some());
+
+    Ok(())
+}
+
+#[test]
+fn test_suspend_threads() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,65 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::{block_on, Timer};
+use futures_lite::{future, FutureExt};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/tags.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("heartbeat", fns.heartbeat)?;
+
+    // Spawn a few threads for two different plugins, tagging them at push time
+    let plugin: LuaFunction = lua.load(MAIN_SCRIPT).eval()?;
+    let finished = lua.create_table()?;
+    for name in ["a", "a", "a", "b"] {
+        let thread = lua.create_thread(plugin.clone())?;
+        sched.set_thread_tag(&thread, format!("plugin-{name}"))?;
+        sched.push_thread_back(thread, (name, finished.clone()))?;
+    }
+    assert_eq!(sched.find_threads("plugin-a")?.len(), 3);
+    assert_eq!(sched.find_threads("plugin-b")?.len(), 1);
+
+    // Run until completion, cancelling all threads of plugin A and letting plugin B finish
+    let host = async {
+        Timer::after(Duration::from_millis(10)).await;
+        let cancelled = sched.cancel_by_tag("plugin-a").expect("failed to cancel");
+        assert_eq!(cancelled, 3);
+        sched
+            .fire_tick(Duration::ZERO)
+            .expect("failed to fire tick");
+        future::pending::<()>().await;
+    };
+    block_on(sched.run().or(host));
+
+    // Only plugin B should have finished, and no threads should be left
+    let finished = finished
+        .sequence_values::<String>()
+        .collect::<LuaResult<Vec<_>>>()?;
+    assert_eq!(finished, vec!["b"]);
+    assert!(sched.find_threads("plugin-a")?.is_empty());
+    assert!(sched.find_threads("plugin-b")?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_tags() -> LuaResult<()> {
+    main()
+}
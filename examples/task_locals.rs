@@ -0,0 +1,99 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{LuaSchedulerExt, LuaSpawnExt, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/task_locals.luau");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RequestId(u32);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct User(String);
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment, which must be static
+    // for it to be usable inside of spawned background futures
+    let lua = Lua::new().into_static();
+    let logged = Rc::new(RefCell::new(Vec::new()));
+
+    lua.globals().set(
+        "requestId",
+        lua.create_function(|lua, ()| Ok(lua.task_local::<RequestId>().map(|id| id.0)))?,
+    )?;
+    lua.globals().set(
+        "whoami",
+        lua.create_function(|lua, ()| Ok(lua.task_local::<User>().map(|user| user.0)))?,
+    )?;
+    lua.globals().set(
+        "login",
+        lua.create_function(|lua, name: String| lua.set_task_local(User(name)))?,
+    )?;
+    lua.globals().set("query", {
+        let logged = Rc::clone(&logged);
+        lua.create_async_function(move |lua, ()| {
+            let logged = Rc::clone(&logged);
+            async move {
+                lua.sleep(Duration::from_millis(10)).await;
+
+                // Log the query in the background, without passing along the request
+                lua.spawn_local(async move {
+                    lua.sleep(Duration::from_millis(10)).await;
+                    let id = lua.task_local::<RequestId>().unwrap();
+                    let user = lua.task_local::<User>().unwrap();
+                    logged.borrow_mut().push((id, user));
+                });
+
+                let id = lua.task_local::<RequestId>().unwrap();
+                let user = lua.task_local::<User>().unwrap();
+                Ok(format!("{} ({})", user.0, id.0))
+            }
+        })?
+    })?;
+
+    // Push a couple of requests, each with their own request id
+    let sched = Scheduler::new(lua);
+    let main = lua.load(MAIN_SCRIPT).into_function()?;
+    let mut ids = Vec::new();
+    for (id, name) in [(1, "alice"), (2, "bob"), (3, "carol")] {
+        let thread = lua.create_thread(main.clone())?;
+        sched.set_task_local(&thread, RequestId(id))?;
+        ids.push(sched.push_thread_front(thread, (id, name))?);
+    }
+
+    // Run until completion
+    block_on(sched.run());
+
+    // All requests should have succeeded, and logged using their own values
+    for id in ids {
+        sched.get_thread_result(id).unwrap()?;
+    }
+    let mut logged = logged.borrow().clone();
+    logged.sort_by_key(|(id, _)| id.0);
+    assert_eq!(
+        logged,
+        vec![
+            (RequestId(1), User("alice".to_string())),
+            (RequestId(2), User("bob".to_string())),
+            (RequestId(3), User("carol".to_string())),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_task_locals() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,76 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, LuaSchedulerExt, Scheduler, ThreadId};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/task_objects.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawnTask", fns.spawn_task)?;
+    lua.globals().set("wait", fns.wait)?;
+    lua.globals().set("error", fns.error)?;
+    lua.globals().set(
+        "track",
+        lua.create_function(|lua, thread: LuaThread| {
+            lua.track_thread(ThreadId::from(&thread));
+            Ok(())
+        })?,
+    )?;
+    lua.globals().set(
+        "take",
+        lua.create_function(|lua, thread: LuaThread| {
+            match lua.get_thread_result(ThreadId::from(&thread)) {
+                Some(result) => result,
+                None => Ok(LuaMultiValue::new()),
+            }
+        })?,
+    )?;
+
+    // Load the main script into the scheduler, and keep track of the thread we spawn
+    let main = lua.load(MAIN_SCRIPT);
+    let id = sched.push_thread_front(main, ())?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // The main thread should have observed the results of all of its tasks
+    let res = sched.get_thread_result(id).unwrap().unwrap();
+    let results = Vec::<String>::from_lua_multi(res, &lua)?;
+    assert_eq!(
+        results,
+        vec![
+            "instant",
+            "sum 6",
+            "error oops",
+            "error code 42",
+            "cancelled",
+            "shared",
+            "taken"
+        ]
+    );
+
+    // Tasks remove the results they own once collected, so none of them should be left behind
+    lua.gc_collect()?;
+    assert_eq!(sched.tracking_stats().tracked, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_task_objects() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,68 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/thread_budget.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("wait", fns.wait)?;
+
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let callback_errors = Arc::clone(&errors);
+    sched.set_error_callback(move |e| callback_errors.lock().unwrap().push(e));
+
+    // No thread may run for longer than this without yielding
+    sched.set_thread_budget(Some(Duration::from_millis(20)));
+
+    // Push a runaway thread, one that tries to catch its budget error, and a well-behaved one
+    let main = lua.load(MAIN_SCRIPT).into_function()?;
+    let runaway = sched.push_thread_front(main.clone(), "runaway")?;
+    let caught = sched.push_thread_front(main.clone(), "caught")?;
+    let behaved = sched.push_thread_front(main, ())?;
+
+    // The scheduler should not freeze, even though one thread never yields
+    let start = Instant::now();
+    block_on(sched.run());
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    // The runaway thread errored, and the error was reported as usual
+    let err = sched.get_thread_result(runaway).unwrap().unwrap_err();
+    println!("Runaway thread errored: {err}");
+    assert!(err.to_string().contains("exceeded its budget"));
+
+    // Catching the error only delays it, every later interrupt errors until the thread stops
+    let err = sched.get_thread_result(caught).unwrap().unwrap_err();
+    assert!(err.to_string().contains("exceeded its budget"));
+    assert_eq!(errors.lock().unwrap().len(), 2);
+
+    // The well-behaved thread was unaffected
+    let values = sched.get_thread_result(behaved).unwrap()?;
+    assert_eq!(String::from_lua_multi(values, &lua)?, "done");
+
+    Ok(())
+}
+
+#[test]
+fn test_thread_budget() -> LuaResult<()> {
+    main()
+}
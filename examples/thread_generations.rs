@@ -0,0 +1,62 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+fn string<'lua>(lua: &'lua Lua, value: &str) -> LuaResult<LuaValue<'lua>> {
+    Ok(LuaValue::String(lua.create_string(value)?))
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+    lua.globals().set("spawn", fns.spawn)?;
+
+    // Run a thread to completion, leaving its result in the scheduler
+    let thread = lua.create_thread(lua.load("return 'first'").into_function()?)?;
+    let first_id = sched.push_thread_front(thread.clone(), ())?;
+    block_on(sched.run());
+
+    // Resetting the thread reuses its address, exactly like a new thread would after
+    // the old one was garbage collected, and it must not alias the result of the old one
+    thread.reset(lua.load("return 'second'").into_function()?)?;
+    let second_id = sched.push_thread_front(thread.clone(), ())?;
+    assert_ne!(first_id, second_id);
+    block_on(sched.run());
+
+    // Threads spawned from Lua are not tracked, and must never overwrite stored results
+    thread.reset(lua.load("return 'third'").into_function()?)?;
+    let main = lua.load("spawn(...)").into_function()?;
+    let main_id = sched.push_thread_front(main, thread.clone())?;
+    block_on(sched.run());
+    assert_eq!(thread.status(), LuaThreadStatus::Unresumable);
+    sched.get_thread_result(main_id).unwrap()?;
+
+    let first_result = sched.get_thread_result(first_id).unwrap()?;
+    let second_result = sched.get_thread_result(second_id).unwrap()?;
+    assert_eq!(first_result.into_vec(), vec![string(&lua, "first")?]);
+    assert_eq!(second_result.into_vec(), vec![string(&lua, "second")?]);
+
+    // Once all results have been taken, nothing is kept around for the reused address
+    let stats = sched.tracking_stats();
+    assert_eq!(stats.tracked, 0);
+    assert_eq!(stats.completed, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_thread_generations() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,64 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::block_on;
+use futures_lite::future::zip;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{LuaSchedulerExt, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/thread_handles.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+
+    lua.globals().set(
+        "sleep",
+        lua.create_async_function(|lua, secs: f64| {
+            let sleep = lua.sleep(Duration::from_secs_f64(secs));
+            async move { Ok(sleep.await.as_secs_f64()) }
+        })?,
+    )?;
+
+    // Push a couple of threads, and create handles for them
+    let main = lua.load(MAIN_SCRIPT).into_function()?;
+    let fast = sched.thread_handle(sched.push_thread_front(&main, ("fast", 0.01))?);
+    let slow = sched.thread_handle(sched.push_thread_front(&main, ("slow", 10.0))?);
+    assert!(!fast.is_finished());
+    assert!(!slow.is_finished());
+
+    // Join the fast thread while the scheduler runs, and cancel the slow one once it
+    // completes, which should then let the scheduler complete without waiting for it
+    let slow_joined = slow.clone();
+    let joined = async move {
+        let result = fast.join().await;
+        assert!(slow.cancel()?);
+        assert!(slow.is_finished());
+        assert!(!slow.cancel()?);
+        result
+    };
+    let ((), result) = block_on(zip(sched.run(), joined));
+    let result = String::from_lua_multi(result?, &lua)?;
+    assert_eq!(result, "fast finished");
+
+    // The slow thread was cancelled, which is stored as its result
+    let err = block_on(slow_joined.join()).unwrap_err();
+    assert!(err.to_string().contains("cancelled"));
+
+    Ok(())
+}
+
+#[test]
+fn test_thread_handles() -> LuaResult<()> {
+    main()
+}
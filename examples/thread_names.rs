@@ -0,0 +1,76 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::sync::{Arc, Mutex};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler, ThreadId};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/thread_names.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("defer", fns.defer)?;
+    lua.globals().set("wait", fns.wait)?;
+
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let callback_errors = Arc::clone(&errors);
+    sched.set_error_callback(move |e| callback_errors.lock().unwrap().push(e));
+
+    // Push a few jobs, some of them named, and one of them failing deep inside a spawned thread
+    let main = lua.load(MAIN_SCRIPT).into_function()?;
+    let failing = sched.push_thread_front_named("job-failing", main.clone(), true)?;
+    let working = sched.push_thread_front_named("job-working", main.clone(), false)?;
+    let unnamed = sched.push_thread_front(main, true)?;
+    assert_eq!(sched.thread_name(failing)?.as_deref(), Some("job-failing"));
+    assert_eq!(sched.thread_name(working)?.as_deref(), Some("job-working"));
+    assert_eq!(sched.thread_name(unnamed)?, None);
+
+    block_on(sched.run());
+
+    // Errors from spawned threads are attributed to the job that spawned them
+    let errors = errors.lock().unwrap();
+    assert_eq!(errors.len(), 2);
+    let named = errors.iter().find(|e| e.name.is_some()).unwrap();
+    assert_eq!(named.name.as_deref(), Some("job-failing"));
+    assert!(named.thread.is_some_and(|id| id != failing));
+    assert!(named.traceback.is_some());
+    assert!(named
+        .to_string()
+        .starts_with("error in thread named 'job-failing'"));
+    assert!(errors
+        .iter()
+        .all(|e| e.error.to_string().contains("something went wrong")));
+
+    // Names are also available when describing threads
+    let info = sched.describe_thread(failing)?.unwrap();
+    assert_eq!(info.name.as_deref(), Some("job-failing"));
+
+    // Threads that fail to be pushed are never named
+    let dead = lua.create_thread(lua.create_function(|_, ()| Ok(()))?)?;
+    dead.resume::<_, ()>(())?;
+    assert!(sched
+        .push_thread_front_named("job-dead", &dead, ())
+        .is_err());
+    assert_eq!(sched.thread_name(ThreadId::from(&dead))?, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_thread_names() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,95 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::{block_on, Timer};
+use futures_lite::future;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{
+    unstable::{ThreadSnapshot, ThreadState},
+    Functions, Priority, Scheduler,
+};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/thread_snapshot.luau");
+
+fn print_snapshot(snapshot: &[ThreadSnapshot]) {
+    for thread in snapshot {
+        println!(
+            "{:?} {:?} name={:?} tag={:?}",
+            thread.id, thread.state, thread.name, thread.tag
+        );
+    }
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("delay", fns.delay)?;
+    lua.globals().set("wait", fns.wait)?;
+
+    // Nothing is held by the scheduler before anything is pushed
+    assert!(sched.snapshot()?.is_empty());
+
+    // Queued threads are listed along with their queue, name and tag
+    let main = sched.push_thread_front_named("job", lua.load(MAIN_SCRIPT), ())?;
+    let deferred = sched.push_thread_back(lua.create_function(|_, ()| Ok(()))?, ())?;
+    let urgent = sched.push_thread_with_priority(
+        lua.create_function(|_, ()| Ok(()))?,
+        (),
+        Priority::High,
+    )?;
+    sched.set_thread_tag(
+        &lua.create_thread(lua.create_function(|_, ()| Ok(()))?)?,
+        "unrelated",
+    )?;
+
+    let before = sched.snapshot()?;
+    print_snapshot(&before);
+    let states = before.iter().map(|t| (t.id, t.state)).collect::<Vec<_>>();
+    assert_eq!(
+        states,
+        [
+            (urgent, ThreadState::Prioritized),
+            (main, ThreadState::Spawned),
+            (deferred, ThreadState::Deferred),
+        ]
+    );
+    assert_eq!(before[1].name.as_deref(), Some("job"));
+
+    // While running, threads waiting on futures and delayed threads are listed
+    let observe = async {
+        Timer::after(Duration::from_millis(10)).await;
+        sched.snapshot()
+    };
+    let ((), during) = block_on(future::zip(sched.run(), observe));
+    let during = during?;
+    print_snapshot(&during);
+    let mut states = during.iter().map(|t| t.state).collect::<Vec<_>>();
+    states.sort_by_key(|s| format!("{s:?}"));
+    assert_eq!(states, [ThreadState::Delayed, ThreadState::WaitingOnFuture]);
+
+    // Names are inherited by threads spawned and delayed from the named thread
+    assert!(during.iter().all(|t| t.name.as_deref() == Some("job")));
+
+    // Once done, nothing is held by the scheduler anymore
+    assert!(sched.snapshot()?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_thread_snapshot() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,115 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use async_io::block_on;
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Subscriber,
+};
+use tracing_subscriber::{layer::Context, prelude::*, Layer};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/thread_spans.luau");
+
+type Fields = BTreeMap<String, String>;
+
+/**
+    A layer that captures the fields of all spans for Lua thread resumptions.
+*/
+#[derive(Clone, Default)]
+struct CaptureLayer {
+    spans: Arc<Mutex<HashMap<Id, Fields>>>,
+}
+
+struct FieldVisitor<'a>(&'a mut Fields);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}"));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+}
+
+impl<S: Subscriber> Layer<S> for CaptureLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _: Context<'_, S>) {
+        if attrs.metadata().name() == "Scheduler::thread" {
+            let mut fields = Fields::new();
+            attrs.record(&mut FieldVisitor(&mut fields));
+            self.spans.lock().unwrap().insert(id.clone(), fields);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, _: Context<'_, S>) {
+        if let Some(fields) = self.spans.lock().unwrap().get_mut(id) {
+            values.record(&mut FieldVisitor(fields));
+        }
+    }
+}
+
+pub fn main() -> LuaResult<()> {
+    let layer = CaptureLayer::default();
+    let _guard = tracing_subscriber::registry()
+        .with(layer.clone())
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_target(false)
+                .without_time()
+                .with_filter(tracing_subscriber::EnvFilter::from_default_env()),
+        )
+        .set_default();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("wait", fns.wait)?;
+
+    // Run a named job, which spawns a thread that waits for a bit
+    let main = lua.load(MAIN_SCRIPT).set_name("thread_spans");
+    let id = sched.push_thread_front_named("job", main, ())?;
+    block_on(sched.run());
+    sched.get_thread_result(id).unwrap()?;
+
+    // Every resumption ran within a span for its thread, including the thread name
+    let spans = layer.spans.lock().unwrap();
+    for fields in spans.values() {
+        println!("{fields:?}");
+        assert!(fields.contains_key("thread"));
+        assert_eq!(fields.get("name").map(String::as_str), Some("job"));
+    }
+
+    // Job thread, and the spawned thread both when spawned and after waiting
+    assert_eq!(spans.len(), 3);
+
+    // With the debug info feature, the location of the wait is recorded too
+    if cfg!(feature = "debug-info") {
+        assert!(spans.values().any(|fields| {
+            fields
+                .get("source")
+                .is_some_and(|s| s.contains("thread_spans"))
+                && fields.get("line").map(String::as_str) == Some("6")
+        }));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_thread_spans() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,69 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Priority, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/thread_status.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("defer", fns.defer)?;
+    lua.globals().set("wait", fns.wait)?;
+    lua.globals().set("cancel", fns.cancel)?;
+    lua.globals().set("status", fns.status.clone())?;
+
+    // Threads waiting in the scheduler queues are distinguished from other suspended threads
+    let main = lua.create_thread(lua.load(MAIN_SCRIPT).into_function()?)?;
+    let deferred = lua.create_thread(lua.create_function(|_, ()| Ok(()))?)?;
+    let id = sched.push_thread_front(main.clone(), ())?;
+    sched.push_thread_back(deferred.clone(), ())?;
+    assert_eq!(fns.status.call::<_, String>(main)?, "queued");
+    assert_eq!(fns.status.call::<_, String>(deferred)?, "deferred");
+
+    // Every other queue is also taken into account, with delayed threads waiting like suspended ones
+    let noop = lua.create_function(|_, ()| Ok(()))?;
+    let high = lua.create_thread(noop.clone())?;
+    let delayed = lua.create_thread(noop.clone())?;
+    let idle = lua.create_thread(noop)?;
+    sched.push_thread_with_priority(high.clone(), (), Priority::High)?;
+    sched.push_thread_delayed(delayed.clone(), (), Duration::from_millis(10))?;
+    sched.push_thread_idle(idle.clone(), ())?;
+    assert_eq!(fns.status.call::<_, String>(high.clone())?, "queued");
+    assert_eq!(fns.status.call::<_, String>(delayed.clone())?, "suspended");
+    assert_eq!(fns.status.call::<_, String>(idle.clone())?, "deferred");
+
+    // Run until completion
+    block_on(sched.run());
+
+    // The main script should have seen every status
+    let statuses = sched.get_thread_result(id).unwrap()?;
+    let statuses = String::from_lua_multi(statuses, &lua)?;
+    println!("Statuses: {statuses}");
+    assert_eq!(statuses, "deferred,running,suspended,dead,dead");
+    for thread in [high, delayed, idle] {
+        assert_eq!(fns.status.call::<_, String>(thread)?, "dead");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_thread_status() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,55 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/time_slices.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("defer", fns.defer)?;
+
+    let order = lua.create_table()?;
+    lua.globals().set("order", order.clone())?;
+
+    // Give all threads a small time slice, instead of erroring long-running ones
+    sched.set_yield_budget(Some(Duration::from_millis(5)));
+
+    // Load the main script into the scheduler
+    let main = lua.load(MAIN_SCRIPT);
+    sched.push_thread_front(main, ())?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // The long computations should have been preempted, letting the interactive thread run first
+    let order = order
+        .sequence_values::<String>()
+        .collect::<LuaResult<Vec<_>>>()?;
+    assert_eq!(order.first().map(String::as_str), Some("interactive"));
+    assert_eq!(order.len(), 3);
+    assert!(sched.preemption_count() > 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_time_slices() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,75 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{cell::Cell, rc::Rc, time::Duration};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, LuaSchedulerExt, Scheduler, TimerStats, WakeupStats};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/timer_coalescing.luau");
+
+fn measure(resolution: Duration) -> LuaResult<(WakeupStats, TimerStats, usize)> {
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    sched.set_timer_coalescing(resolution);
+
+    let fns = Functions::new(&lua)?;
+    let waits = Rc::new(Cell::new(0));
+    let waits_inner = Rc::clone(&waits);
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set(
+        "wait",
+        lua.create_async_function(move |lua, secs: f64| {
+            waits_inner.set(waits_inner.get() + 1);
+            let sleep = lua.sleep(Duration::from_secs_f64(secs));
+            async move { Ok(sleep.await.as_secs_f64()) }
+        })?,
+    )?;
+
+    // Delayed threads share a timer owned by the scheduler, which is not a sleep
+    sched.push_thread_delayed(lua.load("return"), (), Duration::from_millis(5))?;
+
+    // Run until completion, and return the resulting stats
+    let id = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    block_on(sched.run());
+    sched.get_thread_result(id).unwrap()?;
+    Ok((sched.wakeup_stats(), sched.timer_stats(), waits.get()))
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Without coalescing, nothing should be coalesced
+    let (precise_wakeups, precise_timers, _) = measure(Duration::ZERO)?;
+    assert_eq!(precise_timers, TimerStats::default());
+
+    // With coalescing, most sleeps should share their wakeups with others
+    let (coalesced_wakeups, coalesced_timers, waits) = measure(Duration::from_millis(4))?;
+    assert_eq!(coalesced_timers.sleeps, waits);
+    assert!(coalesced_timers.sleeps >= 100);
+    assert!(coalesced_timers.coalesced >= 50);
+    assert!(coalesced_timers.added_delay <= Duration::from_millis(4) * 200);
+
+    println!("Wakeups without coalescing: {}", precise_wakeups.wakeups);
+    println!("Wakeups with coalescing: {}", coalesced_wakeups.wakeups);
+    println!(
+        "Coalesced {} of {} sleeps, adding {:.3}ms of delay in total",
+        coalesced_timers.coalesced,
+        coalesced_timers.sleeps,
+        coalesced_timers.added_delay.as_secs_f64() * 1000.0
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_timer_coalescing() -> LuaResult<()> {
+    main()
+}
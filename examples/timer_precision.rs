@@ -0,0 +1,55 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{LuaSchedulerExt, Scheduler, TimerPrecision};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/timer_precision.luau");
+
+fn measure(precision: TimerPrecision) -> LuaResult<f64> {
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    sched.set_timer_precision(precision);
+
+    lua.globals().set(
+        "sleep",
+        lua.create_async_function(|lua, secs: f64| {
+            let sleep = lua.sleep(Duration::from_secs_f64(secs));
+            async move { Ok(sleep.await.as_secs_f64()) }
+        })?,
+    )?;
+
+    // Run until completion, and return the mean lateness of all sleeps
+    let id = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    block_on(sched.run());
+    let lateness = f64::from_lua_multi(sched.get_thread_result(id).unwrap()?, &lua)?;
+    Ok(lateness)
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Sleeps should never resume early, but hybrid sleeps should resume closer to their deadline
+    let coarse = measure(TimerPrecision::Coarse)?;
+    let hybrid = measure(TimerPrecision::platform_hybrid())?;
+    println!("Mean lateness with coarse timers: {:.3}ms", coarse * 1000.0);
+    println!("Mean lateness with hybrid timers: {:.3}ms", hybrid * 1000.0);
+    assert!(coarse >= 0.0);
+    assert!(hybrid >= 0.0);
+
+    Ok(())
+}
+
+#[test]
+fn test_timer_precision() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,82 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::{Duration, Instant};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, LuaSpawnExt, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/tokio_companion.luau");
+
+fn run(handle: Option<tokio::runtime::Handle>) -> LuaResult<()> {
+    // Set up a Lua environment, with async functions that need a Tokio reactor
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    if let Some(handle) = handle {
+        sched.set_tokio_handle(handle);
+    }
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set(
+        "tokioSleep",
+        lua.create_async_function(|lua, secs: f64| async move {
+            let elapsed = lua
+                .spawn_tokio(async move {
+                    let start = Instant::now();
+                    tokio::time::sleep(Duration::from_secs_f64(secs)).await;
+                    start.elapsed()
+                })
+                .await;
+            Ok(elapsed.as_secs_f64())
+        })?,
+    )?;
+    lua.globals().set(
+        "tokioWorkers",
+        lua.create_async_function(|lua, ()| async move {
+            let workers = lua
+                .spawn_tokio(async { tokio::runtime::Handle::current().metrics().num_workers() })
+                .await;
+            Ok(workers)
+        })?,
+    )?;
+
+    // Run the script until completion, which should not error
+    let id = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    block_on(sched.run());
+    sched.get_thread_result(id).unwrap()?;
+
+    Ok(())
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Without a handle, the scheduler creates and owns a runtime of its own
+    run(None)?;
+
+    // A handle to an existing runtime may also be given, which is then used instead
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_time()
+        .build()
+        .unwrap();
+    run(Some(runtime.handle().clone()))?;
+
+    // All tokio tasks should have finished once the scheduler completes
+    assert_eq!(runtime.metrics().num_alive_tasks(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_tokio_companion() -> LuaResult<()> {
+    main()
+}
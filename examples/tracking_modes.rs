@@ -0,0 +1,162 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Scheduler, TrackingMode, TrackingStats};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/tracking_modes.luau");
+
+const NUM_THREADS: usize = 50_000;
+
+/**
+    An allocator that keeps count of how many bytes are currently allocated,
+    including by Lua, so that the memory used by each mode can be measured.
+*/
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+            ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/**
+    Returns the number of bytes currently allocated, after collecting any garbage in Lua.
+*/
+fn allocated(lua: &Lua) -> LuaResult<usize> {
+    lua.gc_collect()?;
+    lua.gc_collect()?;
+    Ok(ALLOCATED.load(Ordering::Relaxed))
+}
+
+/**
+    Statistics for a tracking mode, along with the measured memory usage of the whole
+    scheduler, relative to before any threads were pushed, at the same point in time.
+*/
+struct Measurement {
+    stats: TrackingStats,
+    bytes: usize,
+}
+
+/**
+    Pushes and runs lots of threads using the given tracking mode, and
+    then takes all of their results, returning measurements from when all
+    threads had completed, and from after all results were taken.
+*/
+fn measure(mode: TrackingMode) -> LuaResult<(Measurement, Measurement)> {
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    sched.set_tracking_mode(mode);
+    sched.remove_error_callback();
+
+    let start = Instant::now();
+    let func = lua.load(MAIN_SCRIPT).into_function()?;
+    let mut ids = Vec::with_capacity(NUM_THREADS);
+    let baseline = allocated(&lua)?;
+    for index in 0..NUM_THREADS {
+        ids.push(sched.push_thread_back(func.clone(), index)?);
+    }
+    block_on(sched.run());
+
+    let completed = Measurement {
+        stats: sched.tracking_stats(),
+        bytes: allocated(&lua)?.saturating_sub(baseline),
+    };
+    let completed_stats = completed.stats;
+    assert_eq!(completed_stats.tracked, NUM_THREADS);
+    assert_eq!(completed_stats.completed, NUM_THREADS);
+
+    // Results should be the same no matter how they were stored
+    for (index, id) in ids.iter().copied().enumerate() {
+        let result = sched.get_thread_result(id).unwrap();
+        if index % 100 == 0 {
+            assert!(result.unwrap_err().to_string().contains("failed"));
+        } else {
+            let value = result?.into_iter().next().unwrap();
+            assert_eq!(value, LuaValue::Integer(i32::try_from(index * 2).unwrap()));
+        }
+    }
+
+    let taken = Measurement {
+        stats: sched.tracking_stats(),
+        bytes: allocated(&lua)?.saturating_sub(baseline),
+    };
+    assert_eq!(taken.stats.tracked, 0);
+    assert_eq!(taken.stats.completed, 0);
+
+    println!(
+        "{mode:?}: {:?} elapsed, {} bytes ({} estimated for tracking) when completed, \
+        {} bytes ({} estimated for tracking) after taking results",
+        start.elapsed(),
+        completed.bytes,
+        completed.stats.estimated_bytes,
+        taken.bytes,
+        taken.stats.estimated_bytes,
+    );
+
+    Ok((completed, taken))
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    let (default_completed, default_taken) = measure(TrackingMode::Default)?;
+    let (compact_completed, compact_taken) = measure(TrackingMode::Compact)?;
+
+    // Compact mode should use less memory while results are being held on to,
+    // and release that memory once results are taken, unlike the default mode,
+    // both going by its own estimates and by what was actually allocated
+    assert!(compact_completed.stats.estimated_bytes < default_completed.stats.estimated_bytes);
+    assert!(compact_taken.stats.estimated_bytes < default_taken.stats.estimated_bytes);
+    assert!(compact_completed.bytes < default_completed.bytes);
+    assert!(compact_taken.bytes < default_taken.bytes);
+
+    // The measured difference should come from tracking, and match the estimates
+    let estimated_saved =
+        default_completed.stats.estimated_bytes - compact_completed.stats.estimated_bytes;
+    let measured_saved = default_completed.bytes - compact_completed.bytes;
+    println!("Compact saved {measured_saved} bytes, estimated {estimated_saved} bytes");
+    assert!(measured_saved >= estimated_saved / 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_tracking_modes() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,59 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Scheduler, ValueKind};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/value_log.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+
+    // Record values passed out of threads, and reply to any yielded values
+    sched.set_value_logging(8);
+    sched.set_yield_handler(|lua, _, _| Ok(Some("reply".into_lua_multi(lua)?)));
+
+    // Load the main script into the scheduler, and keep track of the thread we spawn
+    let main = lua.load(MAIN_SCRIPT);
+    let id = sched.push_thread_front(main, ())?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // Values are only recorded in debug builds
+    let records = sched.recent_values(8);
+    if !cfg!(debug_assertions) {
+        assert!(records.is_empty());
+        return Ok(());
+    }
+
+    // We should see both the yielded and the returned values, newest first
+    assert_eq!(records.len(), 2);
+    assert!(records.iter().all(|record| record.thread == id));
+
+    assert_eq!(records[1].kind, ValueKind::Yielded);
+    assert_eq!(records[1].values, vec!["ping", "1"]);
+
+    // Long values should have been truncated
+    assert_eq!(records[0].kind, ValueKind::Returned);
+    assert_eq!(records[0].values[..2], ["pong", "reply"]);
+    assert!(records[0].values[2].len() < 1000);
+    assert!(records[0].values[2].ends_with("..."));
+
+    Ok(())
+}
+
+#[test]
+fn test_value_log() -> LuaResult<()> {
+    main()
+}
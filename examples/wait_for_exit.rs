@@ -0,0 +1,54 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::thread;
+
+use async_io::block_on;
+use futures_lite::future;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{ExitReason, Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/wait_for_exit.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("exit", fns.exit)?;
+    lua.globals().set("defer", fns.defer)?;
+
+    // Load the main script into the scheduler
+    let main = lua.load(MAIN_SCRIPT);
+    sched.push_thread_front(main, ())?;
+
+    // Watch for the exit from another thread, as a supervisor might
+    let watcher = sched.wait_for_exit();
+    let supervisor = thread::spawn(move || block_on(watcher));
+
+    // Run until completion, also watching for the exit on this thread
+    let ((), reason) = block_on(future::zip(sched.run(), sched.wait_for_exit()));
+
+    // Both watchers should have seen the exit initiated by the script
+    for reason in [reason, supervisor.join().unwrap()] {
+        match reason {
+            ExitReason::ExitCode(code) => assert!(format!("{code:?}").contains("(3)")),
+            other => panic!("expected an exit code, got {other:?}"),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_wait_for_exit() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,60 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::process::ExitCode;
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/warm_reset.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment, shared by all requests
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("defer", fns.defer)?;
+    lua.globals().set("exit", fns.exit)?;
+
+    let ran = lua.create_table()?;
+    lua.globals().set("ran", ran.clone())?;
+
+    // Run the first request, which exits early
+    lua.globals().set("request", 1)?;
+    let first = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    block_on(sched.run());
+    assert_eq!(sched.get_exit_code(), Some(ExitCode::from(1)));
+
+    // Reset the scheduler, scrubbing everything that was left behind
+    sched.reset()?;
+    assert_eq!(sched.get_exit_code(), None);
+    assert!(sched.get_thread_result(first).is_none());
+
+    // Run the second request, reusing the same functions as before
+    lua.globals().set("request", 2)?;
+    sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    block_on(sched.run());
+    assert_eq!(sched.get_exit_code(), None);
+
+    // Only the work deferred by the second request should have run
+    let ran = ran
+        .sequence_values::<i64>()
+        .collect::<LuaResult<Vec<_>>>()?;
+    assert_eq!(ran, vec![2]);
+
+    Ok(())
+}
+
+#[test]
+fn test_warm_reset() -> LuaResult<()> {
+    main()
+}
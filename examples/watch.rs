@@ -0,0 +1,74 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use async_io::{block_on, Timer};
+use futures_lite::future;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Scheduler, Watch};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/watch.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+
+    let config = Watch::new(String::from("initial"));
+    let records = Rc::new(RefCell::new(Vec::new()));
+    let record_records = Rc::clone(&records);
+    lua.globals().set("config", config.clone())?;
+    lua.globals().set(
+        "record",
+        lua.create_function(move |_, (name, value): (String, String)| {
+            record_records.borrow_mut().push((name, value));
+            Ok(())
+        })?,
+    )?;
+
+    // Start two scripts that watch the same value
+    let main = lua.load(MAIN_SCRIPT).into_function()?;
+    sched.push_thread_back(main.clone(), "first")?;
+    sched.push_thread_back(main, "second")?;
+
+    // Change the value a few times while the scripts are running,
+    // including twice in a row, which should only be seen once
+    let host_loop = async {
+        for values in [&["changed"][..], &["skipped", "coalesced"], &["done"]] {
+            Timer::after(Duration::from_millis(10)).await;
+            for value in values {
+                config.set((*value).to_string());
+            }
+        }
+    };
+    block_on(future::zip(sched.run(), host_loop));
+
+    // Both scripts should have seen the exact same values
+    let expected = ["initial", "changed", "coalesced", "done"];
+    for name in ["first", "second"] {
+        let seen = records
+            .borrow()
+            .iter()
+            .filter(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(seen, expected);
+    }
+    assert_eq!(config.get(), "done");
+    assert_eq!(config.version(), 4);
+
+    Ok(())
+}
+
+#[test]
+fn test_watch() -> LuaResult<()> {
+    main()
+}
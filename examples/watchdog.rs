@@ -0,0 +1,75 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_io::{block_on, Timer};
+use futures_lite::{future, FutureExt};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, LuaSchedulerExt, Scheduler, WatchdogPolicy};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/watchdog.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("heartbeat", fns.heartbeat)?;
+    lua.globals().set(
+        "hang",
+        lua.create_async_function(|_, ()| future::pending::<LuaResult<()>>())?,
+    )?;
+    lua.globals().set(
+        "nativeHang",
+        lua.create_native_async_function(|_, ()| future::pending::<LuaResult<()>>())?,
+    )?;
+
+    // Cancel anything that takes longer than a few milliseconds, collecting all reports
+    let stuck = Arc::new(Mutex::new(Vec::new()));
+    let stuck_inner = Arc::clone(&stuck);
+    sched.set_watchdog(Duration::from_millis(10), WatchdogPolicy::Cancel);
+    sched.set_watchdog_callback(move |task| {
+        println!("Stuck task detected: {task:?}");
+        stuck_inner.lock().unwrap().push(task);
+    });
+
+    // Load the main script into the scheduler, and keep track of the thread we spawn
+    let main = lua.load(MAIN_SCRIPT);
+    let id = sched.push_thread_front(main, ())?;
+
+    // Run until completion, ticking once both stuck tasks should have been cancelled
+    let host = async {
+        Timer::after(Duration::from_millis(50)).await;
+        sched
+            .fire_tick(Duration::ZERO)
+            .expect("failed to fire tick");
+        future::pending::<()>().await;
+    };
+    block_on(sched.run().or(host));
+
+    // The main script should have completed, and both stuck tasks should have been cancelled
+    sched.get_thread_result(id).unwrap()?;
+    let stuck = stuck.lock().unwrap();
+    assert_eq!(stuck.len(), 2);
+    assert!(stuck.iter().all(|task| task.cancelled));
+
+    Ok(())
+}
+
+#[test]
+fn test_watchdog() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,67 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::time::Duration;
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::Scheduler;
+
+const MAIN_SCRIPT: &str = include_str!("./lua/yield_budget.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+
+    let order = lua.create_table()?;
+    lua.globals().set("order", order.clone())?;
+
+    // Load the main script into the scheduler, with a small yield budget
+    let main = lua.load(MAIN_SCRIPT);
+    let id = sched.push_thread_front(main, ())?;
+    sched.set_thread_yield_budget(id, Duration::from_millis(5));
+
+    // Defer another thread, which would normally only run after the main script
+    let other = lua.load("table.insert(order, 'other')");
+    sched.push_thread_back(other, ())?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // The main script should have automatically yielded, letting the other thread run first
+    let values = order
+        .clone()
+        .sequence_values::<String>()
+        .collect::<LuaResult<Vec<_>>>()?;
+    assert_eq!(values, vec!["other", "busy"]);
+    assert!(sched.get_thread_result(id).unwrap().is_ok());
+
+    // Coroutines resumed from inside a thread with a budget are never yielded automatically
+    order.clear()?;
+    sched.set_yield_budget(Some(Duration::from_millis(5)));
+    let preempted = sched.preemption_count();
+    let id = sched.push_thread_front(lua.load(MAIN_SCRIPT), "generator")?;
+    block_on(sched.run());
+
+    let values = order
+        .sequence_values::<String>()
+        .collect::<LuaResult<Vec<_>>>()?;
+    assert_eq!(values, vec!["generator"]);
+    assert!(sched.get_thread_result(id).unwrap().is_ok());
+    assert!(sched.preemption_count() > preempted);
+
+    Ok(())
+}
+
+#[test]
+fn test_yield_budget() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,53 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{cell::RefCell, rc::Rc};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::Scheduler;
+
+const MAIN_SCRIPT: &str = include_str!("./lua/yield_handler.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+
+    // Collect all yielded values, resuming with the running total of them
+    let yielded = Rc::new(RefCell::new(Vec::new()));
+    let yielded_inner = Rc::clone(&yielded);
+    sched.set_yield_handler(move |lua, _, values| {
+        let value = u32::from_lua_multi(values, lua)?;
+        let mut yielded = yielded_inner.borrow_mut();
+        yielded.push(value);
+        let total = yielded.iter().sum::<u32>();
+        Ok(Some(total.into_lua_multi(lua)?))
+    });
+
+    // Load the main script into the scheduler, and keep track of the thread we spawn
+    let main = lua.load(MAIN_SCRIPT);
+    let id = sched.push_thread_front(main, ())?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // The host should have seen every value, and the script the final total
+    assert_eq!(*yielded.borrow(), vec![1, 2, 3, 4, 5]);
+    let res = sched.get_thread_result(id).unwrap()?;
+    assert_eq!(u32::from_lua_multi(res, &lua)?, 15);
+
+    Ok(())
+}
+
+#[test]
+fn test_yield_handler() -> LuaResult<()> {
+    main()
+}
@@ -0,0 +1,94 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use std::{cell::Cell, rc::Rc, time::Duration};
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{ChunkOptions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/yield_points.luau");
+
+fn take_order(order: &LuaTable) -> LuaResult<Vec<String>> {
+    let values = order
+        .clone()
+        .sequence_values::<String>()
+        .collect::<LuaResult<Vec<_>>>()?;
+    order.clear()?;
+    Ok(values)
+}
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+
+    let order = lua.create_table()?;
+    lua.globals().set("order", order.clone())?;
+
+    // Set an interrupt through the scheduler, which is chained with the one used for budgets
+    let interrupts = Rc::new(Cell::new(0));
+    let counter = Rc::clone(&interrupts);
+    sched.set_interrupt(move |_| {
+        counter.set(counter.get() + 1);
+        Ok(LuaVmState::Continue)
+    });
+
+    // Push the main script with a small yield budget, enforced by the scheduler's interrupt
+    let options = ChunkOptions {
+        yield_budget: Some(Duration::from_millis(5)),
+        ..ChunkOptions::default()
+    };
+    let id = sched.push_chunk("=yield_points", MAIN_SCRIPT, Some(options))?;
+
+    // Defer another thread, which would normally only run after the main script
+    let other = lua.load("table.insert(order, 'other')");
+    sched.push_thread_back(other, ())?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // The main script should have yielded once over budget, letting the other thread run first
+    assert_eq!(take_order(&order)?, vec!["other", "busy"]);
+    assert!(sched.get_thread_result(id).unwrap().is_ok());
+    assert!(sched.preemption_count() > 0);
+    assert!(interrupts.get() > 0);
+
+    // The source of the chunk is left as it is, so errors point at the lines they came from
+    let id = sched.push_chunk("=lines", "--!strict\n\nerror('oops')", Some(options))?;
+    block_on(sched.run());
+    let err = sched.get_thread_result(id).unwrap().unwrap_err();
+    assert!(err.to_string().contains("lines:3: oops"));
+    sched.remove_interrupt();
+
+    // Interrupts set through the scheduler keep running alongside budgets
+    let interrupts = Rc::new(Cell::new(0));
+    let counter = Rc::clone(&interrupts);
+    sched.set_interrupt(move |_| {
+        counter.set(counter.get() + 1);
+        Ok(LuaVmState::Continue)
+    });
+    sched.set_thread_budget(Some(Duration::from_secs(10)));
+    sched.set_yield_budget(Some(Duration::from_secs(10)));
+
+    let id = sched.push_thread_front(lua.load(MAIN_SCRIPT), ())?;
+    block_on(sched.run());
+
+    assert_eq!(take_order(&order)?, vec!["busy"]);
+    assert!(sched.get_thread_result(id).unwrap().is_ok());
+    assert!(interrupts.get() > 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_yield_points() -> LuaResult<()> {
+    main()
+}
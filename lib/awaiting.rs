@@ -0,0 +1,73 @@
+use std::{cell::RefCell, rc::Rc};
+
+use rustc_hash::FxHashSet;
+
+use crate::thread_id::ThreadId;
+
+/**
+    Set of Lua threads that are currently waiting for an async function to complete.
+
+    These threads will be resumed by the scheduler once their async function completes,
+    and resuming them from anywhere else would resume them twice, corrupting their state.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct AwaitingThreads {
+    threads: Rc<RefCell<FxHashSet<ThreadId>>>,
+}
+
+impl AwaitingThreads {
+    pub fn new() -> Self {
+        Self {
+            threads: Rc::new(RefCell::new(FxHashSet::default())),
+        }
+    }
+
+    #[inline]
+    pub fn insert(&self, id: ThreadId) {
+        self.threads.borrow_mut().insert(id);
+    }
+
+    #[inline]
+    pub fn remove(&self, id: ThreadId) {
+        self.threads.borrow_mut().remove(&id);
+    }
+
+    /**
+        Marks the given thread as awaiting until the returned guard is dropped.
+    */
+    pub fn guard(&self, id: ThreadId) -> AwaitingGuard {
+        self.insert(id);
+        AwaitingGuard {
+            threads: self.clone(),
+            id,
+        }
+    }
+
+    #[inline]
+    pub fn clear(&self) {
+        self.threads.borrow_mut().clear();
+    }
+
+    pub fn contains(&self, id: ThreadId) -> bool {
+        self.threads.borrow().contains(&id)
+    }
+
+    #[cfg(feature = "unstable")]
+    pub fn ids(&self) -> Vec<ThreadId> {
+        self.threads.borrow().iter().copied().collect()
+    }
+}
+
+/**
+    Guard returned by [`AwaitingThreads::guard`], unmarking its thread when dropped.
+*/
+pub(crate) struct AwaitingGuard {
+    threads: AwaitingThreads,
+    id: ThreadId,
+}
+
+impl Drop for AwaitingGuard {
+    fn drop(&mut self) {
+        self.threads.remove(self.id);
+    }
+}
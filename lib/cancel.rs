@@ -0,0 +1,108 @@
+use mlua::prelude::*;
+
+use crate::{
+    delay::DelayedThreads, idle::IdleQueue, native::NativeAsyncQueue, primitives::Primitives,
+    queue::ThreadQueue, result_map::ThreadResultMap, stats::Stats, suspend::SuspendedThreads,
+    thread_id::ThreadId, tick::Ticks,
+};
+
+pub(crate) const ERR_CANCELLED: &str = "thread was cancelled";
+
+/**
+    Everything that a thread must be removed from when it is cancelled.
+
+    This is shared by every way of cancelling or closing a thread, both from Lua and
+    from Rust, so that a cancelled thread is never left behind in any scheduler queue.
+*/
+#[derive(Clone)]
+pub(crate) struct Cancellation {
+    queues: [ThreadQueue; 3],
+    idle: IdleQueue,
+    ticks: Ticks,
+    delayed: DelayedThreads,
+    native: NativeAsyncQueue,
+    suspended: SuspendedThreads,
+    result_map: ThreadResultMap,
+    stats: Stats,
+    primitives: Primitives,
+}
+
+impl Cancellation {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        queues: [ThreadQueue; 3],
+        idle: IdleQueue,
+        ticks: Ticks,
+        delayed: DelayedThreads,
+        native: NativeAsyncQueue,
+        suspended: SuspendedThreads,
+        result_map: ThreadResultMap,
+        stats: Stats,
+        primitives: Primitives,
+    ) -> Self {
+        Self {
+            queues,
+            idle,
+            ticks,
+            delayed,
+            native,
+            suspended,
+            result_map,
+            stats,
+            primitives,
+        }
+    }
+
+    /**
+        Cancels the given thread, closing it and removing it from every scheduler queue.
+
+        Tracked threads that have not yet completed are given an error as their result, so
+        that anything waiting for them is woken up instead of waiting forever. Threads
+        that could no longer be resumed are still closed, but not counted as cancelled.
+
+        Returns the values returned from closing the thread.
+
+        # Errors
+
+        Errors if the thread could not be closed, such as when it is currently running.
+    */
+    pub fn cancel<'lua>(
+        &self,
+        lua: &'lua Lua,
+        thread: &LuaThread<'lua>,
+    ) -> LuaResult<LuaMultiValue<'lua>> {
+        let id = ThreadId::from(thread);
+        let resumable = thread.status() == LuaThreadStatus::Resumable;
+        let values = match self
+            .primitives
+            .get(lua, "close")?
+            .call::<_, LuaMultiValue>(thread)
+        {
+            Ok(values) => values,
+            Err(LuaError::CoroutineInactive) => LuaMultiValue::new(),
+            Err(e) => return Err(e),
+        };
+
+        for queue in &self.queues {
+            while queue.take_item(id).is_some() {}
+        }
+        while self.idle.threads().take_item(id).is_some() {}
+        self.ticks.cancel(id);
+        self.delayed.cancel(id);
+        self.native.cancel(id);
+        self.suspended.unsuspend(id);
+        if resumable {
+            self.stats.record_cancelled();
+        }
+
+        let tracked_id = self.result_map.current(id);
+        if self.result_map.is_tracked(tracked_id) && !self.result_map.is_completed(tracked_id) {
+            self.result_map
+                .insert(lua, tracked_id, Err(LuaError::runtime(ERR_CANCELLED)));
+        } else {
+            self.result_map.abandon(lua, id);
+        }
+
+        Ok(values)
+    }
+}
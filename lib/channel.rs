@@ -0,0 +1,187 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use concurrent_queue::ConcurrentQueue;
+use event_listener::Event;
+use mlua::prelude::*;
+
+const ERR_CLOSED: &str = "channel receiver was dropped";
+
+struct ChannelShared<T> {
+    queue: ConcurrentQueue<T>,
+    event: Event,
+    senders: AtomicUsize,
+}
+
+impl<T> ChannelShared<T> {
+    fn is_disconnected(&self) -> bool {
+        self.senders.load(Ordering::Acquire) == 0
+    }
+
+    /**
+        Waits until a value has been sent, or until all senders have been dropped.
+    */
+    async fn recv(&self) -> Option<T> {
+        loop {
+            if let Ok(value) = self.queue.pop() {
+                break Some(value);
+            }
+            if self.is_disconnected() {
+                // NOTE: The last value could have been sent right before
+                // the last sender was dropped, so we need to check again
+                break self.queue.pop().ok();
+            }
+            let listener = self.event.listen();
+            // NOTE: Need to check again, we could have gotten a new
+            // value, or lost the last sender, while creating our listener
+            if self.queue.is_empty() && !self.is_disconnected() {
+                listener.await;
+            }
+        }
+    }
+}
+
+/**
+    A channel for sending values from Rust to Lua threads.
+
+    Created using [`RuntimeChannel::create`], which returns a [`ChannelSender`] along with the
+    channel itself. The sender is [`Send`] and [`Sync`], and may be cloned and moved to any other
+    thread, while the channel is a Lua userdata that should be passed to Lua, and has these methods:
+
+    - `channel:recv()` yields the calling thread until a value has been sent, and then returns it,
+      or returns `nil` once all senders have been dropped and there are no more values to receive
+    - `channel:tryRecv()` returns the next value if there is one, without yielding, and `nil` otherwise
+    - `channel:isClosed()` returns `true` once all senders have been dropped, and all values were received
+
+    Threads waiting in `recv` are woken up by the scheduler as soon as a value is sent, without
+    any polling, meaning that the scheduler must be running for waiting threads to receive values.
+
+    Dropping the channel, such as when it is garbage collected, makes any further sends fail.
+*/
+pub struct RuntimeChannel<T> {
+    shared: Arc<ChannelShared<T>>,
+}
+
+impl<T> RuntimeChannel<T>
+where
+    T: for<'lua> IntoLua<'lua> + Send + 'static,
+{
+    /**
+        Creates a new, unbounded channel, returning its sender and the channel itself.
+    */
+    #[must_use]
+    pub fn create() -> (ChannelSender<T>, Self) {
+        let shared = Arc::new(ChannelShared {
+            queue: ConcurrentQueue::unbounded(),
+            event: Event::new(),
+            senders: AtomicUsize::new(1),
+        });
+        let sender = ChannelSender {
+            shared: Arc::clone(&shared),
+        };
+        (sender, Self { shared })
+    }
+}
+
+impl<T> Drop for RuntimeChannel<T> {
+    fn drop(&mut self) {
+        self.shared.queue.close();
+    }
+}
+
+impl<T> std::fmt::Debug for RuntimeChannel<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RuntimeChannel")
+            .field("queued", &self.shared.queue.len())
+            .field("senders", &self.shared.senders.load(Ordering::Acquire))
+            .finish()
+    }
+}
+
+impl<T> LuaUserData for RuntimeChannel<T>
+where
+    T: for<'lua> IntoLua<'lua> + Send + 'static,
+{
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_function("recv", |lua, this: LuaAnyUserData| {
+            let shared = this.borrow::<Self>().map(|this| Arc::clone(&this.shared));
+            async move {
+                match shared?.recv().await {
+                    Some(value) => value.into_lua(lua),
+                    None => Ok(LuaValue::Nil),
+                }
+            }
+        });
+        methods.add_method("tryRecv", |lua, this, ()| match this.shared.queue.pop() {
+            Ok(value) => value.into_lua(lua),
+            Err(_) => Ok(LuaValue::Nil),
+        });
+        methods.add_method("isClosed", |_, this, ()| {
+            Ok(this.shared.is_disconnected() && this.shared.queue.is_empty())
+        });
+    }
+}
+
+/**
+    The sending half of a [`RuntimeChannel`].
+
+    The channel is closed once all senders have been dropped, after which
+    any threads waiting in `recv` receive `nil` instead of waiting forever.
+*/
+pub struct ChannelSender<T> {
+    shared: Arc<ChannelShared<T>>,
+}
+
+impl<T> ChannelSender<T> {
+    /**
+        Sends a value to the channel, waking up a thread waiting to receive it, if any.
+
+        # Errors
+
+        Errors if the channel was dropped, meaning that no more values can be received.
+    */
+    pub fn send(&self, value: T) -> LuaResult<()> {
+        self.shared
+            .queue
+            .push(value)
+            .map_err(|_| LuaError::runtime(ERR_CLOSED))?;
+        self.shared.event.notify(usize::MAX);
+        Ok(())
+    }
+
+    /**
+        Returns `true` if the channel was dropped, meaning that no more values can be sent.
+    */
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.shared.queue.is_closed()
+    }
+}
+
+impl<T> Clone for ChannelSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::AcqRel);
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for ChannelSender<T> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.event.notify(usize::MAX);
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for ChannelSender<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChannelSender")
+            .field("queued", &self.shared.queue.len())
+            .field("closed", &self.is_closed())
+            .finish()
+    }
+}
@@ -0,0 +1,103 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::{prelude::*, SerializeOptions};
+use rustc_hash::FxHashMap;
+use serde::de::DeserializeOwned;
+use serde_value::Value;
+
+use crate::{tags::ThreadTags, thread_id::ThreadId};
+
+/**
+    A serializable progress blob, saved by a Lua thread using `checkpoint`.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    value: Value,
+}
+
+impl Checkpoint {
+    /**
+        Deserializes this checkpoint into the given type.
+
+        # Errors
+
+        Errors if the checkpoint can not be deserialized into the given type.
+    */
+    pub fn deserialize<T: DeserializeOwned>(&self) -> LuaResult<T> {
+        self.value
+            .clone()
+            .deserialize_into()
+            .map_err(|e| LuaError::DeserializeError(e.to_string()))
+    }
+
+    /**
+        Converts this checkpoint back into a Lua value.
+
+        # Errors
+
+        Errors when out of memory.
+    */
+    pub fn to_lua<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        let options = SerializeOptions::new()
+            .serialize_none_to_null(false)
+            .serialize_unit_to_null(false);
+        lua.to_value_with(&self.value, options)
+    }
+}
+
+/**
+    Storage for the latest checkpoints saved by Lua threads.
+
+    Checkpoints are keyed by tag for tagged threads, such as supervised
+    services, so that they outlive any single thread, and keyed by thread
+    id for any other threads.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct Checkpoints {
+    tags: ThreadTags,
+    by_tag: Rc<RefCell<FxHashMap<String, Checkpoint>>>,
+    by_thread: Rc<RefCell<FxHashMap<ThreadId, Checkpoint>>>,
+}
+
+impl Checkpoints {
+    pub fn new(tags: ThreadTags) -> Self {
+        Self {
+            tags,
+            by_tag: Rc::new(RefCell::new(FxHashMap::default())),
+            by_thread: Rc::new(RefCell::new(FxHashMap::default())),
+        }
+    }
+
+    pub fn clear(&self) {
+        self.by_tag.borrow_mut().clear();
+        self.by_thread.borrow_mut().clear();
+    }
+
+    /**
+        Saves the given Lua value as the latest checkpoint for the given thread.
+
+        # Errors
+
+        Errors if the given value is not serializable.
+    */
+    pub fn save(&self, lua: &Lua, thread: &LuaThread, value: LuaValue) -> LuaResult<()> {
+        let checkpoint = Checkpoint {
+            value: lua.from_value(value)?,
+        };
+        if let Some(tag) = self.tags.get(lua, thread) {
+            self.by_tag.borrow_mut().insert(tag, checkpoint);
+        } else {
+            let id = ThreadId::from(thread);
+            self.by_thread.borrow_mut().insert(id, checkpoint);
+        }
+        Ok(())
+    }
+
+    pub fn get_tag(&self, tag: &str) -> Option<Checkpoint> {
+        self.by_tag.borrow().get(tag).cloned()
+    }
+
+    pub fn get_thread(&self, id: ThreadId) -> Option<Checkpoint> {
+        self.by_thread.borrow().get(&id).cloned()
+    }
+}
@@ -0,0 +1,94 @@
+use std::{cell::Cell, rc::Rc, time::Duration};
+
+use mlua::Compiler;
+
+/**
+    Luau compiler settings for chunks pushed to a [`Scheduler`].
+
+    Defaults to the same settings as the Luau compiler itself, meaning
+    optimization and debug level `1`, and no coverage instrumentation,
+    and without any yield budget.
+
+    See [`Scheduler::push_chunk`] and [`Scheduler::set_chunk_options`] for more information.
+
+    [`Scheduler`]: crate::Scheduler
+    [`Scheduler::push_chunk`]: crate::Scheduler::push_chunk
+    [`Scheduler::set_chunk_options`]: crate::Scheduler::set_chunk_options
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkOptions {
+    /// The optimization level, from `0` (none) to `2` (including inlining and loop unrolling).
+    pub optimization_level: u8,
+    /// The debug level, from `0` (no debug info) to `2` (full debug info, including locals).
+    pub debug_level: u8,
+    /// The coverage level, from `0` (none) to `2` (statement and expression coverage).
+    pub coverage_level: u8,
+    /// The yield budget for the thread running the chunk, enforced using the scheduler's
+    /// Luau interrupt once the chunk is pushed, see [`Scheduler::push_chunk`](crate::Scheduler::push_chunk).
+    pub yield_budget: Option<Duration>,
+}
+
+impl ChunkOptions {
+    /**
+        Creates a Luau compiler using these options.
+    */
+    #[must_use]
+    pub fn compiler(&self) -> Compiler {
+        Compiler::new()
+            .set_optimization_level(self.optimization_level)
+            .set_debug_level(self.debug_level)
+            .set_coverage_level(self.coverage_level)
+    }
+
+    /**
+        Compiles the given source into bytecode using these options.
+
+        The bytecode may later be pushed using [`Scheduler::push_bytecode`]. Note that
+        compilation errors are encoded into the bytecode, and only surface once loaded.
+
+        The yield budget applies to the thread running the chunk, and is only
+        set by [`Scheduler::push_chunk`], never compiled into the bytecode.
+
+        [`Scheduler::push_chunk`]: crate::Scheduler::push_chunk
+        [`Scheduler::push_bytecode`]: crate::Scheduler::push_bytecode
+    */
+    #[must_use]
+    pub fn compile(&self, source: impl AsRef<[u8]>) -> Vec<u8> {
+        self.compiler().compile(source)
+    }
+}
+
+impl Default for ChunkOptions {
+    fn default() -> Self {
+        Self {
+            optimization_level: 1,
+            debug_level: 1,
+            coverage_level: 0,
+            yield_budget: None,
+        }
+    }
+}
+
+/**
+    Default chunk options for a scheduler, used when no options are given at push time.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct DefaultChunkOptions {
+    inner: Rc<Cell<ChunkOptions>>,
+}
+
+impl DefaultChunkOptions {
+    pub fn new() -> Self {
+        Self {
+            inner: Rc::new(Cell::new(ChunkOptions::default())),
+        }
+    }
+
+    pub fn get(&self) -> ChunkOptions {
+        self.inner.get()
+    }
+
+    pub fn set(&self, options: ChunkOptions) {
+        self.inner.set(options);
+    }
+}
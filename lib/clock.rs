@@ -0,0 +1,291 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    future::Future,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use async_io::Timer;
+use futures_lite::{future, FutureExt};
+use rustc_hash::FxHashMap;
+
+use crate::util::instant_after;
+
+/**
+    The approximate resolution of timers on the current platform.
+
+    Windows timers are only as precise as the system timer interrupt, which
+    defaults to ~15.6ms, while other platforms generally have precise timers.
+*/
+const PLATFORM_TIMER_RESOLUTION: Duration = if cfg!(windows) {
+    Duration::from_millis(16)
+} else {
+    Duration::from_millis(1)
+};
+
+/**
+    The precision used by a scheduler when sleeping, see [`Scheduler::set_timer_precision`].
+
+    [`Scheduler::set_timer_precision`]: crate::Scheduler::set_timer_precision
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimerPrecision {
+    /// Sleep using only timers, with precision depending on the platform.
+    #[default]
+    Coarse,
+    /// Sleep using timers until the deadline is within the given threshold, and then
+    /// busy-wait for the remaining time, yielding to other work while waiting.
+    Hybrid {
+        /// How close to the deadline to switch from timers to busy-waiting.
+        spin_threshold: Duration,
+    },
+}
+
+impl TimerPrecision {
+    /**
+        Creates a hybrid timer precision, with a threshold suited to the timer resolution of the current platform.
+    */
+    #[must_use]
+    pub const fn platform_hybrid() -> Self {
+        Self::Hybrid {
+            spin_threshold: PLATFORM_TIMER_RESOLUTION,
+        }
+    }
+}
+
+/**
+    Statistics about sleeps coalesced by a scheduler, see [`Scheduler::set_timer_coalescing`].
+
+    [`Scheduler::set_timer_coalescing`]: crate::Scheduler::set_timer_coalescing
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimerStats {
+    /// The number of sleeps started while coalescing was enabled.
+    pub sleeps: usize,
+    /// The number of sleeps that shared a wakeup with another pending sleep.
+    pub coalesced: usize,
+    /// The total amount of time that deadlines were pushed back by, to share wakeups.
+    pub added_delay: Duration,
+}
+
+/**
+    The clock used by a scheduler, for all of its timing primitives.
+
+    Time is measured relative to when the scheduler was created, and
+    all sleeping goes through the same timer, so that builtins do not
+    need to pick their own timer implementation to stay consistent.
+
+    Dropping any future returned by the clock cancels its timer.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct Clock {
+    epoch: Instant,
+    precision: Rc<Cell<TimerPrecision>>,
+    coalescing: Rc<Cell<Duration>>,
+    buckets: Rc<RefCell<FxHashMap<u128, usize>>>,
+    sleeping: Rc<RefCell<BTreeMap<Instant, usize>>>,
+    stats: Rc<Cell<TimerStats>>,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            precision: Rc::new(Cell::new(TimerPrecision::default())),
+            coalescing: Rc::new(Cell::new(Duration::ZERO)),
+            buckets: Rc::new(RefCell::new(FxHashMap::default())),
+            sleeping: Rc::new(RefCell::new(BTreeMap::new())),
+            stats: Rc::new(Cell::new(TimerStats::default())),
+        }
+    }
+
+    pub fn precision(&self) -> TimerPrecision {
+        self.precision.get()
+    }
+
+    pub fn set_precision(&self, precision: TimerPrecision) {
+        self.precision.set(precision);
+    }
+
+    pub fn coalescing(&self) -> Duration {
+        self.coalescing.get()
+    }
+
+    pub fn set_coalescing(&self, resolution: Duration) {
+        self.coalescing.set(resolution);
+    }
+
+    pub fn stats(&self) -> TimerStats {
+        self.stats.get()
+    }
+
+    /**
+        Returns the number of sleeps started through [`Clock::sleep`] that are currently pending.
+
+        Delayed threads and internal deadlines of the scheduler are not included here.
+    */
+    pub fn pending_sleeps(&self) -> usize {
+        self.sleeping.borrow().values().sum()
+    }
+
+    /**
+        Returns the earliest deadline out of all pending sleeps started through [`Clock::sleep`], if any.
+    */
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.sleeping.borrow().keys().next().copied()
+    }
+
+    /**
+        Rounds the given deadline up to the next multiple of the coalescing resolution,
+        measured from the epoch, so that nearly-equal deadlines become exactly equal.
+
+        Returns the deadline along with a guard for its bucket, if coalescing is enabled.
+    */
+    fn coalesce(&self, deadline: Instant) -> (Instant, Option<BucketGuard>) {
+        let resolution = self.coalescing.get().as_nanos();
+        if resolution == 0 {
+            return (deadline, None);
+        }
+
+        let since_epoch = deadline.saturating_duration_since(self.epoch).as_nanos();
+        let bucket = since_epoch.div_ceil(resolution);
+        let offset = u64::try_from(bucket * resolution).unwrap_or(u64::MAX);
+        let coalesced = self.epoch + Duration::from_nanos(offset);
+
+        let mut stats = self.stats.get();
+        stats.sleeps += 1;
+        stats.added_delay += coalesced.saturating_duration_since(deadline);
+        let mut buckets = self.buckets.borrow_mut();
+        let pending = buckets.entry(bucket).or_default();
+        if *pending > 0 {
+            stats.coalesced += 1;
+        }
+        *pending += 1;
+        self.stats.set(stats);
+
+        let guard = BucketGuard {
+            bucket,
+            buckets: Rc::clone(&self.buckets),
+        };
+        (coalesced, Some(guard))
+    }
+
+    /**
+        Returns the time elapsed since the scheduler was created.
+    */
+    pub fn now(&self) -> Duration {
+        self.epoch.elapsed()
+    }
+
+    /**
+        Sleeps for the given duration, returning the time that actually elapsed.
+    */
+    pub async fn sleep(self, duration: Duration) -> Duration {
+        let start = Instant::now();
+        let (deadline, _bucket) = self.coalesce(instant_after(duration));
+        let _pending = PendingGuard::new(deadline, &self.sleeping);
+        self.wait_until(deadline).await;
+        start.elapsed()
+    }
+
+    /**
+        Runs the given future, giving up and dropping it if it does not complete in time.
+    */
+    pub async fn timeout<F: Future>(self, duration: Duration, fut: F) -> Option<F::Output> {
+        let fut_timeout = async {
+            self.sleep(duration).await;
+            None
+        };
+        async { Some(fut.await) }.or(fut_timeout).await
+    }
+
+    /**
+        Waits until the given deadline, using the precision of the clock.
+
+        Unlike [`Clock::sleep`], this is never coalesced and is not counted as a pending sleep
+        or in [`TimerStats`], and is meant for deadlines that the scheduler keeps internally.
+    */
+    pub async fn wait_until(&self, deadline: Instant) {
+        match self.precision.get() {
+            TimerPrecision::Coarse => {
+                Timer::at(deadline).await;
+            }
+            TimerPrecision::Hybrid { spin_threshold } => {
+                if let Some(coarse) = deadline.checked_sub(spin_threshold) {
+                    if coarse > Instant::now() {
+                        Timer::at(coarse).await;
+                    }
+                }
+                // NOTE: Yielding instead of spinning in place lets any other
+                // work on the same executor run while we wait for the deadline
+                while Instant::now() < deadline {
+                    future::yield_now().await;
+                }
+            }
+        }
+    }
+
+    /**
+        Runs the given future, giving up and dropping it if it does not complete by the deadline.
+
+        Like [`Clock::wait_until`], this is not counted as a pending sleep or in [`TimerStats`].
+    */
+    pub async fn timeout_at<F: Future>(&self, deadline: Instant, fut: F) -> Option<F::Output> {
+        let fut_timeout = async {
+            self.wait_until(deadline).await;
+            None
+        };
+        async { Some(fut.await) }.or(fut_timeout).await
+    }
+}
+
+/**
+    Keeps track of a pending sleep in a coalescing bucket, removing it once dropped.
+*/
+struct BucketGuard {
+    bucket: u128,
+    buckets: Rc<RefCell<FxHashMap<u128, usize>>>,
+}
+
+impl Drop for BucketGuard {
+    fn drop(&mut self) {
+        let mut buckets = self.buckets.borrow_mut();
+        if let Some(pending) = buckets.get_mut(&self.bucket) {
+            *pending -= 1;
+            if *pending == 0 {
+                buckets.remove(&self.bucket);
+            }
+        }
+    }
+}
+
+/**
+    Keeps track of a pending sleep and its deadline, removing it once dropped.
+*/
+struct PendingGuard {
+    deadline: Instant,
+    sleeping: Rc<RefCell<BTreeMap<Instant, usize>>>,
+}
+
+impl PendingGuard {
+    fn new(deadline: Instant, sleeping: &Rc<RefCell<BTreeMap<Instant, usize>>>) -> Self {
+        *sleeping.borrow_mut().entry(deadline).or_default() += 1;
+        Self {
+            deadline,
+            sleeping: Rc::clone(sleeping),
+        }
+    }
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        let mut sleeping = self.sleeping.borrow_mut();
+        if let Some(pending) = sleeping.get_mut(&self.deadline) {
+            *pending -= 1;
+            if *pending == 0 {
+                sleeping.remove(&self.deadline);
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    cell::RefCell,
+    future::Future,
+    panic,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use tokio::{
+    runtime::{Builder, Handle, Runtime},
+    task::JoinHandle,
+};
+
+const ERR_RUNTIME_BUILD: &str = "failed to build companion tokio runtime";
+const ERR_RUNTIME_SHUTDOWN: &str = "companion tokio runtime was shut down";
+
+/**
+    Companion Tokio runtime for a scheduler, used by [`LuaSpawnExt::spawn_tokio`].
+
+    Futures that need a Tokio reactor, such as those from `reqwest` or `sqlx`, run
+    on this runtime, and their results are forwarded back into the main executor.
+
+    The runtime used is, in order of preference:
+
+    - The handle given to [`Scheduler::set_tokio_handle`]
+    - The runtime that the scheduler itself is running within, if any
+    - A multi-threaded runtime owned by the scheduler, created on first use
+
+    [`LuaSpawnExt::spawn_tokio`]: crate::LuaSpawnExt::spawn_tokio
+    [`Scheduler::set_tokio_handle`]: crate::Scheduler::set_tokio_handle
+*/
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TokioCompanion {
+    handle: Rc<RefCell<Option<Handle>>>,
+    owned: Rc<RefCell<Option<Runtime>>>,
+}
+
+impl TokioCompanion {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_handle(&self, handle: Handle) {
+        self.handle.replace(Some(handle));
+    }
+
+    pub fn handle(&self) -> Handle {
+        if let Some(handle) = self.handle.borrow().as_ref() {
+            return handle.clone();
+        }
+        if let Ok(handle) = Handle::try_current() {
+            return handle;
+        }
+        self.owned
+            .borrow_mut()
+            .get_or_insert_with(|| {
+                Builder::new_multi_thread()
+                    .thread_name("lua-tokio")
+                    .enable_all()
+                    .build()
+                    .expect(ERR_RUNTIME_BUILD)
+            })
+            .handle()
+            .clone()
+    }
+
+    /**
+        Spawns the given future on the companion runtime, returning a future for its output.
+
+        The future is aborted if the returned future is dropped before completing.
+    */
+    pub fn spawn<F>(&self, fut: F) -> AbortOnDrop<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        AbortOnDrop(self.handle().spawn(fut))
+    }
+
+    /**
+        Shuts down the owned runtime, if one was created, without waiting for its tasks.
+    */
+    pub fn shutdown(&self) {
+        if let Some(runtime) = self.owned.borrow_mut().take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/**
+    A [`JoinHandle`] that aborts its task when dropped, and resumes any panics from it.
+*/
+pub(crate) struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> Future for AbortOnDrop<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        Pin::new(&mut self.0).poll(cx).map(|result| match result {
+            Ok(value) => value,
+            Err(e) => match e.try_into_panic() {
+                Ok(payload) => panic::resume_unwind(payload),
+                Err(e) => panic!("{ERR_RUNTIME_SHUTDOWN}: {e}"),
+            },
+        })
+    }
+}
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
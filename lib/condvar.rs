@@ -0,0 +1,103 @@
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use mlua::prelude::*;
+
+use crate::queue::DeferredThreadQueue;
+
+/**
+    Lua implementation of `Condvar:wait`.
+
+    Parks the calling thread, releases the mutex, and then yields until
+    notified. Since the scheduler is single-threaded, nothing can run in
+    between parking the thread and releasing the mutex, making it atomic.
+*/
+pub(crate) const WAIT_IMPL_LUA: &str = r"
+local cv, mutex = ...
+park(cv)
+mutex:unlock()
+yield()
+mutex:lock()
+";
+
+/**
+    A condition variable that can be used from Lua.
+
+    Waiting threads are parked inside of the condition variable, and resumed
+    through the deferred thread queue whenever they are notified.
+
+    Any Lua value with `lock` and `unlock` methods may be used as a mutex.
+*/
+pub(crate) struct Condvar {
+    queue: DeferredThreadQueue,
+    waiters: Rc<RefCell<VecDeque<LuaRegistryKey>>>,
+    wait: Rc<LuaRegistryKey>,
+}
+
+impl Condvar {
+    pub fn new(queue: DeferredThreadQueue, wait: Rc<LuaRegistryKey>) -> Self {
+        Self {
+            queue,
+            waiters: Rc::new(RefCell::new(VecDeque::new())),
+            wait,
+        }
+    }
+
+    /**
+        Parks the currently running thread, until notified.
+
+        Note that this does not yield, that must be done separately.
+    */
+    pub fn park(&self, lua: &Lua) -> LuaResult<()> {
+        let key = lua.create_registry_value(lua.current_thread())?;
+        self.waiters.borrow_mut().push_back(key);
+        Ok(())
+    }
+
+    /**
+        Wakes up a single parked thread, if any.
+
+        Threads that are no longer resumable (such as cancelled threads) are skipped.
+    */
+    pub fn notify_one(&self, lua: &Lua) -> LuaResult<()> {
+        loop {
+            let Some(key) = self.waiters.borrow_mut().pop_front() else {
+                break Ok(());
+            };
+            let thread: LuaThread = lua.registry_value(&key)?;
+            lua.remove_registry_value(key)?;
+            if thread.status() == LuaThreadStatus::Resumable {
+                self.queue.push_item(lua, thread, ())?;
+                break Ok(());
+            }
+        }
+    }
+
+    /**
+        Wakes up all parked threads.
+    */
+    pub fn notify_all(&self, lua: &Lua) -> LuaResult<()> {
+        let keys = self.waiters.borrow_mut().drain(..).collect::<Vec<_>>();
+        for key in keys {
+            let thread: LuaThread = lua.registry_value(&key)?;
+            lua.remove_registry_value(key)?;
+            if thread.status() == LuaThreadStatus::Resumable {
+                self.queue.push_item(lua, thread, ())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl LuaUserData for Condvar {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_function_get("wait", |lua, this| {
+            let this = this.borrow::<Self>()?;
+            lua.registry_value::<LuaFunction>(&this.wait)
+        });
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("notifyOne", |lua, this, ()| this.notify_one(lua));
+        methods.add_method("notifyAll", |lua, this, ()| this.notify_all(lua));
+    }
+}
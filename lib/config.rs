@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use crate::{
+    chunk::ChunkOptions, clock::TimerPrecision, tracking::TrackingMode, watchdog::WatchdogPolicy,
+};
+
+/**
+    A read-only snapshot of the effective configuration of a scheduler.
+
+    See [`Scheduler::config`] for more information.
+
+    [`Scheduler::config`]: crate::Scheduler::config
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchedulerConfig {
+    /// If the scheduler is in deterministic mode.
+    pub deterministic: bool,
+    /// If the scheduler is in single-threaded mode.
+    pub single_threaded: bool,
+    /// The maximum number of spawned threads resumed per scheduler cycle, if one is set.
+    pub max_spawned_per_cycle: Option<usize>,
+    /// The capacity of the spawned, deferred, and high priority queues, if one is set.
+    pub queue_capacity: Option<usize>,
+    /// The default yield budget for all Lua threads, if one is set.
+    pub yield_budget: Option<Duration>,
+    /// The number of Lua threads that have a yield budget of their own set.
+    pub yield_budgets: usize,
+    /// The budget that no Lua thread may exceed during a single resumption, if one is set.
+    pub thread_budget: Option<Duration>,
+    /// The maximum lifetime and policy of the watchdog, if one is set.
+    pub watchdog: Option<(Duration, WatchdogPolicy)>,
+    /// The threshold for reporting long polls, if a long poll callback is set.
+    pub long_poll_threshold: Option<Duration>,
+    /// The queue depths at which queue pressure is reported, in ascending order.
+    pub queue_pressure_thresholds: Vec<usize>,
+    /// The number of errors from tracked Lua threads that are retained.
+    pub error_retention: usize,
+    /// The number of values from tracked Lua threads that are recorded, in debug builds.
+    pub value_log_capacity: usize,
+    /// The number of tracked Lua threads that have their origins retained, for respawning.
+    pub respawn_retention: usize,
+    /// The default Luau compiler settings for pushed chunks.
+    pub chunk_options: ChunkOptions,
+    /// The precision used when sleeping.
+    pub timer_precision: TimerPrecision,
+    /// The resolution that sleep deadlines are coalesced to, or zero if disabled.
+    pub timer_coalescing: Duration,
+    /// How tracked threads and their results are stored.
+    pub tracking_mode: TrackingMode,
+    /// If the scheduler is in strict mode.
+    pub strict: bool,
+}
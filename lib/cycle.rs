@@ -0,0 +1,77 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use mlua::prelude::*;
+
+type CycleHook = Box<dyn Fn(&Lua, u64)>;
+
+/**
+    The cycle counter of a scheduler, along with any hooks to call when cycles start and end.
+
+    A cycle is a single iteration of the main loop of the scheduler - waking up, and then
+    resuming all Lua threads and spawning all futures that were ready at that point.
+*/
+#[derive(Clone)]
+pub(crate) struct Cycles {
+    current: Rc<Cell<u64>>,
+    on_start: Rc<RefCell<Vec<CycleHook>>>,
+    on_end: Rc<RefCell<Vec<CycleHook>>>,
+}
+
+impl Cycles {
+    pub fn new() -> Self {
+        Self {
+            current: Rc::new(Cell::new(0)),
+            on_start: Rc::new(RefCell::new(Vec::new())),
+            on_end: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    #[inline]
+    pub fn current(&self) -> u64 {
+        self.current.get()
+    }
+
+    pub fn add_start_hook(&self, hook: impl Fn(&Lua, u64) + 'static) {
+        self.on_start.borrow_mut().push(Box::new(hook));
+    }
+
+    pub fn add_end_hook(&self, hook: impl Fn(&Lua, u64) + 'static) {
+        self.on_end.borrow_mut().push(Box::new(hook));
+    }
+
+    pub fn clear_hooks(&self) {
+        self.on_start.borrow_mut().clear();
+        self.on_end.borrow_mut().clear();
+    }
+
+    /**
+        Starts the next cycle, calling any start hooks.
+    */
+    pub fn start(&self, lua: &Lua) {
+        let cycle = self.current.get() + 1;
+        self.current.set(cycle);
+        for hook in self.on_start.borrow().iter() {
+            hook(lua, cycle);
+        }
+    }
+
+    /**
+        Ends the current cycle, calling any end hooks.
+    */
+    pub fn end(&self, lua: &Lua) {
+        let cycle = self.current.get();
+        for hook in self.on_end.borrow().iter() {
+            hook(lua, cycle);
+        }
+    }
+
+    /**
+        Resets the cycle counter, keeping any hooks.
+    */
+    pub fn reset(&self) {
+        self.current.set(0);
+    }
+}
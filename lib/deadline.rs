@@ -0,0 +1,188 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Instant,
+};
+
+use event_listener::Event;
+use futures_lite::FutureExt;
+use mlua::prelude::*;
+use rustc_hash::FxHashMap;
+
+use crate::{clock::Clock, thread_id::ThreadId};
+
+const ERR_DEADLINE_EXCEEDED: &str = "thread exceeded its deadline";
+
+/**
+    Deadlines for Lua threads, see [`Scheduler::push_thread_with_deadline`].
+
+    Threads are kept in a Lua table with weak keys, so that they can be cancelled once
+    their deadline has passed, without preventing them from being garbage collected.
+
+    Threads whose deadlines pass at the same time are cancelled in the order that their
+    deadlines were first set, and never in the order of their addresses, which may differ
+    between runs, so that cancellations are deterministic given the same deadlines.
+
+    [`Scheduler::push_thread_with_deadline`]: crate::Scheduler::push_thread_with_deadline
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct Deadlines {
+    instants: Rc<RefCell<FxHashMap<ThreadId, (Instant, u64)>>>,
+    sequence: Rc<Cell<u64>>,
+    table: Rc<RefCell<Option<LuaRegistryKey>>>,
+    event: Rc<Event>,
+}
+
+impl Deadlines {
+    pub fn new() -> Self {
+        Self {
+            instants: Rc::new(RefCell::new(FxHashMap::default())),
+            sequence: Rc::new(Cell::new(0)),
+            table: Rc::new(RefCell::new(None)),
+            event: Rc::new(Event::new()),
+        }
+    }
+
+    pub fn error() -> LuaError {
+        LuaError::runtime(ERR_DEADLINE_EXCEEDED)
+    }
+
+    fn table<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaTable<'lua>> {
+        if let Some(key) = &*self.table.borrow() {
+            return lua.registry_value(key);
+        }
+        let table = lua.create_table()?;
+        let meta = lua.create_table_from([("__mode", "k")])?;
+        table.set_metatable(Some(meta));
+        self.table
+            .replace(Some(lua.create_registry_value(table.clone())?));
+        Ok(table)
+    }
+
+    /**
+        Sets the deadline for the given thread, keeping any earlier deadline it already has.
+    */
+    pub fn set(&self, lua: &Lua, thread: &LuaThread, deadline: Instant) -> LuaResult<()> {
+        let id = ThreadId::from(thread);
+        {
+            let mut deadlines = self.instants.borrow_mut();
+            let sequence = self.sequence.get();
+            let (current, _) = deadlines.entry(id).or_insert_with(|| {
+                self.sequence.set(sequence.wrapping_add(1));
+                (deadline, sequence)
+            });
+            *current = (*current).min(deadline);
+        }
+        self.table(lua)?.raw_set(thread.clone(), true)?;
+        self.event.notify(usize::MAX);
+        Ok(())
+    }
+
+    /**
+        Gives the given thread the same deadline as the currently running thread, if it has one.
+    */
+    pub fn inherit(&self, lua: &Lua, thread: &LuaThread) -> LuaResult<()> {
+        match self.get(ThreadId::from(&lua.current_thread())) {
+            Some(deadline) => self.set(lua, thread, deadline),
+            None => Ok(()),
+        }
+    }
+
+    pub fn get(&self, id: ThreadId) -> Option<Instant> {
+        self.instants
+            .borrow()
+            .get(&id)
+            .map(|(deadline, _)| *deadline)
+    }
+
+    pub fn is_expired(&self, id: ThreadId) -> bool {
+        self.get(id)
+            .is_some_and(|deadline| deadline <= Instant::now())
+    }
+
+    pub fn remove(&self, id: ThreadId) {
+        self.instants.borrow_mut().remove(&id);
+    }
+
+    pub fn clear(&self) {
+        self.instants.borrow_mut().clear();
+        self.table.borrow_mut().take();
+    }
+
+    /**
+        Removes all threads whose deadlines have passed, returning those that still exist.
+    */
+    pub fn take_expired<'lua>(&self, lua: &'lua Lua) -> LuaResult<Vec<LuaThread<'lua>>> {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        self.instants
+            .borrow_mut()
+            .retain(|id, (deadline, sequence)| {
+                let keep = *deadline > now;
+                if !keep {
+                    expired.push((*deadline, *sequence, *id));
+                }
+                keep
+            });
+        if expired.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let table = self.table(lua)?;
+        let mut threads = Vec::new();
+        for pair in table.clone().pairs::<LuaThread, LuaValue>() {
+            let (thread, _) = pair?;
+            let id = ThreadId::from(&thread);
+            if let Some(&(deadline, sequence, _)) = expired.iter().find(|(_, _, e)| *e == id) {
+                threads.push((deadline, sequence, thread));
+            }
+        }
+        // NOTE: Tables are iterated in the order of their hashed keys, so we must sort
+        // the threads to cancel the earliest deadlines first, and ties in set order
+        threads.sort_by_key(|(deadline, sequence, _)| (*deadline, *sequence));
+        let threads = threads
+            .into_iter()
+            .map(|(_, _, thread)| thread)
+            .collect::<Vec<_>>();
+        for thread in &threads {
+            table.raw_set(thread.clone(), LuaValue::Nil)?;
+        }
+        Ok(threads)
+    }
+
+    /**
+        Waits until the earliest deadline has passed, also waking up
+        whenever a new deadline is set, since it may be earlier.
+    */
+    pub async fn wait_for_expiry(&self, clock: &Clock) {
+        loop {
+            let listener = self.event.listen();
+            let earliest = self
+                .instants
+                .borrow()
+                .values()
+                .map(|(deadline, _)| *deadline)
+                .min();
+            let Some(earliest) = earliest else {
+                listener.await;
+                continue;
+            };
+            if earliest <= Instant::now() {
+                return;
+            }
+            let expired = async {
+                clock.wait_until(earliest).await;
+                true
+            };
+            if expired
+                .or(async {
+                    listener.await;
+                    false
+                })
+                .await
+            {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use event_listener::Event;
+use futures_lite::FutureExt;
+use mlua::prelude::*;
+
+use crate::{
+    clock::Clock,
+    key_pool::RegistryKeyPool,
+    location::{Location, ThreadLocations},
+    queue::ThreadQueue,
+    thread_id::ThreadId,
+    traits::IntoLuaThread,
+    util::{instant_after, ThreadWithArgs},
+};
+
+/**
+    Lua threads that are scheduled to be resumed after a delay, ordered by when they are due.
+
+    All delayed threads share a single timer, which is always set for the earliest due thread,
+    meaning delaying thousands of threads does not require thousands of separate timers.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct DelayedThreads {
+    items: Rc<RefCell<BTreeMap<(Instant, u64), ThreadWithArgs>>>,
+    counter: Rc<Cell<u64>>,
+    event: Rc<Event>,
+    keys: RegistryKeyPool,
+    locations: ThreadLocations,
+}
+
+impl DelayedThreads {
+    pub fn new(locations: &ThreadLocations) -> Self {
+        Self {
+            items: Rc::new(RefCell::new(BTreeMap::new())),
+            counter: Rc::new(Cell::new(0)),
+            event: Rc::new(Event::new()),
+            keys: RegistryKeyPool::new(),
+            locations: locations.clone(),
+        }
+    }
+
+    pub fn push_item<'lua>(
+        &self,
+        lua: &'lua Lua,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+        delay: Duration,
+    ) -> LuaResult<ThreadId> {
+        let thread = thread.into_lua_thread(lua)?;
+        let id = ThreadQueue::check_pushable(lua, &thread)?;
+        let args = args.into_lua_multi(lua)?;
+        self.push_checked(lua, thread, args, delay)?;
+        Ok(id)
+    }
+
+    /**
+        Delays the given thread without checking if it can be resumed, see [`ThreadQueue::push_checked`].
+    */
+    pub fn push_checked<'lua>(
+        &self,
+        lua: &'lua Lua,
+        thread: LuaThread<'lua>,
+        args: LuaMultiValue<'lua>,
+        delay: Duration,
+    ) -> LuaResult<()> {
+        let id = ThreadId::from(&thread);
+
+        tracing::trace!("pushing delayed item with {} args", args.len());
+        let stored = ThreadWithArgs::new(lua, &self.keys, thread, args)?;
+
+        // NOTE: The counter keeps threads that are due at the
+        // same instant in the order that they were pushed
+        let seq = self.counter.get();
+        self.counter.set(seq + 1);
+        self.items
+            .borrow_mut()
+            .insert((instant_after(delay), seq), stored);
+        self.locations.add(id, Location::Delayed);
+        self.event.notify(usize::MAX);
+
+        Ok(())
+    }
+
+    /**
+        Removes all threads that are due, in the order that they became due.
+    */
+    pub fn drain_due<'lua>(&self, lua: &'lua Lua) -> Vec<(LuaThread<'lua>, LuaMultiValue<'lua>)> {
+        let now = Instant::now();
+        let mut due = Vec::new();
+        loop {
+            let stored = {
+                let mut items = self.items.borrow_mut();
+                match items.first_key_value() {
+                    Some(((at, _), _)) if *at <= now => items.pop_first().map(|(_, s)| s),
+                    _ => None,
+                }
+            };
+            // NOTE: Must not hold the borrow here, creating lazy args may push more items
+            match stored {
+                Some(stored) => {
+                    self.locations.remove(stored.thread_id(), Location::Delayed);
+                    due.extend(stored.into_inner(lua, &self.keys));
+                }
+                None => break,
+            }
+        }
+        due
+    }
+
+    /**
+        Waits until the earliest delayed thread is due, also waking up
+        whenever a new thread is delayed, since it may be due earlier.
+    */
+    pub async fn wait_for_item(&self, clock: &Clock) {
+        loop {
+            let listener = self.event.listen();
+            let earliest = self.next_due();
+            let Some(earliest) = earliest else {
+                listener.await;
+                continue;
+            };
+            if earliest <= Instant::now() {
+                return;
+            }
+            let due = async {
+                clock.wait_until(earliest).await;
+                true
+            };
+            if due
+                .or(async {
+                    listener.await;
+                    false
+                })
+                .await
+            {
+                return;
+            }
+        }
+    }
+
+    /**
+        Returns the ids of all delayed threads, in the order that they will become due.
+    */
+    #[cfg(feature = "unstable")]
+    pub fn thread_ids(&self) -> Vec<ThreadId> {
+        self.items
+            .borrow()
+            .values()
+            .map(ThreadWithArgs::thread_id)
+            .collect()
+    }
+
+    /**
+        Removes the given thread, if it is delayed, so that it is never resumed.
+
+        Returns `true` if the thread was delayed.
+    */
+    pub fn cancel(&self, id: ThreadId) -> bool {
+        if !self.contains(id) {
+            return false;
+        }
+        self.items.borrow_mut().retain(|_, stored| {
+            let keep = stored.thread_id() != id;
+            if !keep {
+                self.locations.remove(id, Location::Delayed);
+            }
+            keep
+        });
+        // NOTE: Wake up anything waiting for the cancelled thread, so that
+        // it may wait for the next thread instead, or stop waiting entirely
+        self.event.notify(usize::MAX);
+        true
+    }
+
+    /**
+        Removes all delayed threads, without resuming them.
+    */
+    pub fn clear(&self) {
+        self.items.borrow_mut().clear();
+        self.locations.clear(Location::Delayed);
+    }
+
+    /**
+        Checks if the given thread is delayed.
+    */
+    pub fn contains(&self, id: ThreadId) -> bool {
+        self.locations.contains(id, Location::Delayed)
+    }
+
+    /**
+        Removes all delayed threads, returning them so that they may be reported as leaked.
+    */
+    pub fn drain_all<'lua>(&self, lua: &'lua Lua) -> Vec<(LuaThread<'lua>, LuaMultiValue<'lua>)> {
+        let items = std::mem::take(&mut *self.items.borrow_mut());
+        self.locations.clear(Location::Delayed);
+        items
+            .into_values()
+            .filter_map(|stored| stored.into_inner(lua, &self.keys))
+            .collect()
+    }
+
+    /**
+        Returns the instant that the earliest delayed thread is due at, if any.
+    */
+    pub fn next_due(&self) -> Option<Instant> {
+        self.items.borrow().keys().next().map(|(at, _)| *at)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.items.borrow().len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.items.borrow().is_empty()
+    }
+}
@@ -0,0 +1,158 @@
+#![allow(clippy::module_name_repetitions)]
+
+use std::{
+    cell::{Cell, RefCell},
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures_lite::FutureExt;
+use mlua::prelude::*;
+
+use crate::{queue::LocalBoxFuture, tags::ThreadTags, thread_id::ThreadId};
+
+type LongPollCallback = Box<dyn Fn(LongPoll) + Send + 'static>;
+
+/**
+    A single poll of a future or Lua thread that took longer than the configured threshold.
+
+    See [`Scheduler::set_long_poll_callback`] for more information.
+
+    [`Scheduler::set_long_poll_callback`]: crate::Scheduler::set_long_poll_callback
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LongPoll {
+    /// How long the poll took, blocking the scheduler.
+    pub duration: Duration,
+    /// The Lua thread that was running, or that spawned the future.
+    pub thread: Option<ThreadId>,
+    /// The tag of the Lua thread, if any.
+    pub tag: Option<String>,
+}
+
+/**
+    Diagnostics for tasks spawned on the local executor of a scheduler.
+
+    Keeps track of the number of active tasks, and detects any polls that
+    take longer than a configurable threshold, blocking the executor.
+*/
+#[derive(Clone)]
+pub(crate) struct Diagnostics {
+    active: Rc<Cell<usize>>,
+    completed: Rc<Cell<usize>>,
+    threshold: Rc<Cell<Option<Duration>>>,
+    callback: Rc<RefCell<Option<LongPollCallback>>>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self {
+            active: Rc::new(Cell::new(0)),
+            completed: Rc::new(Cell::new(0)),
+            threshold: Rc::new(Cell::new(None)),
+            callback: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn replace_long_poll(
+        &self,
+        threshold: Duration,
+        callback: impl Fn(LongPoll) + Send + 'static,
+    ) {
+        self.threshold.set(Some(threshold));
+        self.callback.borrow_mut().replace(Box::new(callback));
+    }
+
+    pub fn long_poll_threshold(&self) -> Option<Duration> {
+        self.threshold.get()
+    }
+
+    pub fn clear_long_poll(&self) {
+        self.threshold.set(None);
+        self.callback.borrow_mut().take();
+    }
+
+    pub fn active_tasks(&self) -> usize {
+        self.active.get()
+    }
+
+    pub fn completed_tasks(&self) -> usize {
+        self.completed.get()
+    }
+
+    /**
+        Gets the tag for the given thread, but only if long polls are being
+        detected, to avoid doing any unnecessary work for attribution.
+    */
+    pub fn tag_for(&self, lua: &Lua, tags: &ThreadTags, thread: &LuaThread) -> Option<String> {
+        if self.threshold.get().is_some() {
+            tags.get(lua, thread)
+        } else {
+            None
+        }
+    }
+
+    /**
+        Wraps the given future, counting it as an active task until
+        dropped, and reporting any polls that take too long.
+    */
+    pub fn monitor<'fut>(
+        &self,
+        fut: impl Future<Output = ()> + 'fut,
+        thread: Option<ThreadId>,
+        tag: Option<String>,
+    ) -> MonitoredFuture<'fut> {
+        self.active.set(self.active.get() + 1);
+        MonitoredFuture {
+            inner: fut.boxed_local(),
+            diagnostics: self.clone(),
+            thread,
+            tag,
+        }
+    }
+}
+
+/**
+    A future that is counted as an active task, and reports any polls that take too long.
+*/
+pub(crate) struct MonitoredFuture<'fut> {
+    inner: LocalBoxFuture<'fut>,
+    diagnostics: Diagnostics,
+    thread: Option<ThreadId>,
+    tag: Option<String>,
+}
+
+impl Future for MonitoredFuture<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Some(threshold) = self.diagnostics.threshold.get() else {
+            return self.inner.poll(cx);
+        };
+        let start = Instant::now();
+        let res = self.inner.poll(cx);
+        let duration = start.elapsed();
+        if duration > threshold {
+            if let Some(callback) = &*self.diagnostics.callback.borrow() {
+                callback(LongPoll {
+                    duration,
+                    thread: self.thread,
+                    tag: self.tag.clone(),
+                });
+            }
+        }
+        res
+    }
+}
+
+impl Drop for MonitoredFuture<'_> {
+    fn drop(&mut self) {
+        let active = &self.diagnostics.active;
+        active.set(active.get().saturating_sub(1));
+        let completed = &self.diagnostics.completed;
+        completed.set(completed.get() + 1);
+    }
+}
@@ -0,0 +1,84 @@
+use std::{cell::Cell, rc::Rc};
+
+use mlua::prelude::*;
+
+const ERR_DRAINING: &str = "scheduler is shutting down, no new threads may be spawned";
+
+/**
+    Progress of a scheduler that is draining, see [`Scheduler::begin_drain`].
+
+    Tasks are resumptions of Lua threads, and thread-local futures. Note that threads
+    parked while waiting for something else, such as a tick, are not counted as remaining.
+
+    [`Scheduler::begin_drain`]: crate::Scheduler::begin_drain
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DrainStatus {
+    /// The number of tasks that have completed since draining began.
+    pub drained: usize,
+    /// The number of tasks that are still queued or running.
+    pub remaining: usize,
+}
+
+/**
+    Drain state for a scheduler.
+
+    Once draining, no new threads may be spawned, but any
+    threads and futures already in the scheduler will finish.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct Drain {
+    started_at: Rc<Cell<Option<usize>>>,
+}
+
+impl Drain {
+    pub fn new() -> Self {
+        Self {
+            started_at: Rc::new(Cell::new(None)),
+        }
+    }
+
+    /**
+        Begins draining, given the total number of tasks completed so far.
+    */
+    pub fn begin(&self, completed: usize) {
+        if self.started_at.get().is_none() {
+            self.started_at.set(Some(completed));
+        }
+    }
+
+    pub fn reset(&self) {
+        self.started_at.set(None);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.started_at.get().is_some()
+    }
+
+    /**
+        Gets the current drain status, given the total number
+        of tasks completed so far, and the number remaining.
+    */
+    pub fn status(&self, completed: usize, remaining: usize) -> Option<DrainStatus> {
+        let started_at = self.started_at.get()?;
+        Some(DrainStatus {
+            drained: completed.saturating_sub(started_at),
+            remaining,
+        })
+    }
+
+    /**
+        Checks that new threads may be spawned.
+
+        # Errors
+
+        Errors if the scheduler is draining.
+    */
+    pub fn check(&self) -> LuaResult<()> {
+        if self.is_draining() {
+            Err(LuaError::runtime(ERR_DRAINING))
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -1,22 +1,95 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, fmt, rc::Rc, time::SystemTime};
 
 use mlua::prelude::*;
 
-type ErrorCallback = Box<dyn Fn(LuaError) + Send + 'static>;
+use crate::{
+    error_history::split_error,
+    error_value::ThreadError,
+    names::ThreadNames,
+    output::{Output, OutputLevel},
+    primitives::Primitives,
+    tags::ThreadTags,
+    thread_id::ThreadId,
+};
+
+type ErrorCallback = Box<dyn Fn(UncaughtError) + Send + 'static>;
+
+/**
+    An uncaught error passed to the error callback, see [`Scheduler::set_error_callback`].
+
+    Displays as the original error, prefixed with the name of the thread if it has one.
+
+    [`Scheduler::set_error_callback`]: crate::Scheduler::set_error_callback
+*/
+#[derive(Debug, Clone)]
+pub struct UncaughtError {
+    /// The id of the thread that errored, if the error came from a thread.
+    pub thread: Option<ThreadId>,
+    /// The name of the thread, if it had one.
+    pub name: Option<String>,
+    /// The tag of the thread, if it had one.
+    pub tag: Option<String>,
+    /// The original error.
+    pub error: LuaError,
+    /// The Luau traceback of the error, if one was available.
+    pub traceback: Option<String>,
+    /// When the error was caught by the scheduler.
+    pub timestamp: SystemTime,
+}
+
+impl UncaughtError {
+    fn new(error: &LuaError) -> Self {
+        let (_, traceback) = split_error(error);
+        Self {
+            thread: None,
+            name: None,
+            tag: None,
+            error: error.clone(),
+            traceback,
+            timestamp: SystemTime::now(),
+        }
+    }
+
+    fn from_thread(lua: &Lua, thread: &LuaThread, error: &LuaError) -> Self {
+        let name = lua
+            .app_data_ref::<ThreadNames>()
+            .and_then(|names| names.get(lua, thread));
+        let tag = lua
+            .app_data_ref::<ThreadTags>()
+            .and_then(|tags| tags.get(lua, thread));
+        Self {
+            thread: Some(ThreadId::from(thread)),
+            name,
+            tag,
+            ..Self::new(error)
+        }
+    }
+}
+
+impl fmt::Display for UncaughtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = &self.name {
+            writeln!(f, "error in thread named '{name}'")?;
+        }
+        write!(f, "{}", self.error)
+    }
+}
 
 #[derive(Clone)]
 pub(crate) struct ThreadErrorCallback {
     inner: Rc<RefCell<Option<ErrorCallback>>>,
+    handler: Rc<RefCell<Option<LuaRegistryKey>>>,
 }
 
 impl ThreadErrorCallback {
     pub fn new() -> Self {
         Self {
             inner: Rc::new(RefCell::new(None)),
+            handler: Rc::new(RefCell::new(None)),
         }
     }
 
-    pub fn replace(&self, callback: impl Fn(LuaError) + Send + 'static) {
+    pub fn replace(&self, callback: impl Fn(UncaughtError) + Send + 'static) {
         self.inner.borrow_mut().replace(Box::new(callback));
     }
 
@@ -25,14 +98,99 @@ impl ThreadErrorCallback {
     }
 
     pub fn call(&self, error: &LuaError) {
+        self.report(UncaughtError::new(error));
+    }
+
+    fn report(&self, error: UncaughtError) {
         if let Some(cb) = &*self.inner.borrow() {
-            cb(error.clone());
+            cb(error);
+        }
+    }
+
+    /**
+        Sets the Lua error handler, which is called before the error callback
+        for uncaught errors in threads, and may choose to handle them instead.
+    */
+    pub fn set_handler(&self, handler: Option<LuaRegistryKey>) {
+        *self.handler.borrow_mut() = handler;
+    }
+
+    pub fn clear_handler(&self) {
+        self.handler.borrow_mut().take();
+    }
+
+    /**
+        Reports an uncaught error from the given thread, first to
+        the Lua error handler, if any, then to the error callback.
+
+        The error callback is skipped if the Lua error handler returned
+        a truthy value, meaning that it handled the error by itself.
+    */
+    pub fn call_thread(&self, lua: &Lua, thread: &LuaThread, error: &LuaError) {
+        match self.call_handler(lua, thread, error) {
+            Ok(true) => {}
+            Ok(false) => {
+                let error = UncaughtError::from_thread(lua, thread, error);
+                write_output(lua, thread, &error);
+                self.report(error);
+            }
+            Err(e) => {
+                let error = UncaughtError::from_thread(lua, thread, error);
+                let handler_error = UncaughtError::from_thread(lua, thread, &e);
+                write_output(lua, thread, &error);
+                write_output(lua, thread, &handler_error);
+                self.report(error);
+                self.report(handler_error);
+            }
         }
     }
+
+    fn call_handler(&self, lua: &Lua, thread: &LuaThread, error: &LuaError) -> LuaResult<bool> {
+        // NOTE: Must not hold the borrow while calling, the
+        // handler may replace itself, or remove the handler
+        let handler = match &*self.handler.borrow() {
+            Some(key) => lua.registry_value::<LuaFunction>(key)?,
+            None => return Ok(false),
+        };
+
+        let message = if let Some(err) = ThreadError::from_error(error) {
+            err.value(lua)?
+        } else {
+            let message = match error {
+                LuaError::RuntimeError(message) => message.clone(),
+                other => other.to_string(),
+            };
+            LuaValue::String(lua.create_string(message)?)
+        };
+
+        let traceback = match lua.app_data_ref::<Primitives>().map(|p| p.clone()) {
+            Some(primitives) => primitives
+                .get(lua, "traceback")?
+                .call::<_, LuaValue>(thread.clone())?,
+            None => LuaValue::Nil,
+        };
+
+        let result = handler.call::<_, LuaValue>((thread.clone(), message, traceback))?;
+        Ok(!matches!(result, LuaValue::Nil | LuaValue::Boolean(false)))
+    }
+}
+
+/**
+    Writes the given uncaught error to the output sink, if one is set.
+*/
+fn write_output(lua: &Lua, thread: &LuaThread, error: &UncaughtError) {
+    let output = lua.app_data_ref::<Output>().map(|o| o.clone());
+    if let Some(output) = output.filter(Output::has_sink) {
+        output.write(
+            ThreadId::from(thread),
+            OutputLevel::Error,
+            error.to_string(),
+        );
+    }
 }
 
 #[allow(clippy::needless_pass_by_value)]
-fn default_error_callback(e: LuaError) {
+fn default_error_callback(e: UncaughtError) {
     eprintln!("{e}");
 }
 
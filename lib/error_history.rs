@@ -0,0 +1,166 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    rc::Rc,
+    time::SystemTime,
+};
+
+use mlua::prelude::*;
+
+use crate::{
+    names::ThreadNames, tags::ThreadTags, thread_id::ThreadId, thread_info::ThreadRecords,
+};
+
+/**
+    A record of a Lua thread that errored, see [`Scheduler::recent_errors`].
+
+    [`Scheduler::recent_errors`]: crate::Scheduler::recent_errors
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorRecord {
+    /// The id of the thread that errored.
+    pub thread: ThreadId,
+    /// The tag of the thread, if it had one.
+    pub tag: Option<String>,
+    /// The name of the thread, if it had one.
+    pub name: Option<String>,
+    /// The error message, without any traceback.
+    pub message: String,
+    /// The traceback of the error, if one was available.
+    pub traceback: Option<String>,
+    /// The name of the chunk the error originated from, if it could be determined.
+    pub chunk_name: Option<String>,
+    /// When the thread was first pushed to the scheduler, if it was pushed.
+    pub pushed_at: Option<SystemTime>,
+    /// When the thread errored.
+    pub failed_at: SystemTime,
+}
+
+/**
+    Bounded history of errors from tracked Lua threads.
+
+    Errors are only retained once a capacity has been set, and the
+    oldest records are discarded once the capacity has been reached.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct ErrorHistory {
+    capacity: Rc<Cell<usize>>,
+    records: Rc<RefCell<VecDeque<ErrorRecord>>>,
+}
+
+impl ErrorHistory {
+    pub fn new() -> Self {
+        Self {
+            capacity: Rc::new(Cell::new(0)),
+            records: Rc::new(RefCell::new(VecDeque::new())),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity.get()
+    }
+
+    pub fn set_capacity(&self, capacity: usize) {
+        self.capacity.set(capacity);
+        let mut records = self.records.borrow_mut();
+        while records.len() > capacity {
+            records.pop_front();
+        }
+    }
+
+    pub fn clear(&self) {
+        self.records.borrow_mut().clear();
+    }
+
+    /**
+        Records an error for the given thread, if errors are being retained.
+    */
+    pub fn record(&self, lua: &Lua, id: ThreadId, error: &LuaError) {
+        let capacity = self.capacity.get();
+        if capacity == 0 {
+            return;
+        }
+
+        let thread = lua
+            .app_data_ref::<ThreadRecords>()
+            .and_then(|records| records.find(lua, id).ok().flatten());
+        let tag = thread.as_ref().and_then(|thread| {
+            let tags = lua.app_data_ref::<ThreadTags>()?;
+            tags.get(lua, thread)
+        });
+        let name = thread.as_ref().and_then(|thread| {
+            let names = lua.app_data_ref::<ThreadNames>()?;
+            names.get(lua, thread)
+        });
+        let pushed_at = thread.as_ref().and_then(|thread| {
+            let records = lua.app_data_ref::<ThreadRecords>()?;
+            records.pushed_at(lua, thread)
+        });
+
+        let (message, traceback) = split_error(error);
+        let chunk_name = chunk_name(&message);
+        let record = ErrorRecord {
+            thread: id,
+            tag,
+            name,
+            message,
+            traceback,
+            chunk_name,
+            pushed_at,
+            failed_at: SystemTime::now(),
+        };
+
+        let mut records = self.records.borrow_mut();
+        if records.len() >= capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /**
+        Returns up to `n` of the most recent records, newest first.
+    */
+    pub fn recent(&self, n: usize) -> Vec<ErrorRecord> {
+        self.records
+            .borrow()
+            .iter()
+            .rev()
+            .take(n)
+            .cloned()
+            .collect()
+    }
+}
+
+/**
+    Splits a Lua error into its message and traceback, if it has one.
+*/
+pub(crate) fn split_error(error: &LuaError) -> (String, Option<String>) {
+    match error {
+        LuaError::CallbackError { traceback, cause } => {
+            let (message, _) = split_error(cause);
+            (message, Some(traceback.clone()))
+        }
+        LuaError::RuntimeError(message) => match message.split_once("\nstack traceback:") {
+            Some((message, traceback)) => (
+                message.to_string(),
+                Some(format!("stack traceback:{traceback}")),
+            ),
+            None => (message.clone(), None),
+        },
+        _ => (error.to_string(), None),
+    }
+}
+
+/**
+    Extracts the chunk name from an error message, such as `[string "name"]:1: message`.
+*/
+fn chunk_name(message: &str) -> Option<String> {
+    if let Some(rest) = message.strip_prefix("[string \"") {
+        let (name, _) = rest.split_once("\"]:")?;
+        return Some(name.to_string());
+    }
+    let (name, rest) = message.split_once(':')?;
+    let (line, _) = rest.split_once(':')?;
+    line.parse::<u32>().ok()?;
+    Some(name.to_string())
+}
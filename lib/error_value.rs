@@ -0,0 +1,177 @@
+use std::{error::Error as StdError, fmt, rc::Rc, sync::Arc};
+
+use mlua::prelude::*;
+
+/**
+    An error from a Lua thread that was raised with a non-string value, such as a table or userdata.
+
+    Lua errors raised with non-string values are normally converted into strings once they reach
+    Rust, losing their structure. When errors are raised using [`Functions::error`], the scheduler
+    instead preserves the original value, and stores this error inside of the [`LuaError`] that
+    is passed to the error callback and stored in the result map for the thread.
+
+    Use [`ThreadError::from_error`] to get it back out of a [`LuaError`].
+
+    [`Functions::error`]: crate::Functions::error
+*/
+#[derive(Debug, Clone)]
+pub struct ThreadError {
+    error: LuaError,
+    value: Arc<LuaRegistryKey>,
+}
+
+impl ThreadError {
+    /**
+        Gets the thread error from the given [`LuaError`], if it was raised with a preserved value.
+    */
+    #[must_use]
+    pub fn from_error(error: &LuaError) -> Option<&Self> {
+        error.downcast_ref()
+    }
+
+    /**
+        Returns the error as it would have been without the preserved value.
+    */
+    #[must_use]
+    pub fn error(&self) -> &LuaError {
+        &self.error
+    }
+
+    /**
+        Returns the original value that the error was raised with.
+
+        # Errors
+
+        Errors if the given Lua state is not the one that the error was raised in.
+    */
+    pub fn value<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        lua.registry_value(&self.value)
+    }
+}
+
+impl fmt::Display for ThreadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl StdError for ThreadError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/**
+    Values of errors raised using [`Functions::error`], keyed by the thread that raised them.
+
+    Values are stored in a table with weak keys, so that values for
+    threads that caught their own errors do not outlive the thread.
+
+    [`Functions::error`]: crate::Functions::error
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct ErrorValues {
+    table: Rc<LuaRegistryKey>,
+}
+
+impl ErrorValues {
+    pub fn new(lua: &Lua) -> Self {
+        let table = lua.create_table().expect("out of memory");
+        let meta = lua
+            .create_table_from([("__mode", "k")])
+            .expect("out of memory");
+        table.set_metatable(Some(meta));
+        Self {
+            table: Rc::new(lua.create_registry_value(table).expect("out of memory")),
+        }
+    }
+
+    pub fn table<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaTable<'lua>> {
+        lua.registry_value(&self.table)
+    }
+
+    /**
+        Removes all preserved values, keeping the same table, since
+        it is also referenced by any previously created functions.
+    */
+    pub fn clear(&self, lua: &Lua) -> LuaResult<()> {
+        self.table(lua)?.clear()
+    }
+
+    /**
+        Takes the preserved value for the given thread, if it raised one.
+
+        This is used for threads that errored outside of the scheduler, where the original
+        error was already taken by whoever resumed the thread, and only the value remains.
+    */
+    pub fn take<'lua>(&self, lua: &'lua Lua, thread: &LuaThread<'lua>) -> Option<LuaValue<'lua>> {
+        let table = self.table(lua).ok()?;
+        let value = table.raw_get::<_, LuaValue>(thread.clone()).ok()?;
+        let _ = table.raw_set(thread.clone(), LuaValue::Nil);
+        (!value.is_nil()).then_some(value)
+    }
+
+    /**
+        Attaches the preserved value for the given thread to its error, if it raised one.
+
+        The value is only attached if the error was actually raised with it,
+        and not with some other value after the thread caught the first error.
+    */
+    pub fn attach(&self, lua: &Lua, thread: &LuaThread, error: LuaError) -> LuaError {
+        let Ok(table) = self.table(lua) else {
+            return error;
+        };
+        let Ok(value) = table.raw_get::<_, LuaValue>(thread.clone()) else {
+            return error;
+        };
+        if value.is_nil() {
+            return error;
+        }
+        let _ = table.raw_set(thread.clone(), LuaValue::Nil);
+
+        let LuaError::RuntimeError(message) = &error else {
+            return error;
+        };
+        let Ok(raised) = value.to_string() else {
+            return error;
+        };
+        let matches = message
+            .strip_prefix(&raised)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('\n'));
+        if !matches {
+            return error;
+        }
+
+        match lua.create_registry_value(value) {
+            Ok(key) => LuaError::external(ThreadError {
+                error,
+                value: Arc::new(key),
+            }),
+            Err(_) => error,
+        }
+    }
+}
+
+/**
+    Creates an error from a value that a Lua thread raised, preserving any non-string value.
+*/
+pub(crate) fn error_from_value(lua: &Lua, value: LuaValue) -> LuaError {
+    match value {
+        LuaValue::Error(error) => error,
+        LuaValue::String(s) => LuaError::runtime(s.to_string_lossy()),
+        value => {
+            let error = LuaError::runtime(
+                value
+                    .to_string()
+                    .unwrap_or_else(|_| "<unprintable error>".to_string()),
+            );
+            match lua.create_registry_value(value) {
+                Ok(key) => LuaError::external(ThreadError {
+                    error,
+                    value: Arc::new(key),
+                }),
+                Err(_) => error,
+            }
+        }
+    }
+}
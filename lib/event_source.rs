@@ -0,0 +1,125 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use event_listener::Event;
+use futures_lite::{Stream, StreamExt};
+use mlua::prelude::*;
+
+use crate::{drain::Drain, queue::DeferredThreadQueue, util::LazyArgs};
+
+/**
+    Backpressure strategy for an event source, deciding what happens to received
+    items while previous invocations of its handler are still waiting to run.
+
+    See [`Scheduler::spawn_event_source`](crate::Scheduler::spawn_event_source) for more information.
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Every received item is deferred, with no limit on waiting invocations.
+    #[default]
+    Unbounded,
+    /// Items received while the given number of invocations are waiting are dropped.
+    Drop(usize),
+    /// The source stops receiving items while the given number of invocations are
+    /// waiting, leaving any further items buffered in the source itself.
+    Buffer(usize),
+    /// At most one invocation is ever waiting, and items received while it is
+    /// waiting replace its item, so that only the latest item is handled.
+    Coalesce,
+}
+
+/**
+    A Rust stream of items, piped into deferred invocations of a Lua handler.
+*/
+pub(crate) struct EventSource<S: Stream> {
+    stream: S,
+    backpressure: Backpressure,
+    pending: Rc<Cell<usize>>,
+    event: Rc<Event>,
+    latest: Rc<RefCell<Option<S::Item>>>,
+}
+
+impl<S> EventSource<S>
+where
+    S: Stream + Unpin + 'static,
+    S::Item: for<'lua> IntoLuaMulti<'lua> + 'static,
+{
+    pub fn new(stream: S, backpressure: Backpressure) -> Self {
+        Self {
+            stream,
+            backpressure,
+            pending: Rc::new(Cell::new(0)),
+            event: Rc::new(Event::new()),
+            latest: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /**
+        Receives items until the stream ends, deferring an invocation of the handler for each.
+
+        # Errors
+
+        Errors if the scheduler is draining, or if an invocation could not be deferred.
+    */
+    pub async fn run(mut self, lua: &Lua, handler: LuaFunction<'_>) -> LuaResult<()> {
+        while let Some(item) = self.stream.next().await {
+            match self.backpressure {
+                Backpressure::Unbounded => self.defer(lua, &handler, Some(item))?,
+                Backpressure::Drop(cap) => {
+                    if self.pending.get() < cap {
+                        self.defer(lua, &handler, Some(item))?;
+                    } else {
+                        tracing::trace!("event source dropped an item");
+                    }
+                }
+                Backpressure::Buffer(cap) => {
+                    while self.pending.get() >= cap.max(1) {
+                        let listener = self.event.listen();
+                        // NOTE: Need to check again, invocations could have
+                        // started running while we were creating our listener
+                        if self.pending.get() >= cap.max(1) {
+                            listener.await;
+                        }
+                    }
+                    self.defer(lua, &handler, Some(item))?;
+                }
+                Backpressure::Coalesce => {
+                    let replaced = self.latest.borrow_mut().replace(item).is_some();
+                    if !replaced {
+                        self.defer(lua, &handler, None)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn defer(&self, lua: &Lua, handler: &LuaFunction, item: Option<S::Item>) -> LuaResult<()> {
+        lua.app_data_ref::<Drain>()
+            .expect("event sources can only run within an active scheduler")
+            .check()?;
+        let queue = lua
+            .app_data_ref::<DeferredThreadQueue>()
+            .expect("event sources can only run within an active scheduler");
+
+        // NOTE: Items are only converted into Lua values once the invocation is about
+        // to run, which is also when it stops counting towards the backpressure limit
+        let pending = Rc::clone(&self.pending);
+        let event = Rc::clone(&self.event);
+        let latest = Rc::clone(&self.latest);
+        let args: LazyArgs = Box::new(move |lua| {
+            pending.set(pending.get() - 1);
+            event.notify(usize::MAX);
+            match item.or_else(|| latest.borrow_mut().take()) {
+                Some(item) => item.into_lua_multi(lua),
+                None => Ok(LuaMultiValue::new()),
+            }
+        });
+
+        queue.push_item_with(lua, handler.clone(), args)?;
+        self.pending.set(self.pending.get() + 1);
+        Ok(())
+    }
+}
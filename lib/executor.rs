@@ -0,0 +1,183 @@
+use std::{
+    future::Future,
+    rc::{Rc, Weak as WeakRc},
+    sync::{Arc, Weak as WeakArc},
+};
+
+use async_executor::{Executor, LocalExecutor, Task};
+use mlua::prelude::*;
+
+const ERR_NOT_RUNNING: &str = "tasks can only be spawned within an active scheduler";
+const ERR_DROPPED: &str = "executor was dropped";
+
+/**
+    Marker trait for futures that may be spawned using [`LuaSpawnExt::spawn`].
+
+    This is the same as [`Send`], unless the `local-spawn` feature is enabled, in which case
+    it is implemented for all types, and futures that are not [`Send`] may also be spawned.
+    Note that with the feature enabled, spawned futures always run on the same thread as
+    the scheduler, even when it is not in single-threaded mode.
+
+    [`LuaSpawnExt::spawn`]: crate::LuaSpawnExt::spawn
+*/
+#[cfg(not(feature = "local-spawn"))]
+pub trait MaybeSend: Send {}
+
+#[cfg(not(feature = "local-spawn"))]
+impl<T: Send> MaybeSend for T {}
+
+/**
+    Marker trait for futures that may be spawned using [`LuaSpawnExt::spawn`].
+
+    This is the same as [`Send`], unless the `local-spawn` feature is enabled, in which case
+    it is implemented for all types, and futures that are not [`Send`] may also be spawned.
+    Note that with the feature enabled, spawned futures always run on the same thread as
+    the scheduler, even when it is not in single-threaded mode.
+
+    [`LuaSpawnExt::spawn`]: crate::LuaSpawnExt::spawn
+*/
+#[cfg(feature = "local-spawn")]
+pub trait MaybeSend {}
+
+#[cfg(feature = "local-spawn")]
+impl<T> MaybeSend for T {}
+
+/**
+    The executor that drives the main loop of a running scheduler forward,
+    and that any futures spawned using [`LuaSpawnExt::spawn`] run on.
+
+    In single-threaded mode, this is a [`LocalExecutor`], avoiding the
+    synchronization that the multi-threaded [`Executor`] needs.
+
+    With the `local-spawn` feature enabled, the multi-threaded [`Executor`] is also paired
+    with a [`LocalExecutor`], which futures that are not [`Send`] are spawned on instead.
+
+    [`LuaSpawnExt::spawn`]: crate::LuaSpawnExt::spawn
+*/
+pub(crate) enum MainExecutor {
+    Shared(
+        Arc<Executor<'static>>,
+        #[cfg(feature = "local-spawn")] Rc<LocalExecutor<'static>>,
+    ),
+    Local(Rc<LocalExecutor<'static>>),
+}
+
+impl MainExecutor {
+    pub fn new(single_threaded: bool) -> Self {
+        if single_threaded {
+            Self::Local(Rc::new(LocalExecutor::new()))
+        } else {
+            Self::Shared(
+                Arc::new(Executor::new()),
+                #[cfg(feature = "local-spawn")]
+                Rc::new(LocalExecutor::new()),
+            )
+        }
+    }
+
+    /**
+        Stores a weak reference to this executor in the given Lua state, so that it may be spawned on.
+
+        Returns `false` if the Lua state already has an executor stored.
+    */
+    pub fn attach(&self, lua: &Lua) -> bool {
+        if lua.app_data_ref::<WeakArc<Executor>>().is_some()
+            || lua.app_data_ref::<WeakRc<LocalExecutor>>().is_some()
+        {
+            return false;
+        }
+        match self {
+            #[cfg(not(feature = "local-spawn"))]
+            Self::Shared(exec) => {
+                lua.set_app_data(Arc::downgrade(exec));
+            }
+            #[cfg(feature = "local-spawn")]
+            Self::Shared(exec, local) => {
+                lua.set_app_data(Arc::downgrade(exec));
+                lua.set_app_data(Rc::downgrade(local));
+            }
+            Self::Local(exec) => {
+                lua.set_app_data(Rc::downgrade(exec));
+            }
+        }
+        true
+    }
+
+    /**
+        Removes the weak reference to this executor from the given Lua state.
+
+        Returns `false` if it was already removed.
+    */
+    pub fn detach(&self, lua: &Lua) -> bool {
+        match self {
+            #[cfg(not(feature = "local-spawn"))]
+            Self::Shared(_) => lua.remove_app_data::<WeakArc<Executor>>().is_some(),
+            #[cfg(feature = "local-spawn")]
+            Self::Shared(_, _) => {
+                lua.remove_app_data::<WeakRc<LocalExecutor>>();
+                lua.remove_app_data::<WeakArc<Executor>>().is_some()
+            }
+            Self::Local(_) => lua.remove_app_data::<WeakRc<LocalExecutor>>().is_some(),
+        }
+    }
+
+    /**
+        Removes any executor reference from the given Lua state, once the executor itself is gone.
+    */
+    pub fn remove(lua: &Lua) {
+        lua.remove_app_data::<WeakArc<Executor>>();
+        lua.remove_app_data::<WeakRc<LocalExecutor>>();
+    }
+
+    pub async fn run<T>(&self, fut: impl Future<Output = T>) -> T {
+        match self {
+            #[cfg(not(feature = "local-spawn"))]
+            Self::Shared(exec) => exec.run(fut).await,
+            #[cfg(feature = "local-spawn")]
+            Self::Shared(exec, local) => exec.run(local.run(fut)).await,
+            Self::Local(exec) => exec.run(fut).await,
+        }
+    }
+}
+
+/**
+    Spawns the given future on the executor stored in the given Lua state.
+*/
+#[cfg(not(feature = "local-spawn"))]
+pub(crate) fn spawn<F, T>(lua: &Lua, fut: F) -> Task<T>
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    if let Some(exec) = lua.app_data_ref::<WeakArc<Executor>>() {
+        return exec.upgrade().expect(ERR_DROPPED).spawn(fut);
+    }
+    spawn_local(lua, fut)
+}
+
+/**
+    Spawns the given future on the local executor stored in the given Lua state.
+
+    Futures may not be [`Send`], so they can never run on the multi-threaded executor, and
+    instead run on the local executor that is paired with it, see [`MainExecutor`].
+*/
+#[cfg(feature = "local-spawn")]
+pub(crate) fn spawn<F, T>(lua: &Lua, fut: F) -> Task<T>
+where
+    F: Future<Output = T> + 'static,
+    T: 'static,
+{
+    spawn_local(lua, fut)
+}
+
+fn spawn_local<F, T>(lua: &Lua, fut: F) -> Task<T>
+where
+    F: Future<Output = T> + 'static,
+    T: 'static,
+{
+    lua.app_data_ref::<WeakRc<LocalExecutor>>()
+        .expect(ERR_NOT_RUNNING)
+        .upgrade()
+        .expect(ERR_DROPPED)
+        .spawn(fut)
+}
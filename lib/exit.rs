@@ -1,10 +1,47 @@
-use std::{cell::Cell, process::ExitCode, rc::Rc};
+use std::{
+    cell::Cell,
+    process::ExitCode,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
 
 use event_listener::Event;
+use mlua::prelude::*;
+
+/**
+    How a scheduler should exit once an exit code has been set.
+
+    See [`Scheduler::set_exit_code_with_mode`] for more information.
+
+    [`Scheduler::set_exit_code_with_mode`]: crate::Scheduler::set_exit_code_with_mode
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExitMode {
+    /// Stop resuming queued threads, but wait for running threads to finish before exiting.
+    Graceful,
+    /// Exit immediately, dropping any threads that are still running.
+    #[default]
+    Force,
+}
+
+impl<'lua> FromLua<'lua> for ExitMode {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::String(s) if s == "graceful" => Ok(Self::Graceful),
+            LuaValue::String(s) if s == "force" => Ok(Self::Force),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ExitMode",
+                message: Some("Expected \"graceful\" or \"force\"".to_string()),
+            }),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub(crate) struct Exit {
     code: Rc<Cell<Option<ExitCode>>>,
+    mode: Rc<Cell<ExitMode>>,
     event: Rc<Event>,
 }
 
@@ -12,12 +49,18 @@ impl Exit {
     pub fn new() -> Self {
         Self {
             code: Rc::new(Cell::new(None)),
+            mode: Rc::new(Cell::new(ExitMode::Force)),
             event: Rc::new(Event::new()),
         }
     }
 
     pub fn set(&self, code: ExitCode) {
+        self.set_with_mode(code, ExitMode::Force);
+    }
+
+    pub fn set_with_mode(&self, code: ExitCode, mode: ExitMode) {
         self.code.set(Some(code));
+        self.mode.set(mode);
         self.event.notify(usize::MAX);
     }
 
@@ -25,7 +68,84 @@ impl Exit {
         self.code.get()
     }
 
+    pub fn mode(&self) -> ExitMode {
+        self.mode.get()
+    }
+
+    pub fn reset(&self) {
+        self.code.set(None);
+        self.mode.set(ExitMode::Force);
+    }
+
     pub async fn listen(&self) {
         self.event.listen().await;
     }
 }
+
+/**
+    The reason that a scheduler stopped running.
+
+    See [`Scheduler::wait_for_exit`] for more information.
+
+    [`Scheduler::wait_for_exit`]: crate::Scheduler::wait_for_exit
+*/
+#[derive(Debug, Clone, Copy)]
+pub enum ExitReason {
+    /// All Lua threads and futures completed.
+    Completed,
+    /// An exit code was set, either from Lua or from Rust.
+    ExitCode(ExitCode),
+    /// The stop future given to [`Scheduler::run_until`] completed,
+    /// or a [`SchedulerStepper`] was dropped before the scheduler completed.
+    ///
+    /// [`Scheduler::run_until`]: crate::Scheduler::run_until
+    /// [`SchedulerStepper`]: crate::SchedulerStepper
+    Stopped,
+}
+
+/**
+    Thread-safe watch for the exit reason of a scheduler, so
+    that exits may be waited for from any thread or async task.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct ExitWatch {
+    reason: Arc<Mutex<Option<ExitReason>>>,
+    event: Arc<Event>,
+}
+
+impl ExitWatch {
+    pub fn new() -> Self {
+        Self {
+            reason: Arc::new(Mutex::new(None)),
+            event: Arc::new(Event::new()),
+        }
+    }
+
+    pub fn reset(&self) {
+        self.reason.lock().unwrap().take();
+    }
+
+    pub fn set(&self, reason: ExitReason) {
+        self.reason.lock().unwrap().replace(reason);
+        self.event.notify(usize::MAX);
+    }
+
+    fn get(&self) -> Option<ExitReason> {
+        *self.reason.lock().unwrap()
+    }
+
+    pub async fn wait(self) -> ExitReason {
+        loop {
+            if let Some(reason) = self.get() {
+                break reason;
+            }
+            let listener = self.event.listen();
+            // NOTE: Need to check again, we could have gotten
+            // an exit reason while creating our listener
+            if let Some(reason) = self.get() {
+                break reason;
+            }
+            listener.await;
+        }
+    }
+}
@@ -1,18 +1,50 @@
 #![allow(unused_imports)]
 #![allow(clippy::too_many_lines)]
 
-use std::process::ExitCode;
+use std::{
+    process::ExitCode,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
+use async_io::Timer;
 use mlua::prelude::*;
 
 use crate::{
+    awaiting::AwaitingThreads,
+    cancel::Cancellation,
+    checkpoint::Checkpoints,
+    clock::Clock,
+    condvar::{Condvar, WAIT_IMPL_LUA},
+    cycle::Cycles,
+    deadline::Deadlines,
+    delay::DelayedThreads,
+    drain::Drain,
     error_callback::ThreadErrorCallback,
+    error_value::{error_from_value, ErrorValues, ThreadError},
+    exit::{Exit, ExitMode},
+    inject::Injections,
+    jobs::{JobOutput, Jobs},
+    location::{Location, ThreadLocations},
+    names::ThreadNames,
+    native::{create_native_async_function, NativeAsyncQueue},
+    output::{Output, OutputLevel},
+    preempt::Preemption,
+    primitives::Primitives,
     queue::{DeferredThreadQueue, SpawnedThreadQueue},
     result_map::ThreadResultMap,
+    resume_hooks::ResumeHooks,
     scheduler::Scheduler,
+    stats::Stats,
+    strict::StrictMode,
+    suspend::SuspendedThreads,
+    task::{Task, AWAIT_IMPL_LUA},
     thread_id::ThreadId,
-    traits::LuaSchedulerExt,
-    util::{is_poll_pending, LuaThreadOrFunction, ThreadResult},
+    thread_info::ThreadRecords,
+    thread_span::ThreadSpans,
+    tick::Ticks,
+    traits::{spawn_local_unwatched, LuaSchedulerExt},
+    util::{duration_from_secs, is_poll_pending, CachedChunk, LuaThreadOrFunction},
 };
 
 const ERR_METADATA_NOT_ATTACHED: &str = "\
@@ -21,11 +53,27 @@ Lua state does not have scheduler metadata attached!\
 \nScheduler functions must always be created from within an active scheduler.\
 ";
 
+const ERR_RESUME_AWAITING: &str = "cannot resume thread awaiting async operation";
+const ERR_RESUME_SUSPENDED: &str = "cannot resume suspended thread";
+const ERR_TASK_NOT_RESUMABLE: &str = "cannot spawn task for thread that is not resumable";
+
 const EXIT_IMPL_LUA: &str = r"
 exit(...)
 yield()
 ";
 
+const EXIT_WITH_CLEANUP_IMPL_LUA: &str = r"
+local handlers, code = ...
+for i = 1, #handlers do
+    local ok, err = pcall(handlers[i])
+    if not ok then
+        report(err)
+    end
+end
+exit(code)
+yield()
+";
+
 const WRAP_IMPL_LUA: &str = r"
 local t = create(...)
 return function(...)
@@ -38,6 +86,85 @@ return function(...)
 end
 ";
 
+const ERROR_IMPL_LUA: &str = r"
+return function(value, level)
+    if type(value) ~= 'string' then
+        values[running()] = value
+        error(value)
+    elseif level == 0 then
+        error(value, 0)
+    else
+        error(value, (level or 1) + 1)
+    end
+end
+";
+
+const HEARTBEAT_IMPL_LUA: &str = r"
+park()
+return yield()
+";
+
+const DEBOUNCE_IMPL_LUA: &str = r"
+local fn, secs = ...
+local args, deadline
+local pending = false
+return function(...)
+    args = pack(...)
+    deadline = clock() + secs
+    if not pending then
+        pending = true
+        defer(function()
+            while clock() < deadline do
+                sleep(deadline - clock())
+            end
+            pending = false
+            local a = args
+            args = nil
+            defer(fn, unpack(a, 1, a.n))
+        end)
+    end
+end
+";
+
+const THROTTLE_IMPL_LUA: &str = r"
+local fn, secs = ...
+local args, last
+local pending = false
+return function(...)
+    if not pending and (last == nil or clock() - last >= secs) then
+        last = clock()
+        defer(fn, ...)
+        return
+    end
+    args = pack(...)
+    if not pending then
+        pending = true
+        defer(function()
+            while clock() < last + secs do
+                sleep(last + secs - clock())
+            end
+            pending = false
+            last = clock()
+            local a = args
+            args = nil
+            defer(fn, unpack(a, 1, a.n))
+        end)
+    end
+end
+";
+
+static WRAP_IMPL: CachedChunk = CachedChunk::new("=__scheduler_wrap", WRAP_IMPL_LUA);
+static EXIT_IMPL: CachedChunk = CachedChunk::new("=__scheduler_exit", EXIT_IMPL_LUA);
+static EXIT_WITH_CLEANUP_IMPL: CachedChunk =
+    CachedChunk::new("=__scheduler_exit_with_cleanup", EXIT_WITH_CLEANUP_IMPL_LUA);
+static CONDVAR_WAIT_IMPL: CachedChunk =
+    CachedChunk::new("=__scheduler_condvar_wait", WAIT_IMPL_LUA);
+static TASK_AWAIT_IMPL: CachedChunk = CachedChunk::new("=__scheduler_task_await", AWAIT_IMPL_LUA);
+static DEBOUNCE_IMPL: CachedChunk = CachedChunk::new("=__scheduler_debounce", DEBOUNCE_IMPL_LUA);
+static THROTTLE_IMPL: CachedChunk = CachedChunk::new("=__scheduler_throttle", THROTTLE_IMPL_LUA);
+static ERROR_IMPL: CachedChunk = CachedChunk::new("=__scheduler_error", ERROR_IMPL_LUA);
+static HEARTBEAT_IMPL: CachedChunk = CachedChunk::new("=__scheduler_heartbeat", HEARTBEAT_IMPL_LUA);
+
 /**
     A collection of lua functions that may be called to interact with a [`Scheduler`].
 
@@ -49,6 +176,8 @@ pub struct Functions<'lua> {
         Implementation of `coroutine.resume` that handles async polling properly.
 
         Defers onto the scheduler queue if the thread calls an async function.
+        Threads that are already awaiting an async function can not be resumed,
+        since the scheduler will resume them, and instead return `false` and an error.
     */
     pub resume: LuaFunction<'lua>,
     /**
@@ -63,6 +192,22 @@ pub struct Functions<'lua> {
         Spawns onto the scheduler queue if not completed.
     */
     pub spawn: LuaFunction<'lua>,
+    /**
+        Spawns a function / thread the same way as [`Functions::spawn`], and returns
+        both the thread and a task object that can be used to observe its result.
+
+        The task object has the following methods:
+
+        - `task:await()` yields until the thread completes, and then returns its values, or raises its error
+        - `task:isDone()` returns `true` if the thread has completed, errored, or was cancelled
+        - `task:cancel()` cancels the thread the same as [`Functions::close`], returning `false` if it had already completed
+
+        Any number of threads may await the same task, and awaiting a task that
+        has already completed returns its values or raises its error right away.
+        The result is removed once the task object is garbage collected, unless the
+        thread was already tracked by the host, such as using [`LuaSchedulerExt::track_thread`].
+    */
+    pub spawn_task: LuaFunction<'lua>,
     /**
         Defers a function / thread onto the scheduler queue.
 
@@ -70,15 +215,199 @@ pub struct Functions<'lua> {
     */
     pub defer: LuaFunction<'lua>,
     /**
-        Cancels a function / thread, removing it from the queue.
+        Cancels a function / thread, removing it from every scheduler queue.
+
+        Threads waiting in [`Functions::wait`], or any other native async function,
+        have their sleep or call dropped right away, instead of it running to completion,
+        and threads delayed using [`Functions::delay`] are removed from the scheduler.
+
+        This is the same as [`Functions::close`], except that nothing is returned.
     */
     pub cancel: LuaFunction<'lua>,
+    /**
+        Promotes a deferred thread, moving it to the front of the spawned queue.
+
+        Returns `true` if the thread was promoted, and `false` if it was not deferred.
+
+        See [`Scheduler::promote`] for more information.
+    */
+    pub promote: LuaFunction<'lua>,
+    /**
+        Implementation of `coroutine.status` that takes the scheduler into account.
+
+        Returns one of the following:
+
+        - `"queued"` if the thread is waiting in the high priority or spawned queue to be resumed
+        - `"deferred"` if the thread is waiting in the deferred or idle queue to be resumed
+        - `"running"` if the thread is running, or resumed another thread that is running
+        - `"suspended"` if the thread is waiting for anything else, such as an async function,
+          its delay to elapse, or the next tick
+        - `"dead"` if the thread has completed, errored, or was cancelled
+    */
+    pub status: LuaFunction<'lua>,
+    /**
+        Implementation of `coroutine.close` that takes the scheduler into account.
+
+        Closes the thread the same way as `coroutine.close`, and also removes it from every scheduler
+        queue. Tracked threads that had not yet completed get an error stored as their result,
+        meaning [`Scheduler::wait_for_thread`] and friends will not wait forever for a closed thread.
+    */
+    pub close: LuaFunction<'lua>,
     /**
         Exits the scheduler, stopping all other threads and closing the scheduler.
 
+        Takes an optional exit code, and an optional mode, either `"force"` to exit immediately,
+        which is the default, or `"graceful"` to first let any running threads finish,
+        see [`Scheduler::set_exit_code_with_mode`] for more information.
+
         Yields the calling thread to ensure that it does not continue.
     */
     pub exit: LuaFunction<'lua>,
+    /**
+        Runs the given array of cleanup functions in order, and then exits the scheduler.
+
+        Cleanup functions run on the calling thread, and may yield or call async functions.
+        Any cleanup function that errors is reported to the error callback, without stopping
+        the remaining cleanup functions from running, or the scheduler from exiting.
+
+        Yields the calling thread once all cleanup functions have run, like `exit`.
+    */
+    pub exit_with_cleanup: LuaFunction<'lua>,
+    /**
+        Exits the scheduler once the given duration in seconds has passed.
+
+        Does not yield the calling thread, which, along with all other
+        threads, may keep running until the scheduler exits.
+    */
+    pub exit_after: LuaFunction<'lua>,
+    /**
+        Creates a new condition variable.
+
+        Threads may call `cv:wait(mutex)` to atomically release the mutex and yield,
+        until another thread calls `cv:notifyOne()` or `cv:notifyAll()`, which resumes
+        waiting threads using the scheduler queue. The mutex is re-locked before returning.
+
+        Any Lua value with `lock` and `unlock` methods may be used as a mutex.
+    */
+    pub condvar: LuaFunction<'lua>,
+    /**
+        Creates a debounced version of a function, given a duration in seconds.
+
+        The returned function may be called from any thread, and will run the original function
+        through the scheduler queue once the given duration has passed without any new calls.
+        Only the arguments from the most recent call are passed to the original function.
+    */
+    pub debounce: LuaFunction<'lua>,
+    /**
+        Creates a throttled version of a function, given a duration in seconds.
+
+        The returned function may be called from any thread, and will run the original function
+        through the scheduler queue at most once per the given duration. Calls made while throttled
+        are coalesced into a single trailing call, using the arguments from the most recent call.
+    */
+    pub throttle: LuaFunction<'lua>,
+    /**
+        Yields the calling thread until the next tick is fired by the host.
+
+        Returns the delta time of the tick, in seconds.
+
+        See [`Scheduler::fire_tick`] for more information.
+    */
+    pub heartbeat: LuaFunction<'lua>,
+    /**
+        Saves the given serializable value as the latest checkpoint for the calling thread.
+
+        Supervised services that are restarted will be passed their latest checkpoint.
+
+        See [`Scheduler::get_thread_checkpoint`] and [`Scheduler::get_service_checkpoint`].
+    */
+    pub checkpoint: LuaFunction<'lua>,
+    /**
+        Runs a background job registered by the host, given its name and an optional arguments value.
+
+        Yields the calling thread until the job completes, and returns its output.
+
+        See [`Scheduler::register_job`] for more information.
+    */
+    pub run_job: LuaFunction<'lua>,
+    /**
+        Implementation of `error` that preserves non-string error values, such as tables.
+
+        Errors raised with non-string values reach the error callback and result map
+        with the original value attached, see [`ThreadError`] for more information.
+        String errors behave exactly the same as with the default `error` function.
+
+        Note that this is not injected by [`Functions::inject_compat`], and must be set
+        as the global `error` function manually to preserve values for all errors.
+    */
+    pub error: LuaFunction<'lua>,
+    /**
+        Sets a Lua function to handle uncaught errors from any thread in the scheduler, or removes it if given `nil`.
+
+        The handler is called with the thread, the error message or value, and a traceback for the thread,
+        before the error callback set in Rust. If the handler returns a truthy value, the error is considered
+        handled, and the error callback is skipped. Note that the handler may not yield, and should spawn
+        a new thread to do any asynchronous work, such as reporting the error somewhere.
+
+        Intended to be exposed to scripts in environments where the host does not handle errors
+        by itself, for example as `__runtime.setErrorHandler`.
+    */
+    pub set_error_handler: LuaFunction<'lua>,
+    /**
+        Implementation of `pcall` that works transparently across async boundaries.
+
+        Luau already lets threads yield through `pcall`, meaning async functions called inside of it
+        work as expected, and this is the pristine `pcall` captured when the [`Scheduler`] was created.
+        It is provided so that it can be injected alongside [`Functions::xpcall`], restoring correct
+        behavior for environments where `pcall` was replaced by something that can not yield.
+    */
+    pub pcall: LuaFunction<'lua>,
+    /**
+        Implementation of `xpcall` that works transparently across async boundaries.
+
+        Just like [`Functions::pcall`], this is the pristine `xpcall` captured when the [`Scheduler`]
+        was created - the protected function may yield freely, and the error handler is called
+        where the error was raised, meaning tracebacks created inside of it include that location.
+        Note that the error handler itself may not yield, the same as with the default `xpcall`.
+    */
+    pub xpcall: LuaFunction<'lua>,
+    /**
+        Yields the calling thread for the given duration in seconds, or until the next cycle if not given.
+
+        Returns the time that actually elapsed, in seconds. Sleeping goes through the clock of
+        the [`Scheduler`], respecting its timer precision and coalescing settings.
+
+        Safe to cancel - cancelling a waiting thread stops its sleep, and the
+        [`Scheduler`] will not keep running just to finish the sleep.
+    */
+    pub wait: LuaFunction<'lua>,
+    /**
+        Spawns a function or thread with the given arguments once the given duration in seconds has passed.
+
+        Returns a thread that may be cancelled to prevent the function from ever running. Delayed
+        threads are managed by the [`Scheduler`] itself, see [`Scheduler::push_thread_delayed`].
+    */
+    pub delay: LuaFunction<'lua>,
+    /**
+        Gets the current cycle number of the [`Scheduler`].
+
+        See [`Scheduler::current_cycle`] for more information.
+    */
+    pub cycle: LuaFunction<'lua>,
+    /**
+        Writes the given values to the output sink of the [`Scheduler`], along with the current thread.
+
+        Values are formatted the same way as with the default `print` function.
+        See [`Scheduler::set_output_sink`] for more information.
+    */
+    pub print: LuaFunction<'lua>,
+    /**
+        Writes the given values to the output sink of the [`Scheduler`] as a warning, along with the current thread.
+
+        Values are formatted the same way as with the default `print` function.
+        See [`Scheduler::set_output_sink`] for more information.
+    */
+    pub warn: LuaFunction<'lua>,
 }
 
 impl<'lua> Functions<'lua> {
@@ -87,7 +416,7 @@ impl<'lua> Functions<'lua> {
 
         # Errors
 
-        Errors when out of memory, or if default Lua globals are missing.
+        Errors when out of memory, or if default Lua globals were missing when the [`Scheduler`] was created.
 
         # Panics
 
@@ -111,36 +440,157 @@ impl<'lua> Functions<'lua> {
             .expect(ERR_METADATA_NOT_ATTACHED)
             .clone();
 
+        let preemption = lua
+            .app_data_ref::<Preemption>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+
+        let native = lua
+            .app_data_ref::<NativeAsyncQueue>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+
+        let primitives = lua
+            .app_data_ref::<Primitives>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+
+        let spawn_drain = lua
+            .app_data_ref::<Drain>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let defer_drain = spawn_drain.clone();
+        let delay_drain = spawn_drain.clone();
+
+        let awaiting = lua
+            .app_data_ref::<AwaitingThreads>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let spawn_awaiting = awaiting.clone();
+
+        let strict = lua
+            .app_data_ref::<StrictMode>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let resume_strict = strict.clone();
+
+        let error_values = lua
+            .app_data_ref::<ErrorValues>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let resume_error_values = error_values.clone();
+
+        let suspended = lua
+            .app_data_ref::<SuspendedThreads>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let resume_suspended = suspended.clone();
+
+        let deadlines = lua
+            .app_data_ref::<Deadlines>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let spawn_deadlines = deadlines.clone();
+        let delay_deadlines = deadlines.clone();
+
+        let names = lua
+            .app_data_ref::<ThreadNames>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let spawn_names = names.clone();
+        let delay_names = names.clone();
+        let spawn_spans = ThreadSpans::new(names.clone(), primitives.clone());
+
+        let counters = lua
+            .app_data_ref::<Stats>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let spawn_stats = counters.clone();
+
+        let resume_hooks = lua
+            .app_data_ref::<ResumeHooks>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let spawn_hooks = resume_hooks.clone();
+        let defer_stats = counters.clone();
+        let spawn_suspended = suspended.clone();
+        let spawn_error_values = error_values.clone();
+        let spawn_strict = strict.clone();
+        let defer_strict = strict.clone();
+        let delay_strict = strict.clone();
+        let exit_strict = strict.clone();
+        let cleanup_error_callback = error_callback.clone();
+        let handler_error_callback = error_callback.clone();
+        let spawn_native = native.clone();
+
+        let promote_spawn_queue = spawn_queue.clone();
+        let promote_defer_queue = defer_queue.clone();
+        let status_locations = lua
+            .app_data_ref::<ThreadLocations>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let status_awaiting = awaiting.clone();
+        let status_native = native.clone();
+        let status_primitives = primitives.clone();
         let resume_queue = defer_queue.clone();
+        let condvar_queue = defer_queue.clone();
+        let task_queue = defer_queue.clone();
         let resume_map = result_map.clone();
+        let resume_preemption = preemption.clone();
         let resume =
             lua.create_function(move |lua, (thread, args): (LuaThread, LuaMultiValue)| {
                 let _span = tracing::trace_span!("Scheduler::fn_resume").entered();
-                match thread.resume::<_, LuaMultiValue>(args.clone()) {
+                let id = ThreadId::from(&thread);
+                if awaiting.contains(id) || native.is_awaiting(id) {
+                    // Will be resumed by the scheduler once its async function
+                    // completes, resuming it here would resume it twice
+                    return (false, ERR_RESUME_AWAITING).into_lua_multi(lua);
+                }
+                if resume_suspended.is_suspended(id) {
+                    return (false, ERR_RESUME_SUSPENDED).into_lua_multi(lua);
+                }
+                if let Err(e) = resume_strict.check_resumable(&thread, "resume") {
+                    return (false, e.to_string()).into_lua_multi(lua);
+                }
+                // NOTE: The thread may need to be re-queued after resuming it, which must
+                // never fail, so a full queue needs to be rejected before it starts running
+                resume_queue.check_capacity()?;
+                resume_hooks.before(id);
+                let result = {
+                    let _slice = resume_preemption.slice(id);
+                    thread.resume::<_, LuaMultiValue>(args.clone())
+                };
+                resume_hooks.after(id, &result);
+                let preempted = resume_preemption.take_yielded(id);
+                match result {
                     Ok(v) => {
-                        if v.get(0).map(is_poll_pending).unwrap_or_default() {
+                        if v.get(0).is_some_and(is_poll_pending) {
                             // Pending, defer to scheduler and return nil
-                            resume_queue.push_item(lua, &thread, args)?;
+                            resume_queue.push_item_unbounded(lua, &thread, args)?;
+                            awaiting.insert(id);
+                            (true, LuaValue::Nil).into_lua_multi(lua)
+                        } else if preempted {
+                            // Automatically yielded, defer to scheduler and return nil
+                            resume_queue.push_item_unbounded(lua, &thread, ())?;
+                            (true, LuaValue::Nil).into_lua_multi(lua)
+                        } else if native.is_awaiting(ThreadId::from(&thread)) {
+                            // Waiting for a native async function, which will
+                            // resume it through the scheduler, so just return nil
                             (true, LuaValue::Nil).into_lua_multi(lua)
                         } else {
                             // Not pending, store the value if thread is done
                             if thread.status() != LuaThreadStatus::Resumable {
                                 let id = ThreadId::from(&thread);
-                                if resume_map.is_tracked(id) {
-                                    let res = ThreadResult::new(Ok(v.clone()), lua);
-                                    resume_map.insert(id, res);
-                                }
+                                resume_map.complete(lua, id, Ok(v.clone()));
                             }
                             (true, v).into_lua_multi(lua)
                         }
                     }
                     Err(e) => {
                         // Not pending, store the error
+                        let e = resume_error_values.attach(lua, &thread, e);
                         let id = ThreadId::from(&thread);
-                        if resume_map.is_tracked(id) {
-                            let res = ThreadResult::new(Err(e.clone()), lua);
-                            resume_map.insert(id, res);
-                        }
+                        resume_map.complete(lua, id, Err(e.clone()));
                         (false, e.to_string()).into_lua_multi(lua)
                     }
                 }
@@ -148,55 +598,76 @@ impl<'lua> Functions<'lua> {
 
         let wrap_env = lua.create_table_from(vec![
             ("resume", resume.clone()),
-            ("error", lua.globals().get::<_, LuaFunction>("error")?),
-            ("select", lua.globals().get::<_, LuaFunction>("select")?),
-            ("unpack", lua.globals().get::<_, LuaFunction>("unpack")?),
-            (
-                "create",
-                lua.globals()
-                    .get::<_, LuaTable>("coroutine")?
-                    .get::<_, LuaFunction>("create")?,
-            ),
+            ("error", primitives.get(lua, "error")?),
+            ("select", primitives.get(lua, "select")?),
+            ("unpack", primitives.get(lua, "unpack")?),
+            ("create", primitives.get(lua, "create")?),
         ])?;
-        let wrap = lua
-            .load(WRAP_IMPL_LUA)
-            .set_name("=__scheduler_wrap")
-            .set_environment(wrap_env)
-            .into_function()?;
+        let wrap = WRAP_IMPL.load(lua, wrap_env)?;
 
         let spawn_map = result_map.clone();
+        let spawn_defer_queue = defer_queue.clone();
         let spawn = lua.create_function(
             move |lua, (tof, args): (LuaThreadOrFunction, LuaMultiValue)| {
                 let _span = tracing::trace_span!("Scheduler::fn_spawn").entered();
+                spawn_drain.check()?;
                 let thread = tof.into_thread(lua)?;
+                let id = ThreadId::from(&thread);
+                if spawn_awaiting.contains(id) || spawn_native.is_awaiting(id) {
+                    return Err(LuaError::runtime(ERR_RESUME_AWAITING));
+                }
+                if spawn_suspended.is_suspended(id) {
+                    return Err(LuaError::runtime(ERR_RESUME_SUSPENDED));
+                }
+                spawn_strict.check_resumable(&thread, "spawn")?;
+                spawn_deadlines.inherit(lua, &thread)?;
+                spawn_names.inherit(lua, &thread)?;
                 if thread.status() == LuaThreadStatus::Resumable {
+                    // NOTE: Same as for resume, a full queue must be
+                    // rejected before the thread starts running
+                    spawn_queue.check_capacity()?;
+                    // NOTE: Spawned threads may never be queued, such as when they wait for
+                    // a native async function, but should still be known to the scheduler
+                    if let Some(records) = lua.app_data_ref::<ThreadRecords>() {
+                        records.record_push(lua, &thread)?;
+                    }
+                    spawn_stats.record_spawned();
+                    spawn_stats.record_resumed();
+                    let _thread_span = spawn_spans.span(lua, &thread).entered();
                     // NOTE: We need to resume the thread once instantly for correct behavior,
                     // and only if we get the pending value back we can spawn to async executor
-                    match thread.resume::<_, LuaMultiValue>(args.clone()) {
+                    spawn_hooks.before(id);
+                    let result = {
+                        let _slice = preemption.slice(id);
+                        thread.resume::<_, LuaMultiValue>(args.clone())
+                    };
+                    spawn_hooks.after(id, &result);
+                    let preempted = preemption.take_yielded(id);
+                    match result {
                         Ok(v) => {
-                            if v.get(0).map(is_poll_pending).unwrap_or_default() {
-                                spawn_queue.push_item(lua, &thread, args)?;
+                            if v.get(0).is_some_and(is_poll_pending) {
+                                spawn_queue.push_item_unbounded(lua, &thread, args)?;
+                                spawn_awaiting.insert(id);
+                            } else if preempted {
+                                // Automatically yielded, must be re-queued to keep running
+                                spawn_defer_queue.push_item_unbounded(lua, &thread, ())?;
                             } else {
                                 // Not pending, store the value if thread is done
                                 if thread.status() != LuaThreadStatus::Resumable {
                                     let id = ThreadId::from(&thread);
-                                    if spawn_map.is_tracked(id) {
-                                        let res = ThreadResult::new(Ok(v), lua);
-                                        spawn_map.insert(id, res);
-                                    }
+                                    spawn_map.complete(lua, id, Ok(v));
                                 }
                             }
                         }
                         Err(e) => {
-                            error_callback.call(&e);
+                            let e = spawn_error_values.attach(lua, &thread, e);
+                            spawn_stats.record_errored();
+                            error_callback.call_thread(lua, &thread, &e);
                             // Not pending, store the error
                             let id = ThreadId::from(&thread);
-                            if spawn_map.is_tracked(id) {
-                                let res = ThreadResult::new(Err(e), lua);
-                                spawn_map.insert(id, res);
-                            }
+                            spawn_map.complete(lua, id, Err(e));
                         }
-                    };
+                    }
                 }
                 Ok(thread)
             },
@@ -205,60 +676,374 @@ impl<'lua> Functions<'lua> {
         let defer = lua.create_function(
             move |lua, (tof, args): (LuaThreadOrFunction, LuaMultiValue)| {
                 let _span = tracing::trace_span!("Scheduler::fn_defer").entered();
+                defer_drain.check()?;
                 let thread = tof.into_thread(lua)?;
+                defer_strict.check_resumable(&thread, "defer")?;
                 if thread.status() == LuaThreadStatus::Resumable {
+                    deadlines.inherit(lua, &thread)?;
+                    names.inherit(lua, &thread)?;
                     defer_queue.push_item(lua, &thread, args)?;
+                    defer_stats.record_deferred();
                 }
                 Ok(thread)
             },
         )?;
 
-        let close = lua
-            .globals()
-            .get::<_, LuaTable>("coroutine")?
-            .get::<_, LuaFunction>("close")?;
-        let close_key = lua.create_registry_value(close)?;
+        let promote = lua.create_function(move |_, thread: LuaThread| {
+            let _span = tracing::trace_span!("Scheduler::fn_promote").entered();
+            match promote_defer_queue.take_item(ThreadId::from(&thread)) {
+                Some(stored) => {
+                    promote_spawn_queue.push_item_front(stored);
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        })?;
+
+        let status = lua.create_function(move |lua, thread: LuaThread| {
+            let _span = tracing::trace_span!("Scheduler::fn_status").entered();
+            let co_status = status_primitives
+                .get(lua, "status")?
+                .call::<_, String>(&thread)?;
+            let id = ThreadId::from(&thread);
+            Ok(match co_status.as_str() {
+                "running" | "normal" => "running",
+                "dead" => "dead",
+                // NOTE: Threads waiting for an async function may also be queued,
+                // but are only queued so that the scheduler can drive the function
+                _ if status_awaiting.contains(id) || status_native.is_awaiting(id) => "suspended",
+                _ if [Location::High, Location::Spawn]
+                    .into_iter()
+                    .any(|location| status_locations.contains(id, location)) =>
+                {
+                    "queued"
+                }
+                _ if [Location::Defer, Location::Idle]
+                    .into_iter()
+                    .any(|location| status_locations.contains(id, location)) =>
+                {
+                    "deferred"
+                }
+                // NOTE: Delayed threads, and threads parked until the next tick, wait
+                // for time to pass or for the host, same as any other suspended thread
+                _ => "suspended",
+            })
+        })?;
+
+        let cancellation = lua
+            .app_data_ref::<Cancellation>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let close_cancellation = cancellation.clone();
         let cancel = lua.create_function(move |lua, thread: LuaThread| {
             let _span = tracing::trace_span!("Scheduler::fn_cancel").entered();
-            let close: LuaFunction = lua.registry_value(&close_key)?;
-            match close.call(thread) {
-                Err(LuaError::CoroutineInactive) | Ok(()) => Ok(()),
-                Err(e) => Err(e),
-            }
+            cancellation.cancel(lua, &thread)?;
+            Ok(())
+        })?;
+
+        let close = lua.create_function(move |lua, thread: LuaThread| {
+            let _span = tracing::trace_span!("Scheduler::fn_close").entered();
+            close_cancellation.cancel(lua, &thread)
         })?;
 
         let exit_env = lua.create_table_from(vec![
             (
                 "exit",
-                lua.create_function(|lua, code: Option<u8>| {
+                lua.create_function(|lua, (code, mode): (Option<u8>, Option<ExitMode>)| {
                     let _span = tracing::trace_span!("Scheduler::fn_exit").entered();
                     let code = code.map(ExitCode::from).unwrap_or_default();
-                    lua.set_exit_code(code);
+                    lua.app_data_ref::<Exit>()
+                        .expect(ERR_METADATA_NOT_ATTACHED)
+                        .set_with_mode(code, mode.unwrap_or_default());
                     Ok(())
                 })?,
             ),
+            ("yield", primitives.get(lua, "yield")?),
+        ])?;
+        let exit = EXIT_IMPL.load(lua, exit_env.clone())?;
+
+        exit_env.set(
+            "report",
+            lua.create_function(move |lua, err: LuaValue| {
+                // NOTE: Reporting must never error, or the remaining cleanup
+                // handlers would be skipped and the exit code never set
+                cleanup_error_callback.call(&error_from_value(lua, err));
+                Ok(())
+            })?,
+        )?;
+        exit_env.set("pcall", primitives.get(lua, "pcall")?)?;
+        let exit_with_cleanup = EXIT_WITH_CLEANUP_IMPL.load(lua, exit_env)?;
+
+        let exit_clock = lua
+            .app_data_ref::<Clock>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .to_owned();
+        let exit_after = lua.create_function(move |lua, (secs, code): (f64, Option<u8>)| {
+            let _span = tracing::trace_span!("Scheduler::fn_exit_after").entered();
+            exit_strict.check_running(lua)?;
+            let code = code.map(ExitCode::from).unwrap_or_default();
+            let exit = lua
+                .app_data_ref::<Exit>()
+                .expect(ERR_METADATA_NOT_ATTACHED)
+                .clone();
+            let delay = duration_from_secs(secs);
+            let clock = exit_clock.clone();
+            spawn_local_unwatched(lua, async move {
+                clock.sleep(delay).await;
+                exit.set(code);
+            });
+            Ok(())
+        })?;
+
+        let condvar_env = lua.create_table_from(vec![
+            (
+                "park",
+                lua.create_function(|lua, cv: LuaAnyUserData| cv.borrow::<Condvar>()?.park(lua))?,
+            ),
+            ("yield", primitives.get(lua, "yield")?),
+        ])?;
+        let condvar_wait = CONDVAR_WAIT_IMPL.load(lua, condvar_env)?;
+        let condvar_wait_key = Rc::new(lua.create_registry_value(condvar_wait)?);
+        let condvar = lua.create_function(move |_, ()| {
+            Ok(Condvar::new(
+                condvar_queue.clone(),
+                Rc::clone(&condvar_wait_key),
+            ))
+        })?;
+
+        let task_env = lua.create_table_from(vec![
+            (
+                "park",
+                lua.create_function(|lua, task: LuaAnyUserData| task.borrow::<Task>()?.park(lua))?,
+            ),
             (
-                "yield",
-                lua.globals()
-                    .get::<_, LuaTable>("coroutine")?
-                    .get::<_, LuaFunction>("yield")?,
+                "result",
+                lua.create_function(|lua, task: LuaAnyUserData| {
+                    task.borrow::<Task>()?.result(lua)
+                })?,
             ),
+            ("yield", primitives.get(lua, "yield")?),
+            ("error", primitives.get(lua, "error")?),
+            ("pack", primitives.get(lua, "pack")?),
+            ("unpack", primitives.get(lua, "unpack")?),
         ])?;
-        let exit = lua
-            .load(EXIT_IMPL_LUA)
-            .set_name("=__scheduler_exit")
-            .set_environment(exit_env)
-            .into_function()?;
+        let task_await = TASK_AWAIT_IMPL.load(lua, task_env)?;
+        let task_await_key = Rc::new(lua.create_registry_value(task_await)?);
+        let task_close_key = Rc::new(lua.create_registry_value(close.clone())?);
+        let task_map = result_map.clone();
+        let task_spawn_key = lua.create_registry_value(spawn.clone())?;
+        let spawn_task = lua.create_function(
+            move |lua, (tof, args): (LuaThreadOrFunction, LuaMultiValue)| {
+                let _span = tracing::trace_span!("Scheduler::fn_spawn_task").entered();
+                let thread = tof.into_thread(lua)?;
+                if thread.status() != LuaThreadStatus::Resumable {
+                    return Err(LuaError::runtime(ERR_TASK_NOT_RESUMABLE));
+                }
+                // NOTE: The thread must be tracked before it is spawned, since
+                // spawning resumes it right away, and it may complete instantly
+                let current = task_map.current(ThreadId::from(&thread));
+                let owns_result = !task_map.is_tracked(current) || task_map.is_completed(current);
+                let id = task_map.track_thread(ThreadId::from(&thread));
+                let spawn: LuaFunction = lua.registry_value(&task_spawn_key)?;
+                let thread = spawn.call::<_, LuaThread>((thread, args))?;
+                let task = Task::new(
+                    lua,
+                    id,
+                    &thread,
+                    task_map.clone(),
+                    task_queue.clone(),
+                    Rc::clone(&task_close_key),
+                    Rc::clone(&task_await_key),
+                    owns_result,
+                )?;
+                Ok((thread, task))
+            },
+        )?;
+
+        let clock = lua
+            .app_data_ref::<Clock>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .to_owned();
+        let sleep_clock = clock.clone();
+        let timing_env = lua.create_table_from(vec![
+            ("defer", defer.clone()),
+            (
+                "clock",
+                lua.create_function(move |_, ()| Ok(clock.now().as_secs_f64()))?,
+            ),
+            (
+                "sleep",
+                primitives.with_coroutine_global(lua, || {
+                    lua.create_async_function(move |_, secs: f64| {
+                        let clock = sleep_clock.clone();
+                        async move {
+                            clock.sleep(duration_from_secs(secs)).await;
+                            Ok(())
+                        }
+                    })
+                })?,
+            ),
+            ("pack", primitives.get(lua, "pack")?),
+            ("unpack", primitives.get(lua, "unpack")?),
+        ])?;
+        let debounce = DEBOUNCE_IMPL.load(lua, timing_env.clone())?;
+        let throttle = THROTTLE_IMPL.load(lua, timing_env)?;
+
+        let ticks = lua
+            .app_data_ref::<Ticks>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let heartbeat_env = lua.create_table_from(vec![
+            ("park", lua.create_function(move |lua, ()| ticks.park(lua))?),
+            ("yield", primitives.get(lua, "yield")?),
+        ])?;
+        let heartbeat = HEARTBEAT_IMPL.load(lua, heartbeat_env)?;
+
+        let checkpoints = lua
+            .app_data_ref::<Checkpoints>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let checkpoint = lua.create_function(move |lua, state: LuaValue| {
+            let _span = tracing::trace_span!("Scheduler::fn_checkpoint").entered();
+            checkpoints.save(lua, &lua.current_thread(), state)
+        })?;
+
+        let jobs = lua
+            .app_data_ref::<Jobs>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let run_job = create_native_async_function(
+            lua,
+            move |lua, (name, args): (String, Option<LuaValue>)| {
+                let _span = tracing::trace_span!("Scheduler::fn_run_job", job = %name).entered();
+                let job = jobs.start(lua, &name, args.unwrap_or(LuaValue::Nil));
+                async move { Ok(JobOutput(job?.await?)) }
+            },
+        )?;
+
+        let error_env = lua.create_table_from(vec![
+            ("error", primitives.get(lua, "error")?),
+            ("type", primitives.get(lua, "type")?),
+            ("running", primitives.get(lua, "running")?),
+        ])?;
+        error_env.set("values", error_values.table(lua)?)?;
+        let error = ERROR_IMPL
+            .load(lua, error_env)?
+            .call::<_, LuaFunction>(())?;
+
+        let wait_clock = lua
+            .app_data_ref::<Clock>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let wait = create_native_async_function(lua, move |_, secs: Option<f64>| {
+            let _span = tracing::trace_span!("Scheduler::fn_wait").entered();
+            let clock = wait_clock.clone();
+            let duration = duration_from_secs(secs.unwrap_or_default());
+            async move { Ok(clock.sleep(duration).await.as_secs_f64()) }
+        })?;
+
+        let delayed = lua
+            .app_data_ref::<DelayedThreads>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let delay = lua.create_function(
+            move |lua, (secs, tof, args): (f64, LuaThreadOrFunction, LuaMultiValue)| {
+                let _span = tracing::trace_span!("Scheduler::fn_delay").entered();
+                delay_drain.check()?;
+                let thread = tof.into_thread(lua)?;
+                delay_strict.check_resumable(&thread, "delay")?;
+                if thread.status() == LuaThreadStatus::Resumable {
+                    delay_deadlines.inherit(lua, &thread)?;
+                    delay_names.inherit(lua, &thread)?;
+                    let duration = duration_from_secs(secs);
+                    delayed.push_item(lua, &thread, args, duration)?;
+                }
+                Ok(thread)
+            },
+        )?;
+
+        let pcall = primitives.get(lua, "pcall")?;
+        let xpcall = primitives.get(lua, "xpcall")?;
+
+        let set_error_handler = lua.create_function(move |lua, handler: Option<LuaFunction>| {
+            let handler = handler.map(|f| lua.create_registry_value(f)).transpose()?;
+            handler_error_callback.set_handler(handler);
+            Ok(())
+        })?;
+
+        let cycles = lua
+            .app_data_ref::<Cycles>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let cycle = lua.create_function(move |_, ()| Ok(cycles.current()))?;
+
+        let output = lua
+            .app_data_ref::<Output>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let warn_output = output.clone();
+        let print = lua.create_function(move |lua, values: LuaMultiValue| {
+            output.write_values(lua, OutputLevel::Info, values)
+        })?;
+        let warn = lua.create_function(move |lua, values: LuaMultiValue| {
+            warn_output.write_values(lua, OutputLevel::Warn, values)
+        })?;
 
         Ok(Self {
             resume,
             wrap,
             spawn,
+            spawn_task,
             defer,
             cancel,
+            promote,
+            status,
+            close,
             exit,
+            exit_with_cleanup,
+            exit_after,
+            condvar,
+            debounce,
+            throttle,
+            heartbeat,
+            checkpoint,
+            run_job,
+            error,
+            set_error_handler,
+            pcall,
+            xpcall,
+            wait,
+            delay,
+            cycle,
+            print,
+            warn,
         })
     }
+
+    /**
+        Creates a `task` library table, equivalent to the `task` library found in Roblox, containing:
+
+        - `task.spawn` - see [`Functions::spawn`]
+        - `task.defer` - see [`Functions::defer`]
+        - `task.delay` - see [`Functions::delay`]
+        - `task.wait` - see [`Functions::wait`]
+        - `task.cancel` - see [`Functions::cancel`]
+
+        The table is not set as a global, and may be extended with other functions before doing so.
+
+        # Errors
+
+        Errors when out of memory.
+    */
+    pub fn task_lib(&self, lua: &'lua Lua) -> LuaResult<LuaTable<'lua>> {
+        lua.create_table_from(vec![
+            ("spawn", self.spawn.clone()),
+            ("defer", self.defer.clone()),
+            ("delay", self.delay.clone()),
+            ("wait", self.wait.clone()),
+            ("cancel", self.cancel.clone()),
+        ])
+    }
 }
 
 impl Functions<'_> {
@@ -269,15 +1054,81 @@ impl Functions<'_> {
 
         - `coroutine.resume`
         - `coroutine.wrap`
+        - `coroutine.close`
+        - `pcall`
+        - `xpcall`
+
+        The original functions are restored automatically once the [`Scheduler`] is dropped,
+        or may be restored manually using [`Functions::uninject`].
 
         # Errors
 
         Errors when out of memory, or if default Lua globals are missing.
+
+        # Panics
+
+        Panics when the given [`Lua`] instance does not have an attached [`Scheduler`].
     */
     pub fn inject_compat(&self, lua: &Lua) -> LuaResult<()> {
+        let injections = lua
+            .app_data_ref::<Injections>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
         let co: LuaTable = lua.globals().get("coroutine")?;
-        co.set("resume", self.resume.clone())?;
-        co.set("wrap", self.wrap.clone())?;
+        injections.inject(lua, &co, "resume", self.resume.clone())?;
+        injections.inject(lua, &co, "wrap", self.wrap.clone())?;
+        injections.inject(lua, &co, "close", self.close.clone())?;
+        let globals = lua.globals();
+        injections.inject(lua, &globals, "pcall", self.pcall.clone())?;
+        injections.inject(lua, &globals, "xpcall", self.xpcall.clone())?;
         Ok(())
     }
+
+    /**
+        Injects output functions that write to the output sink of the [`Scheduler`] into the given [`Lua`] instance.
+
+        This will overwrite the following functions:
+
+        - `print`
+        - `warn`
+
+        The original functions are restored the same way as with [`Functions::inject_compat`].
+
+        # Errors
+
+        Errors when out of memory.
+
+        # Panics
+
+        Panics when the given [`Lua`] instance does not have an attached [`Scheduler`].
+    */
+    pub fn inject_output(&self, lua: &Lua) -> LuaResult<()> {
+        let injections = lua
+            .app_data_ref::<Injections>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
+        let globals = lua.globals();
+        injections.inject(lua, &globals, "print", self.print.clone())?;
+        injections.inject(lua, &globals, "warn", self.warn.clone())?;
+        Ok(())
+    }
+
+    /**
+        Restores all functions that were overwritten using [`Functions::inject_compat`].
+
+        This happens automatically once the [`Scheduler`] is dropped, since injected functions are bound
+        to the scheduler they were created for, and will not work without it. Does nothing if there are
+        no injected functions to restore, or if the given [`Lua`] instance has no attached [`Scheduler`].
+
+        # Errors
+
+        Errors when out of memory.
+    */
+    pub fn uninject(lua: &Lua) -> LuaResult<()> {
+        let injections = lua.app_data_ref::<Injections>().map(|i| i.clone());
+        match injections {
+            Some(injections) => injections.restore(lua),
+            None => Ok(()),
+        }
+    }
 }
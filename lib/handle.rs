@@ -0,0 +1,127 @@
+use std::fmt;
+
+use mlua::prelude::*;
+
+use crate::{
+    cancel::Cancellation, result_map::ThreadResultMap, strict::StrictMode, thread_id::ThreadId,
+    thread_info::ThreadRecords,
+};
+
+/**
+    A handle to a [`LuaThread`] that was pushed to a [`Scheduler`], similar to a `JoinHandle`.
+
+    Created using [`Scheduler::thread_handle`], with the [`ThreadId`] returned by any of the
+    push methods, and may be used instead of calling [`Scheduler::get_thread_result`]
+    and [`Scheduler::wait_for_thread`] separately.
+
+    Dropping the handle does not cancel the thread.
+
+    [`Scheduler`]: crate::Scheduler
+    [`Scheduler::thread_handle`]: crate::Scheduler::thread_handle
+    [`Scheduler::get_thread_result`]: crate::Scheduler::get_thread_result
+    [`Scheduler::wait_for_thread`]: crate::Scheduler::wait_for_thread
+*/
+#[derive(Clone)]
+pub struct ThreadHandle<'lua> {
+    lua: &'lua Lua,
+    id: ThreadId,
+    result_map: ThreadResultMap,
+    records: ThreadRecords,
+    cancellation: Cancellation,
+}
+
+impl<'lua> ThreadHandle<'lua> {
+    pub(crate) fn new(
+        lua: &'lua Lua,
+        id: ThreadId,
+        result_map: ThreadResultMap,
+        records: ThreadRecords,
+        cancellation: Cancellation,
+    ) -> Self {
+        Self {
+            lua,
+            id,
+            result_map,
+            records,
+            cancellation,
+        }
+    }
+
+    /**
+        Returns the id of the thread.
+    */
+    #[must_use]
+    pub fn id(&self) -> ThreadId {
+        self.id
+    }
+
+    /**
+        Returns `true` if the thread has completed, or was cancelled using this handle.
+
+        Also returns `true` if the result of the thread has already been taken.
+    */
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        !self.result_map.is_tracked(self.id) || self.result_map.is_completed(self.id)
+    }
+
+    /**
+        Waits for the thread to complete, and takes its result out of the scheduler.
+
+        Note that the scheduler must be running for the thread to complete.
+
+        # Errors
+
+        Errors with the error of the thread, if it errored or was cancelled using this handle,
+        or if its result was already taken, such as by [`Scheduler::get_thread_result`].
+
+        [`Scheduler::get_thread_result`]: crate::Scheduler::get_thread_result
+    */
+    pub async fn join(self) -> LuaResult<LuaMultiValue<'lua>> {
+        if !self.result_map.is_tracked(self.id) {
+            return Err(StrictMode::untracked_result());
+        }
+        self.result_map.listen(self.id).await;
+        match self.result_map.remove(self.id) {
+            Some(result) => result.value(self.lua),
+            None => Err(StrictMode::untracked_result()),
+        }
+    }
+
+    /**
+        Cancels the thread, if it has not yet completed.
+
+        Any call to [`ThreadHandle::join`] will then return an error, and the error is
+        also stored as the result of the thread, same as when a thread exceeds its deadline.
+
+        Returns `true` if the thread was cancelled, and `false` if it had already completed.
+
+        # Errors
+
+        Errors if the thread could not be closed.
+    */
+    pub fn cancel(&self) -> LuaResult<bool> {
+        // NOTE: The address of a completed thread may since have been reused
+        // by another thread, which must not be found and cancelled in its place
+        if self.result_map.is_completed(self.id) {
+            return Ok(false);
+        }
+        let id = self.id.base();
+        let Some(thread) = self.records.find(self.lua, id)? else {
+            return Ok(false);
+        };
+        if thread.status() != LuaThreadStatus::Resumable {
+            return Ok(false);
+        }
+        self.cancellation.cancel(self.lua, &thread)?;
+        Ok(true)
+    }
+}
+
+impl fmt::Debug for ThreadHandle<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThreadHandle")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,132 @@
+#![allow(clippy::module_name_repetitions)]
+
+use std::{
+    cell::Cell,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures_lite::FutureExt;
+use mlua::prelude::*;
+
+use crate::{
+    location::{Location, ThreadLocations},
+    queue::{FuturesQueue, LocalBoxFuture, ThreadQueue},
+};
+
+/**
+    Statistics about idle work processed by a scheduler.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IdleStats {
+    /// The number of idle Lua threads that have been resumed.
+    pub threads_processed: usize,
+    /// The number of idle futures that have been spawned.
+    pub futures_processed: usize,
+    /// The total amount of time spent running idle Lua threads and futures.
+    pub time_used: Duration,
+}
+
+/**
+    Queues for storing idle [`LuaThread`]s and futures.
+
+    Idle work is only processed when there is no other work to be done.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct IdleQueue {
+    threads: ThreadQueue,
+    futures: FuturesQueue<'static>,
+    stats: Rc<Cell<IdleStats>>,
+}
+
+impl IdleQueue {
+    pub fn new(locations: &ThreadLocations) -> Self {
+        Self {
+            threads: ThreadQueue::new(locations, Location::Idle),
+            futures: FuturesQueue::new(),
+            stats: Rc::new(Cell::new(IdleStats::default())),
+        }
+    }
+
+    pub fn threads(&self) -> &ThreadQueue {
+        &self.threads
+    }
+
+    pub fn futures(&self) -> &FuturesQueue<'static> {
+        &self.futures
+    }
+
+    pub fn stats(&self) -> IdleStats {
+        self.stats.get()
+    }
+
+    /**
+        Pops a single idle Lua thread, if any, and records it in the idle stats.
+    */
+    pub fn pop_thread<'lua>(
+        &self,
+        lua: &'lua Lua,
+    ) -> Option<(LuaThread<'lua>, LuaMultiValue<'lua>)> {
+        let item = self.threads.pop_item(lua)?;
+        let mut stats = self.stats.get();
+        stats.threads_processed += 1;
+        self.stats.set(stats);
+        Some(item)
+    }
+
+    /**
+        Pops a single idle future, if any, and records it in the idle stats.
+    */
+    pub fn pop_future(&self) -> Option<LocalBoxFuture<'static>> {
+        let item = self.futures.pop_item()?;
+        let mut stats = self.stats.get();
+        stats.futures_processed += 1;
+        self.stats.set(stats);
+        Some(item)
+    }
+
+    /**
+        Wraps the given future, recording the time spent polling it in the idle stats.
+    */
+    pub fn timed<'fut>(&self, fut: impl Future<Output = ()> + 'fut) -> TimedFuture<'fut> {
+        TimedFuture {
+            inner: fut.boxed_local(),
+            stats: Rc::clone(&self.stats),
+        }
+    }
+
+    pub async fn wait_for_item(&self) {
+        self.threads
+            .wait_for_item()
+            .or(self.futures.wait_for_item())
+            .await;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.threads.is_empty() && self.futures.is_empty()
+    }
+}
+
+/**
+    A future that records the time spent polling it in the idle stats.
+*/
+pub(crate) struct TimedFuture<'fut> {
+    inner: LocalBoxFuture<'fut>,
+    stats: Rc<Cell<IdleStats>>,
+}
+
+impl Future for TimedFuture<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let start = Instant::now();
+        let res = self.inner.poll(cx);
+        let mut stats = self.stats.get();
+        stats.time_used += start.elapsed();
+        self.stats.set(stats);
+        res
+    }
+}
@@ -0,0 +1,79 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::prelude::*;
+
+/**
+    A single value that was overwritten by an injected function.
+*/
+#[derive(Debug)]
+struct Injected {
+    table: LuaRegistryKey,
+    key: &'static str,
+    original: LuaRegistryKey,
+}
+
+/**
+    Values overwritten when injecting scheduler functions into a Lua state,
+    such as using [`Functions::inject_compat`], so that they can be restored.
+
+    Injected functions are bound to the queues of a single scheduler, and restoring the originals
+    once the scheduler is dropped lets a persistent Lua state outlive any number of schedulers.
+
+    [`Functions::inject_compat`]: crate::Functions::inject_compat
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct Injections {
+    injected: Rc<RefCell<Vec<Injected>>>,
+}
+
+impl Injections {
+    pub fn new() -> Self {
+        Self {
+            injected: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /**
+        Sets the given key in the given table to the given value, remembering the original value.
+
+        Injecting into the same key more than once keeps the very first original value.
+    */
+    pub fn inject<'lua>(
+        &self,
+        lua: &'lua Lua,
+        table: &LuaTable<'lua>,
+        key: &'static str,
+        value: impl IntoLua<'lua>,
+    ) -> LuaResult<()> {
+        let mut injected = self.injected.borrow_mut();
+        let mut seen = false;
+        for i in injected.iter() {
+            if i.key == key && lua.registry_value::<LuaTable>(&i.table)? == *table {
+                seen = true;
+                break;
+            }
+        }
+        if !seen {
+            injected.push(Injected {
+                table: lua.create_registry_value(table.clone())?,
+                key,
+                original: lua.create_registry_value(table.get::<_, LuaValue>(key)?)?,
+            });
+        }
+        drop(injected);
+        table.set(key, value)
+    }
+
+    /**
+        Restores all values overwritten by injected functions, in reverse order of injection.
+    */
+    pub fn restore(&self, lua: &Lua) -> LuaResult<()> {
+        let injected = self.injected.take();
+        for i in injected.into_iter().rev() {
+            let table = lua.registry_value::<LuaTable>(&i.table)?;
+            let original = lua.registry_value::<LuaValue>(&i.original)?;
+            table.set(i.key, original)?;
+        }
+        Ok(())
+    }
+}
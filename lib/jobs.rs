@@ -0,0 +1,80 @@
+use std::{cell::RefCell, future::Future, pin::Pin, rc::Rc};
+
+use mlua::{prelude::*, SerializeOptions};
+use rustc_hash::FxHashMap;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_value::Value;
+
+use crate::traits::LuaSpawnExt;
+
+type JobFuture = Pin<Box<dyn Future<Output = LuaResult<Value>>>>;
+type JobFactory = Rc<dyn Fn(&Lua, LuaValue) -> LuaResult<JobFuture>>;
+
+/**
+    Registry of named background jobs, which Lua may run using `runJob`.
+
+    Job arguments are deserialized from Lua, and job outputs serialized back into Lua,
+    so that jobs themselves may run on the main executor without touching the Lua state.
+*/
+#[derive(Clone)]
+pub(crate) struct Jobs {
+    factories: Rc<RefCell<FxHashMap<String, JobFactory>>>,
+}
+
+impl Jobs {
+    pub fn new() -> Self {
+        Self {
+            factories: Rc::new(RefCell::new(FxHashMap::default())),
+        }
+    }
+
+    pub fn insert<F, FR, A, R>(&self, name: String, factory: F)
+    where
+        F: Fn(A) -> FR + 'static,
+        FR: Future<Output = LuaResult<R>> + Send + 'static,
+        A: DeserializeOwned,
+        R: Serialize + Send + 'static,
+    {
+        let factory: JobFactory = Rc::new(move |lua, args| {
+            let args = lua.from_value(args)?;
+            let task = lua.spawn(factory(args));
+            Ok(Box::pin(async move {
+                let output = task.await?;
+                serde_value::to_value(output).map_err(|e| LuaError::SerializeError(e.to_string()))
+            }))
+        });
+        self.factories.borrow_mut().insert(name, factory);
+    }
+
+    /**
+        Starts the job with the given name on the main executor.
+
+        # Errors
+
+        Errors if no job with the given name has been registered,
+        or if the given arguments could not be deserialized.
+    */
+    pub fn start(&self, lua: &Lua, name: &str, args: LuaValue) -> LuaResult<JobFuture> {
+        // NOTE: Must not hold any borrows while calling into the factory,
+        // since it may register new jobs or run other jobs recursively
+        let factory = self.factories.borrow().get(name).cloned();
+        match factory {
+            Some(factory) => factory(lua, args),
+            None => Err(LuaError::runtime(format!("unknown job '{name}'"))),
+        }
+    }
+}
+
+/**
+    The serialized output of a job, converted back into a Lua value when returned.
+*/
+pub(crate) struct JobOutput(pub Value);
+
+impl<'lua> IntoLua<'lua> for JobOutput {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        let options = SerializeOptions::new()
+            .serialize_none_to_null(false)
+            .serialize_unit_to_null(false);
+        lua.to_value_with(&self.0, options)
+    }
+}
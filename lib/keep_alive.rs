@@ -0,0 +1,83 @@
+use std::{cell::Cell, rc::Rc};
+
+use event_listener::Event;
+
+#[derive(Debug, Default)]
+struct KeepAliveState {
+    count: Cell<usize>,
+    released: Event,
+}
+
+/**
+    Keep-alive guards for a scheduler, see [`Scheduler::keep_alive`].
+
+    [`Scheduler::keep_alive`]: crate::Scheduler::keep_alive
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct KeepAlives {
+    state: Rc<KeepAliveState>,
+}
+
+impl KeepAlives {
+    pub fn new() -> Self {
+        Self {
+            state: Rc::new(KeepAliveState::default()),
+        }
+    }
+
+    pub fn acquire(&self) -> KeepAlive {
+        KeepAlive::new(Rc::clone(&self.state))
+    }
+
+    pub fn is_held(&self) -> bool {
+        self.state.count.get() > 0
+    }
+
+    /**
+        Waits until the last keep-alive guard has been dropped.
+    */
+    pub async fn wait_for_release(&self) {
+        if self.is_held() {
+            self.state.released.listen().await;
+        } else {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/**
+    A guard that keeps a scheduler running while it is idle, see [`Scheduler::keep_alive`].
+
+    The scheduler may stop once all guards for it have been dropped, and cloning
+    a guard creates another guard that keeps the scheduler running on its own.
+
+    [`Scheduler::keep_alive`]: crate::Scheduler::keep_alive
+*/
+#[derive(Debug)]
+#[must_use = "the scheduler is only kept running while the guard is alive"]
+pub struct KeepAlive {
+    state: Rc<KeepAliveState>,
+}
+
+impl KeepAlive {
+    fn new(state: Rc<KeepAliveState>) -> Self {
+        state.count.set(state.count.get() + 1);
+        Self { state }
+    }
+}
+
+impl Clone for KeepAlive {
+    fn clone(&self) -> Self {
+        Self::new(Rc::clone(&self.state))
+    }
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        let count = self.state.count.get() - 1;
+        self.state.count.set(count);
+        if count == 0 {
+            self.state.released.notify(usize::MAX);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::prelude::*;
+
+/**
+    The maximum number of unused keys that a single pool holds on to.
+*/
+const MAX_POOLED_KEYS: usize = 1024;
+
+/**
+    A pool of reusable Lua registry keys.
+
+    Creating and removing registry values for every queued thread churns the Lua registry,
+    so instead of removing values once they are taken out, the pool replaces them with `nil`
+    and keeps their keys around, reusing the same registry slots for values stored later on.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct RegistryKeyPool {
+    keys: Rc<RefCell<Vec<LuaRegistryKey>>>,
+}
+
+impl RegistryKeyPool {
+    pub fn new() -> Self {
+        Self {
+            keys: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /**
+        Stores the given value in the Lua registry, reusing a pooled key if there is one.
+    */
+    pub fn store<'lua>(
+        &self,
+        lua: &'lua Lua,
+        value: impl IntoLua<'lua>,
+    ) -> LuaResult<LuaRegistryKey> {
+        let pooled = self.keys.borrow_mut().pop();
+        match pooled {
+            Some(key) => {
+                lua.replace_registry_value(&key, value)?;
+                Ok(key)
+            }
+            None => lua.create_registry_value(value),
+        }
+    }
+
+    /**
+        Takes the value for the given key back out of the Lua registry, returning the key to the pool.
+    */
+    pub fn take<'lua, T: FromLua<'lua>>(
+        &self,
+        lua: &'lua Lua,
+        key: LuaRegistryKey,
+    ) -> LuaResult<T> {
+        let value = lua.registry_value(&key)?;
+        lua.replace_registry_value(&key, LuaValue::Nil)?;
+        let mut keys = self.keys.borrow_mut();
+        if keys.len() < MAX_POOLED_KEYS {
+            keys.push(key);
+        }
+        Ok(value)
+    }
+}
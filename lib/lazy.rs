@@ -0,0 +1,118 @@
+use std::{
+    cell::{Cell, RefCell},
+    future::Future,
+    rc::Rc,
+};
+
+use event_listener::Event;
+use mlua::prelude::*;
+
+const ERR_NOT_LOADED: &str = "\
+Lazy value has not been loaded yet!\
+\nCall the lazy value to load it first - Luau can not yield inside of metamethods such as __index.\
+";
+
+/**
+    Shared state for a lazily loaded value.
+*/
+#[derive(Default)]
+struct LazyState {
+    value: RefCell<Option<LuaRegistryKey>>,
+    loading: Cell<bool>,
+    event: Event,
+}
+
+/**
+    Guard that marks a lazy value as no longer loading when dropped.
+
+    This makes sure other waiting threads get woken up, even if the
+    thread that started loading the value was cancelled mid-load.
+*/
+struct LoadingGuard(Rc<LazyState>);
+
+impl Drop for LoadingGuard {
+    fn drop(&mut self) {
+        self.0.loading.set(false);
+        self.0.event.notify(usize::MAX);
+    }
+}
+
+/**
+    Creates a proxy table that runs the given async loader when called, and caches its result.
+
+    Any threads calling the proxy while the value is being loaded
+    will yield until loading has completed, and then get the cached value.
+
+    Errors returned by the loader are not cached, and the next call will try loading again.
+*/
+pub(crate) fn create_lazy_async<'lua, F, FR, R>(
+    lua: &'lua Lua,
+    loader: F,
+) -> LuaResult<LuaTable<'lua>>
+where
+    F: Fn(&'lua Lua) -> FR + 'static,
+    FR: Future<Output = LuaResult<R>> + 'lua,
+    R: IntoLua<'lua>,
+{
+    let state = Rc::new(LazyState::default());
+
+    let get_state = Rc::clone(&state);
+    let get = lua.create_async_function(move |lua, _: LuaMultiValue| {
+        let state = Rc::clone(&get_state);
+        let load = if state.value.borrow().is_none() && !state.loading.get() {
+            state.loading.set(true);
+            Some((loader(lua), LoadingGuard(Rc::clone(&state))))
+        } else {
+            None
+        };
+        async move {
+            if let Some((fut, guard)) = load {
+                let value = fut.await?.into_lua(lua)?;
+                state
+                    .value
+                    .replace(Some(lua.create_registry_value(value.clone())?));
+                drop(guard);
+                return Ok(value);
+            }
+            loop {
+                if let Some(key) = &*state.value.borrow() {
+                    return lua.registry_value::<LuaValue>(key);
+                }
+                if !state.loading.get() {
+                    return Err(LuaError::runtime("lazy value failed to load"));
+                }
+                let listener = state.event.listen();
+                // NOTE: Need to check again, loading could
+                // have completed while creating our listener
+                if state.loading.get() {
+                    listener.await;
+                }
+            }
+        }
+    })?;
+
+    let index_state = Rc::clone(&state);
+    let index =
+        lua.create_function(move |lua, (_, key): (LuaValue, LuaValue)| {
+            match &*index_state.value.borrow() {
+                Some(value_key) => match lua.registry_value::<LuaValue>(value_key)? {
+                    LuaValue::Table(t) => t.get::<_, LuaValue>(key),
+                    LuaValue::UserData(u) => u.get::<_, LuaValue>(key),
+                    v => Err(LuaError::runtime(format!(
+                        "attempt to index lazy {} value",
+                        v.type_name()
+                    ))),
+                },
+                None => Err(LuaError::runtime(ERR_NOT_LOADED)),
+            }
+        })?;
+
+    let meta = lua.create_table_from(vec![
+        ("__call", LuaValue::Function(get)),
+        ("__index", LuaValue::Function(index)),
+        ("__metatable", LuaValue::Boolean(false)),
+    ])?;
+    let proxy = lua.create_table()?;
+    proxy.set_metatable(Some(meta));
+    Ok(proxy)
+}
@@ -0,0 +1,74 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::thread_id::ThreadId;
+
+type LeakCallback = Box<dyn Fn(LeakReport) + Send + 'static>;
+
+/**
+    A report of Lua threads and values left behind by a scheduler, see [`Scheduler::set_leak_callback`].
+
+    [`Scheduler::set_leak_callback`]: crate::Scheduler::set_leak_callback
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LeakReport {
+    /// Tracked threads that never completed.
+    pub unfinished_threads: Vec<ThreadId>,
+    /// Queued threads that were never resumed.
+    pub queued_threads: Vec<ThreadId>,
+    /// The number of registry values still held by queued threads and unclaimed thread results.
+    pub registry_values: usize,
+}
+
+impl LeakReport {
+    /**
+        Returns `true` if nothing was leaked.
+    */
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.unfinished_threads.is_empty()
+            && self.queued_threads.is_empty()
+            && self.registry_values == 0
+    }
+}
+
+/**
+    Detector for Lua threads and values left behind by a scheduler when it is dropped.
+*/
+#[derive(Clone)]
+pub(crate) struct LeakDetector {
+    callback: Rc<RefCell<Option<LeakCallback>>>,
+}
+
+impl LeakDetector {
+    pub fn new() -> Self {
+        Self {
+            callback: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn replace(&self, callback: impl Fn(LeakReport) + Send + 'static) {
+        self.callback.borrow_mut().replace(Box::new(callback));
+    }
+
+    pub fn clear(&self) {
+        self.callback.borrow_mut().take();
+    }
+
+    /**
+        Reports the given leaks, if there were any.
+    */
+    pub fn report(&self, report: LeakReport) {
+        if report.is_empty() {
+            return;
+        }
+        tracing::debug!(
+            unfinished = report.unfinished_threads.len(),
+            queued = report.queued_threads.len(),
+            registry_values = report.registry_values,
+            "leaks detected"
+        );
+        if let Some(callback) = &*self.callback.borrow() {
+            callback(report);
+        }
+    }
+}
@@ -1,16 +1,109 @@
+mod awaiting;
+mod cancel;
+mod channel;
+mod checkpoint;
+mod chunk;
+mod clock;
+#[cfg(feature = "tokio")]
+mod companion;
+mod condvar;
+mod config;
+mod cycle;
+mod deadline;
+mod delay;
+mod diagnostics;
+mod drain;
 mod error_callback;
+mod error_history;
+mod error_value;
+mod event_source;
+mod executor;
 mod exit;
 mod functions;
+mod handle;
+mod idle;
+mod inject;
+mod jobs;
+mod keep_alive;
+mod key_pool;
+mod lazy;
+mod leaks;
+mod locals;
+mod location;
+mod names;
+mod native;
+mod output;
+mod preempt;
+mod pressure;
+mod primitives;
+mod promise;
 mod queue;
+mod remote;
+mod respawn;
 mod result_map;
+mod result_stream;
+mod result_transform;
+mod resume_hooks;
 mod scheduler;
+mod scoped_globals;
+mod stats;
 mod status;
+mod step;
+mod strict;
+mod supervisor;
+mod suspend;
+mod tags;
+mod task;
 mod thread_id;
+mod thread_info;
+mod thread_span;
+mod tick;
+mod tracking;
 mod traits;
 mod util;
+mod value_log;
+mod wakeups;
+mod watch;
+mod watchdog;
+mod yield_handler;
 
+pub mod prelude;
+#[cfg(feature = "unstable")]
+pub mod unstable;
+
+pub use channel::{ChannelSender, RuntimeChannel};
+pub use checkpoint::Checkpoint;
+pub use chunk::ChunkOptions;
+pub use clock::{TimerPrecision, TimerStats};
+pub use config::SchedulerConfig;
+pub use diagnostics::LongPoll;
+pub use drain::DrainStatus;
+pub use error_callback::UncaughtError;
+pub use error_history::ErrorRecord;
+pub use error_value::ThreadError;
+pub use event_source::Backpressure;
+pub use executor::MaybeSend;
+pub use exit::{ExitMode, ExitReason};
 pub use functions::Functions;
+pub use handle::ThreadHandle;
+pub use idle::IdleStats;
+pub use keep_alive::KeepAlive;
+pub use leaks::LeakReport;
+pub use output::{OutputLevel, OutputRecord, OutputSink};
+pub use pressure::QueuePressure;
+pub use queue::{Priority, QueueFull, ThreadErrored};
+pub use remote::SchedulerHandle;
+pub use result_map::ThreadCompletion;
 pub use scheduler::Scheduler;
+pub use stats::SchedulerStats;
 pub use status::Status;
-pub use thread_id::ThreadId;
+pub use step::SchedulerStepper;
+pub use supervisor::{RestartEvent, RestartOptions, RestartPolicy};
+pub use thread_id::{ScopedThreadId, ThreadId};
+pub use thread_info::ThreadInfo;
+pub use tracking::{TrackingMode, TrackingStats};
 pub use traits::{IntoLuaThread, LuaSchedulerExt, LuaSpawnExt};
+pub use value_log::{ValueKind, ValueRecord};
+pub use wakeups::WakeupStats;
+pub use watch::Watch;
+pub use watchdog::{StuckTask, WatchdogPolicy};
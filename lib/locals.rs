@@ -0,0 +1,169 @@
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use mlua::prelude::*;
+use rustc_hash::FxHashMap;
+
+type LocalValues = Rc<RefCell<FxHashMap<TypeId, Box<dyn Any>>>>;
+
+/**
+    Task-local values of a single Lua thread, shared with any futures it has spawned.
+*/
+#[derive(Debug, Clone, Default)]
+struct LocalMap(LocalValues);
+
+impl LuaUserData for LocalMap {}
+
+/**
+    Task-local storage for Lua threads, see [`LuaSchedulerExt::task_local`].
+
+    Values are kept in a Lua table with weak keys, so that they are dropped along with their
+    thread once it has been garbage collected, and can never be seen by a thread that happens
+    to reuse the id of a previous thread. Futures spawned by a thread hold on to its values
+    instead, and make them available as the current task-local values while being polled.
+
+    [`LuaSchedulerExt::task_local`]: crate::LuaSchedulerExt::task_local
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct TaskLocals {
+    table: Rc<RefCell<Option<LuaRegistryKey>>>,
+    scope: Rc<RefCell<Option<LocalMap>>>,
+}
+
+impl TaskLocals {
+    pub fn new() -> Self {
+        Self {
+            table: Rc::new(RefCell::new(None)),
+            scope: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    fn table<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaTable<'lua>> {
+        if let Some(key) = &*self.table.borrow() {
+            return lua.registry_value(key);
+        }
+        let table = lua.create_table()?;
+        let meta = lua.create_table_from([("__mode", "k")])?;
+        table.set_metatable(Some(meta));
+        self.table
+            .replace(Some(lua.create_registry_value(table.clone())?));
+        Ok(table)
+    }
+
+    /**
+        Gets the values of the given thread, if it has any.
+    */
+    fn get_map(&self, lua: &Lua, thread: &LuaThread) -> Option<LocalMap> {
+        let table = self.table(lua).ok()?;
+        let ud = table.raw_get::<_, Option<LuaAnyUserData>>(thread).ok()??;
+        let map = ud.borrow::<LocalMap>().ok()?;
+        Some(map.clone())
+    }
+
+    /**
+        Gets the values of the given thread, creating them if it has none.
+    */
+    fn get_or_create_map(&self, lua: &Lua, thread: &LuaThread) -> LuaResult<LocalMap> {
+        if let Some(map) = self.get_map(lua, thread) {
+            return Ok(map);
+        }
+        let map = LocalMap::default();
+        self.table(lua)?
+            .raw_set(thread.clone(), lua.create_userdata(map.clone())?)?;
+        Ok(map)
+    }
+
+    /**
+        Gets the current values - those of the future being polled, if
+        any, otherwise those of the currently running Lua thread.
+    */
+    fn current(&self, lua: &Lua) -> Option<LocalMap> {
+        if let Some(map) = &*self.scope.borrow() {
+            return Some(map.clone());
+        }
+        self.get_map(lua, &lua.current_thread())
+    }
+
+    pub fn get<T: Clone + 'static>(&self, lua: &Lua) -> Option<T> {
+        let map = self.current(lua)?;
+        let values = map.0.borrow();
+        values.get(&TypeId::of::<T>())?.downcast_ref::<T>().cloned()
+    }
+
+    pub fn set<T: 'static>(&self, lua: &Lua, value: T) -> LuaResult<()> {
+        let map = match self.scope.borrow().clone() {
+            Some(map) => map,
+            None => self.get_or_create_map(lua, &lua.current_thread())?,
+        };
+        map.0
+            .borrow_mut()
+            .insert(TypeId::of::<T>(), Box::new(value));
+        Ok(())
+    }
+
+    pub fn set_for_thread<T: 'static>(
+        &self,
+        lua: &Lua,
+        thread: &LuaThread,
+        value: T,
+    ) -> LuaResult<()> {
+        let map = self.get_or_create_map(lua, thread)?;
+        map.0
+            .borrow_mut()
+            .insert(TypeId::of::<T>(), Box::new(value));
+        Ok(())
+    }
+
+    pub fn remove<T: 'static>(&self, lua: &Lua) -> Option<T> {
+        let map = self.current(lua)?;
+        let value = map.0.borrow_mut().remove(&TypeId::of::<T>())?;
+        value.downcast::<T>().ok().map(|value| *value)
+    }
+
+    pub fn clear(&self) {
+        self.table.borrow_mut().take();
+    }
+
+    /**
+        Wraps the given future, spawned by the currently running Lua thread, so that
+        the task-local values of the thread are available while it is being polled.
+    */
+    pub fn scoped<F: Future>(&self, lua: &Lua, fut: F) -> TaskLocalScope<F> {
+        TaskLocalScope {
+            map: self.get_map(lua, &lua.current_thread()),
+            scope: Rc::clone(&self.scope),
+            inner: Box::pin(fut),
+        }
+    }
+}
+
+/**
+    A future that makes the task-local values of the Lua thread that spawned it current while polled.
+*/
+pub(crate) struct TaskLocalScope<F> {
+    map: Option<LocalMap>,
+    scope: Rc<RefCell<Option<LocalMap>>>,
+    inner: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for TaskLocalScope<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Some(map) = self.map.clone() else {
+            return self.inner.as_mut().poll(cx);
+        };
+        // NOTE: Futures may be polled from within other futures, so
+        // the previous scope must be restored once we are done here
+        let previous = self.scope.replace(Some(map));
+        let res = self.inner.as_mut().poll(cx);
+        self.scope.replace(previous);
+        res
+    }
+}
@@ -0,0 +1,79 @@
+use std::{cell::RefCell, rc::Rc};
+
+use rustc_hash::FxHashMap;
+
+use crate::thread_id::ThreadId;
+
+/**
+    A scheduler queue that a thread may be waiting in.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Location {
+    High,
+    Spawn,
+    Defer,
+    Delayed,
+    Tick,
+    Idle,
+}
+
+impl Location {
+    const COUNT: usize = 6;
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/**
+    The queues that each thread is currently waiting in, keyed by the base id of the thread.
+
+    Queues update this whenever threads are pushed to them or taken out of them, so that
+    the status of a thread can be found without scanning through every queue it may be in.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct ThreadLocations {
+    counts: Rc<RefCell<FxHashMap<ThreadId, [u32; Location::COUNT]>>>,
+}
+
+impl ThreadLocations {
+    pub fn new() -> Self {
+        Self {
+            counts: Rc::new(RefCell::new(FxHashMap::default())),
+        }
+    }
+
+    pub fn add(&self, id: ThreadId, location: Location) {
+        let mut counts = self.counts.borrow_mut();
+        counts.entry(id).or_default()[location.index()] += 1;
+    }
+
+    pub fn remove(&self, id: ThreadId, location: Location) {
+        let mut counts = self.counts.borrow_mut();
+        let Some(entry) = counts.get_mut(&id) else {
+            return;
+        };
+        let count = &mut entry[location.index()];
+        *count = count.saturating_sub(1);
+        if entry.iter().all(|count| *count == 0) {
+            counts.remove(&id);
+        }
+    }
+
+    /**
+        Forgets all threads in the given location, such as when its queue is cleared.
+    */
+    pub fn clear(&self, location: Location) {
+        self.counts.borrow_mut().retain(|_, entry| {
+            entry[location.index()] = 0;
+            entry.iter().any(|count| *count > 0)
+        });
+    }
+
+    pub fn contains(&self, id: ThreadId, location: Location) -> bool {
+        self.counts
+            .borrow()
+            .get(&id)
+            .is_some_and(|entry| entry[location.index()] > 0)
+    }
+}
@@ -0,0 +1,91 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::prelude::*;
+
+use crate::thread_id::ThreadId;
+
+/**
+    Names for Lua threads, identifying the logical job that a thread belongs to.
+
+    Unlike tags, names are inherited by any threads spawned from a named thread that
+    do not have a name of their own, so that errors deep inside a job can still be
+    attributed to the job that started them, and are included in reported errors.
+
+    Names are stored in a Lua table with weak keys, meaning naming a
+    thread does not prevent it from being garbage collected.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct ThreadNames {
+    table: Rc<RefCell<Option<LuaRegistryKey>>>,
+}
+
+impl ThreadNames {
+    pub fn new() -> Self {
+        Self {
+            table: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    fn table<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaTable<'lua>> {
+        if let Some(key) = &*self.table.borrow() {
+            return lua.registry_value(key);
+        }
+        let table = lua.create_table()?;
+        let meta = lua.create_table_from([("__mode", "k")])?;
+        table.set_metatable(Some(meta));
+        self.table
+            .replace(Some(lua.create_registry_value(table.clone())?));
+        Ok(table)
+    }
+
+    pub fn clear(&self) {
+        self.table.borrow_mut().take();
+    }
+
+    pub fn insert(&self, lua: &Lua, thread: &LuaThread, name: &str) -> LuaResult<()> {
+        self.table(lua)?.raw_set(thread.clone(), name)
+    }
+
+    pub fn remove(&self, lua: &Lua, thread: &LuaThread) -> LuaResult<()> {
+        // NOTE: Avoid creating the table just to remove something from it
+        if self.table.borrow().is_none() {
+            return Ok(());
+        }
+        self.table(lua)?.raw_set(thread.clone(), LuaValue::Nil)
+    }
+
+    pub fn get(&self, lua: &Lua, thread: &LuaThread) -> Option<String> {
+        // NOTE: Avoid creating the table just to look something up in it
+        self.table.borrow().as_ref()?;
+        self.table(lua).ok()?.raw_get(thread.clone()).ok()?
+    }
+
+    /**
+        Gives the given thread the same name as the currently running thread, unless it has one already.
+    */
+    pub fn inherit(&self, lua: &Lua, thread: &LuaThread) -> LuaResult<()> {
+        if self.get(lua, thread).is_some() {
+            return Ok(());
+        }
+        match self.get(lua, &lua.current_thread()) {
+            Some(name) => self.insert(lua, thread, &name),
+            None => Ok(()),
+        }
+    }
+
+    /**
+        Finds the name of a thread using its id, even if it has already completed.
+    */
+    pub fn find_id(&self, lua: &Lua, id: ThreadId) -> LuaResult<Option<String>> {
+        if self.table.borrow().is_none() {
+            return Ok(None);
+        }
+        for pair in self.table(lua)?.pairs::<LuaThread, String>() {
+            let (thread, name) = pair?;
+            if ThreadId::from(&thread) == id {
+                return Ok(Some(name));
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    cell::{Cell, RefCell},
+    future::Future,
+    rc::Rc,
+};
+
+use concurrent_queue::ConcurrentQueue;
+use event_listener::Event;
+use futures_lite::FutureExt;
+use mlua::prelude::*;
+use rustc_hash::FxHashMap;
+
+use crate::{
+    primitives::Primitives, strict::StrictMode, thread_id::ThreadId, traits::spawn_local_unwatched,
+    util::CachedChunk, watchdog::Watchdog,
+};
+
+/**
+    Lua implementation of native async functions.
+
+    Starts the future and then yields, with the scheduler resuming the thread once the
+    future completes, passing `true` and any returned values, or `false` and an error.
+    Since varargs are passed through directly, no values are lost or packed into tables.
+
+    If the thread is resumed by anything other than the scheduler before the future
+    completes, such as by the original `coroutine.resume`, the call errors instead.
+*/
+const NATIVE_ASYNC_IMPL_LUA: &str = r"
+start(...)
+return check(yield())
+";
+
+const ERR_RESUMED_EARLY: &str = "native async function was resumed before it completed";
+
+static NATIVE_ASYNC_IMPL: CachedChunk =
+    CachedChunk::new("=__scheduler_native_async", NATIVE_ASYNC_IMPL_LUA);
+
+type NativeResult = Box<dyn for<'lua> FnOnce(&'lua Lua) -> LuaResult<LuaMultiValue<'lua>>>;
+
+/**
+    A completed native async call, waiting to resume its thread.
+*/
+struct NativeCompletion {
+    thread: LuaRegistryKey,
+    call: u64,
+    result: NativeResult,
+}
+
+/**
+    A native async call that a thread is currently waiting for.
+*/
+struct AwaitingCall {
+    call: u64,
+    cancelled: Event,
+}
+
+/**
+    Queue for storing completed native async calls, along with
+    the threads that are currently waiting for one, and their calls.
+
+    Provides methods for pushing and draining the queue, as
+    well as listening for new items being pushed to the queue.
+
+    Each call is only ever completed once - calls that were abandoned, because their thread
+    was resumed early, have their completions discarded instead of resuming the thread again,
+    and calls that were cancelled, because their thread was cancelled, are dropped entirely.
+*/
+#[derive(Clone)]
+pub(crate) struct NativeAsyncQueue {
+    queue: Rc<ConcurrentQueue<NativeCompletion>>,
+    event: Rc<Event>,
+    awaiting: Rc<RefCell<FxHashMap<ThreadId, AwaitingCall>>>,
+    counter: Rc<Cell<u64>>,
+    watchdog: Watchdog,
+}
+
+impl NativeAsyncQueue {
+    pub fn new(watchdog: Watchdog) -> Self {
+        Self {
+            queue: Rc::new(ConcurrentQueue::unbounded()),
+            event: Rc::new(Event::new()),
+            awaiting: Rc::new(RefCell::new(FxHashMap::default())),
+            counter: Rc::new(Cell::new(0)),
+            watchdog,
+        }
+    }
+
+    /**
+        Checks if the given thread is currently waiting for a native async call to complete.
+    */
+    #[inline]
+    pub fn is_awaiting(&self, id: ThreadId) -> bool {
+        self.awaiting.borrow().contains_key(&id)
+    }
+
+    /**
+        Returns the number of threads currently waiting for a native async call to complete.
+    */
+    pub fn len(&self) -> usize {
+        self.awaiting.borrow().len()
+    }
+
+    /**
+        Returns the ids of all threads currently waiting for a native async call to complete.
+    */
+    #[cfg(feature = "unstable")]
+    pub fn awaiting_ids(&self) -> Vec<ThreadId> {
+        self.awaiting.borrow().keys().copied().collect()
+    }
+
+    /**
+        Abandons the native async call of the given thread, if it is waiting for one,
+        so that the thread is not resumed again once the call completes.
+
+        Returns `true` if the thread was waiting for a native async call.
+    */
+    fn abandon(&self, id: ThreadId) -> bool {
+        self.awaiting.borrow_mut().remove(&id).is_some()
+    }
+
+    /**
+        Cancels the native async call of the given thread, if it is waiting for one,
+        dropping its future right away instead of letting it run to completion.
+
+        Returns `true` if the thread was waiting for a native async call.
+    */
+    pub fn cancel(&self, id: ThreadId) -> bool {
+        let awaiting = self.awaiting.borrow_mut().remove(&id);
+        match awaiting {
+            Some(awaiting) => {
+                awaiting.cancelled.notify(usize::MAX);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn drain_items<'outer, 'lua>(
+        &'outer self,
+        lua: &'lua Lua,
+    ) -> impl Iterator<Item = (LuaThread<'lua>, LuaMultiValue<'lua>)> + 'outer
+    where
+        'lua: 'outer,
+    {
+        self.queue.try_iter().filter_map(|completion| {
+            let thread: LuaThread = lua.registry_value(&completion.thread).unwrap();
+            lua.remove_registry_value(completion.thread).unwrap();
+            {
+                // NOTE: The thread may have been resumed early, and may even
+                // be waiting for another call by now, so the calls must match
+                let mut awaiting = self.awaiting.borrow_mut();
+                let id = ThreadId::from(&thread);
+                if awaiting.get(&id).map(|a| a.call) != Some(completion.call) {
+                    tracing::trace!("discarding completion of abandoned native async call");
+                    return None;
+                }
+                awaiting.remove(&id);
+            }
+            let args = match (completion.result)(lua) {
+                Ok(values) => (true, values).into_lua_multi(lua),
+                Err(e) => (false, LuaValue::Error(e)).into_lua_multi(lua),
+            }
+            .expect("out of memory");
+            Some((thread, args))
+        })
+    }
+
+    pub async fn wait_for_item(&self) {
+        if self.queue.is_empty() {
+            let listener = self.event.listen();
+            // NOTE: Need to check again, we could have gotten
+            // new queued items while creating our listener
+            if self.queue.is_empty() {
+                listener.await;
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /**
+        Starts a native async call for the currently running thread.
+
+        Note that this does not yield, that must be done separately.
+    */
+    fn start<F, R>(&self, lua: &Lua, fut: F) -> LuaResult<()>
+    where
+        F: Future<Output = LuaResult<R>> + 'static,
+        R: for<'lua> IntoLuaMulti<'lua> + 'static,
+    {
+        if let Some(strict) = lua.app_data_ref::<StrictMode>() {
+            strict.check_running(lua)?;
+        }
+        let thread = lua.current_thread();
+        let id = ThreadId::from(&thread);
+        let key = lua.create_registry_value(thread)?;
+        let call = self.counter.get();
+        self.counter.set(call + 1);
+        let cancelled = Event::new();
+        let listener = cancelled.listen();
+        self.awaiting
+            .borrow_mut()
+            .insert(id, AwaitingCall { call, cancelled });
+
+        let queue = Rc::clone(&self.queue);
+        let event = Rc::clone(&self.event);
+        let watchdog = self.watchdog.clone();
+        spawn_local_unwatched(lua, async move {
+            let watched = async { Some(watchdog.watch(fut, Some(id)).await) };
+            let cancelled = async {
+                listener.await;
+                None
+            };
+            let Some(res) = watched.or(cancelled).await else {
+                tracing::trace!("dropping native async call of cancelled thread");
+                return;
+            };
+            let res = res.unwrap_or_else(|lifetime| Err(Watchdog::timeout_error(lifetime)));
+            let completion = NativeCompletion {
+                thread: key,
+                call,
+                result: Box::new(move |lua| res.and_then(|v| v.into_lua_multi(lua))),
+            };
+            let _ = queue.push(completion);
+            event.notify(usize::MAX);
+        });
+
+        Ok(())
+    }
+}
+
+/**
+    Creates a native async function, see [`LuaSchedulerExt::create_native_async_function`].
+
+    [`LuaSchedulerExt::create_native_async_function`]: crate::LuaSchedulerExt::create_native_async_function
+*/
+pub(crate) fn create_native_async_function<'lua, A, R, F, FR>(
+    lua: &'lua Lua,
+    func: F,
+) -> LuaResult<LuaFunction<'lua>>
+where
+    A: FromLuaMulti<'lua>,
+    R: for<'r> IntoLuaMulti<'r> + 'static,
+    F: Fn(&'lua Lua, A) -> FR + 'static,
+    FR: Future<Output = LuaResult<R>> + 'static,
+{
+    let queue = lua
+        .app_data_ref::<NativeAsyncQueue>()
+        .expect("native async functions can only be created within an active scheduler")
+        .clone();
+    let primitives = lua
+        .app_data_ref::<Primitives>()
+        .expect("native async functions can only be created within an active scheduler")
+        .clone();
+
+    let check_queue = queue.clone();
+    let start = lua.create_function(move |lua, args: A| {
+        let _span = tracing::trace_span!("Scheduler::fn_native_async").entered();
+        queue.start(lua, func(lua, args))
+    })?;
+    let check = lua.create_function(move |lua, (ok, values): (bool, LuaMultiValue)| {
+        // NOTE: The scheduler stops awaiting the call before resuming the thread,
+        // so if we are still awaiting it here, something else resumed the thread
+        if check_queue.abandon(ThreadId::from(&lua.current_thread())) {
+            return Err(LuaError::runtime(ERR_RESUMED_EARLY));
+        }
+        if ok {
+            Ok(values)
+        } else {
+            match values.into_iter().next() {
+                Some(LuaValue::Error(e)) => Err(e),
+                _ => Err(LuaError::runtime("native async function failed")),
+            }
+        }
+    })?;
+
+    let env = lua.create_table_from(vec![
+        ("start", start),
+        ("check", check),
+        ("yield", primitives.get(lua, "yield")?),
+    ])?;
+    NATIVE_ASYNC_IMPL.load(lua, env)
+}
@@ -0,0 +1,120 @@
+use std::{cell::RefCell, rc::Rc, time::SystemTime};
+
+use mlua::prelude::*;
+
+use crate::thread_id::ThreadId;
+
+/**
+    The level of a single [`OutputRecord`].
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OutputLevel {
+    /// Output from `print`.
+    Info,
+    /// Output from `warn`.
+    Warn,
+    /// An uncaught error in a Lua thread.
+    Error,
+}
+
+/**
+    A single piece of output from a Lua thread, see [`Scheduler::set_output_sink`].
+
+    [`Scheduler::set_output_sink`]: crate::Scheduler::set_output_sink
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputRecord {
+    /// The thread that the output came from.
+    pub thread: ThreadId,
+    /// The level of the output.
+    pub level: OutputLevel,
+    /// When the output was written.
+    pub timestamp: SystemTime,
+    /// The output itself, without any trailing newline.
+    pub message: String,
+}
+
+/**
+    A destination for output from Lua threads, see [`Scheduler::set_output_sink`].
+
+    Implemented for all closures that take an [`OutputRecord`].
+
+    [`Scheduler::set_output_sink`]: crate::Scheduler::set_output_sink
+*/
+pub trait OutputSink {
+    /**
+        Writes the given record to this sink.
+    */
+    fn write(&self, record: OutputRecord);
+}
+
+impl<F: Fn(OutputRecord)> OutputSink for F {
+    fn write(&self, record: OutputRecord) {
+        self(record);
+    }
+}
+
+/**
+    The output sink of a scheduler, writing to stdout and stderr if none is set.
+*/
+#[derive(Clone)]
+pub(crate) struct Output {
+    sink: Rc<RefCell<Option<Rc<dyn OutputSink>>>>,
+}
+
+impl Output {
+    pub fn new() -> Self {
+        Self {
+            sink: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn replace(&self, sink: impl OutputSink + 'static) {
+        self.sink.borrow_mut().replace(Rc::new(sink));
+    }
+
+    pub fn clear(&self) {
+        self.sink.borrow_mut().take();
+    }
+
+    #[inline]
+    pub fn has_sink(&self) -> bool {
+        self.sink.borrow().is_some()
+    }
+
+    pub fn write(&self, thread: ThreadId, level: OutputLevel, message: String) {
+        // NOTE: Must not hold the borrow while writing, the sink may be arbitrary code
+        let sink = self.sink.borrow().clone();
+        match sink {
+            Some(sink) => sink.write(OutputRecord {
+                thread,
+                level,
+                timestamp: SystemTime::now(),
+                message,
+            }),
+            None if level == OutputLevel::Info => println!("{message}"),
+            None => eprintln!("{message}"),
+        }
+    }
+
+    /**
+        Writes the given values, formatted the same way as the default `print`, for the current thread.
+    */
+    pub fn write_values(
+        &self,
+        lua: &Lua,
+        level: OutputLevel,
+        values: LuaMultiValue,
+    ) -> LuaResult<()> {
+        let mut message = String::new();
+        for (index, value) in values.into_iter().enumerate() {
+            if index > 0 {
+                message.push('\t');
+            }
+            message.push_str(&value.to_string()?);
+        }
+        self.write(ThreadId::from(&lua.current_thread()), level, message);
+        Ok(())
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    cell::{Cell, RefCell},
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use mlua::{prelude::*, VmState};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::thread_id::ThreadId;
+
+pub(crate) type InterruptCallback = Rc<dyn Fn(&Lua) -> LuaResult<VmState>>;
+
+/**
+    Bookkeeping for automatic yielding (preemption) of Lua threads.
+
+    Threads that have been given a yield budget, or all threads if a default budget
+    is set, will automatically yield at the next possible interrupt point once they
+    have run continuously for longer than their budget, and are then expected to be
+    re-queued by the scheduler.
+
+    Threads that run continuously for longer than the thread budget, if one is set,
+    instead error at the next possible interrupt point, even inside of metamethods.
+
+    Budgets are checked using a Luau interrupt. Any interrupt set by the host through the
+    scheduler is chained with the one installed here, and is always called first, since
+    Luau only supports a single interrupt.
+*/
+#[derive(Clone)]
+pub(crate) struct Preemption {
+    budgets: Rc<RefCell<FxHashMap<ThreadId, Duration>>>,
+    default_budget: Rc<Cell<Option<Duration>>>,
+    thread_budget: Rc<Cell<Option<Duration>>>,
+    preempted: Rc<Cell<usize>>,
+    yielded: Rc<RefCell<FxHashSet<ThreadId>>>,
+    slices: Rc<RefCell<Vec<Slice>>>,
+    enabled: Rc<Cell<bool>>,
+    installed: Rc<Cell<bool>>,
+    interrupt: Rc<RefCell<Option<InterruptCallback>>>,
+}
+
+impl Preemption {
+    pub fn new() -> Self {
+        Self {
+            budgets: Rc::new(RefCell::new(FxHashMap::default())),
+            default_budget: Rc::new(Cell::new(None)),
+            thread_budget: Rc::new(Cell::new(None)),
+            preempted: Rc::new(Cell::new(0)),
+            yielded: Rc::new(RefCell::new(FxHashSet::default())),
+            slices: Rc::new(RefCell::new(Vec::new())),
+            enabled: Rc::new(Cell::new(false)),
+            installed: Rc::new(Cell::new(false)),
+            interrupt: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /**
+        Sets the yield budget for the given thread, installing
+        the interrupt callback into the Lua state if necessary.
+    */
+    pub fn set_budget(&self, lua: &Lua, id: ThreadId, budget: Duration) {
+        self.budgets.borrow_mut().insert(id, budget);
+        self.install(lua);
+    }
+
+    /**
+        Sets the default yield budget for all threads without a budget of their
+        own, installing the interrupt callback into the Lua state if necessary.
+    */
+    pub fn set_default_budget(&self, lua: &Lua, budget: Option<Duration>) {
+        self.default_budget.set(budget);
+        if budget.is_some() {
+            self.install(lua);
+        }
+    }
+
+    pub fn default_budget(&self) -> Option<Duration> {
+        self.default_budget.get()
+    }
+
+    /**
+        Sets the budget that no thread may exceed during a single resumption without
+        erroring, installing the interrupt callback into the Lua state if necessary.
+    */
+    pub fn set_thread_budget(&self, lua: &Lua, budget: Option<Duration>) {
+        self.thread_budget.set(budget);
+        if budget.is_some() {
+            self.install(lua);
+        }
+    }
+
+    pub fn thread_budget(&self) -> Option<Duration> {
+        self.thread_budget.get()
+    }
+
+    /**
+        Creates the error that threads exceeding the given thread budget error with.
+    */
+    pub fn budget_error(budget: Duration) -> LuaError {
+        LuaError::runtime(format!(
+            "thread exceeded its budget of {budget:?} in a single resumption"
+        ))
+    }
+
+    /**
+        Returns the number of times that threads have been automatically yielded.
+    */
+    pub fn preempted(&self) -> usize {
+        self.preempted.get()
+    }
+
+    /**
+        Enables time slices, and installs the interrupt callback into the Lua state if necessary.
+    */
+    fn install(&self, lua: &Lua) {
+        self.enabled.set(true);
+        self.install_interrupt(lua);
+    }
+
+    fn install_interrupt(&self, lua: &Lua) {
+        if !self.installed.replace(true) {
+            let this = self.clone();
+            lua.set_interrupt(move |lua| {
+                // NOTE: Must not hold the borrow while calling, the callback may be arbitrary code
+                let interrupt = this.interrupt.borrow().clone();
+                if let Some(interrupt) = interrupt {
+                    match interrupt(lua)? {
+                        VmState::Continue => {}
+                        VmState::Yield => return Ok(VmState::Yield),
+                    }
+                }
+                this.check(lua)
+            });
+        }
+    }
+
+    /**
+        Sets the interrupt callback of the host, chaining it with the one used for budgets.
+    */
+    pub fn set_interrupt(&self, lua: &Lua, interrupt: Option<InterruptCallback>) {
+        let has_interrupt = interrupt.is_some();
+        self.interrupt.replace(interrupt);
+        if has_interrupt {
+            self.install_interrupt(lua);
+        } else if !self.enabled.get() && self.installed.replace(false) {
+            lua.remove_interrupt();
+        }
+    }
+
+    /**
+        Removes the yield budget for the given thread, if any.
+    */
+    #[inline]
+    pub fn remove_budget(&self, id: ThreadId) {
+        if self.enabled.get() {
+            self.budgets.borrow_mut().remove(&id);
+        }
+    }
+
+    /**
+        Removes all per-thread budgets, keeping the default budget.
+    */
+    pub fn clear_budgets(&self) {
+        self.budgets.borrow_mut().clear();
+        self.yielded.borrow_mut().clear();
+    }
+
+    /**
+        Returns the number of threads that currently have a yield budget.
+    */
+    pub fn budget_count(&self) -> usize {
+        self.budgets.borrow().len()
+    }
+
+    /**
+        Removes the interrupt callback from the Lua state, if it was installed.
+    */
+    pub fn uninstall(&self, lua: &Lua) {
+        self.enabled.set(false);
+        self.interrupt.replace(None);
+        if self.installed.replace(false) {
+            lua.remove_interrupt();
+        }
+    }
+
+    /**
+        Starts a new time slice for the given thread, meaning it is about to be resumed
+        by the scheduler, which then also handles the thread being automatically yielded.
+
+        Slices nest, such as when a thread spawns another thread, and the slice ends once
+        the returned guard is dropped, which must happen right after the thread has been resumed.
+    */
+    #[inline]
+    pub fn slice(&self, id: ThreadId) -> SliceGuard {
+        if !self.enabled.get() {
+            return SliceGuard { slices: None };
+        }
+        self.slices.borrow_mut().push(Slice {
+            id,
+            start: Instant::now(),
+            yielded: false,
+        });
+        SliceGuard {
+            slices: Some(Rc::clone(&self.slices)),
+        }
+    }
+
+    /**
+        Checks if the given thread was automatically yielded, and
+        clears that state, meaning it must now be re-queued.
+    */
+    #[inline]
+    pub fn take_yielded(&self, id: ThreadId) -> bool {
+        let mut yielded = self.yielded.borrow_mut();
+        !yielded.is_empty() && yielded.remove(&id)
+    }
+
+    /**
+        Wraps the given future, starting a new time slice for the given thread every time it is polled.
+    */
+    pub fn sliced<F: Future>(&self, id: ThreadId, fut: F) -> SlicedFuture<F> {
+        SlicedFuture {
+            inner: Box::pin(fut),
+            preemption: self.clone(),
+            id,
+        }
+    }
+
+    fn check(&self, lua: &Lua) -> LuaResult<VmState> {
+        // NOTE: The thread budget covers everything that happens during the resumption of the
+        // outermost thread, so that resuming other threads can not be used to get around it
+        let Some(start) = self.slices.borrow().first().map(|slice| slice.start) else {
+            return Ok(VmState::Continue);
+        };
+        if let Some(budget) = self.thread_budget.get() {
+            if start.elapsed() >= budget {
+                // NOTE: The slice is kept as it is, so that a script catching this
+                // error keeps erroring at every interrupt until the thread stops
+                return Err(Self::budget_error(budget));
+            }
+        }
+        if self.check_yield(lua) {
+            Ok(VmState::Yield)
+        } else {
+            Ok(VmState::Continue)
+        }
+    }
+
+    /**
+        Checks if the thread of the current slice has exceeded its yield budget, in which case
+        it is marked as automatically yielded, and must then yield as soon as possible.
+
+        Only the thread that the slice was started for is ever yielded, and not any coroutines
+        that it resumed by itself, since those would yield back to it instead of the scheduler.
+    */
+    fn check_yield(&self, lua: &Lua) -> bool {
+        let mut slices = self.slices.borrow_mut();
+        let Some(slice) = slices.last_mut().filter(|slice| !slice.yielded) else {
+            return false;
+        };
+        if ThreadId::from(&lua.current_thread()) != slice.id {
+            return false;
+        }
+        let budget = self
+            .budgets
+            .borrow()
+            .get(&slice.id)
+            .copied()
+            .or(self.default_budget.get());
+        let exceeded = budget.is_some_and(|budget| slice.start.elapsed() >= budget);
+        if exceeded {
+            slice.yielded = true;
+            self.preempted.set(self.preempted.get() + 1);
+            self.yielded.borrow_mut().insert(slice.id);
+        }
+        exceeded
+    }
+}
+
+/**
+    A time slice of a thread resumed by the scheduler, see [`Preemption::slice`].
+*/
+struct Slice {
+    id: ThreadId,
+    start: Instant,
+    yielded: bool,
+}
+
+/**
+    Ends a time slice once dropped, see [`Preemption::slice`].
+*/
+pub(crate) struct SliceGuard {
+    slices: Option<Rc<RefCell<Vec<Slice>>>>,
+}
+
+impl Drop for SliceGuard {
+    fn drop(&mut self) {
+        if let Some(slices) = &self.slices {
+            slices.borrow_mut().pop();
+        }
+    }
+}
+
+/**
+    A future that starts a new time slice every time it is polled.
+*/
+pub(crate) struct SlicedFuture<F> {
+    inner: Pin<Box<F>>,
+    preemption: Preemption,
+    id: ThreadId,
+}
+
+impl<F: Future> Future for SlicedFuture<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let _slice = self.preemption.slice(self.id);
+        self.inner.as_mut().poll(cx)
+    }
+}
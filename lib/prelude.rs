@@ -0,0 +1,21 @@
+/*!
+    The stable public API of this crate, for glob importing.
+
+    Everything re-exported here follows semantic versioning, and downstream
+    crates that only depend on this module should not need to change between
+    releases with the same major version. Experimental subsystems live in the
+    `unstable` module instead, behind the `unstable` feature.
+
+    ```rust
+    use mlua_luau_scheduler::prelude::*;
+    ```
+*/
+
+pub use crate::{
+    exit::ExitReason,
+    functions::Functions,
+    scheduler::Scheduler,
+    status::Status,
+    thread_id::ThreadId,
+    traits::{IntoLuaThread, LuaSchedulerExt, LuaSpawnExt},
+};
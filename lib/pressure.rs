@@ -0,0 +1,97 @@
+#![allow(clippy::module_name_repetitions)]
+
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+type PressureCallback = Box<dyn Fn(QueuePressure) + Send + 'static>;
+
+/**
+    A change in the queue pressure of a scheduler.
+
+    See [`Scheduler::set_queue_pressure_callback`] for more information.
+
+    [`Scheduler::set_queue_pressure_callback`]: crate::Scheduler::set_queue_pressure_callback
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueuePressure {
+    /// The number of thresholds currently exceeded, with `0` meaning no pressure.
+    pub level: usize,
+    /// The number of Lua threads pending in the scheduler queues.
+    pub depth: usize,
+}
+
+/**
+    Monitors the depth of the scheduler queues, calling a callback
+    whenever the depth crosses any of the configured thresholds.
+
+    To prevent the signal from flapping when the depth hovers around a threshold,
+    a level is only left once the depth falls below half of its threshold.
+*/
+#[derive(Clone)]
+pub(crate) struct PressureMonitor {
+    thresholds: Rc<RefCell<Vec<usize>>>,
+    level: Rc<Cell<usize>>,
+    callback: Rc<RefCell<Option<PressureCallback>>>,
+}
+
+impl PressureMonitor {
+    pub fn new() -> Self {
+        Self {
+            thresholds: Rc::new(RefCell::new(Vec::new())),
+            level: Rc::new(Cell::new(0)),
+            callback: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn replace(
+        &self,
+        thresholds: impl IntoIterator<Item = usize>,
+        callback: impl Fn(QueuePressure) + Send + 'static,
+    ) {
+        let mut thresholds = thresholds.into_iter().collect::<Vec<_>>();
+        thresholds.sort_unstable();
+        thresholds.dedup();
+        self.thresholds.replace(thresholds);
+        self.level.set(0);
+        self.callback.borrow_mut().replace(Box::new(callback));
+    }
+
+    pub fn thresholds(&self) -> Vec<usize> {
+        self.thresholds.borrow().clone()
+    }
+
+    pub fn clear(&self) {
+        self.thresholds.borrow_mut().clear();
+        self.level.set(0);
+        self.callback.borrow_mut().take();
+    }
+
+    /**
+        Updates the current queue depth, calling the callback if the pressure level changed.
+    */
+    pub fn update(&self, depth: usize) {
+        let callback = self.callback.borrow();
+        let Some(callback) = &*callback else {
+            return;
+        };
+
+        let thresholds = self.thresholds.borrow();
+        let current = self.level.get();
+        let mut level = current;
+        while level < thresholds.len() && depth > thresholds[level] {
+            level += 1;
+        }
+        if level == current {
+            while level > 0 && depth < thresholds[level - 1] / 2 {
+                level -= 1;
+            }
+        }
+
+        if level != current {
+            self.level.set(level);
+            callback(QueuePressure { level, depth });
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use std::rc::Rc;
+
+use mlua::prelude::*;
+use rustc_hash::FxHashMap;
+
+/**
+    Paths to all of the global functions that the scheduler relies on.
+*/
+const PRIMITIVES: &[(&str, &str)] = &[
+    ("", "error"),
+    ("", "pcall"),
+    ("", "select"),
+    ("", "type"),
+    ("", "unpack"),
+    ("", "xpcall"),
+    ("coroutine", "create"),
+    ("coroutine", "close"),
+    ("coroutine", "running"),
+    ("coroutine", "status"),
+    ("coroutine", "yield"),
+    ("debug", "info"),
+    ("debug", "traceback"),
+    ("table", "pack"),
+];
+
+/**
+    Pristine copies of the global functions that the scheduler relies on.
+
+    These are captured from globals once, when the scheduler is created, and stored
+    in the registry - user code replacing or removing globals afterwards, such as
+    sandboxing away `coroutine`, will not affect any of the scheduler functions.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct Primitives {
+    keys: Rc<FxHashMap<&'static str, LuaRegistryKey>>,
+}
+
+impl Primitives {
+    pub fn capture(lua: &Lua) -> Self {
+        let globals = lua.globals();
+        let mut keys = FxHashMap::default();
+        for (lib, name) in PRIMITIVES {
+            let func = if lib.is_empty() {
+                globals.get::<_, LuaFunction>(*name).ok()
+            } else {
+                globals
+                    .get::<_, LuaTable>(*lib)
+                    .and_then(|t| t.get::<_, LuaFunction>(*name))
+                    .ok()
+            };
+            if let Some(key) = func.and_then(|f| lua.create_registry_value(f).ok()) {
+                keys.insert(*name, key);
+            }
+        }
+        Self {
+            keys: Rc::new(keys),
+        }
+    }
+
+    /**
+        Gets the captured primitive with the given name.
+
+        # Errors
+
+        Errors if the primitive was missing from globals when the scheduler was created.
+    */
+    pub fn get<'lua>(&self, lua: &'lua Lua, name: &str) -> LuaResult<LuaFunction<'lua>> {
+        if let Some(key) = self.keys.get(name) {
+            return lua.registry_value(key);
+        }
+        let path = match PRIMITIVES.iter().find(|(_, n)| *n == name) {
+            Some((lib, n)) if !lib.is_empty() => format!("{lib}.{n}"),
+            _ => name.to_string(),
+        };
+        Err(LuaError::runtime(format!(
+            "the global function '{path}' is required by the scheduler, \
+            but was missing when the scheduler was created"
+        )))
+    }
+
+    /**
+        Runs the given function with a `coroutine` global that contains the captured `yield`.
+
+        Some functionality in `mlua`, such as creating async functions, reads `coroutine.yield`
+        directly from globals - this makes sure that keeps working in sandboxed environments.
+        The previous value of the `coroutine` global is restored afterwards.
+    */
+    pub fn with_coroutine_global<T>(
+        &self,
+        lua: &Lua,
+        f: impl FnOnce() -> LuaResult<T>,
+    ) -> LuaResult<T> {
+        let globals = lua.globals();
+        let existing = globals.get::<_, LuaValue>("coroutine")?;
+        let has_yield = match &existing {
+            LuaValue::Table(t) => t.get::<_, LuaValue>("yield")?.is_function(),
+            _ => false,
+        };
+        if has_yield {
+            return f();
+        }
+
+        let coroutine = lua.create_table_from([("yield", self.get(lua, "yield")?)])?;
+        globals.set("coroutine", coroutine)?;
+        let result = f();
+        globals.set("coroutine", existing)?;
+        result
+    }
+}
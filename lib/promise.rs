@@ -0,0 +1,306 @@
+use std::{cell::RefCell, future::Future, rc::Rc};
+
+use event_listener::Event;
+use mlua::prelude::*;
+
+use crate::{
+    error_history::split_error,
+    primitives::Primitives,
+    traits::{spawn_local_unwatched, LuaSchedulerExt},
+    util::CachedChunk,
+    watchdog::Watchdog,
+};
+
+/**
+    Lua implementation of `Promise:await`.
+
+    Waits for the promise to settle, and then returns its values, or raises its error.
+    Errors are passed back from Rust as values, so that they can be raised as they are.
+*/
+const AWAIT_IMPL_LUA: &str = r"
+local r = pack(wait(...))
+if r[1] then
+    return unpack(r, 2, r.n)
+else
+    error(r[2], 0)
+end
+";
+
+/**
+    Lua implementation of `Promise:andThen` and `Promise:catch`, running in a thread of its own.
+
+    Waits for the promise to settle, passes its values or error to the matching callback, if
+    any, and then settles the chained promise with whatever the callback returned or raised.
+    Values and errors without a matching callback are passed through to the chained promise.
+*/
+const CHAIN_IMPL_LUA: &str = r"
+local promise, chained, onResolve, onReject = ...
+local r = pack(pcall(promise.await, promise))
+if r[1] then
+    if onResolve then
+        r = pack(pcall(onResolve, unpack(r, 2, r.n)))
+    end
+elseif onReject then
+    r = pack(pcall(onReject, r[2]))
+end
+settle(chained, unpack(r, 1, r.n))
+";
+
+static AWAIT_IMPL: CachedChunk = CachedChunk::new("=__scheduler_promise_await", AWAIT_IMPL_LUA);
+static CHAIN_IMPL: CachedChunk = CachedChunk::new("=__scheduler_promise_chain", CHAIN_IMPL_LUA);
+
+type PromiseOutput = Box<dyn for<'lua> FnOnce(&'lua Lua) -> LuaResult<LuaMultiValue<'lua>>>;
+
+/**
+    The result of a promise, if it has settled.
+
+    Futures complete without access to the Lua state, so their output is kept
+    as it is until the promise is first used from Lua, and converted then.
+*/
+enum PromiseResult {
+    Output(PromiseOutput),
+    Resolved(LuaRegistryKey),
+    Rejected(LuaRegistryKey),
+}
+
+/**
+    Shared state for a promise, and any threads waiting for it to settle.
+*/
+#[derive(Default)]
+struct PromiseState {
+    result: RefCell<Option<PromiseResult>>,
+    event: Event,
+}
+
+impl PromiseState {
+    fn settle(&self, result: PromiseResult) {
+        self.result.replace(Some(result));
+        self.event.notify(usize::MAX);
+    }
+
+    fn is_settled(&self) -> bool {
+        self.result.borrow().is_some()
+    }
+
+    /**
+        Returns `true` and the values of the settled promise, or `false` and its error.
+
+        Output from futures is converted into Lua values the first time this is called.
+    */
+    fn values<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaMultiValue<'lua>> {
+        let result = self.result.borrow_mut().take();
+        let result = match result {
+            None => return Err(LuaError::runtime("promise has not yet settled")),
+            Some(PromiseResult::Output(output)) => match output(lua) {
+                Ok(values) => PromiseResult::Resolved(store_values(lua, values)?),
+                Err(e) => PromiseResult::Rejected(lua.create_registry_value(split_error(&e).0)?),
+            },
+            Some(result) => result,
+        };
+        let values = match &result {
+            PromiseResult::Resolved(key) => (true, load_values(lua, key)?).into_lua_multi(lua),
+            PromiseResult::Rejected(key) => {
+                (false, lua.registry_value::<LuaValue>(key)?).into_lua_multi(lua)
+            }
+            PromiseResult::Output(_) => unreachable!("promise output was already converted"),
+        };
+        self.result.replace(Some(result));
+        values
+    }
+}
+
+/**
+    Stores the given values in the Lua registry, keeping track of their count, since they may contain `nil`.
+*/
+fn store_values(lua: &Lua, values: LuaMultiValue) -> LuaResult<LuaRegistryKey> {
+    let table = lua.create_table_with_capacity(values.len(), 1)?;
+    table.set("n", values.len())?;
+    for (index, value) in values.into_iter().enumerate() {
+        table.raw_set(index + 1, value)?;
+    }
+    lua.create_registry_value(table)
+}
+
+/**
+    Loads values stored using [`store_values`] back out of the Lua registry.
+*/
+fn load_values<'lua>(lua: &'lua Lua, key: &LuaRegistryKey) -> LuaResult<LuaMultiValue<'lua>> {
+    let table = lua.registry_value::<LuaTable>(key)?;
+    let count = table.raw_get::<_, usize>("n")?;
+    (1..=count)
+        .map(|index| table.raw_get::<_, LuaValue>(index))
+        .collect()
+}
+
+/**
+    Lua functions shared by all promises created from the same promise function.
+*/
+struct PromiseFunctions {
+    wait: LuaRegistryKey,
+    chain: LuaRegistryKey,
+}
+
+/**
+    A promise for the output of a future, returned to Lua by a promise function.
+
+    See [`LuaSchedulerExt::create_promise_function`] for more information.
+
+    [`LuaSchedulerExt::create_promise_function`]: crate::LuaSchedulerExt::create_promise_function
+*/
+struct Promise {
+    state: Rc<PromiseState>,
+    functions: Rc<PromiseFunctions>,
+}
+
+impl Promise {
+    fn new(functions: Rc<PromiseFunctions>) -> Self {
+        Self {
+            state: Rc::new(PromiseState::default()),
+            functions,
+        }
+    }
+
+    /**
+        Waits for the promise to settle in a thread of its own, and then passes its values
+        or error to the given callbacks, returning a new promise for the result of the callback.
+    */
+    fn chain<'lua>(
+        lua: &'lua Lua,
+        this: &LuaAnyUserData<'lua>,
+        on_resolve: Option<LuaFunction<'lua>>,
+        on_reject: Option<LuaFunction<'lua>>,
+    ) -> LuaResult<LuaAnyUserData<'lua>> {
+        let functions = Rc::clone(&this.borrow::<Self>()?.functions);
+        let chain = lua.registry_value::<LuaFunction>(&functions.chain)?;
+        let chained = lua.create_userdata(Self::new(functions))?;
+        // NOTE: Queued arguments can not contain any nil values, so
+        // missing callbacks are passed to the chain thread as false
+        let callback =
+            |f: Option<LuaFunction<'lua>>| f.map_or(LuaValue::Boolean(false), LuaValue::Function);
+        let args = (
+            this.clone(),
+            chained.clone(),
+            callback(on_resolve),
+            callback(on_reject),
+        );
+        lua.push_thread_back(chain, args)?;
+        Ok(chained)
+    }
+}
+
+impl LuaUserData for Promise {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("isSettled", |_, this, ()| Ok(this.state.is_settled()));
+        methods.add_function(
+            "andThen",
+            |lua, (this, on_resolve, on_reject): (LuaAnyUserData, LuaFunction, Option<LuaFunction>)| {
+                Self::chain(lua, &this, Some(on_resolve), on_reject)
+            },
+        );
+        methods.add_function(
+            "catch",
+            |lua, (this, on_reject): (LuaAnyUserData, LuaFunction)| {
+                Self::chain(lua, &this, None, Some(on_reject))
+            },
+        );
+    }
+
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_function_get("await", |lua, this| {
+            let this = this.borrow::<Self>()?;
+            lua.registry_value::<LuaFunction>(&this.functions.wait)
+        });
+    }
+}
+
+/**
+    Creates a promise function, see [`LuaSchedulerExt::create_promise_function`].
+
+    [`LuaSchedulerExt::create_promise_function`]: crate::LuaSchedulerExt::create_promise_function
+*/
+pub(crate) fn create_promise_function<'lua, A, R, F, FR>(
+    lua: &'lua Lua,
+    func: F,
+) -> LuaResult<LuaFunction<'lua>>
+where
+    A: FromLuaMulti<'lua>,
+    R: for<'r> IntoLuaMulti<'r> + 'static,
+    F: Fn(&'lua Lua, A) -> FR + 'static,
+    FR: Future<Output = LuaResult<R>> + 'static,
+{
+    let primitives = lua
+        .app_data_ref::<Primitives>()
+        .expect("promise functions can only be created within an active scheduler")
+        .clone();
+    let watchdog = lua
+        .app_data_ref::<Watchdog>()
+        .expect("promise functions can only be created within an active scheduler")
+        .clone();
+
+    let wait = lua.create_async_function(|lua, this: LuaAnyUserData| {
+        let state = this.borrow::<Promise>().map(|this| Rc::clone(&this.state));
+        async move {
+            let state = state?;
+            loop {
+                if state.is_settled() {
+                    return state.values(lua);
+                }
+                let listener = state.event.listen();
+                // NOTE: Need to check again, the promise could
+                // have settled while creating our listener
+                if !state.is_settled() {
+                    listener.await;
+                }
+            }
+        }
+    })?;
+    let await_env = lua.create_table_from(vec![
+        ("wait", wait),
+        ("error", primitives.get(lua, "error")?),
+        ("pack", primitives.get(lua, "pack")?),
+        ("unpack", primitives.get(lua, "unpack")?),
+    ])?;
+
+    let settle = lua.create_function(
+        |lua, (chained, ok, values): (LuaAnyUserData, bool, LuaMultiValue)| {
+            let chained = chained.borrow::<Promise>()?;
+            let result = if ok {
+                PromiseResult::Resolved(store_values(lua, values)?)
+            } else {
+                let error = values.into_iter().next().unwrap_or(LuaValue::Nil);
+                PromiseResult::Rejected(lua.create_registry_value(error)?)
+            };
+            chained.state.settle(result);
+            Ok(())
+        },
+    )?;
+    let chain_env = lua.create_table_from(vec![
+        ("settle", settle),
+        ("pcall", primitives.get(lua, "pcall")?),
+        ("pack", primitives.get(lua, "pack")?),
+        ("unpack", primitives.get(lua, "unpack")?),
+    ])?;
+
+    let functions = Rc::new(PromiseFunctions {
+        wait: lua.create_registry_value(AWAIT_IMPL.load(lua, await_env)?)?,
+        chain: lua.create_registry_value(CHAIN_IMPL.load(lua, chain_env)?)?,
+    });
+
+    lua.create_function(move |lua, args: A| {
+        let _span = tracing::trace_span!("Scheduler::fn_promise").entered();
+        let fut = func(lua, args);
+        let promise = Promise::new(Rc::clone(&functions));
+        let state = Rc::clone(&promise.state);
+        let watchdog = watchdog.clone();
+        spawn_local_unwatched(lua, async move {
+            let res = watchdog
+                .watch(fut, None)
+                .await
+                .unwrap_or_else(|lifetime| Err(Watchdog::timeout_error(lifetime)));
+            let output: PromiseOutput =
+                Box::new(move |lua| res.and_then(|v| v.into_lua_multi(lua)));
+            state.settle(PromiseResult::Output(output));
+        });
+        Ok(promise)
+    })
+}
@@ -1,30 +1,299 @@
-use std::{pin::Pin, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    error::Error as StdError,
+    fmt,
+    pin::Pin,
+    rc::Rc,
+};
 
 use concurrent_queue::ConcurrentQueue;
 use derive_more::{Deref, DerefMut};
 use event_listener::Event;
 use futures_lite::{Future, FutureExt};
 use mlua::prelude::*;
+use rustc_hash::FxHashMap;
 
-use crate::{traits::IntoLuaThread, util::ThreadWithArgs, ThreadId};
+use crate::{
+    error_value::{error_from_value, ErrorValues},
+    key_pool::RegistryKeyPool,
+    location::{Location, ThreadLocations},
+    primitives::Primitives,
+    result_map::ThreadResultMap,
+    thread_info::ThreadRecords,
+    traits::IntoLuaThread,
+    util::{LazyArgs, ThreadWithArgs},
+    ThreadId,
+};
+
+const ERR_ERROR_TAKEN: &str =
+    "thread errored outside of the scheduler, and its error was taken by whoever resumed it";
+
+/**
+    The priority of a thread pushed to a scheduler, see [`Scheduler::push_thread_with_priority`].
+
+    [`Scheduler::push_thread_with_priority`]: crate::Scheduler::push_thread_with_priority
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Resumed after all other queued threads, the same as deferred threads.
+    Low,
+    /// Resumed in the same order as spawned threads.
+    #[default]
+    Normal,
+    /// Resumed before all other queued threads.
+    High,
+}
+
+/**
+    The error returned when pushing a thread to a scheduler queue that is full.
+
+    See [`Scheduler::set_queue_capacity`] for more information.
+
+    Use [`QueueFull::from_error`] to get it back out of a [`LuaError`].
+
+    [`Scheduler::set_queue_capacity`]: crate::Scheduler::set_queue_capacity
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueFull {
+    capacity: usize,
+}
+
+impl QueueFull {
+    /**
+        Gets the queue full error from the given [`LuaError`], if it is one.
+    */
+    #[must_use]
+    pub fn from_error(error: &LuaError) -> Option<&Self> {
+        error.downcast_ref()
+    }
+
+    /**
+        Returns the capacity of the queue that was full.
+    */
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl fmt::Display for QueueFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "scheduler queue is full (capacity {})", self.capacity)
+    }
+}
+
+impl StdError for QueueFull {}
+
+/**
+    The error returned when pushing a thread that has already errored to a scheduler.
+
+    Threads that have already completed without an error are instead rejected with
+    [`LuaError::CoroutineInactive`], same as when trying to resume them directly.
+
+    Use [`ThreadErrored::from_error`] to get it back out of a [`LuaError`].
+*/
+#[derive(Debug, Clone)]
+pub struct ThreadErrored {
+    error: LuaError,
+}
+
+impl ThreadErrored {
+    /**
+        Gets the thread errored error from the given [`LuaError`], if it is one.
+    */
+    #[must_use]
+    pub fn from_error(error: &LuaError) -> Option<&Self> {
+        error.downcast_ref()
+    }
+
+    /**
+        Returns the error that the thread originally errored with.
+    */
+    #[must_use]
+    pub fn error(&self) -> &LuaError {
+        &self.error
+    }
+}
+
+impl fmt::Display for ThreadErrored {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "thread has already errored: {}", self.error)
+    }
+}
+
+impl StdError for ThreadErrored {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/**
+    Items stored in a [`ThreadQueue`], in order, along with the positions of each thread.
+
+    Items taken out of the middle of the queue leave an empty slot behind until it reaches either
+    end, so that neither taking an item nor pushing to the front ever moves any of the other items.
+*/
+#[derive(Debug, Default)]
+struct QueueItems {
+    slots: VecDeque<Option<ThreadWithArgs>>,
+    /// The position of the first slot, which decreases when pushing to the front.
+    head: i64,
+    positions: FxHashMap<ThreadId, VecDeque<i64>>,
+    len: usize,
+}
+
+impl QueueItems {
+    fn push_back(&mut self, stored: ThreadWithArgs) {
+        let position = self.head + i64::try_from(self.slots.len()).unwrap_or(i64::MAX);
+        let positions = self.positions.entry(stored.thread_id()).or_default();
+        positions.push_back(position);
+        self.slots.push_back(Some(stored));
+        self.len += 1;
+    }
+
+    fn push_front(&mut self, stored: ThreadWithArgs) {
+        self.head -= 1;
+        let positions = self.positions.entry(stored.thread_id()).or_default();
+        positions.push_front(self.head);
+        self.slots.push_front(Some(stored));
+        self.len += 1;
+    }
+
+    fn pop_front(&mut self) -> Option<ThreadWithArgs> {
+        while let Some(slot) = self.slots.pop_front() {
+            self.head += 1;
+            if let Some(stored) = slot {
+                // NOTE: The first item in the queue is always the first for its thread
+                self.forget_first(stored.thread_id());
+                self.len -= 1;
+                return Some(stored);
+            }
+        }
+        None
+    }
+
+    /**
+        Takes the first item for the given thread out of the queue.
+    */
+    fn take(&mut self, id: ThreadId) -> Option<ThreadWithArgs> {
+        let position = self.forget_first(id)?;
+        let index = usize::try_from(position - self.head).ok()?;
+        let stored = self.slots.get_mut(index)?.take();
+        if stored.is_some() {
+            self.len -= 1;
+        }
+        while matches!(self.slots.front(), Some(None)) {
+            self.slots.pop_front();
+            self.head += 1;
+        }
+        while matches!(self.slots.back(), Some(None)) {
+            self.slots.pop_back();
+        }
+        stored
+    }
+
+    fn forget_first(&mut self, id: ThreadId) -> Option<i64> {
+        let positions = self.positions.get_mut(&id)?;
+        let position = positions.pop_front();
+        if positions.is_empty() {
+            self.positions.remove(&id);
+        }
+        position
+    }
+
+    #[cfg(feature = "unstable")]
+    fn iter(&self) -> impl Iterator<Item = &ThreadWithArgs> {
+        self.slots.iter().flatten()
+    }
+
+    fn clear(&mut self) {
+        self.slots.clear();
+        self.positions.clear();
+        self.len = 0;
+    }
+}
 
 /**
     Queue for storing [`LuaThread`]s with associated arguments.
 
     Provides methods for pushing and draining the queue, as
     well as listening for new items being pushed to the queue.
+
+    The queue may be given a capacity, in which case pushing to it while it is full errors
+    with [`QueueFull`], and [`ThreadQueue::wait_for_space`] may be used to wait for space.
 */
 #[derive(Debug, Clone)]
 pub(crate) struct ThreadQueue {
-    queue: Rc<ConcurrentQueue<ThreadWithArgs>>,
+    queue: Rc<RefCell<QueueItems>>,
     event: Rc<Event>,
+    capacity: Rc<Cell<Option<usize>>>,
+    space: Rc<Event>,
+    keys: RegistryKeyPool,
+    locations: ThreadLocations,
+    location: Location,
 }
 
 impl ThreadQueue {
-    pub fn new() -> Self {
-        let queue = Rc::new(ConcurrentQueue::unbounded());
+    pub fn new(locations: &ThreadLocations, location: Location) -> Self {
+        let queue = Rc::new(RefCell::new(QueueItems::default()));
         let event = Rc::new(Event::new());
-        Self { queue, event }
+        let capacity = Rc::new(Cell::new(None));
+        let space = Rc::new(Event::new());
+        let keys = RegistryKeyPool::new();
+        Self {
+            queue,
+            event,
+            capacity,
+            space,
+            keys,
+            locations: locations.clone(),
+            location,
+        }
+    }
+
+    pub fn set_capacity(&self, capacity: Option<usize>) {
+        self.capacity.set(capacity);
+        self.space.notify(usize::MAX);
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity.get()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.capacity
+            .get()
+            .is_some_and(|capacity| self.len() >= capacity)
+    }
+
+    /**
+        Checks that there is space in this queue for another item.
+
+        This is checked before any other work is done when pushing,
+        so that a full queue leaves no trace of the rejected thread.
+    */
+    pub fn check_capacity(&self) -> LuaResult<()> {
+        match self.capacity.get() {
+            Some(capacity) if self.len() >= capacity => {
+                Err(LuaError::external(QueueFull { capacity }))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /**
+        Waits until there is space in this queue for another item.
+    */
+    pub async fn wait_for_space(&self) {
+        while self.is_full() {
+            let listener = self.space.listen();
+            // NOTE: Need to check again, items could
+            // have been drained while creating our listener
+            if self.is_full() {
+                listener.await;
+            }
+        }
     }
 
     pub fn push_item<'lua>(
@@ -32,20 +301,218 @@ impl ThreadQueue {
         lua: &'lua Lua,
         thread: impl IntoLuaThread<'lua>,
         args: impl IntoLuaMulti<'lua>,
+    ) -> LuaResult<ThreadId> {
+        self.check_capacity()?;
+        self.push_item_unbounded(lua, thread, args)
+    }
+
+    /**
+        Pushes an item to this queue, even if it is full.
+
+        This is used for threads that were already running, and are
+        being re-queued by the scheduler itself, which must never fail.
+    */
+    pub fn push_item_unbounded<'lua>(
+        &self,
+        lua: &'lua Lua,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
     ) -> LuaResult<ThreadId> {
         let thread = thread.into_lua_thread(lua)?;
+        let id = Self::check_pushable(lua, &thread)?;
         let args = args.into_lua_multi(lua)?;
+        self.push_checked(lua, thread, args)?;
+        Ok(id)
+    }
+
+    pub fn push_item_with<'lua>(
+        &self,
+        lua: &'lua Lua,
+        thread: impl IntoLuaThread<'lua>,
+        args: LazyArgs,
+    ) -> LuaResult<ThreadId> {
+        self.check_capacity()?;
+        let thread = thread.into_lua_thread(lua)?;
+        let id = Self::check_pushable(lua, &thread)?;
+        self.push_checked_with(lua, thread, args)?;
+        Ok(id)
+    }
+
+    /**
+        Pushes an item to this queue without checking its capacity, or if the thread can be resumed.
 
+        This is used by the scheduler, which checks both before doing any other
+        work for a push, and records the push of the thread by itself.
+    */
+    pub fn push_checked<'lua>(
+        &self,
+        lua: &'lua Lua,
+        thread: LuaThread<'lua>,
+        args: LuaMultiValue<'lua>,
+    ) -> LuaResult<()> {
         tracing::trace!("pushing item to queue with {} args", args.len());
-        let id = ThreadId::from(&thread);
-        let stored = ThreadWithArgs::new(lua, thread, args)?;
+        let stored = ThreadWithArgs::new(lua, &self.keys, thread, args)?;
+        self.push_stored(stored);
+        Ok(())
+    }
 
-        self.queue.push(stored).into_lua_err()?;
+    /**
+        Same as [`ThreadQueue::push_checked`], but with lazily created arguments.
+    */
+    pub fn push_checked_with<'lua>(
+        &self,
+        lua: &'lua Lua,
+        thread: LuaThread<'lua>,
+        args: LazyArgs,
+    ) -> LuaResult<()> {
+        tracing::trace!("pushing item to queue with lazy args");
+        let stored = ThreadWithArgs::new_lazy(lua, &self.keys, thread, args)?;
+        self.push_stored(stored);
+        Ok(())
+    }
+
+    fn push_stored(&self, stored: ThreadWithArgs) {
+        let id = stored.thread_id();
+        self.queue.borrow_mut().push_back(stored);
+        self.locations.add(id, self.location);
         self.event.notify(usize::MAX);
+    }
 
+    fn pop_stored(&self) -> Option<ThreadWithArgs> {
+        // NOTE: Must not hold the borrow past this, creating lazy args may push more items
+        let stored = self.queue.borrow_mut().pop_front()?;
+        self.locations.remove(stored.thread_id(), self.location);
+        self.space.notify(usize::MAX);
+        Some(stored)
+    }
+
+    /**
+        Checks that the given thread can be resumed, and records its push time if it can.
+
+        See [`ThreadQueue::check_resumable`] for how threads that can not be resumed are handled.
+    */
+    pub fn check_pushable(lua: &Lua, thread: &LuaThread) -> LuaResult<ThreadId> {
+        let id = Self::check_resumable(lua, thread)?;
+        if let Some(records) = lua.app_data_ref::<ThreadRecords>() {
+            records.record_push(lua, thread)?;
+        }
         Ok(id)
     }
 
+    /**
+        Checks that the given thread can be resumed.
+
+        Threads that can not be resumed are rejected, and given an immediate result if they are
+        tracked - errored threads are rejected with a [`ThreadErrored`] containing their error.
+    */
+    pub fn check_resumable(lua: &Lua, thread: &LuaThread) -> LuaResult<ThreadId> {
+        // NOTE: Threads that have already completed would only be skipped
+        // once drained, so we skip storing them in the queue entirely here
+        if thread.status() != LuaThreadStatus::Resumable {
+            return Err(Self::reject(lua, thread));
+        }
+        Ok(ThreadId::from(thread))
+    }
+
+    /**
+        Rejects the given thread, which can not be resumed, returning the error to push it with.
+
+        The thread is given an immediate result if it is tracked, and has not yet completed.
+    */
+    pub fn reject(lua: &Lua, thread: &LuaThread) -> LuaError {
+        tracing::trace!("skipping push of completed thread");
+        let error =
+            (thread.status() == LuaThreadStatus::Error).then(|| Self::thread_error(lua, thread));
+        if let Some(result_map) = lua.app_data_ref::<ThreadResultMap>() {
+            let id = result_map.current(ThreadId::from(thread));
+            if result_map.is_tracked(id) && !result_map.is_completed(id) {
+                let result = match &error {
+                    Some(error) => Err(error.clone()),
+                    None => Ok(LuaMultiValue::new()),
+                };
+                result_map.insert(lua, id, result);
+            }
+        }
+        match error {
+            Some(error) => LuaError::external(ThreadErrored { error }),
+            None => LuaError::CoroutineInactive,
+        }
+    }
+
+    /**
+        Gets the error that the given errored thread raised, by closing it.
+
+        Resuming a thread from Rust takes its error, so for threads that errored outside of the
+        scheduler only the preserved value of errors raised using [`Functions::error`] may remain.
+
+        [`Functions::error`]: crate::Functions::error
+    */
+    fn thread_error(lua: &Lua, thread: &LuaThread) -> LuaError {
+        // NOTE: Closing returns whatever is left on the stack of the thread, which is
+        // the error if it was resumed from Lua, but not if it was resumed from Rust
+        let closed = lua
+            .app_data_ref::<Primitives>()
+            .map(|p| p.clone())
+            .and_then(|p| p.get(lua, "close").ok())
+            .and_then(|close| close.call::<_, (LuaValue, LuaValue)>(thread.clone()).ok())
+            .and_then(|(ok, value)| match ok {
+                LuaValue::Boolean(false) if !value.is_nil() => Some(value),
+                _ => None,
+            });
+        let preserved = lua
+            .app_data_ref::<ErrorValues>()
+            .and_then(|values| values.take(lua, thread));
+        match closed.or(preserved) {
+            Some(value) => error_from_value(lua, value),
+            None => LuaError::runtime(ERR_ERROR_TAKEN),
+        }
+    }
+
+    /**
+        Removes the first item for the given thread from this queue, if
+        there is one, keeping all other items in their original order.
+    */
+    pub fn take_item(&self, id: ThreadId) -> Option<ThreadWithArgs> {
+        if !self.contains(id) {
+            return None;
+        }
+        let taken = self.queue.borrow_mut().take(id);
+        if taken.is_some() {
+            self.locations.remove(id, self.location);
+            self.space.notify(usize::MAX);
+        }
+        taken
+    }
+
+    /**
+        Returns the ids of all threads in this queue, in order, without removing them.
+    */
+    #[cfg(feature = "unstable")]
+    pub fn thread_ids(&self) -> Vec<ThreadId> {
+        self.queue
+            .borrow()
+            .iter()
+            .map(ThreadWithArgs::thread_id)
+            .collect()
+    }
+
+    /**
+        Checks if the given thread is in this queue.
+    */
+    pub fn contains(&self, id: ThreadId) -> bool {
+        self.locations.contains(id, self.location)
+    }
+
+    /**
+        Pushes an item taken from another queue to the front of this queue.
+    */
+    pub fn push_item_front(&self, stored: ThreadWithArgs) {
+        let id = stored.thread_id();
+        self.queue.borrow_mut().push_front(stored);
+        self.locations.add(id, self.location);
+        self.event.notify(usize::MAX);
+    }
+
     #[inline]
     pub fn drain_items<'outer, 'lua>(
         &'outer self,
@@ -54,16 +521,36 @@ impl ThreadQueue {
     where
         'lua: 'outer,
     {
-        self.queue.try_iter().map(|stored| stored.into_inner(lua))
+        std::iter::from_fn(|| self.pop_stored())
+            .filter_map(|stored| stored.into_inner(lua, &self.keys))
+    }
+
+    #[inline]
+    pub fn pop_item<'lua>(&self, lua: &'lua Lua) -> Option<(LuaThread<'lua>, LuaMultiValue<'lua>)> {
+        loop {
+            let stored = self.pop_stored()?;
+            if let Some(item) = stored.into_inner(lua, &self.keys) {
+                return Some(item);
+            }
+        }
+    }
+
+    /**
+        Removes all items from this queue, without resuming them.
+    */
+    pub fn clear(&self) {
+        self.queue.borrow_mut().clear();
+        self.locations.clear(self.location);
+        self.space.notify(usize::MAX);
     }
 
     #[inline]
     pub async fn wait_for_item(&self) {
-        if self.queue.is_empty() {
+        if self.is_empty() {
             let listener = self.event.listen();
             // NOTE: Need to check again, we could have gotten
             // new queued items while creating our listener
-            if self.queue.is_empty() {
+            if self.is_empty() {
                 listener.await;
             }
         }
@@ -71,7 +558,12 @@ impl ThreadQueue {
 
     #[inline]
     pub fn is_empty(&self) -> bool {
-        self.queue.is_empty()
+        self.queue.borrow().len == 0
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.queue.borrow().len
     }
 }
 
@@ -82,8 +574,8 @@ impl ThreadQueue {
 pub(crate) struct SpawnedThreadQueue(ThreadQueue);
 
 impl SpawnedThreadQueue {
-    pub fn new() -> Self {
-        Self(ThreadQueue::new())
+    pub fn new(locations: &ThreadLocations) -> Self {
+        Self(ThreadQueue::new(locations, Location::Spawn))
     }
 }
 
@@ -94,8 +586,8 @@ impl SpawnedThreadQueue {
 pub(crate) struct DeferredThreadQueue(ThreadQueue);
 
 impl DeferredThreadQueue {
-    pub fn new() -> Self {
-        Self(ThreadQueue::new())
+    pub fn new(locations: &ThreadLocations) -> Self {
+        Self(ThreadQueue::new(locations, Location::Defer))
     }
 }
 
@@ -131,9 +623,21 @@ impl<'fut> FuturesQueue<'fut> {
         self.queue.try_iter()
     }
 
+    pub fn pop_item(&self) -> Option<LocalBoxFuture<'fut>> {
+        self.queue.pop().ok()
+    }
+
+    pub fn clear(&self) {
+        while self.queue.pop().is_ok() {}
+    }
+
     pub async fn wait_for_item(&self) {
         if self.queue.is_empty() {
             self.event.listen().await;
         }
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
 }
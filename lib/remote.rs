@@ -0,0 +1,252 @@
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+use concurrent_queue::ConcurrentQueue;
+use event_listener::Event;
+use mlua::prelude::*;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use serde_value::Value;
+
+use crate::jobs::JobOutput;
+
+const ERR_CLOSED: &str = "scheduler was dropped";
+
+/**
+    Work pushed to a scheduler from another thread.
+*/
+pub(crate) enum RemoteWork {
+    Bytecode {
+        name: String,
+        bytecode: Vec<u8>,
+        args: Value,
+    },
+    Registered {
+        name: String,
+        args: Value,
+    },
+}
+
+struct RemoteShared {
+    queue: ConcurrentQueue<RemoteWork>,
+    event: Event,
+}
+
+/**
+    A handle for pushing Lua work to a [`Scheduler`] from any other thread.
+
+    Created using [`Scheduler::handle`]. The handle is [`Send`] and [`Sync`], and may be cloned and
+    shared freely, such as between the worker threads of a web server. Pushed work is queued, and
+    the scheduler is woken up to run it as a new Lua thread during its next cycle.
+
+    Arguments are serialized when pushed, and converted into Lua values once the work runs. Arguments
+    that serialize into a sequence, such as tuples, are passed as separate arguments, and `()` passes
+    no arguments at all - wrap arguments in a tuple to pass a sequence as a single table instead.
+
+    Threads started through a handle are not tracked, since their ids are not known when pushing,
+    but may be observed using [`Scheduler::set_completion_callback`]. Any errors that happen
+    before a thread could be started, such as invalid bytecode, or an unknown registered
+    function, are passed to the error callback of the scheduler.
+
+    [`Scheduler`]: crate::Scheduler
+    [`Scheduler::handle`]: crate::Scheduler::handle
+    [`Scheduler::set_completion_callback`]: crate::Scheduler::set_completion_callback
+*/
+#[derive(Clone)]
+pub struct SchedulerHandle {
+    shared: Arc<RemoteShared>,
+}
+
+impl SchedulerHandle {
+    /**
+        Pushes precompiled Luau bytecode to the scheduler, to be run with the given arguments.
+
+        The given name is used as the chunk name in error messages and tracebacks.
+
+        # Errors
+
+        Errors if the arguments could not be serialized, or if the scheduler was dropped.
+    */
+    pub fn push_bytecode(
+        &self,
+        name: impl Into<String>,
+        bytecode: impl Into<Vec<u8>>,
+        args: impl Serialize,
+    ) -> LuaResult<()> {
+        self.push(RemoteWork::Bytecode {
+            name: name.into(),
+            bytecode: bytecode.into(),
+            args: serialize_args(args)?,
+        })
+    }
+
+    /**
+        Pushes a call to a function registered using [`Scheduler::register_function`]
+        to the scheduler, to be run with the given arguments.
+
+        # Errors
+
+        Errors if the arguments could not be serialized, or if the scheduler was dropped.
+
+        [`Scheduler::register_function`]: crate::Scheduler::register_function
+    */
+    pub fn push_registered(&self, name: impl Into<String>, args: impl Serialize) -> LuaResult<()> {
+        self.push(RemoteWork::Registered {
+            name: name.into(),
+            args: serialize_args(args)?,
+        })
+    }
+
+    /**
+        Returns `true` if the scheduler was dropped, meaning that no more work can be pushed.
+    */
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.shared.queue.is_closed()
+    }
+
+    fn push(&self, work: RemoteWork) -> LuaResult<()> {
+        self.shared
+            .queue
+            .push(work)
+            .map_err(|_| LuaError::runtime(ERR_CLOSED))?;
+        self.shared.event.notify(usize::MAX);
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for SchedulerHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SchedulerHandle")
+            .field("queued", &self.shared.queue.len())
+            .field("closed", &self.is_closed())
+            .finish()
+    }
+}
+
+pub(crate) fn serialize_args(args: impl Serialize) -> LuaResult<Value> {
+    serde_value::to_value(args).map_err(|e| LuaError::SerializeError(e.to_string()))
+}
+
+/**
+    Queue for work pushed from other threads, along with
+    the functions that may be called by name from them.
+*/
+#[derive(Clone)]
+pub(crate) struct RemoteQueue {
+    shared: Arc<RemoteShared>,
+    functions: Rc<RefCell<FxHashMap<String, LuaRegistryKey>>>,
+}
+
+impl RemoteQueue {
+    pub fn new() -> Self {
+        Self {
+            shared: Arc::new(RemoteShared {
+                queue: ConcurrentQueue::unbounded(),
+                event: Event::new(),
+            }),
+            functions: Rc::new(RefCell::new(FxHashMap::default())),
+        }
+    }
+
+    pub fn handle(&self) -> SchedulerHandle {
+        SchedulerHandle {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    pub fn register(&self, lua: &Lua, name: String, function: LuaFunction) -> LuaResult<()> {
+        let key = lua.create_registry_value(function)?;
+        self.functions.borrow_mut().insert(name, key);
+        Ok(())
+    }
+
+    pub fn unregister(&self, name: &str) -> bool {
+        self.functions.borrow_mut().remove(name).is_some()
+    }
+
+    pub async fn wait_for_item(&self) {
+        if self.shared.queue.is_empty() {
+            let listener = self.shared.event.listen();
+            // NOTE: Need to check again, we could have gotten
+            // new queued items while creating our listener
+            if self.shared.queue.is_empty() {
+                listener.await;
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shared.queue.is_empty()
+    }
+
+    /**
+        Drains all queued work, turning each item into a function and its arguments.
+    */
+    pub fn drain_items<'lua>(
+        &self,
+        lua: &'lua Lua,
+    ) -> Vec<LuaResult<(LuaFunction<'lua>, LuaMultiValue<'lua>)>> {
+        self.shared
+            .queue
+            .try_iter()
+            .map(|work| self.resolve(lua, work))
+            .collect()
+    }
+
+    /**
+        Turns a single item of work into a function and its arguments.
+    */
+    pub fn resolve<'lua>(
+        &self,
+        lua: &'lua Lua,
+        work: RemoteWork,
+    ) -> LuaResult<(LuaFunction<'lua>, LuaMultiValue<'lua>)> {
+        match work {
+            RemoteWork::Bytecode {
+                name,
+                bytecode,
+                args,
+            } => {
+                let function = lua.load(bytecode).set_name(name).into_function()?;
+                Ok((function, deserialize_args(lua, args)?))
+            }
+            RemoteWork::Registered { name, args } => {
+                let function = match self.functions.borrow().get(&name) {
+                    Some(key) => lua.registry_value::<LuaFunction>(key)?,
+                    None => {
+                        return Err(LuaError::runtime(format!(
+                            "no function registered with the name '{name}'"
+                        )))
+                    }
+                };
+                Ok((function, deserialize_args(lua, args)?))
+            }
+        }
+    }
+
+    /**
+        Removes all queued work, without running it.
+    */
+    pub fn clear(&self) {
+        while self.shared.queue.pop().is_ok() {}
+    }
+
+    /**
+        Closes the queue, so that handles can no longer push work.
+    */
+    pub fn close(&self) {
+        self.shared.queue.close();
+        self.clear();
+    }
+}
+
+fn deserialize_args(lua: &Lua, args: Value) -> LuaResult<LuaMultiValue<'_>> {
+    match args {
+        Value::Unit => Ok(LuaMultiValue::new()),
+        Value::Seq(values) => values
+            .into_iter()
+            .map(|value| JobOutput(value).into_lua(lua))
+            .collect(),
+        value => JobOutput(value).into_lua_multi(lua),
+    }
+}
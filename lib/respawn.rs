@@ -0,0 +1,108 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    rc::Rc,
+};
+
+use mlua::prelude::*;
+
+use crate::thread_id::ThreadId;
+
+/**
+    The function and arguments that a tracked Lua thread was pushed with.
+*/
+#[derive(Debug)]
+struct ThreadOrigin {
+    id: ThreadId,
+    function: LuaRegistryKey,
+    args: LuaRegistryKey,
+}
+
+/**
+    Bounded store of the functions and arguments that tracked Lua threads were pushed with,
+    making it possible to respawn them as fresh threads, see [`Scheduler::respawn`].
+
+    Origins are only retained once a capacity has been set, and the oldest
+    origins are discarded once the capacity has been reached.
+
+    [`Scheduler::respawn`]: crate::Scheduler::respawn
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct ThreadOrigins {
+    capacity: Rc<Cell<usize>>,
+    origins: Rc<RefCell<VecDeque<ThreadOrigin>>>,
+}
+
+impl ThreadOrigins {
+    pub fn new() -> Self {
+        Self {
+            capacity: Rc::new(Cell::new(0)),
+            origins: Rc::new(RefCell::new(VecDeque::new())),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity.get()
+    }
+
+    pub fn set_capacity(&self, capacity: usize) {
+        self.capacity.set(capacity);
+        let mut origins = self.origins.borrow_mut();
+        while origins.len() > capacity {
+            origins.pop_front();
+        }
+    }
+
+    pub fn clear(&self) {
+        self.origins.borrow_mut().clear();
+    }
+
+    /**
+        Retains the function and arguments for the given thread, if origins are being retained.
+
+        Any previous origin for the same id is replaced, since thread ids may be reused.
+    */
+    pub fn record(
+        &self,
+        lua: &Lua,
+        id: ThreadId,
+        function: LuaFunction,
+        args: &LuaMultiValue,
+    ) -> LuaResult<()> {
+        let capacity = self.capacity.get();
+        if capacity == 0 {
+            return Ok(());
+        }
+
+        let origin = ThreadOrigin {
+            id,
+            function: lua.create_registry_value(function)?,
+            args: lua.create_registry_value(args.clone().into_vec())?,
+        };
+
+        let mut origins = self.origins.borrow_mut();
+        origins.retain(|o| o.id != id);
+        if origins.len() >= capacity {
+            origins.pop_front();
+        }
+        origins.push_back(origin);
+        Ok(())
+    }
+
+    /**
+        Gets the function and arguments that the given thread was pushed with, if retained.
+    */
+    pub fn get<'lua>(
+        &self,
+        lua: &'lua Lua,
+        id: ThreadId,
+    ) -> LuaResult<Option<(LuaFunction<'lua>, LuaMultiValue<'lua>)>> {
+        let origins = self.origins.borrow();
+        let Some(origin) = origins.iter().find(|o| o.id == id) else {
+            return Ok(None);
+        };
+        let function = lua.registry_value(&origin.function)?;
+        let args = lua.registry_value::<Vec<LuaValue>>(&origin.args)?;
+        Ok(Some((function, LuaMultiValue::from_vec(args))))
+    }
+}
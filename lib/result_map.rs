@@ -1,51 +1,245 @@
 #![allow(clippy::inline_always)]
 
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, rc::Rc, sync::mpsc::Sender};
 
 use event_listener::Event;
+use mlua::prelude::*;
 // NOTE: This is the hash algorithm that mlua also uses, so we
 // are not adding any additional dependencies / bloat by using it.
-use rustc_hash::{FxHashMap, FxHashSet};
+use rustc_hash::FxHashMap;
 
-use crate::{thread_id::ThreadId, util::ThreadResult};
+use crate::{
+    error_history::ErrorHistory,
+    result_stream::CompletedThreads,
+    result_transform::{ResultTransform, ThreadResultTransform},
+    scoped_globals::ScopedGlobals,
+    thread_id::ThreadId,
+    tracking::{TrackedThreads, TrackingMode, TrackingStats},
+    util::ThreadResult,
+};
+
+pub(crate) type CompletionCallback =
+    Box<dyn for<'lua> FnOnce(&'lua Lua, &LuaResult<LuaMultiValue<'lua>>)>;
+
+pub(crate) type GlobalCompletionCallback =
+    Rc<dyn for<'lua> Fn(&'lua Lua, ThreadId, &LuaResult<LuaMultiValue<'lua>>)>;
+
+/**
+    A notification that a tracked Lua thread has completed, see [`Scheduler::set_completion_sender`].
+
+    [`Scheduler::set_completion_sender`]: crate::Scheduler::set_completion_sender
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadCompletion {
+    /// The id of the thread that completed.
+    pub id: ThreadId,
+    /// If the thread completed successfully, after any result transforms were applied.
+    pub success: bool,
+}
 
 #[derive(Clone)]
 pub(crate) struct ThreadResultMap {
-    tracked: Rc<RefCell<FxHashSet<ThreadId>>>,
-    results: Rc<RefCell<FxHashMap<ThreadId, ThreadResult>>>,
+    threads: Rc<RefCell<TrackedThreads>>,
     events: Rc<RefCell<FxHashMap<ThreadId, Rc<Event>>>>,
+    transform: ThreadResultTransform,
+    transforms: Rc<RefCell<FxHashMap<ThreadId, ResultTransform>>>,
+    history: ErrorHistory,
+    scoped_globals: ScopedGlobals,
+    completed: CompletedThreads,
+    callbacks: Rc<RefCell<FxHashMap<ThreadId, Vec<CompletionCallback>>>>,
+    global_callback: Rc<RefCell<Option<GlobalCompletionCallback>>>,
+    sender: Rc<RefCell<Option<Sender<ThreadCompletion>>>>,
 }
 
 impl ThreadResultMap {
     pub fn new() -> Self {
         Self {
-            tracked: Rc::new(RefCell::new(FxHashSet::default())),
-            results: Rc::new(RefCell::new(FxHashMap::default())),
+            threads: Rc::new(RefCell::new(TrackedThreads::new())),
             events: Rc::new(RefCell::new(FxHashMap::default())),
+            transform: ThreadResultTransform::new(),
+            transforms: Rc::new(RefCell::new(FxHashMap::default())),
+            history: ErrorHistory::new(),
+            scoped_globals: ScopedGlobals::new(),
+            completed: CompletedThreads::new(),
+            callbacks: Rc::new(RefCell::new(FxHashMap::default())),
+            global_callback: Rc::new(RefCell::new(None)),
+            sender: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn transform(&self) -> &ThreadResultTransform {
+        &self.transform
+    }
+
+    pub fn history(&self) -> &ErrorHistory {
+        &self.history
+    }
+
+    pub fn scoped_globals(&self) -> &ScopedGlobals {
+        &self.scoped_globals
+    }
+
+    pub fn completed(&self) -> &CompletedThreads {
+        &self.completed
+    }
+
+    pub fn tracking_mode(&self) -> TrackingMode {
+        self.threads.borrow().mode()
+    }
+
+    pub fn set_tracking_mode(&self, mode: TrackingMode) {
+        self.threads.borrow_mut().set_mode(mode);
+    }
+
+    pub fn tracking_stats(&self) -> TrackingStats {
+        self.threads.borrow().stats()
+    }
+
+    pub fn set_global_callback(&self, callback: Option<GlobalCompletionCallback>) {
+        self.global_callback.replace(callback);
+    }
+
+    fn call_global_callback(&self, lua: &Lua, id: ThreadId, result: &LuaResult<LuaMultiValue>) {
+        // NOTE: Must not hold the borrow while calling, the callback may be arbitrary code
+        let callback = self.global_callback.borrow().clone();
+        if let Some(callback) = callback {
+            callback(lua, id, result);
         }
     }
 
+    pub fn set_sender(&self, sender: Option<Sender<ThreadCompletion>>) {
+        self.sender.replace(sender);
+    }
+
+    pub fn set_thread_transform(&self, id: ThreadId, transform: ResultTransform) {
+        debug_assert!(self.is_tracked(id), "Thread must be tracked");
+        self.transforms.borrow_mut().insert(id, transform);
+    }
+
+    /**
+        Starts tracking the thread with the given id, and returns the id to use for it from now on.
+
+        See [`TrackedThreads::track_thread`] for how threads reusing an old address are given their own id.
+    */
+    pub fn track_thread(&self, id: ThreadId) -> ThreadId {
+        self.threads.borrow_mut().track_thread(id.base())
+    }
+
+    /**
+        Returns the id of the tracked thread currently running with the given id, taking its generation into account.
+
+        Must be used for any id created directly from a thread, before looking up its result.
+    */
     #[inline(always)]
-    pub fn track(&self, id: ThreadId) {
-        self.tracked.borrow_mut().insert(id);
+    pub fn current(&self, id: ThreadId) -> ThreadId {
+        self.threads.borrow().current(id)
     }
 
     #[inline(always)]
     pub fn is_tracked(&self, id: ThreadId) -> bool {
-        self.tracked.borrow().contains(&id)
+        self.threads.borrow().is_tracked(id)
+    }
+
+    #[inline(always)]
+    pub fn is_completed(&self, id: ThreadId) -> bool {
+        self.threads.borrow().is_completed(id)
     }
 
-    pub fn insert(&self, id: ThreadId, result: ThreadResult) {
+    pub fn insert(&self, lua: &Lua, id: ThreadId, result: LuaResult<LuaMultiValue>) {
         debug_assert!(self.is_tracked(id), "Thread must be tracked");
-        self.results.borrow_mut().insert(id, result);
+        // NOTE: Scoped globals are restored first, so that no
+        // transforms or callbacks can observe them afterwards
+        self.scoped_globals.restore(lua, id);
+        // NOTE: Errors are recorded before transforms run, so
+        // that transforms can not hide them from the history
+        if let Err(e) = &result {
+            self.history.record(lua, id, e);
+        }
+        // NOTE: Per-thread transforms take precedence over the default one,
+        // and we must not hold any borrows while calling into user code
+        let thread_transform = self.transforms.borrow_mut().remove(&id);
+        let result = match thread_transform {
+            Some(transform) => transform(lua, result),
+            None => self.transform.call(lua, result),
+        };
+        // NOTE: Callbacks are taken out before being called, so that they
+        // may freely call back into the scheduler, and only ever run once
+        let callbacks = self.callbacks.borrow_mut().remove(&id);
+        for callback in callbacks.into_iter().flatten() {
+            callback(lua, &result);
+        }
+        self.call_global_callback(lua, id, &result);
+        // NOTE: The receiver may have been dropped by the host at any
+        // point, at which point we simply stop sending notifications
+        let completion = ThreadCompletion {
+            id,
+            success: result.is_ok(),
+        };
+        let disconnected = self
+            .sender
+            .borrow()
+            .as_ref()
+            .is_some_and(|sender| sender.send(completion).is_err());
+        if disconnected {
+            self.sender.replace(None);
+        }
+        let result = ThreadResult::new(result, lua);
+        self.threads.borrow_mut().insert(id, result);
         if let Some(event) = self.events.borrow_mut().remove(&id) {
             event.notify(usize::MAX);
         }
+        self.completed.push(id);
+    }
+
+    /**
+        Completes the given thread, storing its result if it is tracked and has not yet completed.
+
+        Untracked threads have no result to store, but are still passed to the global completion callback,
+        and never overwrite the result of an old thread that they may have reused the address of.
+    */
+    pub fn complete(&self, lua: &Lua, id: ThreadId, result: LuaResult<LuaMultiValue>) {
+        let id = self.current(id);
+        if self.is_tracked(id) && !self.is_completed(id) {
+            self.insert(lua, id, result);
+        } else {
+            self.scoped_globals.restore(lua, id);
+            self.call_global_callback(lua, id, &result);
+        }
+    }
+
+    /**
+        Adds a callback to call with the result of the given thread, once it completes.
+
+        If the thread has already completed, the callback is called immediately instead.
+    */
+    pub fn add_callback(&self, lua: &Lua, id: ThreadId, callback: CompletionCallback) {
+        debug_assert!(self.is_tracked(id), "Thread must be tracked");
+        let result = self.threads.borrow().peek(lua, id);
+        match result {
+            Some(result) => callback(lua, &result),
+            None => self
+                .callbacks
+                .borrow_mut()
+                .entry(id)
+                .or_default()
+                .push(callback),
+        }
+    }
+
+    /**
+        Abandons the given thread, such as when an untracked thread was cancelled, meaning that it will never complete.
+
+        Removes all callbacks for the thread without calling them, and restores any scoped globals.
+    */
+    pub fn abandon(&self, lua: &Lua, id: ThreadId) {
+        let id = self.current(id);
+        self.callbacks.borrow_mut().remove(&id);
+        self.scoped_globals.restore(lua, id);
     }
 
     pub async fn listen(&self, id: ThreadId) {
         debug_assert!(self.is_tracked(id), "Thread must be tracked");
-        if !self.results.borrow().contains_key(&id) {
+        if !self.is_completed(id) {
             let listener = {
                 let mut events = self.events.borrow_mut();
                 let event = events.entry(id).or_insert_with(|| Rc::new(Event::new()));
@@ -55,10 +249,62 @@ impl ThreadResultMap {
         }
     }
 
+    /**
+        Returns a copy of the result of the given thread, if it has completed, without removing it.
+    */
+    pub fn peek<'lua>(
+        &self,
+        lua: &'lua Lua,
+        id: ThreadId,
+    ) -> Option<LuaResult<LuaMultiValue<'lua>>> {
+        self.threads.borrow().peek(lua, id)
+    }
+
     pub fn remove(&self, id: ThreadId) -> Option<ThreadResult> {
-        let res = self.results.borrow_mut().remove(&id)?;
-        self.tracked.borrow_mut().remove(&id);
+        let res = self.threads.borrow_mut().remove(id)?;
         self.events.borrow_mut().remove(&id);
+        self.transforms.borrow_mut().remove(&id);
+        self.callbacks.borrow_mut().remove(&id);
         Some(res)
     }
+
+    /**
+        Stops tracking the given thread, such as when nothing can observe its result anymore.
+
+        Removes its result if it has completed, otherwise it is discarded once the thread completes.
+    */
+    pub fn untrack(&self, id: ThreadId) {
+        self.threads.borrow_mut().untrack(id);
+        self.events.borrow_mut().remove(&id);
+        self.transforms.borrow_mut().remove(&id);
+        self.callbacks.borrow_mut().remove(&id);
+    }
+
+    /**
+        Removes all tracked threads, results, transforms and callbacks, as well as the error history.
+
+        The default result transform is kept, since it is not specific to any thread.
+    */
+    pub fn clear(&self) {
+        self.threads.borrow_mut().clear();
+        self.events.borrow_mut().clear();
+        self.transforms.borrow_mut().clear();
+        self.callbacks.borrow_mut().clear();
+        self.completed.clear();
+        self.history.clear();
+    }
+
+    /**
+        Returns all tracked threads that have not yet completed.
+    */
+    pub fn unfinished(&self) -> Vec<ThreadId> {
+        self.threads.borrow().unfinished()
+    }
+
+    /**
+        Returns the number of registry values held by results that have not yet been claimed.
+    */
+    pub fn unclaimed(&self) -> usize {
+        self.threads.borrow().unclaimed()
+    }
 }
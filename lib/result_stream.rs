@@ -0,0 +1,102 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    rc::Rc,
+};
+
+use event_listener::Event;
+
+use crate::thread_id::ThreadId;
+
+/**
+    Tracked threads that have completed, waiting to be taken by a result stream.
+
+    Threads are only queued while at least one stream is alive, so that
+    completed threads do not pile up when nobody is listening for them.
+
+    See [`Scheduler::results_stream`] for more information.
+
+    [`Scheduler::results_stream`]: crate::Scheduler::results_stream
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct CompletedThreads {
+    queue: Rc<RefCell<VecDeque<ThreadId>>>,
+    event: Rc<Event>,
+    streams: Rc<Cell<usize>>,
+}
+
+impl CompletedThreads {
+    pub fn new() -> Self {
+        Self {
+            queue: Rc::new(RefCell::new(VecDeque::new())),
+            event: Rc::new(Event::new()),
+            streams: Rc::new(Cell::new(0)),
+        }
+    }
+
+    /**
+        Queues the given thread, if any stream is currently alive.
+    */
+    pub fn push(&self, id: ThreadId) {
+        if self.streams.get() > 0 {
+            self.queue.borrow_mut().push_back(id);
+            self.event.notify(usize::MAX);
+        }
+    }
+
+    /**
+        Waits for and takes the next completed thread.
+    */
+    pub async fn next(&self) -> ThreadId {
+        loop {
+            if let Some(id) = self.queue.borrow_mut().pop_front() {
+                return id;
+            }
+            // NOTE: We must listen before checking again, or we
+            // may miss a notification that happened in between
+            let listener = self.event.listen();
+            if let Some(id) = self.queue.borrow_mut().pop_front() {
+                return id;
+            }
+            listener.await;
+        }
+    }
+
+    pub fn clear(&self) {
+        self.queue.borrow_mut().clear();
+    }
+
+    /**
+        Registers a new stream, which is unregistered once the returned guard is dropped.
+    */
+    pub fn register(&self) -> StreamGuard {
+        self.streams.set(self.streams.get() + 1);
+        StreamGuard {
+            completed: self.clone(),
+        }
+    }
+}
+
+/**
+    Keeps completed threads queued for as long as it is alive.
+*/
+#[derive(Debug)]
+pub(crate) struct StreamGuard {
+    completed: CompletedThreads,
+}
+
+impl StreamGuard {
+    pub fn completed(&self) -> &CompletedThreads {
+        &self.completed
+    }
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        let streams = self.completed.streams.get() - 1;
+        self.completed.streams.set(streams);
+        if streams == 0 {
+            self.completed.clear();
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::prelude::*;
+
+pub(crate) type ResultTransform = Box<
+    dyn for<'lua> Fn(&'lua Lua, LuaResult<LuaMultiValue<'lua>>) -> LuaResult<LuaMultiValue<'lua>>,
+>;
+
+#[derive(Clone)]
+pub(crate) struct ThreadResultTransform {
+    inner: Rc<RefCell<Option<ResultTransform>>>,
+}
+
+impl ThreadResultTransform {
+    pub fn new() -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn replace(
+        &self,
+        transform: impl for<'lua> Fn(
+                &'lua Lua,
+                LuaResult<LuaMultiValue<'lua>>,
+            ) -> LuaResult<LuaMultiValue<'lua>>
+            + 'static,
+    ) {
+        self.inner.borrow_mut().replace(Box::new(transform));
+    }
+
+    pub fn clear(&self) {
+        self.inner.borrow_mut().take();
+    }
+
+    pub fn call<'lua>(
+        &self,
+        lua: &'lua Lua,
+        result: LuaResult<LuaMultiValue<'lua>>,
+    ) -> LuaResult<LuaMultiValue<'lua>> {
+        match &*self.inner.borrow() {
+            Some(transform) => transform(lua, result),
+            None => result,
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::prelude::*;
+
+use crate::thread_id::ThreadId;
+
+type BeforeResume = Box<dyn Fn(ThreadId)>;
+type AfterResume = Box<dyn for<'lua> Fn(ThreadId, &LuaResult<LuaMultiValue<'lua>>)>;
+
+/**
+    Hooks called around every resumption of a Lua thread performed by a scheduler.
+
+    Stored in app data, so that resumptions from the `spawn` and `resume` functions
+    given to Lua are also wrapped, not only resumptions from the scheduler queues.
+*/
+#[derive(Clone)]
+pub(crate) struct ResumeHooks {
+    before: Rc<RefCell<Option<BeforeResume>>>,
+    after: Rc<RefCell<Option<AfterResume>>>,
+}
+
+impl ResumeHooks {
+    pub fn new() -> Self {
+        Self {
+            before: Rc::new(RefCell::new(None)),
+            after: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn replace(
+        &self,
+        before: impl Fn(ThreadId) + 'static,
+        after: impl for<'lua> Fn(ThreadId, &LuaResult<LuaMultiValue<'lua>>) + 'static,
+    ) {
+        self.before.borrow_mut().replace(Box::new(before));
+        self.after.borrow_mut().replace(Box::new(after));
+    }
+
+    pub fn clear(&self) {
+        self.before.borrow_mut().take();
+        self.after.borrow_mut().take();
+    }
+
+    #[inline]
+    pub fn before(&self, id: ThreadId) {
+        if let Some(hook) = &*self.before.borrow() {
+            hook(id);
+        }
+    }
+
+    #[inline]
+    pub fn after(&self, id: ThreadId, result: &LuaResult<LuaMultiValue>) {
+        if let Some(hook) = &*self.after.borrow() {
+            hook(id, result);
+        }
+    }
+}
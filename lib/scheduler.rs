@@ -1,28 +1,88 @@
 #![allow(clippy::module_name_repetitions)]
 
 use std::{
-    cell::Cell,
+    cell::{Cell, RefCell},
+    future,
+    pin::pin,
     process::ExitCode,
     rc::{Rc, Weak as WeakRc},
-    sync::{Arc, Weak as WeakArc},
+    sync::mpsc::Sender,
     thread::panicking,
+    time::{Duration, Instant},
 };
 
-use futures_lite::prelude::*;
+use futures_lite::{prelude::*, stream};
 use mlua::prelude::*;
+#[cfg(feature = "unstable")]
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{de::DeserializeOwned, Serialize};
 
-use async_executor::{Executor, LocalExecutor};
+use async_executor::LocalExecutor;
 use tracing::{debug, instrument, trace, trace_span, Instrument};
 
+#[cfg(feature = "tokio")]
+use crate::companion::TokioCompanion;
 use crate::{
-    error_callback::ThreadErrorCallback,
-    exit::Exit,
-    queue::{DeferredThreadQueue, FuturesQueue, SpawnedThreadQueue},
-    result_map::ThreadResultMap,
+    awaiting::AwaitingThreads,
+    cancel::Cancellation,
+    checkpoint::{Checkpoint, Checkpoints},
+    chunk::{ChunkOptions, DefaultChunkOptions},
+    clock::{Clock, TimerPrecision, TimerStats},
+    config::SchedulerConfig,
+    cycle::Cycles,
+    deadline::Deadlines,
+    delay::DelayedThreads,
+    diagnostics::{Diagnostics, LongPoll},
+    drain::{Drain, DrainStatus},
+    error_callback::{ThreadErrorCallback, UncaughtError},
+    error_history::ErrorRecord,
+    error_value::ErrorValues,
+    event_source::{Backpressure, EventSource},
+    executor::MainExecutor,
+    exit::{Exit, ExitMode, ExitReason, ExitWatch},
+    handle::ThreadHandle,
+    idle::{IdleQueue, IdleStats},
+    inject::Injections,
+    jobs::Jobs,
+    keep_alive::{KeepAlive, KeepAlives},
+    leaks::{LeakDetector, LeakReport},
+    locals::TaskLocals,
+    location::{Location, ThreadLocations},
+    names::ThreadNames,
+    native::NativeAsyncQueue,
+    output::{Output, OutputSink},
+    preempt::Preemption,
+    pressure::{PressureMonitor, QueuePressure},
+    primitives::Primitives,
+    queue::{DeferredThreadQueue, FuturesQueue, Priority, SpawnedThreadQueue, ThreadQueue},
+    remote::{RemoteQueue, SchedulerHandle},
+    respawn::ThreadOrigins,
+    result_map::{ThreadCompletion, ThreadResultMap},
+    resume_hooks::ResumeHooks,
+    scoped_globals::ScopedGlobals,
+    stats::{SchedulerStats, Stats},
     status::Status,
-    thread_id::ThreadId,
+    step::{SchedulerStepper, Steps},
+    strict::StrictMode,
+    supervisor::{RestartEvent, RestartOptions, Supervisor},
+    suspend::SuspendedThreads,
+    tags::ThreadTags,
+    thread_id::{ScopedThreadId, ThreadId},
+    thread_info::{ThreadInfo, ThreadRecords},
+    thread_span::ThreadSpans,
+    tick::Ticks,
+    tracking::{TrackingMode, TrackingStats},
     traits::IntoLuaThread,
-    util::{run_until_yield, ThreadResult},
+    util::{instant_after, run_until_yield, LazyArgs},
+    value_log::{ValueKind, ValueLog, ValueRecord},
+    wakeups::{WakeupStats, Wakeups},
+    watchdog::{StuckTask, Watchdog, WatchdogPolicy},
+    yield_handler::ThreadYieldHandler,
+};
+#[cfg(feature = "unstable")]
+use crate::{
+    remote::RemoteWork,
+    unstable::{Plugins, SchedulerPlugin, ThreadEvent, ThreadSnapshot, ThreadState},
 };
 
 const ERR_METADATA_ALREADY_ATTACHED: &str = "\
@@ -40,18 +100,88 @@ const ERR_SET_CALLBACK_WHEN_RUNNING: &str = "\
 Cannot set error callback when scheduler is running!\
 ";
 
+const ERR_SET_TRANSFORM_WHEN_RUNNING: &str = "\
+Cannot set result transform when scheduler is running!\
+";
+
+const ERR_SET_MODE_WHEN_RUNNING: &str = "\
+Cannot change scheduler mode when scheduler is running!\
+";
+
+const ERR_THREAD_NOT_TRACKED: &str = "\
+Thread is not being tracked by the scheduler!\
+\nOnly threads pushed to the scheduler can have their results transformed or observed.\
+";
+
+const ERR_NOT_RESPAWNABLE: &str = "\
+Thread can not be respawned, since its origin was not retained!\
+\nOnly functions and chunks pushed to the scheduler while retention is enabled can be respawned.\
+";
+
 /**
     A scheduler for running Lua threads and async tasks.
+
+    Cloning a scheduler is cheap, and all clones share the same state. The scheduler
+    stays attached to its Lua state until the last of its clones has been dropped.
 */
 #[derive(Clone)]
 pub struct Scheduler<'lua> {
     lua: &'lua Lua,
+    queue_high: ThreadQueue,
     queue_spawn: SpawnedThreadQueue,
     queue_defer: DeferredThreadQueue,
+    ticks: Ticks,
+    idle: IdleQueue,
+    native: NativeAsyncQueue,
+    clock: Clock,
+    watchdog: Watchdog,
+    awaiting: AwaitingThreads,
+    preemption: Preemption,
+    supervisor: Supervisor,
+    pressure: PressureMonitor,
+    diagnostics: Diagnostics,
+    leaks: LeakDetector,
+    jobs: Jobs,
+    drain: Drain,
+    tags: ThreadTags,
+    names: ThreadNames,
+    spans: ThreadSpans,
+    stats: Stats,
+    records: ThreadRecords,
+    primitives: Primitives,
+    checkpoints: Checkpoints,
     error_callback: ThreadErrorCallback,
     result_map: ThreadResultMap,
+    yield_handler: ThreadYieldHandler,
+    resume_hooks: ResumeHooks,
+    wakeups: Wakeups,
+    value_log: ValueLog,
+    origins: ThreadOrigins,
+    chunk_options: DefaultChunkOptions,
+    #[cfg(feature = "unstable")]
+    plugins: Plugins,
+    strict: StrictMode,
+    error_values: ErrorValues,
+    suspended: SuspendedThreads,
+    injections: Injections,
+    deadlines: Deadlines,
+    delayed: DelayedThreads,
+    cancellation: Cancellation,
+    locals: TaskLocals,
+    cycles: Cycles,
+    output: Output,
+    instances: Rc<()>,
     status: Rc<Cell<Status>>,
+    deterministic: Rc<Cell<bool>>,
+    single_threaded: Rc<Cell<bool>>,
+    max_spawned: Rc<Cell<Option<usize>>>,
     exit: Exit,
+    exit_watch: ExitWatch,
+    keep_alives: KeepAlives,
+    steps: Steps,
+    remote: RemoteQueue,
+    #[cfg(feature = "tokio")]
+    tokio: TokioCompanion,
 }
 
 impl<'lua> Scheduler<'lua> {
@@ -65,12 +195,62 @@ impl<'lua> Scheduler<'lua> {
         Panics if the given Lua state already has a scheduler attached to it.
     */
     #[must_use]
+    #[allow(clippy::too_many_lines)]
     pub fn new(lua: &'lua Lua) -> Scheduler<'lua> {
-        let queue_spawn = SpawnedThreadQueue::new();
-        let queue_defer = DeferredThreadQueue::new();
+        let locations = ThreadLocations::new();
+        let queue_spawn = SpawnedThreadQueue::new(&locations);
+        let queue_defer = DeferredThreadQueue::new(&locations);
+        let ticks = Ticks::new(&locations);
+        let idle = IdleQueue::new(&locations);
+        let awaiting = AwaitingThreads::new();
+        let preemption = Preemption::new();
+        let tags = ThreadTags::new();
+        let names = ThreadNames::new();
+        let counters = Stats::new();
+        let resume_hooks = ResumeHooks::new();
+        let records = ThreadRecords::new();
+        let checkpoints = Checkpoints::new(tags.clone());
+        let clock = Clock::new();
+        let watchdog = Watchdog::new(clock.clone());
+        let native = NativeAsyncQueue::new(watchdog.clone());
+        let supervisor = Supervisor::new(tags.clone(), checkpoints.clone(), clock.clone());
+        let pressure = PressureMonitor::new();
+        let diagnostics = Diagnostics::new();
+        let leaks = LeakDetector::new();
+        let jobs = Jobs::new();
+        let drain = Drain::new();
         let error_callback = ThreadErrorCallback::default();
         let result_map = ThreadResultMap::new();
         let exit = Exit::new();
+        let primitives = Primitives::capture(lua);
+        let spans = ThreadSpans::new(names.clone(), primitives.clone());
+        let strict = StrictMode::new();
+        let error_values = ErrorValues::new(lua);
+        let suspended = SuspendedThreads::new();
+        let injections = Injections::new();
+        let deadlines = Deadlines::new();
+        let delayed = DelayedThreads::new(&locations);
+        let queue_high = ThreadQueue::new(&locations, Location::High);
+        let cancellation = Cancellation::new(
+            [
+                queue_high.clone(),
+                (*queue_spawn).clone(),
+                (*queue_defer).clone(),
+            ],
+            idle.clone(),
+            ticks.clone(),
+            delayed.clone(),
+            native.clone(),
+            suspended.clone(),
+            result_map.clone(),
+            counters.clone(),
+            primitives.clone(),
+        );
+        let locals = TaskLocals::new();
+        let cycles = Cycles::new();
+        let output = Output::new();
+        #[cfg(feature = "tokio")]
+        let tokio = TokioCompanion::new();
 
         assert!(
             lua.app_data_ref::<SpawnedThreadQueue>().is_none(),
@@ -80,6 +260,22 @@ impl<'lua> Scheduler<'lua> {
             lua.app_data_ref::<DeferredThreadQueue>().is_none(),
             "{ERR_METADATA_ALREADY_ATTACHED}"
         );
+        assert!(
+            lua.app_data_ref::<Ticks>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<IdleQueue>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<NativeAsyncQueue>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<Preemption>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
         assert!(
             lua.app_data_ref::<ThreadErrorCallback>().is_none(),
             "{ERR_METADATA_ALREADY_ATTACHED}"
@@ -92,26 +288,259 @@ impl<'lua> Scheduler<'lua> {
             lua.app_data_ref::<Exit>().is_none(),
             "{ERR_METADATA_ALREADY_ATTACHED}"
         );
+        assert!(
+            lua.app_data_ref::<Primitives>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<Checkpoints>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<ThreadTags>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<ThreadNames>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<Stats>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<ResumeHooks>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<Diagnostics>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<Drain>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<ThreadRecords>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<Jobs>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<AwaitingThreads>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<Clock>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<Watchdog>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<StrictMode>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<ErrorValues>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<SuspendedThreads>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<Injections>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<Deadlines>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<DelayedThreads>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<ThreadLocations>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<Cancellation>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<TaskLocals>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<Cycles>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        assert!(
+            lua.app_data_ref::<Output>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
+        #[cfg(feature = "tokio")]
+        assert!(
+            lua.app_data_ref::<TokioCompanion>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
 
         lua.set_app_data(queue_spawn.clone());
         lua.set_app_data(queue_defer.clone());
+        lua.set_app_data(ticks.clone());
+        lua.set_app_data(idle.clone());
+        lua.set_app_data(native.clone());
+        lua.set_app_data(preemption.clone());
         lua.set_app_data(error_callback.clone());
         lua.set_app_data(result_map.clone());
         lua.set_app_data(exit.clone());
+        lua.set_app_data(primitives.clone());
+        lua.set_app_data(checkpoints.clone());
+        lua.set_app_data(tags.clone());
+        lua.set_app_data(names.clone());
+        lua.set_app_data(counters.clone());
+        lua.set_app_data(resume_hooks.clone());
+        lua.set_app_data(diagnostics.clone());
+        lua.set_app_data(drain.clone());
+        lua.set_app_data(records.clone());
+        lua.set_app_data(jobs.clone());
+        lua.set_app_data(awaiting.clone());
+        lua.set_app_data(clock.clone());
+        lua.set_app_data(watchdog.clone());
+        lua.set_app_data(strict.clone());
+        lua.set_app_data(error_values.clone());
+        lua.set_app_data(suspended.clone());
+        lua.set_app_data(injections.clone());
+        lua.set_app_data(deadlines.clone());
+        lua.set_app_data(delayed.clone());
+        lua.set_app_data(locations.clone());
+        lua.set_app_data(cancellation.clone());
+        lua.set_app_data(locals.clone());
+        lua.set_app_data(cycles.clone());
+        lua.set_app_data(output.clone());
+        #[cfg(feature = "tokio")]
+        lua.set_app_data(tokio.clone());
 
         let status = Rc::new(Cell::new(Status::NotStarted));
+        let deterministic = Rc::new(Cell::new(false));
 
         Scheduler {
             lua,
+            queue_high,
             queue_spawn,
             queue_defer,
+            ticks,
+            idle,
+            native,
+            clock,
+            watchdog,
+            awaiting,
+            preemption,
+            supervisor,
+            pressure,
+            diagnostics,
+            leaks,
+            jobs,
+            drain,
+            tags,
+            names,
+            spans,
+            stats: counters,
+            records,
+            primitives,
+            checkpoints,
             error_callback,
             result_map,
+            yield_handler: ThreadYieldHandler::new(),
+            resume_hooks,
+            wakeups: Wakeups::new(),
+            value_log: ValueLog::new(),
+            origins: ThreadOrigins::new(),
+            chunk_options: DefaultChunkOptions::new(),
+            #[cfg(feature = "unstable")]
+            plugins: Plugins::new(),
+            strict,
+            error_values,
+            suspended,
+            injections,
+            deadlines,
+            delayed,
+            cancellation,
+            locals,
+            cycles,
+            output,
+            instances: Rc::new(()),
             status,
+            deterministic,
+            single_threaded: Rc::new(Cell::new(false)),
+            max_spawned: Rc::new(Cell::new(None)),
             exit,
+            exit_watch: ExitWatch::new(),
+            keep_alives: KeepAlives::new(),
+            steps: Steps::new(),
+            remote: RemoteQueue::new(),
+            #[cfg(feature = "tokio")]
+            tokio,
         }
     }
 
+    /**
+        Enables or disables strict mode for this scheduler.
+
+        In strict mode, the scheduler detects common misuse of its API,
+        and returns descriptive errors with hints, instead of silently
+        doing nothing or panicking. This includes, but is not limited to:
+
+        - Pushing threads created by a different Lua state
+        - Pushing, spawning, or deferring threads that have completed or were cancelled
+        - Resuming cancelled threads using [`Functions::resume`]
+        - Getting results for threads that are not tracked using [`Scheduler::get_thread_result`]
+        - Calling [`Functions`] that start async work while the scheduler is not running
+
+        Strict mode has a small performance cost, and is mostly intended for use during development.
+
+        [`Functions`]: crate::Functions
+        [`Functions::resume`]: crate::Functions::resume
+    */
+    pub fn set_strict(&self, strict: bool) {
+        self.strict.set_enabled(strict);
+    }
+
+    /**
+        Returns `true` if this scheduler is in strict mode.
+
+        See [`Scheduler::set_strict`] for more information.
+    */
+    #[must_use]
+    pub fn is_strict(&self) -> bool {
+        self.strict.is_enabled()
+    }
+
+    /**
+        Adds the given plugin to this scheduler, calling its [`SchedulerPlugin::on_init`] hook.
+
+        See [`SchedulerPlugin`] for more information.
+
+        # Errors
+
+        Errors if the [`SchedulerPlugin::on_init`] hook of the plugin errors.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    #[cfg(feature = "unstable")]
+    pub fn with_plugin(self, plugin: impl SchedulerPlugin + 'static) -> LuaResult<Self> {
+        assert!(!self.status().is_running(), "{ERR_SET_MODE_WHEN_RUNNING}");
+        self.plugins.add(self.lua, plugin)?;
+        Ok(self)
+    }
+
     /**
         Sets the current status of this scheduler and emits relevant tracing events.
     */
@@ -129,140 +558,2609 @@ impl<'lua> Scheduler<'lua> {
     }
 
     /**
-        Sets the error callback for this scheduler.
+        Enables or disables deterministic mode for this scheduler.
+
+        In deterministic mode, the scheduler guarantees that given identical inputs
+        (the same pushed threads, and the same values returned from async functions, in the
+        same order) it will always make identical scheduling decisions, on any machine:
+
+        - Spawned threads are always resumed before deferred threads, in the order they were pushed
+        - Deferred threads are always resumed in the order they were pushed
+        - Threads whose deadlines pass at the same time are cancelled in the order their deadlines were set
+        - Futures spawned using [`LuaSpawnExt::spawn`] always run on the same thread as the scheduler,
+          and are polled in the order they were woken up, the same as in single-threaded mode
+
+        Deterministic mode overrides [`Scheduler::set_single_threaded`], since the multi-threaded
+        executor does not poll futures in a fixed order, and may pick which ones to poll at random.
+        The scheduler itself never makes any randomized choices, so there is no seed to configure.
+
+        Note that the timing of async functions themselves, such as timers
+        or I/O, is outside of the scheduler's control and not covered by this.
+
+        [`LuaSpawnExt::spawn`]: crate::LuaSpawnExt::spawn
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn set_deterministic(&self, deterministic: bool) {
+        assert!(!self.status().is_running(), "{ERR_SET_MODE_WHEN_RUNNING}");
+        self.deterministic.set(deterministic);
+    }
+
+    /**
+        Returns `true` if this scheduler is in deterministic mode.
+
+        See [`Scheduler::set_deterministic`] for more information.
+    */
+    #[must_use]
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic.get()
+    }
+
+    /**
+        Enables or disables single-threaded mode for this scheduler.
+
+        By default, futures spawned using [`LuaSpawnExt::spawn`] run on a multi-threaded
+        [`Executor`], which needs atomics and reference counting that is safe to share across
+        threads. Embedders that run everything on a single thread anyway may enable this mode
+        to use only a [`LocalExecutor`] instead, which avoids some of that overhead when
+        spawning lots of futures. Spawned futures still run on the same thread either way.
+
+        With the `local-spawn` feature enabled, the [`Send`] bound is also relaxed for futures
+        spawned using [`LuaSpawnExt::spawn`], which then always run on a [`LocalExecutor`].
+
+        [`LuaSpawnExt::spawn`]: crate::LuaSpawnExt::spawn
+        [`Executor`]: async_executor::Executor
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn set_single_threaded(&self, single_threaded: bool) {
+        assert!(!self.status().is_running(), "{ERR_SET_MODE_WHEN_RUNNING}");
+        self.single_threaded.set(single_threaded);
+    }
+
+    /**
+        Returns `true` if this scheduler is in single-threaded mode.
+
+        See [`Scheduler::set_single_threaded`] for more information.
+    */
+    #[must_use]
+    pub fn is_single_threaded(&self) -> bool {
+        self.single_threaded.get()
+    }
+
+    /**
+        Sets the maximum number of spawned threads that are resumed per scheduler cycle.
+
+        By default, the spawned queue is always drained fully before the deferred queue, so
+        a flood of spawned threads delays deferred threads until the flood is over. With a
+        limit set, any spawned threads over the limit stay queued for the next cycle, and the
+        deferred queue is still drained every cycle, guaranteeing that deferred work makes
+        progress. Spawned threads are still resumed in the order they were pushed.
+
+        Note that with a limit set, deferred threads may be resumed before spawned threads that
+        were pushed earlier, even in deterministic mode, although the order stays deterministic.
+
+        Setting the limit to `None` removes it.
+    */
+    pub fn set_max_spawned_per_cycle(&self, max: Option<usize>) {
+        self.max_spawned.set(max);
+    }
+
+    /**
+        Returns the maximum number of spawned threads that are resumed per scheduler cycle, if any.
+
+        See [`Scheduler::set_max_spawned_per_cycle`] for more information.
+    */
+    #[must_use]
+    pub fn max_spawned_per_cycle(&self) -> Option<usize> {
+        self.max_spawned.get()
+    }
+
+    /**
+        Sets the capacity of the spawned, deferred, and high priority queues of this scheduler.
+
+        By default, queues are unbounded, and a script that spawns or defers threads in a loop may
+        use up all available memory. With a capacity set, each of these queues holds at most that
+        many threads, and pushing to a full queue errors with [`QueueFull`] instead - both from
+        Rust, and from Lua, where the error is raised in the script that tried to push.
+
+        Threads that were already running, and are being re-queued by the scheduler itself,
+        such as automatically yielded threads, are never rejected - threads spawned or resumed
+        from Lua are instead rejected before they start running. To wait for space instead
+        of erroring, use [`Scheduler::push_thread_front_async`] or [`Scheduler::push_thread_back_async`].
+
+        Setting the capacity to `None` removes it.
+
+        [`QueueFull`]: crate::QueueFull
+    */
+    pub fn set_queue_capacity(&self, capacity: Option<usize>) {
+        self.queue_high.set_capacity(capacity);
+        self.queue_spawn.set_capacity(capacity);
+        self.queue_defer.set_capacity(capacity);
+    }
+
+    /**
+        Returns the capacity of the queues of this scheduler, if one is set.
+
+        See [`Scheduler::set_queue_capacity`] for more information.
+    */
+    #[must_use]
+    pub fn queue_capacity(&self) -> Option<usize> {
+        self.queue_spawn.capacity()
+    }
+
+    /**
+        Sets the timer precision for this scheduler.
+
+        This affects all sleeping done by the scheduler and its builtins, such as
+        [`LuaSchedulerExt::sleep`], supervisor backoff, `debounce`, and `throttle`.
+
+        Timers on some platforms, notably Windows, have a low resolution, making short sleeps
+        resume late and in bursts. Using [`TimerPrecision::Hybrid`] avoids this by busy-waiting
+        for the last part of each sleep, at the cost of some CPU usage, and
+        [`TimerPrecision::platform_hybrid`] picks a threshold suited to the current platform.
+
+        [`LuaSchedulerExt::sleep`]: crate::LuaSchedulerExt::sleep
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn set_timer_precision(&self, precision: TimerPrecision) {
+        assert!(!self.status().is_running(), "{ERR_SET_MODE_WHEN_RUNNING}");
+        self.clock.set_precision(precision);
+    }
+
+    /**
+        Sets the timer coalescing resolution for this scheduler, or disables coalescing if given zero.
+
+        With coalescing enabled, the deadline of every sleep is rounded up to the next multiple
+        of the given resolution, so that many sleeps with nearly-equal deadlines - such as mass
+        `wait()` calls from Lua - share a single wakeup, and resume during the same cycle. Sleeps
+        will never resume early, but may resume up to one resolution later than requested.
+
+        Like [`Scheduler::set_timer_precision`], this affects all sleeping done by the scheduler.
+        See [`Scheduler::timer_stats`] for measuring the effect of coalescing when tuning it.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn set_timer_coalescing(&self, resolution: Duration) {
+        assert!(!self.status().is_running(), "{ERR_SET_MODE_WHEN_RUNNING}");
+        self.clock.set_coalescing(resolution);
+    }
+
+    /**
+        Returns statistics about sleeps coalesced by this scheduler.
+
+        See [`TimerStats`] and [`Scheduler::set_timer_coalescing`] for more information.
+    */
+    #[must_use]
+    pub fn timer_stats(&self) -> TimerStats {
+        self.clock.stats()
+    }
+
+    /**
+        Returns the number of sleeps that are currently pending in this scheduler.
+
+        This includes all sleeps started through the clock of the scheduler, such as
+        `task.wait` from Lua, as well as [`LuaSchedulerExt::sleep`] and
+        [`LuaSchedulerExt::timeout`] from async functions, and every thread
+        delayed using `task.delay` or [`Scheduler::push_thread_delayed`].
+
+        Timers that the scheduler uses internally, such as for thread deadlines,
+        are not included, since they never resume anything on their own.
+
+        [`LuaSchedulerExt::sleep`]: crate::LuaSchedulerExt::sleep
+        [`LuaSchedulerExt::timeout`]: crate::LuaSchedulerExt::timeout
+    */
+    #[must_use]
+    pub fn pending_sleeps(&self) -> usize {
+        self.clock.pending_sleeps() + self.delayed.len()
+    }
+
+    /**
+        Returns how long it is until the earliest pending sleep in this scheduler wakes up.
+
+        Same as for [`Scheduler::pending_sleeps`], this includes delayed threads.
+
+        Returns `None` if there are no pending sleeps, and a zero duration if
+        the earliest sleep is already past its deadline, but has not yet resumed.
+    */
+    #[must_use]
+    pub fn next_sleep_wakeup(&self) -> Option<Duration> {
+        let next = match (self.clock.next_deadline(), self.delayed.next_due()) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        next.map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /**
+        Sets how this scheduler stores tracked threads and their results.
+
+        Every thread pushed to the scheduler is tracked until its result is taken using
+        [`Scheduler::get_thread_result`], and with hundreds of thousands of tracked threads,
+        storing them can use a significant amount of memory. [`TrackingMode::Compact`] stores
+        results in a fraction of the space, and releases memory as results are taken, so that
+        memory usage follows the number of tracked threads instead of its peak.
+
+        Any threads that are already tracked are kept, and converted to the new mode.
+        See [`Scheduler::tracking_stats`] for measuring memory usage in either mode.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn set_tracking_mode(&self, mode: TrackingMode) {
+        assert!(!self.status().is_running(), "{ERR_SET_MODE_WHEN_RUNNING}");
+        self.result_map.set_tracking_mode(mode);
+    }
+
+    /**
+        Returns how this scheduler stores tracked threads and their results.
+
+        See [`Scheduler::set_tracking_mode`] for more information.
+    */
+    #[must_use]
+    pub fn tracking_mode(&self) -> TrackingMode {
+        self.result_map.tracking_mode()
+    }
+
+    /**
+        Returns statistics about the threads tracked by this scheduler, including their estimated memory usage.
+
+        See [`TrackingStats`] and [`Scheduler::set_tracking_mode`] for more information.
+    */
+    #[must_use]
+    pub fn tracking_stats(&self) -> TrackingStats {
+        self.result_map.tracking_stats()
+    }
+
+    /**
+        Returns a snapshot of the effective configuration of this scheduler.
+
+        This is useful for logging the settings a scheduler is operating with, and
+        is also emitted as a `tracing` debug event whenever the scheduler starts running.
+
+        Note that the snapshot does not update if the configuration changes afterwards.
+    */
+    #[must_use]
+    pub fn config(&self) -> SchedulerConfig {
+        SchedulerConfig {
+            deterministic: self.is_deterministic(),
+            single_threaded: self.is_single_threaded(),
+            max_spawned_per_cycle: self.max_spawned_per_cycle(),
+            queue_capacity: self.queue_capacity(),
+            yield_budget: self.preemption.default_budget(),
+            yield_budgets: self.preemption.budget_count(),
+            thread_budget: self.preemption.thread_budget(),
+            watchdog: self.watchdog.config(),
+            long_poll_threshold: self.diagnostics.long_poll_threshold(),
+            queue_pressure_thresholds: self.pressure.thresholds(),
+            error_retention: self.result_map.history().capacity(),
+            value_log_capacity: self.value_log.capacity(),
+            respawn_retention: self.origins.capacity(),
+            chunk_options: self.chunk_options.get(),
+            timer_precision: self.clock.precision(),
+            timer_coalescing: self.clock.coalescing(),
+            tracking_mode: self.tracking_mode(),
+            strict: self.is_strict(),
+        }
+    }
+
+    /**
+        Sets the error callback for this scheduler.
+
+        This callback will be called whenever a Lua thread errors, with an [`UncaughtError`]
+        containing the original error along with the id, name and tag of the thread, so that
+        errors can be attributed to specific jobs, even when many of them run concurrently.
+
+        Overwrites any previous error callback.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn set_error_callback(&self, callback: impl Fn(UncaughtError) + Send + 'static) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.error_callback.replace(callback);
+    }
+
+    /**
+        Clears the error callback for this scheduler.
+
+        This will remove any current error callback, including default(s).
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn remove_error_callback(&self) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.error_callback.clear();
+    }
+
+    /**
+        Sets the output sink for this scheduler.
+
+        Output written using [`Functions::print`] and [`Functions::warn`], which may be injected
+        as the `print` and `warn` globals using [`Functions::inject_output`], is sent to the sink
+        along with the id of the thread that wrote it, its level, and a timestamp, making it
+        possible for hosts running many scripts at once to untangle interleaved output.
+
+        While a sink is set, uncaught errors in Lua threads are also written to it, as
+        [`OutputLevel::Error`] records, before being passed to the error callback. Errors
+        handled by a Lua error handler are not written. Without a sink, output is written
+        to stdout, and warnings to stderr.
+
+        Overwrites any previous output sink.
+
+        [`Functions::print`]: crate::Functions::print
+        [`Functions::warn`]: crate::Functions::warn
+        [`Functions::inject_output`]: crate::Functions::inject_output
+        [`OutputLevel::Error`]: crate::OutputLevel::Error
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn set_output_sink(&self, sink: impl OutputSink + 'static) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.output.replace(sink);
+    }
+
+    /**
+        Removes the output sink for this scheduler, writing output to stdout and stderr again.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn remove_output_sink(&self) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.output.clear();
+    }
+
+    /**
+        Sets the result transform for this scheduler.
+
+        This transform will be called with the final result of every tracked
+        Lua thread, before the result is stored and made available through
+        [`Scheduler::get_thread_result`]. It may be used to, for example,
+        convert errors into structured values, or trim large return values.
+
+        Overwrites any previous result transform.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn set_result_transform(
+        &self,
+        transform: impl for<'a> Fn(&'a Lua, LuaResult<LuaMultiValue<'a>>) -> LuaResult<LuaMultiValue<'a>>
+            + 'static,
+    ) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_TRANSFORM_WHEN_RUNNING}"
+        );
+        self.result_map.transform().replace(transform);
+    }
+
+    /**
+        Clears the result transform for this scheduler.
+
+        Note that this does not clear any per-thread result transforms.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn remove_result_transform(&self) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_TRANSFORM_WHEN_RUNNING}"
+        );
+        self.result_map.transform().clear();
+    }
+
+    /**
+        Sets the result transform for the tracked [`LuaThread`] with the given [`ThreadId`].
+
+        This transform takes precedence over the scheduler-wide transform
+        set using [`Scheduler::set_result_transform`], and will only be
+        called once, with the final result of the given thread.
+
+        Overwrites any previous result transform for the given thread.
+
+        # Panics
+
+        Panics if the given thread is not being tracked by this scheduler.
+    */
+    pub fn set_thread_result_transform(
+        &self,
+        id: ThreadId,
+        transform: impl for<'a> Fn(&'a Lua, LuaResult<LuaMultiValue<'a>>) -> LuaResult<LuaMultiValue<'a>>
+            + 'static,
+    ) {
+        assert!(self.result_map.is_tracked(id), "{ERR_THREAD_NOT_TRACKED}");
+        self.result_map
+            .set_thread_transform(id, Box::new(transform));
+    }
+
+    /**
+        Adds a callback to call with the result of the tracked [`LuaThread`] with the given [`ThreadId`].
+
+        The callback is called on the scheduler thread once the given thread completes, with
+        its result, after any result transforms have been applied. This is a lighter-weight
+        alternative to waiting for the thread, and multiple callbacks may be added per thread.
+
+        If the thread has already completed, the callback is called immediately instead.
+        If the thread is cancelled, its callbacks are called with the cancellation error.
+
+        # Panics
+
+        Panics if the given thread is not being tracked by this scheduler.
+    */
+    pub fn on_thread_complete(
+        &self,
+        id: ThreadId,
+        callback: impl for<'a> FnOnce(&'a Lua, &LuaResult<LuaMultiValue<'a>>) + 'static,
+    ) {
+        assert!(self.result_map.is_tracked(id), "{ERR_THREAD_NOT_TRACKED}");
+        self.result_map
+            .add_callback(self.lua, id, Box::new(callback));
+    }
+
+    /**
+        Sets a callback to call with the result of every [`LuaThread`] that completes.
+
+        Unlike [`Scheduler::on_thread_complete`], this callback is called for all threads run by the
+        scheduler, including ones spawned from Lua that are not tracked, without needing to know
+        their ids up front. This makes it suitable for logging, metrics, or piping results elsewhere.
+
+        For tracked threads, the callback is called after any result transforms and completion callbacks
+        have run, and the result itself may still be retrieved using [`Scheduler::get_thread_result`].
+
+        Overwrites any previous completion callback.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn set_completion_callback(
+        &self,
+        callback: impl for<'a> Fn(&'a Lua, ThreadId, &LuaResult<LuaMultiValue<'a>>) + 'static,
+    ) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.result_map.set_global_callback(Some(Rc::new(callback)));
+    }
+
+    /**
+        Removes the completion callback for this scheduler.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn remove_completion_callback(&self) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.result_map.set_global_callback(None);
+    }
+
+    /**
+        Sets a channel sender to notify whenever a tracked [`LuaThread`] completes.
+
+        Each notification contains the id of the thread, and if it completed successfully. Since
+        the sender is [`Send`], the receiving end may live on any other thread, such as a UI thread
+        or a game loop, to learn about completions without needing access to the scheduler.
+
+        Notifications are sent after any result transforms and completion callbacks have run, and the
+        result itself may still be retrieved using [`Scheduler::get_thread_result`] afterwards.
+        Once the receiver has been dropped, the sender is removed and no more notifications are sent.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn set_completion_sender(&self, sender: Sender<ThreadCompletion>) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.result_map.set_sender(Some(sender));
+    }
+
+    /**
+        Removes the completion sender for this scheduler.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn remove_completion_sender(&self) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.result_map.set_sender(None);
+    }
+
+    /**
+        Sets how many errors from tracked Lua threads this scheduler should retain.
+
+        Retained errors are kept separately from thread results, meaning they remain
+        available even after the result has been taken using [`Scheduler::get_thread_result`],
+        and can be queried using [`Scheduler::recent_errors`]. Once the given number of errors
+        have been retained, the oldest errors are discarded to make room for new ones.
+
+        Errors are not retained by default, and setting the retention to `0` disables it.
+    */
+    pub fn set_error_retention(&self, capacity: usize) {
+        self.result_map.history().set_capacity(capacity);
+    }
+
+    /**
+        Returns up to `n` of the most recent errors from tracked Lua threads, newest first.
+
+        See [`Scheduler::set_error_retention`] for more information.
+    */
+    #[must_use]
+    pub fn recent_errors(&self, n: usize) -> Vec<ErrorRecord> {
+        self.result_map.history().recent(n)
+    }
+
+    /**
+        Sets the number of values passed out of tracked Lua threads to record, in debug builds.
+
+        Whenever a tracked thread yields or returns values, the values are stringified, truncated
+        to a maximum length, and recorded so that they can be queried using [`Scheduler::recent_values`].
+        This makes it possible to step through a failing scheduling scenario and see what data flowed
+        between threads. Once the given number of records have been retained, the oldest are discarded.
+
+        Values are not recorded by default, and setting the capacity to `0` disables recording.
+        In release builds, values are never recorded, regardless of the capacity.
+    */
+    pub fn set_value_logging(&self, capacity: usize) {
+        self.value_log.set_capacity(capacity);
+    }
+
+    /**
+        Returns up to `n` of the most recently recorded values from tracked Lua threads, newest first.
+
+        See [`Scheduler::set_value_logging`] for more information.
+    */
+    #[must_use]
+    pub fn recent_values(&self, n: usize) -> Vec<ValueRecord> {
+        self.value_log.recent(n)
+    }
+
+    /**
+        Sets how many tracked Lua threads this scheduler should retain the origins of, for respawning.
+
+        Whenever a function or chunk is pushed using [`Scheduler::push_thread_front`] or
+        [`Scheduler::push_thread_back`], the function and its arguments are retained, so that
+        the thread can later be started over as a fresh thread using [`Scheduler::respawn`].
+        Once the given number of origins have been retained, the oldest are discarded.
+
+        Origins are not retained by default, and setting the retention to `0` disables it.
+        Note that retained functions and arguments are kept alive until they are discarded.
+    */
+    pub fn set_respawn_retention(&self, capacity: usize) {
+        self.origins.set_capacity(capacity);
+    }
+
+    /**
+        Respawns the tracked [`LuaThread`] with the given [`ThreadId`], creating a fresh thread
+        from the same function and arguments that it was originally pushed with, and deferring
+        it onto the scheduler queue - this is useful for retrying failed script jobs.
+
+        The original thread is left as-is, and does not need to have completed. Respawned threads
+        also have their origins retained, meaning they may be respawned again in turn.
+
+        See [`Scheduler::set_respawn_retention`] for more information.
+
+        # Returns
+
+        Returns the [`ThreadId`] of the new thread, which can be used to retrieve its result.
+
+        # Errors
+
+        Errors if the origin of the thread was not retained, when out of memory,
+        or if the scheduler is draining.
+    */
+    pub fn respawn(&self, id: ThreadId) -> LuaResult<ThreadId> {
+        match self.origins.get(self.lua, id.base())? {
+            Some((function, args)) => self.push_thread_back(function, args),
+            None => Err(LuaError::runtime(ERR_NOT_RESPAWNABLE)),
+        }
+    }
+
+    /**
+        Sets the yield handler for this scheduler.
+
+        This handler will be called whenever a Lua thread run by the scheduler yields one or more
+        values using a plain `coroutine.yield`, which would otherwise be discarded. It may return
+        arguments to resume the thread with during the next cycle, through the deferred queue,
+        or `None` to leave the thread suspended, enabling generator-like protocols with Lua.
+
+        Threads yielding no values at all are assumed to be waiting on something else to resume
+        them, such as a tick or a condition variable, and are never passed to the handler.
+
+        Overwrites any previous yield handler.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn set_yield_handler(
+        &self,
+        handler: impl for<'a> Fn(&'a Lua, ThreadId, LuaMultiValue<'a>) -> LuaResult<Option<LuaMultiValue<'a>>>
+            + 'static,
+    ) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.yield_handler.replace(handler);
+    }
+
+    /**
+        Clears the yield handler for this scheduler.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn remove_yield_handler(&self) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.yield_handler.clear();
+    }
+
+    /**
+        Sets the resume hooks for this scheduler.
+
+        The `before` hook is called right before any Lua thread is resumed by the scheduler, and
+        the `after` hook right after it yields, errors, or completes, along with its result. This
+        includes threads resumed from the scheduler queues, as well as threads resumed using the
+        `spawn` and `resume` functions given to Lua, such as through `task.spawn` and `coroutine.wrap`.
+
+        Time spent awaiting an async function is included within a single resumption, unless the
+        function was created using [`LuaSchedulerExt::create_native_async_function`], in which case
+        the thread yields back to the scheduler, and is resumed again once the function completes.
+
+        Overwrites any previous resume hooks.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+
+        [`LuaSchedulerExt::create_native_async_function`]: crate::LuaSchedulerExt::create_native_async_function
+    */
+    pub fn set_resume_hooks(
+        &self,
+        before: impl Fn(ThreadId) + 'static,
+        after: impl for<'a> Fn(ThreadId, &LuaResult<LuaMultiValue<'a>>) + 'static,
+    ) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.resume_hooks.replace(before, after);
+    }
+
+    /**
+        Clears the resume hooks for this scheduler.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn remove_resume_hooks(&self) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.resume_hooks.clear();
+    }
+
+    /**
+        Sets the yield budget for the [`LuaThread`] with the given [`ThreadId`].
+
+        Once the thread has been running continuously for longer than the given budget,
+        it will automatically yield at the next possible point (a function call or loop
+        iteration) and then be re-queued onto the deferred queue, letting other threads
+        run in between. This lets long-running chunks make progress across scheduler
+        cycles without blocking other threads, and without having to yield manually.
+
+        This is implemented using a Luau interrupt, and will replace any other interrupt
+        callback set directly on the Lua state for as long as this scheduler exists. To
+        use an interrupt of your own alongside budgets, see [`Scheduler::set_interrupt`].
+        To give a chunk a budget as it is pushed, see [`ChunkOptions::yield_budget`].
+
+        Only the thread itself is yielded, and never coroutines that it resumes on its own,
+        such as using `coroutine.wrap` - those keep running until they yield back to it.
+
+        Note that Luau does not allow yielding inside of metamethods, so a thread that
+        exceeds its budget while running a metamethod will error instead of yielding.
+    */
+    pub fn set_thread_yield_budget(&self, id: ThreadId, budget: Duration) {
+        self.preemption.set_budget(self.lua, id.base(), budget);
+    }
+
+    /**
+        Sets the default yield budget for all Lua threads resumed by this scheduler.
+
+        This works just like [`Scheduler::set_thread_yield_budget`], but for all threads
+        that do not have a budget of their own, and is a softer alternative to erroring
+        threads that run for too long - long computations will instead make progress
+        across scheduler cycles, without blocking any interactive threads.
+
+        The number of times threads have been automatically yielded can
+        be retrieved using [`Scheduler::preemption_count`].
+
+        Setting the default budget to `None` removes it.
+    */
+    pub fn set_yield_budget(&self, budget: Option<Duration>) {
+        self.preemption.set_default_budget(self.lua, budget);
+    }
+
+    /**
+        Sets the thread budget for all Lua threads resumed by this scheduler.
+
+        Any thread that runs continuously for longer than the budget, during a single resumption,
+        will error at the next possible interrupt point, with the error stored as its result and
+        passed to the error callback, same as any other error. This prevents untrusted scripts
+        from freezing the entire scheduler with a busy loop, such as `while true do end`.
+        Catching the error, such as using `pcall`, does not reset the budget - every later
+        interrupt errors again, until the thread stops running.
+
+        This is implemented using the same Luau interrupt as [`Scheduler::set_yield_budget`],
+        and the two may be combined, in which case the thread budget should be the larger one.
+        Note that interrupts only happen while running Luau code, meaning a thread that
+        is blocked inside of a native function can not be interrupted until it returns.
+        Any interrupt set directly on the Lua state is replaced, see [`Scheduler::set_interrupt`].
+
+        Setting the thread budget to `None` removes it.
+    */
+    pub fn set_thread_budget(&self, budget: Option<Duration>) {
+        self.preemption.set_thread_budget(self.lua, budget);
+    }
+
+    /**
+        Returns the number of times that Lua threads have been automatically yielded,
+        due to exceeding their yield budget, and re-queued onto the deferred queue.
+
+        See [`Scheduler::set_yield_budget`] for more information.
+    */
+    #[must_use]
+    pub fn preemption_count(&self) -> usize {
+        self.preemption.preempted()
+    }
+
+    /**
+        Sets the Luau interrupt callback for the Lua state of this scheduler.
+
+        Luau only supports a single interrupt, which the scheduler also uses for yield and
+        thread budgets, so any interrupt set directly using [`Lua::set_interrupt`] is replaced
+        once a budget is set. Interrupts set using this method are instead chained with the one
+        used for budgets, and always run first - if the callback errors or yields, budgets are
+        not checked during that interrupt.
+
+        The callback is removed again once the scheduler is dropped.
+    */
+    pub fn set_interrupt(&self, callback: impl Fn(&Lua) -> LuaResult<LuaVmState> + 'static) {
+        self.preemption
+            .set_interrupt(self.lua, Some(Rc::new(callback)));
+    }
+
+    /**
+        Removes the Luau interrupt callback set using [`Scheduler::set_interrupt`].
+    */
+    pub fn remove_interrupt(&self) {
+        self.preemption.set_interrupt(self.lua, None);
+    }
+
+    /**
+        Starts a supervised, long-running service on this scheduler.
+
+        The given factory function is used to create a new Lua thread for the service,
+        which is then spawned. Whenever that thread completes or errors, the scheduler
+        will create and spawn a new thread from the same factory function, according
+        to the given [`RestartOptions`], isolating any crashes to the service itself.
+
+        Returns the [`ThreadId`] of the first thread started for the service.
+
+        # Errors
+
+        Errors when out of memory, or if the scheduler is draining.
+    */
+    pub fn supervise(
+        &self,
+        name: impl Into<String>,
+        factory: LuaFunction<'lua>,
+        options: RestartOptions,
+    ) -> LuaResult<ThreadId> {
+        self.drain.check()?;
+        let thread = self
+            .supervisor
+            .add(self.lua, name.into(), factory, options)?;
+        let id = ThreadId::from(&thread);
+        self.queue_spawn.push_item(self.lua, thread, ())?;
+        Ok(id)
+    }
+
+    /**
+        Sets the restart callback for this scheduler.
+
+        This callback will be called whenever a supervised service is restarted,
+        see [`Scheduler::supervise`] for more information about supervised services.
+
+        Overwrites any previous restart callback.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn set_restart_callback(&self, callback: impl Fn(RestartEvent) + Send + 'static) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.supervisor.replace_callback(callback);
+    }
+
+    /**
+        Clears the restart callback for this scheduler.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn remove_restart_callback(&self) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.supervisor.clear_callback();
+    }
+
+    /**
+        Sets the queue pressure callback for this scheduler.
+
+        This callback will be called whenever the number of Lua threads pending in the
+        spawned and deferred queues crosses any of the given thresholds, letting hosts
+        that feed work from external sources throttle their intake accordingly.
+
+        To prevent the signal from flapping, a threshold is only considered to be
+        crossed in the downward direction once the depth falls below half of it.
+
+        Overwrites any previous queue pressure callback.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn set_queue_pressure_callback(
+        &self,
+        thresholds: impl IntoIterator<Item = usize>,
+        callback: impl Fn(QueuePressure) + Send + 'static,
+    ) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.pressure.replace(thresholds, callback);
+    }
+
+    /**
+        Clears the queue pressure callback for this scheduler.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn remove_queue_pressure_callback(&self) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.pressure.clear();
+    }
+
+    /**
+        Tags the given [`LuaThread`], so that it can be found using [`Scheduler::find_threads`].
+
+        Tags may be used to operate on logical groups of threads, such as all threads
+        belonging to a plugin, without having to keep track of thread ids separately.
+        Tags are also attached to checkpoints and tracing spans for the thread.
+
+        A thread may only have a single tag, tagging it again replaces any previous tag.
+        Tagging a thread does not prevent it from being garbage collected.
+
+        # Errors
+
+        Errors when out of memory.
+    */
+    pub fn set_thread_tag(&self, thread: &LuaThread<'lua>, tag: impl AsRef<str>) -> LuaResult<()> {
+        self.tags.insert(self.lua, thread, tag.as_ref())?;
+        // NOTE: Tagged threads may never be pushed, but should still be found by their id
+        self.records.record_thread(self.lua, thread)
+    }
+
+    /**
+        Names the given [`LuaThread`], identifying the logical job that it belongs to.
+
+        Names are inherited by any threads spawned from a named thread, unless they have a name of
+        their own, and are attached to uncaught errors from named threads before they are passed to
+        the error callback. They are also included in [`Scheduler::describe_thread`] and error records.
+
+        A thread may only have a single name, naming it again replaces any previous name.
+        Naming a thread does not prevent it from being garbage collected.
+
+        # Errors
+
+        Errors when out of memory.
+    */
+    pub fn set_thread_name(
+        &self,
+        thread: &LuaThread<'lua>,
+        name: impl AsRef<str>,
+    ) -> LuaResult<()> {
+        self.names.insert(self.lua, thread, name.as_ref())
+    }
+
+    /**
+        Gets the name of the [`LuaThread`] with the given id, if it has one.
+
+        Note that this has to search through all named threads, and is
+        meant for debugging and error reporting, not for hot paths.
+
+        # Errors
+
+        Errors when out of memory.
+    */
+    pub fn thread_name(&self, id: ThreadId) -> LuaResult<Option<String>> {
+        self.names.find_id(self.lua, id.base())
+    }
+
+    /**
+        Sets a task-local value of type `T` for the given [`LuaThread`], replacing any previous value.
+
+        This makes it possible to attach per-request state to a thread before pushing it, which
+        builtins may then retrieve using [`LuaSchedulerExt::task_local`], instead of it having
+        to be passed through every Lua function call. Note that the value is dropped once the
+        thread has been garbage collected, and any futures it spawned have completed.
+
+        [`LuaSchedulerExt::task_local`]: crate::LuaSchedulerExt::task_local
+
+        # Errors
+
+        Errors when out of memory.
+    */
+    pub fn set_task_local<T: 'static>(&self, thread: &LuaThread<'lua>, value: T) -> LuaResult<()> {
+        self.locals.set_for_thread(self.lua, thread, value)
+    }
+
+    /**
+        Finds all [`LuaThread`]s with the given tag that have not yet completed.
+
+        See [`Scheduler::set_thread_tag`] for more information about tags.
+
+        # Errors
+
+        Errors when out of memory.
+    */
+    pub fn find_threads(&self, tag: impl AsRef<str>) -> LuaResult<Vec<ThreadId>> {
+        self.tags.find_ids(self.lua, tag.as_ref())
+    }
+
+    /**
+        Cancels all [`LuaThread`]s with the given tag that have not yet completed.
+
+        Returns the number of threads that were cancelled.
+
+        See [`Scheduler::set_thread_tag`] for more information about tags.
+
+        # Errors
+
+        Errors when out of memory, or if `coroutine.close` was missing when the scheduler was created.
+    */
+    pub fn cancel_by_tag(&self, tag: impl AsRef<str>) -> LuaResult<usize> {
+        let threads = self.tags.find(self.lua, tag.as_ref())?;
+        for thread in &threads {
+            self.cancellation.cancel(self.lua, thread)?;
+        }
+        Ok(threads.len())
+    }
+
+    /**
+        Suspends the [`LuaThread`] with the given [`ThreadId`], without cancelling it.
+
+        A suspended thread will not be resumed by the scheduler until it is unsuspended using
+        [`Scheduler::resume_suspended_thread`] - any queued resumption of the thread, as well
+        as the completion of any async function it is waiting for, is held back until then,
+        and Lua may not resume the thread manually either.
+
+        This is useful for stepping through threads in a debugger, modal flows, or temporarily
+        freezing a misbehaving thread while investigating it. Note that the scheduler will not
+        complete while any thread with held back resumptions is suspended.
+
+        Returns `true` if the thread was suspended, and `false` if it was already suspended.
+    */
+    #[allow(clippy::must_use_candidate)]
+    pub fn suspend_thread(&self, id: ThreadId) -> bool {
+        self.suspended.suspend(id.base())
+    }
+
+    /**
+        Resumes a [`LuaThread`] suspended using [`Scheduler::suspend_thread`].
+
+        Any resumptions of the thread that were held back while it was suspended continue
+        as usual, and the thread may be resumed again by anything else in the scheduler.
+
+        Returns `true` if the thread was unsuspended, and `false` if it was not suspended.
+    */
+    #[allow(clippy::must_use_candidate)]
+    pub fn resume_suspended_thread(&self, id: ThreadId) -> bool {
+        self.suspended.unsuspend(id.base())
+    }
+
+    /**
+        Returns `true` if the [`LuaThread`] with the given [`ThreadId`] is currently suspended.
+
+        See [`Scheduler::suspend_thread`] for more information.
+    */
+    #[must_use]
+    pub fn is_thread_suspended(&self, id: ThreadId) -> bool {
+        self.suspended.is_suspended(id.base())
+    }
+
+    /**
+        Returns the current cycle number of this scheduler.
+
+        A cycle is a single iteration of the main loop of the scheduler - waking up, and then
+        resuming all Lua threads and spawning all futures that were ready at that point. The
+        first cycle is number `1`, and the number increases by one every cycle, never skipping
+        or repeating numbers, which makes it suitable for frame-based logic and for asserting
+        on when things happen in deterministic tests. Returns `0` if no cycle has started yet.
+
+        Cycles continue counting across runs, and are only reset by [`Scheduler::reset`].
+        Lua may also get the current cycle number using [`Functions::cycle`].
+
+        [`Functions::cycle`]: crate::Functions::cycle
+    */
+    #[must_use]
+    pub fn current_cycle(&self) -> u64 {
+        self.cycles.current()
+    }
+
+    /**
+        Adds a hook to call with the cycle number whenever a scheduler cycle starts.
+
+        Start hooks are called after the scheduler has woken up, but before any Lua threads
+        are resumed. Multiple hooks may be added, and are called in the order they were added.
+
+        See [`Scheduler::current_cycle`] for more information.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn on_cycle_start(&self, hook: impl Fn(&Lua, u64) + 'static) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.cycles.add_start_hook(hook);
+    }
+
+    /**
+        Adds a hook to call with the cycle number whenever a scheduler cycle ends.
+
+        End hooks are called once all Lua threads and futures that were ready during the cycle
+        have been resumed and spawned. Multiple hooks may be added, and are called in the order
+        they were added. Note that if the scheduler exits or is stopped during a cycle, the
+        end hooks are not called for that cycle.
+
+        See [`Scheduler::current_cycle`] for more information.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn on_cycle_end(&self, hook: impl Fn(&Lua, u64) + 'static) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.cycles.add_end_hook(hook);
+    }
+
+    /**
+        Removes all cycle start and end hooks from this scheduler.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn remove_cycle_hooks(&self) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.cycles.clear_hooks();
+    }
+
+    /**
+        Returns the epoch of this scheduler.
+
+        Epochs are unique per scheduler, and are used to scope thread ids
+        so that they can be referenced outside of this scheduler, such as
+        in logs, see [`Scheduler::scope_thread_id`] for more information.
+    */
+    #[must_use]
+    pub fn epoch(&self) -> u64 {
+        self.records.epoch()
+    }
+
+    /**
+        Scopes the given [`ThreadId`] to the epoch of this scheduler.
+
+        Scoped thread ids may be serialized using the `serde` feature, and later
+        resolved back using [`Scheduler::resolve_thread_id`] while this scheduler is live.
+    */
+    #[must_use]
+    pub fn scope_thread_id(&self, id: ThreadId) -> ScopedThreadId {
+        self.records.scope(id)
+    }
+
+    /**
+        Resolves the given [`ScopedThreadId`] back into a [`ThreadId`].
+
+        Returns `None` if the id was scoped to a different scheduler.
+    */
+    #[must_use]
+    pub fn resolve_thread_id(&self, scoped: ScopedThreadId) -> Option<ThreadId> {
+        if scoped.epoch == self.records.epoch() {
+            Some(scoped.id)
+        } else {
+            None
+        }
+    }
+
+    /**
+        Describes the [`LuaThread`] with the given id, returning its tag, name, status and push time.
+
+        Returns `None` if the thread was never pushed to or tagged on this
+        scheduler, or if the thread has since been garbage collected.
+
+        # Errors
+
+        Errors when out of memory.
+    */
+    pub fn describe_thread(&self, id: ThreadId) -> LuaResult<Option<ThreadInfo>> {
+        let Some(thread) = self.records.find(self.lua, id)? else {
+            return Ok(None);
+        };
+        Ok(Some(ThreadInfo {
+            id: self.records.scope(id),
+            tag: self.tags.get(self.lua, &thread),
+            name: self.names.get(self.lua, &thread),
+            status: thread.status(),
+            pushed_at: self.records.pushed_at(self.lua, &thread),
+        }))
+    }
+
+    /**
+        Lists all Lua threads currently held by this scheduler, along with
+        what each of them is waiting for, and their names and tags, if any.
+
+        Threads are listed in the order that they will be resumed within each state, and each thread
+        is only listed once - spawned threads that are waiting on a future are listed as waiting.
+
+        Note that this has to search through all known threads, and is
+        meant for tooling such as developer consoles, not for hot paths.
+
+        # Errors
+
+        Errors when out of memory.
+    */
+    #[cfg(feature = "unstable")]
+    pub fn snapshot(&self) -> LuaResult<Vec<ThreadSnapshot>> {
+        let mut waiting = self.awaiting.ids();
+        waiting.extend(self.native.awaiting_ids());
+        let states = [
+            (ThreadState::Suspended, self.suspended.ids()),
+            (ThreadState::WaitingOnFuture, waiting),
+            (ThreadState::Prioritized, self.queue_high.thread_ids()),
+            (ThreadState::Spawned, self.queue_spawn.thread_ids()),
+            (ThreadState::Deferred, self.queue_defer.thread_ids()),
+            (ThreadState::WaitingForTick, self.ticks.thread_ids(self.lua)),
+            (ThreadState::Delayed, self.delayed.thread_ids()),
+            (ThreadState::Idle, self.idle.threads().thread_ids()),
+        ];
+
+        let threads = self
+            .records
+            .threads(self.lua)?
+            .into_iter()
+            .map(|thread| (ThreadId::from(&thread), thread))
+            .collect::<FxHashMap<_, _>>();
+
+        let mut seen = FxHashSet::default();
+        let mut snapshot = Vec::new();
+        for (state, ids) in states {
+            for id in ids {
+                let thread = threads.get(&id);
+                // NOTE: Cancelled threads may still be queued, but will never run again
+                let finished = thread.is_some_and(|t| t.status() != LuaThreadStatus::Resumable);
+                if finished || !seen.insert(id) {
+                    continue;
+                }
+                snapshot.push(ThreadSnapshot {
+                    id,
+                    state,
+                    name: thread.and_then(|t| self.names.get(self.lua, t)),
+                    tag: thread.and_then(|t| self.tags.get(self.lua, t)),
+                });
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /**
+        Begins draining this scheduler, as part of a soft shutdown.
+
+        While draining, any threads already queued and any futures already in flight will
+        still run to completion, but spawning new threads is no longer possible - `spawn`
+        and `defer` will throw a catchable error in Lua, pushing threads from Rust will
+        return an error, and supervised services will no longer be restarted.
+
+        Draining can not be stopped once it has begun, and calling this again does nothing.
+    */
+    pub fn begin_drain(&self) {
+        self.drain.begin(self.diagnostics.completed_tasks());
+    }
+
+    /**
+        Returns the drain progress of this scheduler, or `None` if it is not draining.
+
+        See [`Scheduler::begin_drain`] for more information.
+    */
+    #[must_use]
+    pub fn drain_status(&self) -> Option<DrainStatus> {
+        let queued = self.queue_high.len()
+            + self.queue_spawn.len()
+            + self.queue_defer.len()
+            + self.idle.threads().len()
+            + self.delayed.len();
+        let remaining = self.diagnostics.active_tasks() + queued;
+        self.drain
+            .status(self.diagnostics.completed_tasks(), remaining)
+    }
+
+    /**
+        Returns the number of active tasks on this scheduler.
+
+        Active tasks are Lua threads and thread-local futures that have
+        been spawned on the scheduler, but have not yet completed.
+    */
+    #[must_use]
+    pub fn active_tasks(&self) -> usize {
+        self.diagnostics.active_tasks()
+    }
+
+    /**
+        Sets the long poll callback for this scheduler.
+
+        This callback will be called whenever a single poll of a Lua thread or a thread-local
+        future takes longer than the given threshold, blocking the scheduler in the meantime.
+        The thread that was running, or that spawned the future, along with its tag, will be
+        reported, making it possible to find accidentally blocking functions in production.
+
+        Overwrites any previous long poll callback.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn set_long_poll_callback(
+        &self,
+        threshold: Duration,
+        callback: impl Fn(LongPoll) + Send + 'static,
+    ) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.diagnostics.replace_long_poll(threshold, callback);
+    }
+
+    /**
+        Clears the long poll callback for this scheduler.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn remove_long_poll_callback(&self) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.diagnostics.clear_long_poll();
+    }
+
+    /**
+        Sets the watchdog for this scheduler, detecting tasks that never complete.
+
+        Any thread-local future, native async call, or Lua thread awaiting an async function,
+        that has been running for longer than the given maximum lifetime is reported using
+        `tracing`, and to the callback set using [`Scheduler::set_watchdog_callback`].
+
+        With [`WatchdogPolicy::Cancel`], such tasks are also cancelled. Native async calls
+        resume their thread with a timeout error, while Lua threads stuck inside of other
+        async functions can not be resumed, and are closed with a timeout error instead.
+
+        Only tasks started after setting the watchdog are watched.
+    */
+    pub fn set_watchdog(&self, max_lifetime: Duration, policy: WatchdogPolicy) {
+        self.watchdog.replace(max_lifetime, policy);
+    }
+
+    /**
+        Sets the callback for stuck tasks detected by the watchdog.
+
+        See [`Scheduler::set_watchdog`] for more information.
+
+        Overwrites any previous watchdog callback.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn set_watchdog_callback(&self, callback: impl Fn(StuckTask) + Send + 'static) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.watchdog.replace_callback(callback);
+    }
+
+    /**
+        Removes the watchdog for this scheduler, along with any watchdog callback.
+
+        Tasks that were already being watched will continue to be watched.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn remove_watchdog(&self) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.watchdog.clear();
+    }
+
+    /**
+        Sets the leak callback for this scheduler.
+
+        This callback will be called when the scheduler is dropped, if any Lua threads or values
+        were left behind - tracked threads that never completed, queued threads that were never
+        resumed, and registry values still held by those queued threads or by unclaimed results.
+
+        Queued threads are removed from their queues when detecting leaks,
+        so their registry values do not outlive the scheduler.
+
+        Overwrites any previous leak callback.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn set_leak_callback(&self, callback: impl Fn(LeakReport) + Send + 'static) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.leaks.replace(callback);
+    }
+
+    /**
+        Clears the leak callback for this scheduler.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn remove_leak_callback(&self) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
+        );
+        self.leaks.clear();
+    }
+
+    /**
+        Registers a named background job, which Lua threads may run using `runJob`.
+
+        Whenever Lua runs the job, its arguments are deserialized and passed to the given factory,
+        and the returned future is spawned on the main executor, meaning it must be [`Send`].
+        The calling Lua thread yields until the job completes, and is then resumed with the
+        job output serialized back into a Lua value, or with an error if the job failed.
+
+        This gives Lua controlled access to host capabilities by name, without
+        needing to expose a separate global function for every single job.
+
+        Overwrites any previous job registered with the same name.
+    */
+    pub fn register_job<F, FR, A, R>(&self, name: impl Into<String>, factory: F)
+    where
+        F: Fn(A) -> FR + 'static,
+        FR: Future<Output = LuaResult<R>> + Send + 'static,
+        A: DeserializeOwned,
+        R: Serialize + Send + 'static,
+    {
+        self.jobs.insert(name.into(), factory);
+    }
+
+    /**
+        Returns a handle for pushing Lua work to this scheduler from any other thread.
+
+        See [`SchedulerHandle`] for more information.
+    */
+    #[must_use]
+    pub fn handle(&self) -> SchedulerHandle {
+        self.remote.handle()
+    }
+
+    /**
+        Registers a named function, which may be called from other threads
+        using [`SchedulerHandle::push_registered`].
+
+        Overwrites any previous function registered with the same name.
+
+        # Errors
+
+        Errors when out of memory.
+    */
+    pub fn register_function(
+        &self,
+        name: impl Into<String>,
+        function: LuaFunction<'lua>,
+    ) -> LuaResult<()> {
+        self.remote.register(self.lua, name.into(), function)
+    }
+
+    /**
+        Removes a function registered using [`Scheduler::register_function`].
+
+        Returns `true` if a function with the given name was registered.
+    */
+    #[allow(clippy::must_use_candidate)]
+    pub fn unregister_function(&self, name: &str) -> bool {
+        self.remote.unregister(name)
+    }
+
+    /**
+        Turns work pushed from another thread into a function and its
+        arguments, resolving registered functions by name if necessary.
+    */
+    #[cfg(feature = "unstable")]
+    pub(crate) fn resolve_remote(
+        &self,
+        work: RemoteWork,
+    ) -> LuaResult<(LuaFunction<'lua>, LuaMultiValue<'lua>)> {
+        self.remote.resolve(self.lua, work)
+    }
+
+    /**
+        Sets the Tokio runtime that futures spawned using [`LuaSpawnExt::spawn_tokio`] run on.
+
+        By default, futures run on the Tokio runtime that the scheduler itself is running within,
+        if any, and otherwise on a multi-threaded runtime owned by the scheduler, which is created
+        on first use, and shut down once the scheduler is dropped.
+
+        [`LuaSpawnExt::spawn_tokio`]: crate::LuaSpawnExt::spawn_tokio
+    */
+    #[cfg(feature = "tokio")]
+    pub fn set_tokio_handle(&self, handle: tokio::runtime::Handle) {
+        self.tokio.set_handle(handle);
+    }
+
+    /**
+        Spawns an event source, piping items received from the given stream into Lua.
+
+        For each received item, a new invocation of the given handler is deferred onto the
+        scheduler queue, and called with the item converted into Lua values. Any receiver that
+        implements [`Stream`], such as those of `async-channel` or `flume`, may be used here.
+
+        The given [`Backpressure`] strategy decides what happens to items received
+        while previous invocations of the handler are still waiting to run.
+
+        Returns the id of the Lua thread receiving items, which runs until the stream ends,
+        and may be cancelled to stop receiving items. If the scheduler starts draining,
+        the thread stops receiving items and errors.
+
+        # Errors
+
+        Errors when out of memory, or if the scheduler is draining.
+    */
+    pub fn spawn_event_source<S>(
+        &self,
+        stream: S,
+        handler: LuaFunction<'lua>,
+        backpressure: Backpressure,
+    ) -> LuaResult<ThreadId>
+    where
+        S: Stream + Unpin + 'static,
+        S::Item: for<'a> IntoLuaMulti<'a> + 'static,
+    {
+        let source = Rc::new(RefCell::new(Some(EventSource::new(stream, backpressure))));
+        let receiver = self
+            .lua
+            .create_async_function(move |lua, handler: LuaFunction| {
+                let source = source.borrow_mut().take();
+                async move {
+                    match source {
+                        Some(source) => source.run(lua, handler).await,
+                        None => Err(LuaError::runtime("event source is already running")),
+                    }
+                }
+            })?;
+        self.push_thread_front(receiver, handler)
+    }
+
+    /**
+        Gets the latest checkpoint saved by the [`LuaThread`] with the given [`ThreadId`].
+
+        Note that checkpoints saved by supervised services are not keyed by thread,
+        and must instead be read using [`Scheduler::get_service_checkpoint`].
+    */
+    #[must_use]
+    pub fn get_thread_checkpoint(&self, id: ThreadId) -> Option<Checkpoint> {
+        self.checkpoints.get_thread(id.base())
+    }
+
+    /**
+        Gets the latest checkpoint saved by any thread of the supervised service with the given name.
+
+        Whenever a supervised service is restarted, its new thread
+        is also passed this checkpoint as its first argument.
+    */
+    #[must_use]
+    pub fn get_service_checkpoint(&self, name: &str) -> Option<Checkpoint> {
+        self.checkpoints.get_tag(name)
+    }
+
+    /**
+        Gets the exit code for this scheduler, if one has been set.
+    */
+    #[must_use]
+    pub fn get_exit_code(&self) -> Option<ExitCode> {
+        self.exit.get()
+    }
+
+    /**
+        Sets the exit code for this scheduler.
+
+        This will cause [`Scheduler::run`] to exit immediately, same as [`ExitMode::Force`].
+    */
+    pub fn set_exit_code(&self, code: ExitCode) {
+        self.exit.set(code);
+    }
+
+    /**
+        Sets the exit code for this scheduler, exiting using the given mode.
+
+        With [`ExitMode::Force`], this will cause [`Scheduler::run`] to exit immediately.
+
+        With [`ExitMode::Graceful`], no more threads are resumed from any of the queues, but
+        threads that are already running, meaning threads waiting for an async function to
+        complete, keep being resumed until they finish, and [`Scheduler::run`] exits after.
+
+        Setting the exit code again, such as with [`ExitMode::Force`] while a graceful exit
+        is waiting for running threads, replaces both the exit code and the mode.
+    */
+    pub fn set_exit_code_with_mode(&self, code: ExitCode, mode: ExitMode) {
+        self.exit.set_with_mode(code, mode);
+    }
+
+    /**
+        Returns a guard that keeps this scheduler running while it is idle.
+
+        Normally, [`Scheduler::run`] completes as soon as there are no Lua threads or futures left.
+        While any guard is alive, the scheduler instead waits for more work when idle, making it
+        possible for the host to keep pushing threads into an already running scheduler, such as
+        from a game server tick loop that is polled alongside it. Once all guards have been dropped,
+        the scheduler completes as usual the next time that it becomes idle.
+
+        Setting an exit code, or completing the stop future given to [`Scheduler::run_until`],
+        still stops the scheduler even while guards are alive, and a scheduler that is draining
+        completes once idle regardless of any guards, see [`Scheduler::begin_drain`].
+    */
+    pub fn keep_alive(&self) -> KeepAlive {
+        self.keep_alives.acquire()
+    }
+
+    /**
+        Restarts this scheduler, so that it may be run again, continuing where the previous run left off.
+
+        Runs that completed or were stopped may always be continued by running the scheduler again,
+        but runs that exited or were drained would exit again immediately. Restarting clears the
+        exit code and drain state, which is useful for running the same scheduler once per batch
+        of work, such as once per game tick, even if a script exited during a previous batch.
+
+        Unlike [`Scheduler::reset`], this keeps all other state, including any queued and parked
+        Lua threads, tracked threads along with their results, and all callbacks and configuration.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn restart(&self) {
+        assert!(!self.status().is_running(), "{ERR_SET_MODE_WHEN_RUNNING}");
+
+        self.exit.reset();
+        self.drain.reset();
+        self.set_status(Status::NotStarted);
+    }
+
+    /**
+        Resets this scheduler, so that it may be run again without any state lingering from previous runs.
+
+        This is useful for running one script per request on a persistent Lua state, and will:
+
+        - Remove all queued and parked Lua threads, without resuming them
+        - Remove all supervised services, checkpoints, tags, and per-thread yield budgets
+        - Remove all tracked threads along with their results, transforms and completion callbacks
+        - Clear the exit code, drain state, recorded errors and values, and preserved error values
+        - Remove any Lua error handler set by scripts using [`Functions::set_error_handler`](crate::Functions::set_error_handler)
+
+        All registry values held by the scheduler for any of the above are released.
+
+        Configuration such as callbacks, modes, registered jobs and plugins is kept, and any
+        [`Functions`](crate::Functions) created for this scheduler remain usable afterwards,
+        so there is no need to recreate or reinject them.
+
+        # Errors
+
+        Errors if the preserved error values could not be cleared.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn reset(&self) -> LuaResult<()> {
+        assert!(!self.status().is_running(), "{ERR_SET_MODE_WHEN_RUNNING}");
+
+        self.queue_high.clear();
+        self.queue_spawn.clear();
+        self.queue_defer.clear();
+        self.remote.clear();
+        self.ticks.clear();
+        self.idle.threads().clear();
+        self.idle.futures().clear();
+        self.awaiting.clear();
+        self.suspended.clear();
+        self.deadlines.clear();
+        self.delayed.clear();
+        self.locals.clear();
+
+        self.supervisor.clear();
+        self.checkpoints.clear();
+        self.tags.clear();
+        self.names.clear();
+        self.records.clear();
+        self.preemption.clear_budgets();
+
+        self.result_map.scoped_globals().restore_all(self.lua);
+        self.result_map.clear();
+        self.value_log.clear();
+        self.origins.clear();
+        self.error_values.clear(self.lua)?;
+        self.error_callback.clear_handler();
+
+        self.exit.reset();
+        self.drain.reset();
+        self.cycles.reset();
+        self.set_status(Status::NotStarted);
+
+        // NOTE: Registry values that were dropped above are only
+        // released once the Lua state expires its dropped keys
+        self.lua.expire_registry_values();
+        Ok(())
+    }
+
+    /**
+        Spawns a chunk / function / thread onto the scheduler queue.
+
+        Threads are guaranteed to be resumed in the order that they were pushed to the queue.
+
+        # Returns
+
+        Returns a [`ThreadId`] that can be used to retrieve the result of the thread.
+
+        Note that the result may not be available until [`Scheduler::run`] completes.
+
+        # Errors
+
+        Errors when out of memory, if the scheduler is draining, or with [`LuaError::CoroutineInactive`]
+        if the given thread has already completed, or [`ThreadErrored`] if it errored, in which case it
+        is never pushed to the queue, and an immediate result is stored for it if it is being tracked.
+
+        [`ThreadErrored`]: crate::ThreadErrored
+    */
+    pub fn push_thread_front(
+        &self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+    ) -> LuaResult<ThreadId> {
+        self.push_thread_to(Priority::Normal, thread, args, None)
+    }
+
+    /**
+        Defers a chunk / function / thread onto the scheduler queue.
+
+        Deferred threads are guaranteed to run after all spawned threads either yield or complete.
+
+        Threads are guaranteed to be resumed in the order that they were pushed to the queue.
+
+        # Returns
+
+        Returns a [`ThreadId`] that can be used to retrieve the result of the thread.
+
+        Note that the result may not be available until [`Scheduler::run`] completes.
+
+        # Errors
+
+        Errors when out of memory, if the scheduler is draining, or with [`LuaError::CoroutineInactive`]
+        if the given thread has already completed, or [`ThreadErrored`] if it errored, in which case it
+        is never pushed to the queue, and an immediate result is stored for it if it is being tracked.
+
+        [`ThreadErrored`]: crate::ThreadErrored
+    */
+    pub fn push_thread_back(
+        &self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+    ) -> LuaResult<ThreadId> {
+        self.push_thread_to(Priority::Low, thread, args, None)
+    }
+
+    /**
+        Spawns a chunk / function / thread onto the scheduler queue, with the given name.
+
+        The name identifies the logical job that the thread belongs to, and is inherited by any
+        threads spawned from it, so that uncaught errors passed to the error callback can be
+        attributed to the job that caused them, even if they happened in a spawned thread.
+
+        See [`Scheduler::push_thread_front`] and [`Scheduler::set_thread_name`] for more information.
+
+        # Errors
+
+        Errors the same as [`Scheduler::push_thread_front`], in which case the thread is not named.
+    */
+    pub fn push_thread_front_named(
+        &self,
+        name: impl AsRef<str>,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+    ) -> LuaResult<ThreadId> {
+        let thread = thread.into_lua_thread(self.lua)?;
+        // NOTE: The name is set before pushing so that any error reported while pushing is
+        // attributed to it, but must not be kept around for a thread that was never pushed
+        let previous = self.names.get(self.lua, &thread);
+        self.set_thread_name(&thread, name)?;
+        let result = self.push_thread_front(&thread, args);
+        if result.is_err() {
+            match previous {
+                Some(previous) => self.names.insert(self.lua, &thread, &previous)?,
+                None => self.names.remove(self.lua, &thread)?,
+            }
+        }
+        result
+    }
+
+    /**
+        Pushes a chunk / function / thread onto the scheduler queue for the given [`Priority`].
+
+        Queues are drained in order of priority every time the scheduler checks for work, meaning
+        higher-priority threads are always resumed before lower-priority threads that were queued
+        at the same time. This is useful for making latency-sensitive work, such as network
+        callbacks, preempt less important work, such as cosmetic timers.
+
+        - [`Priority::High`] threads are resumed before any other queued threads.
+        - [`Priority::Normal`] threads are spawned, the same as with [`Scheduler::push_thread_front`].
+        - [`Priority::Low`] threads are deferred, the same as with [`Scheduler::push_thread_back`].
+
+        Threads with the same priority are guaranteed to be resumed in the order that they were pushed.
+
+        # Returns
+
+        Returns a [`ThreadId`] that can be used to retrieve the result of the thread.
+
+        Note that the result may not be available until [`Scheduler::run`] completes.
+
+        # Errors
+
+        Errors when out of memory, if the scheduler is draining, or with [`LuaError::CoroutineInactive`]
+        if the given thread has already completed, or [`ThreadErrored`] if it errored, in which case it
+        is never pushed to the queue, and an immediate result is stored for it if it is being tracked.
+
+        [`ThreadErrored`]: crate::ThreadErrored
+    */
+    pub fn push_thread_with_priority(
+        &self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+        priority: Priority,
+    ) -> LuaResult<ThreadId> {
+        self.push_thread_to(priority, thread, args, None)
+    }
+
+    /**
+        Spawns a chunk / function / thread onto the scheduler queue, waiting for space if it is full.
+
+        This is the same as [`Scheduler::push_thread_front`], but instead of erroring when the queue
+        is full, it waits until the running scheduler has drained enough threads to make space.
+        Without a capacity set using [`Scheduler::set_queue_capacity`], this never waits.
+
+        Note that the queue is only drained while the scheduler is running, so this
+        must be awaited concurrently with the scheduler, otherwise it may never complete.
+
+        # Errors
+
+        Errors in the same cases as [`Scheduler::push_thread_front`], except for a full queue.
+    */
+    pub async fn push_thread_front_async(
+        &self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+    ) -> LuaResult<ThreadId> {
+        self.queue_spawn.wait_for_space().await;
+        self.push_thread_front(thread, args)
+    }
+
+    /**
+        Defers a chunk / function / thread onto the scheduler queue, waiting for space if it is full.
+
+        This is the same as [`Scheduler::push_thread_back`], but waits for space in the queue
+        instead of erroring, see [`Scheduler::push_thread_front_async`] for more information.
+
+        # Errors
+
+        Errors in the same cases as [`Scheduler::push_thread_back`], except for a full queue.
+    */
+    pub async fn push_thread_back_async(
+        &self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+    ) -> LuaResult<ThreadId> {
+        self.queue_defer.wait_for_space().await;
+        self.push_thread_back(thread, args)
+    }
+
+    /**
+        Spawns a chunk / function / thread onto the scheduler queue, with a deadline.
+
+        If the thread has not completed once the deadline has passed, it is cancelled, and an
+        error is stored as its result and passed to the error callback. The deadline
+        also applies to any threads spawned or deferred by the thread, using [`Functions`],
+        making it possible to enforce end-to-end timeouts for requests handled by Lua.
+
+        Builtins may check the deadline of the calling thread using [`LuaSchedulerExt::current_deadline`],
+        for example to pass it on to any requests made to other services.
+
+        See [`Scheduler::push_thread_front`] for more information.
+
+        [`Functions`]: crate::Functions
+        [`LuaSchedulerExt::current_deadline`]: crate::LuaSchedulerExt::current_deadline
+
+        # Errors
+
+        Errors when out of memory, if the scheduler is draining, or with [`LuaError::CoroutineInactive`]
+        if the given thread has already completed, or [`ThreadErrored`] if it errored, in which case it
+        is never pushed to the queue, and an immediate result is stored for it if it is being tracked.
+
+        [`ThreadErrored`]: crate::ThreadErrored
+    */
+    pub fn push_thread_with_deadline(
+        &self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+        deadline: Instant,
+    ) -> LuaResult<ThreadId> {
+        self.push_thread_to(Priority::Normal, thread, args, Some(deadline))
+    }
+
+    /**
+        Spawns a chunk / function / thread onto the scheduler queue, with a timeout.
+
+        This is the same as [`Scheduler::push_thread_with_deadline`], with the deadline set to
+        the given duration from now. If the thread has not completed in time, it is cancelled,
+        and a timeout error is stored as its result. This is useful for running untrusted
+        scripts that may never complete - note that scripts that never yield at all
+        also need a yield budget, see [`Scheduler::set_yield_budget`].
+
+        # Errors
+
+        Errors when out of memory, if the scheduler is draining, or with [`LuaError::CoroutineInactive`]
+        if the given thread has already completed, or [`ThreadErrored`] if it errored, in which case it
+        is never pushed to the queue, and an immediate result is stored for it if it is being tracked.
+
+        [`ThreadErrored`]: crate::ThreadErrored
+    */
+    pub fn push_thread_with_timeout(
+        &self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+        timeout: Duration,
+    ) -> LuaResult<ThreadId> {
+        self.push_thread_with_deadline(thread, args, instant_after(timeout))
+    }
+
+    /**
+        Spawns a chunk / function / thread onto the scheduler queue, with temporary global overrides.
+
+        The given globals are installed right before the thread is first resumed, and removed once
+        it completes, errors, or is cancelled, restoring whatever values they had before. This is
+        useful for per-invocation context, such as `script` or `request` objects.
+
+        Note that globals are shared by all threads, so other threads that run while the overrides are
+        installed will also see them. If multiple threads override the same global, the most recently
+        installed override is the one that is visible, regardless of which order the threads complete in.
+
+        See [`Scheduler::push_thread_front`] for more information.
+
+        # Errors
+
+        Errors when out of memory, if the scheduler is draining, if any of the given globals do not have
+        string keys, or with [`LuaError::CoroutineInactive`] if the given thread has already completed,
+        or [`ThreadErrored`] if it errored, in which case it is never pushed to the queue, and an
+        immediate result is stored for it if it is being tracked.
+
+        [`ThreadErrored`]: crate::ThreadErrored
+    */
+    pub fn push_thread_with_globals(
+        &self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+        globals: &LuaTable<'lua>,
+    ) -> LuaResult<ThreadId> {
+        let overrides = ScopedGlobals::collect(self.lua, globals)?;
+        let id = self.push_thread_to(Priority::Normal, thread, args, None)?;
+        self.result_map.scoped_globals().set(id, overrides);
+        Ok(id)
+    }
+
+    /**
+        Schedules a chunk / function / thread to be resumed once the given delay has elapsed.
+
+        Delayed threads share a single timer with the scheduler, so delaying thousands of threads
+        is cheap, and does not require creating a wrapper thread that sleeps before running.
+        Threads that are due at the same time are guaranteed to be resumed in the order
+        that they were pushed, and threads that are cancelled before then are skipped.
+
+        # Returns
+
+        Returns a [`ThreadId`] that can be used to retrieve the result of the thread.
+
+        Note that the result may not be available until [`Scheduler::run`] completes.
+
+        # Errors
+
+        Errors when out of memory, if the scheduler is draining, or with [`LuaError::CoroutineInactive`]
+        if the given thread has already completed, or [`ThreadErrored`] if it errored, in which case it
+        is never delayed, and an immediate result is stored for it if it is being tracked.
+
+        [`ThreadErrored`]: crate::ThreadErrored
+    */
+    pub fn push_thread_delayed(
+        &self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+        delay: Duration,
+    ) -> LuaResult<ThreadId> {
+        let (id, thread, args) = self.prepare_push(thread, args, None)?;
+        self.delayed.push_checked(self.lua, thread, args, delay)?;
+        Ok(id)
+    }
+
+    fn push_thread_to(
+        &self,
+        priority: Priority,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+        deadline: Option<Instant>,
+    ) -> LuaResult<ThreadId> {
+        let queue = self.queue_for(priority);
+        queue.check_capacity()?;
+        let (id, thread, args) = self.prepare_push(thread, args, deadline)?;
+        queue.push_checked(self.lua, thread, args)?;
+        self.record_push(priority);
+        Ok(id)
+    }
+
+    fn push_thread_with_to(
+        &self,
+        priority: Priority,
+        thread: impl IntoLuaThread<'lua>,
+        args: LazyArgs,
+    ) -> LuaResult<ThreadId> {
+        let queue = self.queue_for(priority);
+        queue.check_capacity()?;
+        // NOTE: Lazy arguments are not known until the thread is about
+        // to be resumed, so there is no origin to retain for respawning
+        let (id, thread, _) = self.prepare_thread(thread)?;
+        queue.push_checked_with(self.lua, thread, args)?;
+        self.record_push(priority);
+        Ok(id)
+    }
+
+    fn queue_for(&self, priority: Priority) -> &ThreadQueue {
+        match priority {
+            Priority::High => &self.queue_high,
+            Priority::Normal => &self.queue_spawn,
+            Priority::Low => &self.queue_defer,
+        }
+    }
+
+    fn record_push(&self, priority: Priority) {
+        if priority == Priority::Low {
+            self.stats.record_deferred();
+        } else {
+            self.stats.record_spawned();
+        }
+    }
+
+    fn prepare_push(
+        &self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+        deadline: Option<Instant>,
+    ) -> LuaResult<(ThreadId, LuaThread<'lua>, LuaMultiValue<'lua>)> {
+        let (id, thread, function) = self.prepare_thread(thread)?;
+        let args = args.into_lua_multi(self.lua)?;
+        if let Some(function) = function {
+            self.origins
+                .record(self.lua, ThreadId::from(&thread), function, &args)?;
+        }
+        if let Some(deadline) = deadline {
+            self.deadlines.set(self.lua, &thread, deadline)?;
+        }
+        Ok((id, thread, args))
+    }
+
+    fn prepare_thread(
+        &self,
+        thread: impl IntoLuaThread<'lua>,
+    ) -> LuaResult<(ThreadId, LuaThread<'lua>, Option<LuaFunction<'lua>>)> {
+        self.drain.check()?;
+        let (thread, function) = thread.into_lua_thread_with_function(self.lua)?;
+        self.strict.check_owned(self.lua, &thread)?;
+        // NOTE: Threads that can not be resumed are rejected before any other state is
+        // recorded for them, and are only tracked so that their immediate result can be
+        // taken, which also means they never leave an origin or deadline behind
+        if thread.status() != LuaThreadStatus::Resumable {
+            self.result_map.track_thread(ThreadId::from(&thread));
+            return Err(self
+                .strict
+                .explain_push(ThreadQueue::reject(self.lua, &thread)));
+        }
+        let id = self.result_map.track_thread(ThreadId::from(&thread));
+        self.records.record_push(self.lua, &thread)?;
+        Ok((id, thread, function))
+    }
+
+    /**
+        Spawns a chunk / function / thread onto the scheduler queue, with lazily created arguments.
+
+        The given function is called to create the arguments for the thread once it
+        is about to be resumed, instead of when it is pushed, and is never called if
+        the thread is cancelled or completes before then. If it errors, the thread is
+        not resumed and the error is stored as the result of the thread. Since the arguments
+        are not known when the thread is pushed, it can not be used with [`Scheduler::respawn`].
+
+        See [`Scheduler::push_thread_front`] for more information.
+
+        # Errors
+
+        Errors when out of memory, if the scheduler is draining, or with [`LuaError::CoroutineInactive`]
+        if the given thread has already completed, or [`ThreadErrored`] if it errored, in which case it
+        is never pushed to the queue, and an immediate result is stored for it if it is being tracked.
+
+        [`ThreadErrored`]: crate::ThreadErrored
+    */
+    pub fn push_thread_front_with(
+        &self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl for<'a> FnOnce(&'a Lua) -> LuaResult<LuaMultiValue<'a>> + 'static,
+    ) -> LuaResult<ThreadId> {
+        self.push_thread_with_to(Priority::Normal, thread, Box::new(args))
+    }
+
+    /**
+        Defers a chunk / function / thread onto the scheduler queue, with lazily created arguments.
+
+        The given function is called to create the arguments for the thread once it
+        is about to be resumed, instead of when it is pushed, and is never called if
+        the thread is cancelled or completes before then. If it errors, the thread is
+        not resumed and the error is stored as the result of the thread. Since the arguments
+        are not known when the thread is pushed, it can not be used with [`Scheduler::respawn`].
+
+        See [`Scheduler::push_thread_back`] for more information.
+
+        # Errors
+
+        Errors when out of memory, if the scheduler is draining, or with [`LuaError::CoroutineInactive`]
+        if the given thread has already completed, or [`ThreadErrored`] if it errored, in which case it
+        is never pushed to the queue, and an immediate result is stored for it if it is being tracked.
+
+        [`ThreadErrored`]: crate::ThreadErrored
+    */
+    pub fn push_thread_back_with(
+        &self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl for<'a> FnOnce(&'a Lua) -> LuaResult<LuaMultiValue<'a>> + 'static,
+    ) -> LuaResult<ThreadId> {
+        self.push_thread_with_to(Priority::Low, thread, Box::new(args))
+    }
+
+    /**
+        Sets the default Luau compiler settings for chunks pushed using [`Scheduler::push_chunk`].
+
+        This lets hosts compile production scripts with full optimizations and no debug info,
+        while still compiling other chunks, such as REPL input, with full debug info per push.
+
+        # Panics
+
+        Panics if the scheduler is currently running.
+    */
+    pub fn set_chunk_options(&self, options: ChunkOptions) {
+        assert!(!self.status().is_running(), "{ERR_SET_MODE_WHEN_RUNNING}");
+        self.chunk_options.set(options);
+    }
+
+    /**
+        Compiles the given Luau source and spawns it onto the scheduler queue.
+
+        The source is compiled using the given options, or the default options for this scheduler
+        if none are given, see [`Scheduler::set_chunk_options`]. The given name is used as the
+        chunk name in error messages and tracebacks, following the usual Luau conventions.
+
+        With [`ChunkOptions::yield_budget`] set, the thread running the chunk is given that yield budget,
+        the same as using [`Scheduler::set_thread_yield_budget`] right after pushing it, meaning it is
+        enforced using the Luau interrupt of this scheduler, without changing the source of the chunk.
+
+        See [`Scheduler::push_thread_front`] for more information.
+
+        # Errors
+
+        Errors if the source could not be compiled, when out of memory, or if the scheduler is draining.
+    */
+    pub fn push_chunk(
+        &self,
+        name: impl Into<String>,
+        source: impl AsRef<[u8]>,
+        options: Option<ChunkOptions>,
+    ) -> LuaResult<ThreadId> {
+        let options = options.unwrap_or_else(|| self.chunk_options.get());
+        let id = self.push_bytecode(name, options.compile(source))?;
+        if let Some(budget) = options.yield_budget {
+            self.preemption.set_budget(self.lua, id.base(), budget);
+        }
+        Ok(id)
+    }
+
+    /**
+        Spawns the given precompiled Luau bytecode onto the scheduler queue.
+
+        Compiler settings are baked into bytecode, so to compile bytecode with
+        specific settings ahead of time, use [`ChunkOptions::compile`].
+
+        See [`Scheduler::push_thread_front`] for more information.
+
+        # Errors
+
+        Errors if the bytecode is invalid or contains a compilation error,
+        when out of memory, or if the scheduler is draining.
+    */
+    pub fn push_bytecode(
+        &self,
+        name: impl Into<String>,
+        bytecode: impl AsRef<[u8]>,
+    ) -> LuaResult<ThreadId> {
+        let chunk = self.lua.load(bytecode.as_ref()).set_name(name);
+        self.push_thread_front(chunk, ())
+    }
+
+    /**
+        Promotes a deferred thread, moving it to the front of the spawned queue.
+
+        This lets a deferred thread that became urgent, such as when user input arrives,
+        run before any other queued threads, keeping any arguments it was deferred with.
+
+        Returns `true` if the thread was promoted, and `false` if it was not in the deferred
+        queue - for example if it was never deferred, or has already been resumed since.
+        If a thread was deferred multiple times, only its first queued resumption is promoted.
+
+        # Errors
+
+        Errors if the thread could not be pushed to the spawned queue.
+    */
+    pub fn promote(&self, id: ThreadId) -> LuaResult<bool> {
+        match self.queue_defer.take_item(id.base()) {
+            Some(stored) => {
+                self.queue_spawn.push_item_front(stored);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /**
+        Pushes a chunk / function / thread onto the idle queue.
+
+        Idle threads are only resumed when there is no other work to be done - when there are no
+        spawned or deferred threads waiting to run, and no futures are ready to make progress.
+        A single idle thread is resumed at a time, so that other work may interrupt idle work.
+
+        Threads are guaranteed to be resumed in the order that they were pushed to the queue.
+
+        # Returns
+
+        Returns a [`ThreadId`] that can be used to retrieve the result of the thread.
+
+        Note that the result may not be available until [`Scheduler::run`] completes.
+
+        # Errors
+
+        Errors when out of memory, if the scheduler is draining, or with [`LuaError::CoroutineInactive`]
+        if the given thread has already completed, or [`ThreadErrored`] if it errored, in which case it
+        is never pushed to the queue, and an immediate result is stored for it if it is being tracked.
+
+        [`ThreadErrored`]: crate::ThreadErrored
+    */
+    pub fn push_thread_idle(
+        &self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+    ) -> LuaResult<ThreadId> {
+        let queue = self.idle.threads();
+        queue.check_capacity()?;
+        let (id, thread, args) = self.prepare_push(thread, args, None)?;
+        queue.push_checked(self.lua, thread, args)?;
+        self.stats.record_deferred();
+        Ok(id)
+    }
+
+    /**
+        Returns statistics about idle work processed by this scheduler.
+
+        See [`Scheduler::push_thread_idle`] and [`LuaSpawnExt::spawn_idle`] for more information.
+
+        [`LuaSpawnExt::spawn_idle`]: crate::LuaSpawnExt::spawn_idle
+    */
+    #[must_use]
+    pub fn idle_stats(&self) -> IdleStats {
+        self.idle.stats()
+    }
+
+    /**
+        Returns statistics about how often this scheduler woke up, and how long it spent parked.
+
+        The scheduler already parks on a single combined future whenever it has no work to do,
+        so these statistics are meant for verifying that, such as that threads sleeping on long
+        timers do not cause frequent wakeups. See [`WakeupStats`] for more information.
+    */
+    #[must_use]
+    pub fn wakeup_stats(&self) -> WakeupStats {
+        self.wakeups.stats()
+    }
+
+    /**
+        Returns counters for the work done by this scheduler, such as the number of
+        threads spawned, resumed and errored, suitable for exporting as metrics.
+
+        See [`SchedulerStats`] for more information.
+    */
+    #[must_use]
+    pub fn stats(&self) -> SchedulerStats {
+        let queued = self.queue_high.len()
+            + self.queue_spawn.len()
+            + self.queue_defer.len()
+            + self.idle.threads().len()
+            + self.ticks.num_threads(self.lua)
+            + self.delayed.len();
+        SchedulerStats {
+            threads_alive: queued + self.native.len() + self.stats.running(),
+            cycles: self.cycles.current(),
+            ..self.stats.stats()
+        }
+    }
+
+    /**
+        Gets the tracked result for the [`LuaThread`] with the given [`ThreadId`].
+
+        Depending on the current [`Scheduler::status`], this method will return:
+
+        - [`Status::NotStarted`]: returns `None`.
+        - [`Status::Running`]: may return `Some(Ok(v))` or `Some(Err(e))`, but it is not guaranteed.
+        - [`Status::Completed`]: returns `Some(Ok(v))` or `Some(Err(e))`.
+
+        Note that this method also takes the value out of the scheduler and
+        stops tracking the given thread, so it may only be called once.
+
+        Any subsequent calls after this method returns `Some` will return `None`, or
+        a descriptive error if strict mode is enabled, see [`Scheduler::set_strict`].
+    */
+    #[must_use]
+    pub fn get_thread_result(&self, id: ThreadId) -> Option<LuaResult<LuaMultiValue<'lua>>> {
+        if self.strict.is_enabled() && !self.result_map.is_tracked(id) {
+            return Some(Err(StrictMode::untracked_result()));
+        }
+        self.result_map.remove(id).map(|r| r.value(self.lua))
+    }
+
+    /**
+        Gets a copy of the tracked result for the [`LuaThread`] with the given [`ThreadId`].
+
+        This works just like [`Scheduler::get_thread_result`], but leaves the result in the
+        scheduler and keeps tracking the thread, so that it may be observed any number of
+        times, such as by multiple host systems that are all interested in the same thread.
+
+        Returned values are shared with the stored result, meaning that tables and other
+        reference types are the same objects every time, and are not deep copies.
+
+        The result stays stored until it is taken using [`Scheduler::get_thread_result`].
+    */
+    #[must_use]
+    pub fn peek_thread_result(&self, id: ThreadId) -> Option<LuaResult<LuaMultiValue<'lua>>> {
+        if self.strict.is_enabled() && !self.result_map.is_tracked(id) {
+            return Some(Err(StrictMode::untracked_result()));
+        }
+        self.result_map.peek(self.lua, id)
+    }
+
+    /**
+        Returns a stream of tracked [`LuaThread`]s as they complete, along with their results.
+
+        This is useful for long-lived schedulers that have threads pushed to them continuously,
+        such as job servers, where waiting for [`Scheduler::run`] to complete before retrieving
+        results is not an option. Results are taken out of the scheduler the same way as with
+        [`Scheduler::get_thread_result`], and each result is only ever yielded once, even if
+        multiple streams are alive at the same time.
+
+        Only threads that complete while a stream is alive are yielded, and the stream never
+        ends on its own, so it should be polled alongside the scheduler, for example using
+        [`Scheduler::run_until`], or in a separate task that is dropped once done.
+    */
+    pub fn results_stream(
+        &self,
+    ) -> impl Stream<Item = (ThreadId, LuaResult<LuaMultiValue<'lua>>)> + 'lua {
+        let lua = self.lua;
+        let result_map = self.result_map.clone();
+        let guard = result_map.completed().register();
+        stream::unfold((guard, result_map), move |(guard, result_map)| async move {
+            loop {
+                let id = guard.completed().next().await;
+                // NOTE: The result may have already been taken by
+                // the host, in which case the thread is skipped
+                if let Some(result) = result_map.remove(id) {
+                    let item = (id, result.value(lua));
+                    return Some((item, (guard, result_map)));
+                }
+            }
+        })
+    }
+
+    /**
+        Fires a tick, resuming all threads currently waiting for one with the given delta time.
+
+        Threads wait for ticks using [`Functions::heartbeat`], and are resumed during
+        a separate phase of the scheduler, before any spawned or deferred threads.
 
-        This callback will be called whenever a Lua thread errors.
+        Note that threads waiting for a tick will keep the scheduler running,
+        so the host must keep firing ticks for those threads to complete.
 
-        Overwrites any previous error callback.
+        # Errors
 
-        # Panics
+        Errors when out of memory.
 
-        Panics if the scheduler is currently running.
+        [`Functions::heartbeat`]: crate::Functions::heartbeat
     */
-    pub fn set_error_callback(&self, callback: impl Fn(LuaError) + Send + 'static) {
-        assert!(
-            !self.status().is_running(),
-            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
-        );
-        self.error_callback.replace(callback);
+    pub fn fire_tick(&self, dt: Duration) -> LuaResult<()> {
+        self.ticks.fire(self.lua, dt.as_secs_f64())
     }
 
     /**
-        Clears the error callback for this scheduler.
-
-        This will remove any current error callback, including default(s).
+        Waits for this scheduler to stop running, returning the reason it stopped.
 
-        # Panics
+        If the scheduler is not currently running, this will wait for the next run to stop,
+        unless the scheduler has already run and stopped, in which case the reason for the
+        last stop is returned instantly.
 
-        Panics if the scheduler is currently running.
+        The returned future does not borrow the scheduler, and is [`Send`], so it may
+        be awaited from other async tasks, including ones running on other threads,
+        letting hosts react to exits initiated by scripts.
     */
-    pub fn remove_error_callback(&self) {
-        assert!(
-            !self.status().is_running(),
-            "{ERR_SET_CALLBACK_WHEN_RUNNING}"
-        );
-        self.error_callback.clear();
+    pub fn wait_for_exit(&self) -> impl Future<Output = ExitReason> + Send + 'static {
+        self.exit_watch.clone().wait()
     }
 
     /**
-        Gets the exit code for this scheduler, if one has been set.
+        Waits for the [`LuaThread`] with the given [`ThreadId`] to complete.
+
+        This will return instantly if the thread has already completed, or if the
+        thread is not tracked, since no result will ever be stored for it.
     */
-    #[must_use]
-    pub fn get_exit_code(&self) -> Option<ExitCode> {
-        self.exit.get()
+    pub async fn wait_for_thread(&self, id: ThreadId) {
+        if self.result_map.is_tracked(id) {
+            self.result_map.listen(id).await;
+        }
     }
 
     /**
-        Sets the exit code for this scheduler.
+        Waits for the [`LuaThread`] with the given [`ThreadId`] to complete, and returns its result.
+
+        This combines [`Scheduler::wait_for_thread`] and [`Scheduler::get_thread_result`], taking
+        the result out of the scheduler the same way, so it may only be awaited once per thread.
+
+        # Errors
 
-        This will cause [`Scheduler::run`] to exit immediately.
+        Errors if the thread errored, or if the thread is not tracked, or its result was already
+        taken, instead of waiting forever for a result that will never be stored.
     */
-    pub fn set_exit_code(&self, code: ExitCode) {
-        self.exit.set(code);
+    pub async fn join_thread(&self, id: ThreadId) -> LuaResult<LuaMultiValue<'lua>> {
+        self.thread_handle(id).join().await
     }
 
     /**
-        Spawns a chunk / function / thread onto the scheduler queue.
+        Creates a [`ThreadHandle`] for the [`LuaThread`] with the given [`ThreadId`].
 
-        Threads are guaranteed to be resumed in the order that they were pushed to the queue.
+        Handles combine [`Scheduler::get_thread_result`] and [`Scheduler::wait_for_thread`],
+        and may also be used to cancel the thread, similar to a `JoinHandle`:
 
-        # Returns
+        ```rust
+        use async_io::block_on;
+        use futures_lite::future::zip;
 
-        Returns a [`ThreadId`] that can be used to retrieve the result of the thread.
+        use mlua::prelude::*;
+        use mlua_luau_scheduler::*;
 
-        Note that the result may not be available until [`Scheduler::run`] completes.
+        fn main() -> LuaResult<()> {
+            let lua = Lua::new();
+            let sched = Scheduler::new(&lua);
 
-        # Errors
+            let id = sched.push_thread_front(lua.load("return 1 + 1"), ())?;
+            let handle = sched.thread_handle(id);
 
-        Errors when out of memory.
+            let (_, result) = block_on(zip(sched.run(), handle.join()));
+            assert_eq!(result?.into_vec(), vec![LuaValue::Number(2.0)]);
+
+            Ok(())
+        }
+        ```
     */
-    pub fn push_thread_front(
-        &self,
-        thread: impl IntoLuaThread<'lua>,
-        args: impl IntoLuaMulti<'lua>,
-    ) -> LuaResult<ThreadId> {
-        let id = self.queue_spawn.push_item(self.lua, thread, args)?;
-        self.result_map.track(id);
-        Ok(id)
+    #[must_use]
+    pub fn thread_handle(&self, id: ThreadId) -> ThreadHandle<'lua> {
+        ThreadHandle::new(
+            self.lua,
+            id,
+            self.result_map.clone(),
+            self.records.clone(),
+            self.cancellation.clone(),
+        )
     }
 
     /**
-        Defers a chunk / function / thread onto the scheduler queue.
+        Runs the scheduler until all Lua threads have completed.
 
-        Deferred threads are guaranteed to run after all spawned threads either yield or complete.
+        Note that the given Lua state must be the same one that was
+        used to create this scheduler, otherwise this method will panic.
 
-        Threads are guaranteed to be resumed in the order that they were pushed to the queue.
+        # Panics
 
-        # Returns
+        Panics if the given Lua state already has a scheduler attached to it.
+    */
+    pub async fn run(&self) {
+        self.run_until(future::pending::<()>()).await;
+    }
 
-        Returns a [`ThreadId`] that can be used to retrieve the result of the thread.
+    /**
+        Runs the scheduler forever, until an exit code is set, waiting for more work whenever it is idle.
 
-        Note that the result may not be available until [`Scheduler::run`] completes.
+        This is the same as holding a guard from [`Scheduler::keep_alive`] while running the scheduler,
+        and is useful for long-lived embeddings, where the host keeps pushing threads into the scheduler.
 
-        # Errors
+        # Panics
 
-        Errors when out of memory.
+        Panics if the given Lua state already has a scheduler attached to it.
     */
-    pub fn push_thread_back(
-        &self,
-        thread: impl IntoLuaThread<'lua>,
-        args: impl IntoLuaMulti<'lua>,
-    ) -> LuaResult<ThreadId> {
-        let id = self.queue_defer.push_item(self.lua, thread, args)?;
-        self.result_map.track(id);
-        Ok(id)
+    pub async fn run_forever(&self) {
+        let _keep_alive = self.keep_alive();
+        self.run().await;
     }
 
     /**
-        Gets the tracked result for the [`LuaThread`] with the given [`ThreadId`].
+        Creates a [`SchedulerStepper`] that runs this scheduler one iteration at a time.
 
-        Depending on the current [`Scheduler::status`], this method will return:
+        This is useful for embedding the scheduler inside of an existing loop, such as the frame
+        loop of a game, giving the scheduler a bounded slice of each frame instead of using
+        [`Scheduler::run`], which blocks until all Lua threads have completed:
 
-        - [`Status::NotStarted`]: returns `None`.
-        - [`Status::Running`]: may return `Some(Ok(v))` or `Some(Err(e))`, but it is not guaranteed.
-        - [`Status::Completed`]: returns `Some(Ok(v))` or `Some(Err(e))`.
+        ```rust
+        use async_io::block_on;
 
-        Note that this method also takes the value out of the scheduler and
-        stops tracking the given thread, so it may only be called once.
+        use mlua::prelude::*;
+        use mlua_luau_scheduler::*;
 
-        Any subsequent calls after this method returns `Some` will return `None`.
-    */
-    #[must_use]
-    pub fn get_thread_result(&self, id: ThreadId) -> Option<LuaResult<LuaMultiValue<'lua>>> {
-        self.result_map.remove(id).map(|r| r.value(self.lua))
-    }
+        fn main() -> LuaResult<()> {
+            let lua = Lua::new();
+            let sched = Scheduler::new(&lua);
 
-    /**
-        Waits for the [`LuaThread`] with the given [`ThreadId`] to complete.
+            let id = sched.push_thread_front(lua.load("return 1 + 1"), ())?;
+
+            let mut stepper = sched.stepper();
+            while block_on(stepper.step()) {
+                // Render a frame, handle input, ...
+            }
+
+            let result = sched.get_thread_result(id).unwrap()?;
+            assert_eq!(result.into_vec(), vec![LuaValue::Number(2.0)]);
+
+            Ok(())
+        }
+        ```
+
+        The stepper finishes the same way as running the scheduler would, so a guard from
+        [`Scheduler::keep_alive`] may be held to keep stepping while there is no work to do.
 
-        This will return instantly if the thread has already completed.
+        # Panics
+
+        Panics if the given Lua state already has a scheduler attached to it.
     */
-    pub async fn wait_for_thread(&self, id: ThreadId) {
-        self.result_map.listen(id).await;
+    pub fn stepper(&self) -> SchedulerStepper<'_, 'lua> {
+        SchedulerStepper::new(self, self.steps.clone())
     }
 
     /**
-        Runs the scheduler until all Lua threads have completed.
+        Runs the scheduler until all Lua threads have completed, or until the given future completes.
+
+        The given future, typically a shutdown signal from the host, is raced against all other
+        work in the scheduler, and stops the scheduler the same way as setting an exit code would.
+        Unlike an exit code, stopping the scheduler this way will not leave any exit code set.
 
         Note that the given Lua state must be the same one that was
         used to create this scheduler, otherwise this method will panic.
@@ -272,8 +3170,8 @@ impl<'lua> Scheduler<'lua> {
         Panics if the given Lua state already has a scheduler attached to it.
     */
     #[allow(clippy::too_many_lines)]
-    #[instrument(level = "debug", name = "Scheduler::run", skip(self))]
-    pub async fn run(&self) {
+    #[instrument(level = "debug", name = "Scheduler::run", skip(self, stop))]
+    pub async fn run_until(&self, stop: impl Future<Output = ()>) {
         /*
             Create new executors to use - note that we do not need create multiple executors
             for work stealing, the user may do that themselves if they want to and it will work
@@ -281,12 +3179,13 @@ impl<'lua> Scheduler<'lua> {
 
             The main purpose of the two executors here is just to have one with
             the Send bound, and another (local) one without it, for Lua scheduling.
+            In single-threaded mode, the main executor is also a local executor.
 
             We also use the main executor to drive the main loop below forward,
             saving a tiny bit of processing from going on the Lua executor itself.
         */
         let local_exec = LocalExecutor::new();
-        let main_exec = Arc::new(Executor::new());
+        let main_exec = MainExecutor::new(self.is_single_threaded() || self.is_deterministic());
         let fut_queue = Rc::new(FuturesQueue::new());
 
         /*
@@ -296,114 +3195,383 @@ impl<'lua> Scheduler<'lua> {
             and may happen if the user tries to run multiple schedulers on the same Lua state at once.
         */
         assert!(
-            self.lua.app_data_ref::<WeakArc<Executor>>().is_none(),
+            self.lua.app_data_ref::<WeakRc<FuturesQueue>>().is_none(),
             "{ERR_METADATA_ALREADY_ATTACHED}"
         );
         assert!(
-            self.lua.app_data_ref::<WeakRc<FuturesQueue>>().is_none(),
+            main_exec.attach(self.lua),
             "{ERR_METADATA_ALREADY_ATTACHED}"
         );
 
-        self.lua.set_app_data(Arc::downgrade(&main_exec));
         self.lua.set_app_data(Rc::downgrade(&fut_queue.clone()));
 
         /*
             Manually tick the Lua executor, while running under the main executor.
             Each tick we wait for the next action to perform, in prioritized order:
 
-            1. The exit event is triggered by setting an exit code
-            2. A Lua thread is available to run on the spawned queue
-            3. A Lua thread is available to run on the deferred queue
-            4. A new thread-local future is available to run on the local executor
-            5. Task(s) scheduled on the Lua executor have made progress and should be polled again
+            1. The exit event is triggered by setting an exit code, or the stop future completes
+            2. A Lua thread is available to run on the tick queue
+            3. A Lua thread is available to run on the spawned queue, or was pushed from another thread
+            4. A native async function has completed, and its Lua thread is available to run
+            5. A supervised service is ready to be restarted
+            6. A Lua thread is available to run on the deferred queue
+            7. A new thread-local future is available to run on the local executor
+            8. Task(s) scheduled on the Lua executor have made progress and should be polled again
+            9. A Lua thread or future is available to run on the idle queue
 
             This ordering is vital to ensure that we don't accidentally exit the main loop
             when there are new Lua threads to enqueue and potentially more work to be done.
+
+            Note that the or() chain below is biased and always polls in the order above,
+            which is part of what makes the deterministic scheduling guarantees hold.
         */
+        let stopped = Cell::new(false);
+        let mut stop = pin!(stop);
         let fut = async {
             let result_map = self.result_map.clone();
-            let process_thread = |thread: LuaThread<'lua>, args| {
+            let create_thread_fut = |thread: LuaThread<'lua>, args| {
                 // NOTE: Thread may have been cancelled from Lua
                 // before we got here, so we need to check it again
                 if thread.status() == LuaThreadStatus::Resumable {
                     // Check if we should be tracking this thread
                     let id = ThreadId::from(&thread);
-                    let id_tracked = result_map.is_tracked(id);
+                    let tracked_id = result_map.current(id);
+                    let id_tracked = result_map.is_tracked(tracked_id);
                     let result_map_inner = if id_tracked {
                         Some(result_map.clone())
                     } else {
                         None
                     };
+                    // Attribute the thread, in case it blocks the executor for too long
+                    let tag = self.diagnostics.tag_for(self.lua, &self.tags, &thread);
+                    // Run every resumption within its own span, to trace it back to the thread
+                    let span = self.spans.span(self.lua, &thread);
                     // Create our future which will run the thread and store its final result
+                    let running = self.stats.running_guard();
                     let fut = async move {
-                        if id_tracked {
-                            // Run until yield and check if we got a final result
-                            if let Some(res) = run_until_yield(thread.clone(), args).await {
-                                if let Err(e) = res.as_ref() {
-                                    self.error_callback.call(e);
+                        let _running = running;
+                        // Install any scoped globals right before the first resume
+                        if let Err(e) = self
+                            .result_map
+                            .scoped_globals()
+                            .install(self.lua, tracked_id)
+                        {
+                            self.error_callback.call(&e);
+                        }
+                        // Run until yield and check if we got a final result, making sure
+                        // that Lua can not resume the thread while it is awaiting async work
+                        let fut_run = run_until_yield(thread.clone(), args);
+                        #[cfg(feature = "debug-info")]
+                        let fut_run = self.spans.located(self.lua, thread.clone(), fut_run);
+                        let fut_run = self
+                            .preemption
+                            .sliced(id, self.suspended.gate(thread.clone(), fut_run));
+                        let awaiting = self.awaiting.guard(id);
+                        // NOTE: Threads stuck inside of an async function can not
+                        // be resumed with an error, so we close them instead
+                        let close_thread = || {
+                            if let Ok(close) = self.primitives.get(self.lua, "close") {
+                                let _ = close.call::<_, ()>(thread.clone());
+                            }
+                        };
+                        let res = if self.deadlines.is_expired(id) {
+                            close_thread();
+                            Some(Err(Deadlines::error()))
+                        } else {
+                            self.stats.record_resumed();
+                            self.resume_hooks.before(id);
+                            #[cfg(feature = "unstable")]
+                            self.plugins
+                                .thread_event(self.lua, || ThreadEvent::Resumed(id));
+                            let fut_watched = self.watchdog.watch(fut_run, Some(id));
+                            let watched = match self.deadlines.get(id) {
+                                Some(deadline) => {
+                                    self.clock.timeout_at(deadline, fut_watched).await
                                 }
-                                if thread.status() != LuaThreadStatus::Resumable {
-                                    let thread_res = ThreadResult::new(res, self.lua);
-                                    result_map_inner.unwrap().insert(id, thread_res);
+                                None => Some(fut_watched.await),
+                            };
+                            let res = match watched {
+                                Some(Ok(res)) => res,
+                                Some(Err(lifetime)) => {
+                                    close_thread();
+                                    Some(Err(Watchdog::timeout_error(lifetime)))
                                 }
+                                None => {
+                                    close_thread();
+                                    Some(Err(Deadlines::error()))
+                                }
+                            };
+                            match &res {
+                                Some(res) => self.resume_hooks.after(id, res),
+                                None => self
+                                    .resume_hooks
+                                    .after(id, &Err(LuaError::CoroutineInactive)),
                             }
-                        } else {
-                            // Just run until yield
-                            if let Some(res) = run_until_yield(thread, args).await {
-                                if let Err(e) = res.as_ref() {
-                                    self.error_callback.call(e);
+                            res
+                        };
+                        drop(awaiting);
+                        let preempted = self.preemption.take_yielded(id);
+                        let res = res.map(|res| {
+                            res.map_err(|e| self.error_values.attach(self.lua, &thread, e))
+                        });
+                        if let Some(res) = res {
+                            if let (Some(_), Ok(values)) = (&result_map_inner, &res) {
+                                let kind = if thread.status() == LuaThreadStatus::Resumable {
+                                    ValueKind::Yielded
+                                } else {
+                                    ValueKind::Returned
+                                };
+                                self.value_log.record(id, kind, values);
+                            }
+                            #[cfg(feature = "unstable")]
+                            self.plugins.thread_event(self.lua, || match &res {
+                                Err(e) => ThreadEvent::Errored(id, e.clone()),
+                                Ok(_) if thread.status() == LuaThreadStatus::Resumable => {
+                                    ThreadEvent::Yielded(id)
+                                }
+                                Ok(_) => ThreadEvent::Completed(id),
+                            });
+                            if let Err(e) = res.as_ref() {
+                                self.stats.record_errored();
+                                self.error_callback.call_thread(self.lua, &thread, e);
+                            }
+                            if thread.status() == LuaThreadStatus::Resumable {
+                                // Automatically yielded threads must be re-queued to keep running
+                                if preempted {
+                                    if let Err(e) =
+                                        self.queue_defer.push_item_unbounded(self.lua, thread, ())
+                                    {
+                                        self.error_callback.call(&e);
+                                    }
+                                } else if let Ok(values) = res {
+                                    // Yielded values may be handled by the host, which may
+                                    // also decide to resume the thread during the next cycle
+                                    if !values.is_empty() {
+                                        let resumed = self
+                                            .yield_handler
+                                            .call(self.lua, id, values)
+                                            .and_then(|args| match args {
+                                                Some(args) => self
+                                                    .queue_defer
+                                                    .push_item_unbounded(self.lua, thread, args)
+                                                    .map(|_| ()),
+                                                None => Ok(()),
+                                            });
+                                        if let Err(e) = resumed {
+                                            self.error_callback.call(&e);
+                                        }
+                                    }
+                                }
+                            } else {
+                                self.preemption.remove_budget(id);
+                                self.deadlines.remove(id);
+                                if !self.drain.is_draining() {
+                                    self.supervisor.handle_result(self.lua, id, &res);
                                 }
+                                self.result_map.complete(self.lua, id, res);
                             }
                         }
                     };
+                    Some(
+                        self.diagnostics
+                            .monitor(fut.instrument(span), Some(id), tag),
+                    )
+                } else {
+                    // NOTE: Thread may also have been marked as awaiting
+                    // when it was queued, which we must now undo, and it
+                    // will never complete, so any callbacks will never fire
+                    self.awaiting.remove(ThreadId::from(&thread));
+                    result_map.abandon(self.lua, ThreadId::from(&thread));
+                    None
+                }
+            };
+            let process_thread = |thread: LuaThread<'lua>, args| {
+                if let Some(fut) = create_thread_fut(thread, args) {
                     // Spawn it on the executor
-                    local_exec.spawn(fut).detach();
+                    local_exec.spawn(self.wakeups.waking(fut)).detach();
                 }
             };
 
+            let is_completed = || {
+                local_exec.is_empty()
+                    && self.queue_high.is_empty()
+                    && self.queue_spawn.is_empty()
+                    && self.remote.is_empty()
+                    && self.native.is_empty()
+                    && self.supervisor.is_empty()
+                    && self.queue_defer.is_empty()
+                    && self.delayed.is_empty()
+                    && self.ticks.is_empty()
+                    && self.idle.is_empty()
+                    && !self.ticks.has_waiters(self.lua)
+                    && (!self.keep_alives.is_held() || self.drain.is_draining())
+            };
+
+            // NOTE: Threads may have been cancelled before the scheduler started running,
+            // leaving nothing in any queue to wake it up, so check this before waiting
+            if is_completed() && self.exit.get().is_none() {
+                return ExitReason::Completed;
+            }
+
             loop {
                 let fut_exit = self.exit.listen(); // 1
-                let fut_spawn = self.queue_spawn.wait_for_item(); // 2
-                let fut_defer = self.queue_defer.wait_for_item(); // 3
-                let fut_futs = fut_queue.wait_for_item(); // 4
-
-                // 5
+                let fut_stop = async {
+                    stop.as_mut().await;
+                    stopped.set(true);
+                }; // 1
+                let fut_high = self.queue_high.wait_for_item(); // 2
+                let fut_ticks = self.ticks.wait_for_item(); // 2
+                let fut_spawn = self.queue_spawn.wait_for_item(); // 3
+                let fut_remote = self.remote.wait_for_item(); // 3
+                let fut_native = self.native.wait_for_item(); // 4
+                let fut_restart = self.supervisor.wait_for_item(); // 5
+                let fut_defer = self.queue_defer.wait_for_item(); // 6
+                let fut_futs = fut_queue.wait_for_item(); // 7
+                let fut_deadlines = self.deadlines.wait_for_expiry(&self.clock); // 7
+                let fut_delayed = self.delayed.wait_for_item(&self.clock); // 7
+                let fut_released = self.keep_alives.wait_for_release(); // 7
+
+                // 8
                 let mut num_processed = 0;
                 let span_tick = trace_span!("Scheduler::tick");
                 let fut_tick = async {
                     local_exec.tick().await;
-                    // NOTE: Try to do as much work as possible instead of just a single tick()
+                    // NOTE: Try to do as much work as possible instead of just a single tick(),
+                    // unless a budgeted step has run out of time, leaving the rest for later
                     num_processed += 1;
-                    while local_exec.try_tick() {
+                    while !self.steps.is_over_budget() && local_exec.try_tick() {
                         num_processed += 1;
                     }
                 };
 
-                // 1 + 2 + 3 + 4 + 5
+                // 9
+                let idle_ready = Cell::new(false);
+                let fut_idle = async {
+                    self.idle.wait_for_item().await;
+                    idle_ready.set(true);
+                };
+
+                // 1 + 2 + 3 + 4 + 5 + 6 + 7 + 8 + 9
+                self.wakeups.park();
                 fut_exit
+                    .or(fut_stop)
+                    .or(fut_high)
+                    .or(fut_ticks)
                     .or(fut_spawn)
+                    .or(fut_remote)
+                    .or(fut_native)
+                    .or(fut_restart)
                     .or(fut_defer)
                     .or(fut_futs)
+                    .or(fut_deadlines)
+                    .or(fut_delayed)
+                    .or(fut_released)
                     .or(fut_tick.instrument(span_tick.or_current()))
+                    .or(fut_idle)
                     .await;
+                self.wakeups.wake();
 
-                // Check if we should exit
+                // Check if we should exit, letting any running threads finish first if graceful
                 if self.exit.get().is_some() {
+                    if self.exit.mode() == ExitMode::Graceful {
+                        debug!("graceful exit signal received");
+                        while self.exit.mode() == ExitMode::Graceful
+                            && self.stats.running() + self.native.len() > 0
+                        {
+                            let fut_exit = self.exit.listen();
+                            let fut_native = self.native.wait_for_item();
+                            let fut_futs = fut_queue.wait_for_item();
+                            let fut_tick = async {
+                                local_exec.tick().await;
+                                while local_exec.try_tick() {}
+                            };
+                            fut_exit.or(fut_native).or(fut_futs).or(fut_tick).await;
+                            for (thread, args) in self.native.drain_items(self.lua) {
+                                process_thread(thread, args);
+                            }
+                            for fut in fut_queue.drain_items() {
+                                local_exec.spawn(fut).detach();
+                            }
+                        }
+                    }
                     debug!("exit signal received");
-                    break;
+                    break ExitReason::ExitCode(self.exit.get().unwrap_or_default());
+                }
+                if stopped.get() {
+                    debug!("stop signal received");
+                    break ExitReason::Stopped;
                 }
 
-                // Process spawned threads first, then deferred threads, then futures
+                self.cycles.start(self.lua);
+
+                // Cancel any threads that have exceeded their deadlines
+                self.cancel_expired();
+
+                // Report queue pressure before draining, while the queues are at their deepest
+                self.pressure.update(
+                    self.queue_high.len() + self.queue_spawn.len() + self.queue_defer.len(),
+                );
+
+                // Process high priority threads first, then ticks, then spawned threads, then threads
+                // pushed from other threads, then completed native async calls, then restarted services,
+                // then deferred threads, then threads whose delays have elapsed, then futures
+                let mut num_prioritized = 0;
+                let mut num_ticked = 0;
                 let mut num_spawned = 0;
+                let mut num_remote = 0;
+                let mut num_native = 0;
+                let mut num_restarted = 0;
                 let mut num_deferred = 0;
+                let mut num_delayed = 0;
                 let mut num_futures = 0;
+                {
+                    let _span = trace_span!("Scheduler::drain_prioritized").entered();
+                    for (thread, args) in self.queue_high.drain_items(self.lua) {
+                        process_thread(thread, args);
+                        num_prioritized += 1;
+                    }
+                }
+                {
+                    let _span = trace_span!("Scheduler::drain_ticks").entered();
+                    for (thread, args) in self.ticks.drain_items(self.lua) {
+                        process_thread(thread, args);
+                        num_ticked += 1;
+                    }
+                }
                 {
                     let _span = trace_span!("Scheduler::drain_spawned").entered();
-                    for (thread, args) in self.queue_spawn.drain_items(self.lua) {
+                    let max_spawned = self.max_spawned.get().unwrap_or(usize::MAX);
+                    for (thread, args) in self.queue_spawn.drain_items(self.lua).take(max_spawned) {
                         process_thread(thread, args);
                         num_spawned += 1;
                     }
                 }
+                {
+                    let _span = trace_span!("Scheduler::drain_remote").entered();
+                    for item in self.remote.drain_items(self.lua) {
+                        match item.and_then(|(function, args)| {
+                            Ok((self.lua.create_thread(function)?, args))
+                        }) {
+                            Ok((thread, args)) => process_thread(thread, args),
+                            Err(e) => self.error_callback.call(&e),
+                        }
+                        num_remote += 1;
+                    }
+                }
+                {
+                    let _span = trace_span!("Scheduler::drain_native").entered();
+                    for (thread, args) in self.native.drain_items(self.lua) {
+                        process_thread(thread, args);
+                        num_native += 1;
+                    }
+                }
+                {
+                    let _span = trace_span!("Scheduler::drain_restarted").entered();
+                    for (thread, args) in self.supervisor.drain_items(self.lua) {
+                        process_thread(thread, args);
+                        num_restarted += 1;
+                    }
+                }
                 {
                     let _span = trace_span!("Scheduler::drain_deferred").entered();
                     for (thread, args) in self.queue_defer.drain_items(self.lua) {
@@ -411,58 +3579,249 @@ impl<'lua> Scheduler<'lua> {
                         num_deferred += 1;
                     }
                 }
+                {
+                    let _span = trace_span!("Scheduler::drain_delayed").entered();
+                    for (thread, args) in self.delayed.drain_due(self.lua) {
+                        process_thread(thread, args);
+                        num_delayed += 1;
+                    }
+                }
                 {
                     let _span = trace_span!("Scheduler::drain_futures").entered();
                     for fut in fut_queue.drain_items() {
-                        local_exec.spawn(fut).detach();
+                        local_exec.spawn(self.wakeups.waking(fut)).detach();
                         num_futures += 1;
                     }
                 }
 
+                // Process a single idle thread or future, but only if we had nothing else to do
+                let mut num_idle = 0;
+                if idle_ready.get()
+                    && num_prioritized
+                        + num_ticked
+                        + num_spawned
+                        + num_remote
+                        + num_native
+                        + num_restarted
+                        + num_deferred
+                        + num_delayed
+                        + num_futures
+                        == 0
+                {
+                    let _span = trace_span!("Scheduler::process_idle").entered();
+                    if let Some((thread, args)) = self.idle.pop_thread(self.lua) {
+                        if let Some(fut) = create_thread_fut(thread, args) {
+                            local_exec
+                                .spawn(self.wakeups.waking(self.idle.timed(fut)))
+                                .detach();
+                        }
+                        num_idle += 1;
+                    } else if let Some(fut) = self.idle.pop_future() {
+                        local_exec
+                            .spawn(self.wakeups.waking(self.idle.timed(fut)))
+                            .detach();
+                        num_idle += 1;
+                    }
+                }
+
                 // Empty executor = we didn't spawn any new Lua tasks
                 // above, and there are no remaining tasks to run later
-                let completed = local_exec.is_empty()
-                    && self.queue_spawn.is_empty()
-                    && self.queue_defer.is_empty();
+                let found_work = num_processed
+                    + num_prioritized
+                    + num_ticked
+                    + num_spawned
+                    + num_remote
+                    + num_native
+                    + num_restarted
+                    + num_deferred
+                    + num_delayed
+                    + num_futures
+                    + num_idle
+                    > 0;
+                if !found_work {
+                    self.wakeups.record_empty();
+                }
+                #[cfg(feature = "unstable")]
+                self.plugins.tick(self.lua);
+                self.cycles.end(self.lua);
+                let completed = is_completed();
                 trace!(
+                    cycle = self.cycles.current(),
                     futures_spawned = num_futures,
                     futures_processed = num_processed,
+                    lua_threads_prioritized = num_prioritized,
+                    lua_threads_ticked = num_ticked,
                     lua_threads_spawned = num_spawned,
+                    lua_threads_remote = num_remote,
+                    lua_threads_native = num_native,
+                    lua_threads_restarted = num_restarted,
                     lua_threads_deferred = num_deferred,
+                    lua_threads_delayed = num_delayed,
+                    idle_processed = num_idle,
                     "loop"
                 );
                 if completed {
-                    break;
+                    break ExitReason::Completed;
                 }
+
+                // When stepped manually, each step performs exactly one iteration
+                self.steps.wait_for_next().await;
             }
         };
 
         // Run the executor inside a span until all lua threads complete
+        self.exit_watch.reset();
+        debug!(config = ?self.config(), "config");
         self.set_status(Status::Running);
-        main_exec.run(fut).await;
+        let reason = main_exec.run(fut).await;
         self.set_status(Status::Completed);
 
         // Clean up
-        self.lua
-            .remove_app_data::<WeakArc<Executor>>()
-            .expect(ERR_METADATA_REMOVED);
+        assert!(main_exec.detach(self.lua), "{ERR_METADATA_REMOVED}");
         self.lua
             .remove_app_data::<WeakRc<FuturesQueue>>()
             .expect(ERR_METADATA_REMOVED);
+
+        // Notify plugins and anyone waiting for us to exit, once fully cleaned up
+        #[cfg(feature = "unstable")]
+        self.plugins.shutdown(self.lua, reason);
+        self.exit_watch.set(reason);
+    }
+}
+
+impl Scheduler<'_> {
+    /**
+        Cleans up after a run that was dropped before it completed, such as by dropping a stepper.
+
+        The executors were already dropped along with the run, so only their metadata remains.
+    */
+    pub(crate) fn abandon_run(&self) {
+        MainExecutor::remove(self.lua);
+        self.lua.remove_app_data::<WeakRc<FuturesQueue>>();
+        self.set_status(Status::Completed);
+
+        #[cfg(feature = "unstable")]
+        self.plugins.shutdown(self.lua, ExitReason::Stopped);
+        self.exit_watch.set(ExitReason::Stopped);
+    }
+
+    /**
+        Cancels all threads that have exceeded their deadlines, storing an error as their result.
+
+        Threads currently awaiting an async function inside of the scheduler
+        are skipped, since they are cancelled by their own futures instead.
+    */
+    fn cancel_expired(&self) {
+        let expired = match self.deadlines.take_expired(self.lua) {
+            Ok(expired) => expired,
+            Err(e) => {
+                self.error_callback.call(&e);
+                return;
+            }
+        };
+        for thread in expired {
+            let id = ThreadId::from(&thread);
+            if self.awaiting.contains(id) || thread.status() != LuaThreadStatus::Resumable {
+                continue;
+            }
+            if let Ok(close) = self.primitives.get(self.lua, "close") {
+                let _ = close.call::<_, ()>(thread.clone());
+            }
+            self.suspended.unsuspend(id);
+            let err = Deadlines::error();
+            self.stats.record_errored();
+            self.error_callback.call_thread(self.lua, &thread, &err);
+            let tracked_id = self.result_map.current(id);
+            if self.result_map.is_tracked(tracked_id) && !self.result_map.is_completed(tracked_id) {
+                self.result_map.insert(self.lua, tracked_id, Err(err));
+            } else {
+                self.result_map.abandon(self.lua, id);
+            }
+        }
+    }
+
+    /**
+        Removes any threads left in queues, and reports them along with any other leaks.
+    */
+    fn detect_leaks(&self) {
+        let mut report = LeakReport {
+            unfinished_threads: self.result_map.unfinished(),
+            registry_values: self.result_map.unclaimed(),
+            ..LeakReport::default()
+        };
+        let queued = self
+            .queue_high
+            .drain_items(self.lua)
+            .chain(self.queue_spawn.drain_items(self.lua))
+            .chain(self.queue_defer.drain_items(self.lua))
+            .chain(self.idle.threads().drain_items(self.lua))
+            .chain(self.delayed.drain_all(self.lua));
+        for (thread, _) in queued {
+            // NOTE: Each queued thread holds one registry value for
+            // the thread itself, and one for its packed arguments
+            report.queued_threads.push(ThreadId::from(&thread));
+            report.registry_values += 2;
+        }
+        self.leaks.report(report);
     }
 }
 
 impl Drop for Scheduler<'_> {
+    #[allow(clippy::too_many_lines)]
     fn drop(&mut self) {
+        // NOTE: Clones of a scheduler share all of its state, which
+        // must only be torn down once the very last clone is dropped
+        if Rc::strong_count(&self.instances) > 1 {
+            return;
+        }
+        self.preemption.uninstall(self.lua);
+        // NOTE: Injected functions are bound to this scheduler, and
+        // must not outlive it, so the originals are restored here
+        let _ = self.injections.restore(self.lua);
+        self.result_map.scoped_globals().restore_all(self.lua);
+        self.remote.close();
+        #[cfg(feature = "tokio")]
+        self.tokio.shutdown();
         if panicking() {
             // Do not cause further panics if already panicking, as
             // this may abort the program instead of safely unwinding
             self.lua.remove_app_data::<SpawnedThreadQueue>();
             self.lua.remove_app_data::<DeferredThreadQueue>();
+            self.lua.remove_app_data::<Ticks>();
+            self.lua.remove_app_data::<IdleQueue>();
+            self.lua.remove_app_data::<NativeAsyncQueue>();
+            self.lua.remove_app_data::<Preemption>();
             self.lua.remove_app_data::<ThreadErrorCallback>();
             self.lua.remove_app_data::<ThreadResultMap>();
             self.lua.remove_app_data::<Exit>();
+            self.lua.remove_app_data::<Primitives>();
+            self.lua.remove_app_data::<Checkpoints>();
+            self.lua.remove_app_data::<ThreadTags>();
+            self.lua.remove_app_data::<ThreadNames>();
+            self.lua.remove_app_data::<Stats>();
+            self.lua.remove_app_data::<ResumeHooks>();
+            self.lua.remove_app_data::<Diagnostics>();
+            self.lua.remove_app_data::<Drain>();
+            self.lua.remove_app_data::<ThreadRecords>();
+            self.lua.remove_app_data::<Jobs>();
+            self.lua.remove_app_data::<AwaitingThreads>();
+            self.lua.remove_app_data::<Clock>();
+            self.lua.remove_app_data::<Watchdog>();
+            self.lua.remove_app_data::<StrictMode>();
+            self.lua.remove_app_data::<ErrorValues>();
+            self.lua.remove_app_data::<SuspendedThreads>();
+            self.lua.remove_app_data::<Injections>();
+            self.lua.remove_app_data::<Deadlines>();
+            self.lua.remove_app_data::<DelayedThreads>();
+            self.lua.remove_app_data::<ThreadLocations>();
+            self.lua.remove_app_data::<Cancellation>();
+            self.lua.remove_app_data::<TaskLocals>();
+            self.lua.remove_app_data::<Cycles>();
+            self.lua.remove_app_data::<Output>();
+            #[cfg(feature = "tokio")]
+            self.lua.remove_app_data::<TokioCompanion>();
         } else {
+            self.detect_leaks();
             // In any other case we panic if metadata was removed incorrectly
             self.lua
                 .remove_app_data::<SpawnedThreadQueue>()
@@ -470,6 +3829,18 @@ impl Drop for Scheduler<'_> {
             self.lua
                 .remove_app_data::<DeferredThreadQueue>()
                 .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<Ticks>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<IdleQueue>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<NativeAsyncQueue>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<Preemption>()
+                .expect(ERR_METADATA_REMOVED);
             self.lua
                 .remove_app_data::<ThreadErrorCallback>()
                 .expect(ERR_METADATA_REMOVED);
@@ -479,6 +3850,82 @@ impl Drop for Scheduler<'_> {
             self.lua
                 .remove_app_data::<Exit>()
                 .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<Primitives>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<Checkpoints>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<ThreadTags>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<ThreadNames>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<Stats>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<ResumeHooks>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<Diagnostics>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<Drain>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<ThreadRecords>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<Jobs>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<AwaitingThreads>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<Clock>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<Watchdog>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<StrictMode>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<ErrorValues>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<SuspendedThreads>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<Injections>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<Deadlines>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<DelayedThreads>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<ThreadLocations>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<Cancellation>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<TaskLocals>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<Cycles>()
+                .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<Output>()
+                .expect(ERR_METADATA_REMOVED);
+            #[cfg(feature = "tokio")]
+            self.lua
+                .remove_app_data::<TokioCompanion>()
+                .expect(ERR_METADATA_REMOVED);
         }
     }
 }
@@ -0,0 +1,148 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::prelude::*;
+use rustc_hash::FxHashMap;
+
+use crate::thread_id::ThreadId;
+
+pub(crate) type Overrides = Vec<(String, LuaRegistryKey)>;
+
+/**
+    A global value installed by a single thread, on top of any installed before it.
+*/
+struct Override {
+    thread: ThreadId,
+    value: LuaRegistryKey,
+}
+
+/**
+    A global that has been overridden by one or more threads.
+
+    The original value is kept until all overrides have been removed, and
+    the most recently installed override that remains is always the one
+    that is visible, no matter which order overrides are removed in.
+*/
+struct Overridden {
+    original: LuaRegistryKey,
+    active: Vec<Override>,
+}
+
+/**
+    Temporary global overrides for single threads, see [`Scheduler::push_thread_with_globals`].
+
+    Overrides are installed right before the first resume of their thread,
+    and removed once the thread completes, errors, or is cancelled.
+
+    [`Scheduler::push_thread_with_globals`]: crate::Scheduler::push_thread_with_globals
+*/
+#[derive(Clone)]
+pub(crate) struct ScopedGlobals {
+    pending: Rc<RefCell<FxHashMap<ThreadId, Overrides>>>,
+    installed: Rc<RefCell<FxHashMap<ThreadId, Vec<String>>>>,
+    overridden: Rc<RefCell<FxHashMap<String, Overridden>>>,
+}
+
+impl ScopedGlobals {
+    pub fn new() -> Self {
+        Self {
+            pending: Rc::new(RefCell::new(FxHashMap::default())),
+            installed: Rc::new(RefCell::new(FxHashMap::default())),
+            overridden: Rc::new(RefCell::new(FxHashMap::default())),
+        }
+    }
+
+    /**
+        Collects the given globals into overrides, which may later be set for a thread.
+    */
+    pub fn collect(lua: &Lua, globals: &LuaTable) -> LuaResult<Overrides> {
+        let mut overrides = Vec::new();
+        for pair in globals.clone().pairs::<String, LuaValue>() {
+            let (key, value) = pair?;
+            overrides.push((key, lua.create_registry_value(value)?));
+        }
+        Ok(overrides)
+    }
+
+    /**
+        Sets the given overrides, to be installed once the given thread is first resumed.
+    */
+    pub fn set(&self, id: ThreadId, overrides: Overrides) {
+        self.pending.borrow_mut().insert(id, overrides);
+    }
+
+    /**
+        Installs any pending globals for the given thread, if it has not yet been resumed.
+    */
+    pub fn install(&self, lua: &Lua, id: ThreadId) -> LuaResult<()> {
+        let Some(overrides) = self.pending.borrow_mut().remove(&id) else {
+            return Ok(());
+        };
+        let globals = lua.globals();
+        let mut overridden = self.overridden.borrow_mut();
+        let mut keys = Vec::with_capacity(overrides.len());
+        for (key, value) in overrides {
+            if !overridden.contains_key(&key) {
+                let original = globals.raw_get::<_, LuaValue>(key.as_str())?;
+                let original = lua.create_registry_value(original)?;
+                overridden.insert(
+                    key.clone(),
+                    Overridden {
+                        original,
+                        active: Vec::new(),
+                    },
+                );
+            }
+            globals.raw_set(key.as_str(), lua.registry_value::<LuaValue>(&value)?)?;
+            let entry = overridden
+                .get_mut(&key)
+                .expect("override was just inserted");
+            entry.active.push(Override { thread: id, value });
+            keys.push(key);
+        }
+        self.installed.borrow_mut().insert(id, keys);
+        Ok(())
+    }
+
+    /**
+        Removes any globals installed by the given thread, or that were pending for it.
+    */
+    pub fn restore(&self, lua: &Lua, id: ThreadId) {
+        self.pending.borrow_mut().remove(&id);
+        let Some(keys) = self.installed.borrow_mut().remove(&id) else {
+            return;
+        };
+        let globals = lua.globals();
+        let mut overridden = self.overridden.borrow_mut();
+        for key in keys {
+            let Some(entry) = overridden.get_mut(&key) else {
+                continue;
+            };
+            entry.active.retain(|o| o.thread != id);
+            let visible = match entry.active.last() {
+                Some(last) => &last.value,
+                None => &entry.original,
+            };
+            let value = lua
+                .registry_value::<LuaValue>(visible)
+                .expect("out of memory");
+            globals.raw_set(key.as_str(), value).expect("out of memory");
+            if entry.active.is_empty() {
+                overridden.remove(&key);
+            }
+        }
+    }
+
+    /**
+        Removes all installed and pending globals, restoring all original values.
+    */
+    pub fn restore_all(&self, lua: &Lua) {
+        self.pending.borrow_mut().clear();
+        self.installed.borrow_mut().clear();
+        let globals = lua.globals();
+        for (key, entry) in self.overridden.borrow_mut().drain() {
+            if let Ok(original) = lua.registry_value::<LuaValue>(&entry.original) {
+                let _ = globals.raw_set(key.as_str(), original);
+            }
+        }
+    }
+}
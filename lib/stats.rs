@@ -0,0 +1,114 @@
+use std::{cell::Cell, rc::Rc};
+
+/**
+    Counters for the work done by a scheduler, suitable for exporting as metrics.
+
+    All counts, except for [`SchedulerStats::threads_alive`], only ever increase, and are not
+    cleared when the scheduler is reset, making them usable as counters in systems such as
+    Prometheus without any additional bookkeeping.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SchedulerStats {
+    /// The number of threads spawned, from Rust or using `task.spawn` in Lua.
+    pub threads_spawned: u64,
+    /// The number of threads deferred, from Rust or using `task.defer` in Lua, including idle threads.
+    pub threads_deferred: u64,
+    /// The number of times any thread was resumed by the scheduler.
+    pub threads_resumed: u64,
+    /// The number of threads that stopped with an error, including exceeded deadlines.
+    pub threads_errored: u64,
+    /// The number of threads that were cancelled before they could complete.
+    pub threads_cancelled: u64,
+    /// The number of threads currently queued, delayed, waiting for a tick, or waiting for async work.
+    pub threads_alive: usize,
+    /// The number of futures spawned using [`LuaSpawnExt`](crate::LuaSpawnExt).
+    pub futures_spawned: u64,
+    /// The number of cycles the scheduler has completed, see [`Scheduler::current_cycle`](crate::Scheduler::current_cycle).
+    pub cycles: u64,
+}
+
+/**
+    Tracker for scheduler counters, see [`SchedulerStats`].
+
+    Only contains the monotonic counters and the number of threads currently being
+    run by the executor, the remaining fields are filled in by the scheduler when read.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct Stats {
+    stats: Rc<Cell<SchedulerStats>>,
+    running: Rc<Cell<usize>>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self {
+            stats: Rc::new(Cell::new(SchedulerStats::default())),
+            running: Rc::new(Cell::new(0)),
+        }
+    }
+
+    pub fn stats(&self) -> SchedulerStats {
+        self.stats.get()
+    }
+
+    /**
+        Returns the number of threads currently being run by the executor, including
+        threads that are waiting for async work to complete, see [`Stats::running_guard`].
+    */
+    pub fn running(&self) -> usize {
+        self.running.get()
+    }
+
+    /**
+        Counts a thread as being run by the executor until the returned guard is dropped.
+    */
+    pub fn running_guard(&self) -> RunningGuard {
+        self.running.set(self.running.get() + 1);
+        RunningGuard {
+            running: Rc::clone(&self.running),
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut SchedulerStats)) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+
+    pub fn record_spawned(&self) {
+        self.update(|s| s.threads_spawned += 1);
+    }
+
+    pub fn record_deferred(&self) {
+        self.update(|s| s.threads_deferred += 1);
+    }
+
+    pub fn record_resumed(&self) {
+        self.update(|s| s.threads_resumed += 1);
+    }
+
+    pub fn record_errored(&self) {
+        self.update(|s| s.threads_errored += 1);
+    }
+
+    pub fn record_cancelled(&self) {
+        self.update(|s| s.threads_cancelled += 1);
+    }
+
+    pub fn record_future(&self) {
+        self.update(|s| s.futures_spawned += 1);
+    }
+}
+
+/**
+    Guard returned by [`Stats::running_guard`], uncounting its thread when dropped.
+*/
+pub(crate) struct RunningGuard {
+    running: Rc<Cell<usize>>,
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.running.set(self.running.get() - 1);
+    }
+}
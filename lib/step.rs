@@ -0,0 +1,195 @@
+use std::{
+    cell::Cell,
+    future::{poll_fn, Future},
+    pin::Pin,
+    rc::Rc,
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+use futures_lite::future::poll_once;
+
+use crate::{scheduler::Scheduler, util::instant_after};
+
+#[derive(Debug, Default)]
+struct StepState {
+    stepping: Cell<bool>,
+    requested: Cell<u64>,
+    iterations: Cell<u64>,
+    deadline: Cell<Option<Instant>>,
+}
+
+/**
+    Step requests for a scheduler that is being stepped manually, see [`Scheduler::stepper`].
+
+    [`Scheduler::stepper`]: crate::Scheduler::stepper
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct Steps {
+    state: Rc<StepState>,
+}
+
+impl Steps {
+    pub fn new() -> Self {
+        Self {
+            state: Rc::new(StepState::default()),
+        }
+    }
+
+    pub fn is_stepping(&self) -> bool {
+        self.state.stepping.get()
+    }
+
+    pub fn set_stepping(&self, stepping: bool) {
+        self.state.stepping.set(stepping);
+    }
+
+    pub fn request(&self) {
+        self.state.requested.set(self.state.requested.get() + 1);
+    }
+
+    pub fn iterations(&self) -> u64 {
+        self.state.iterations.get()
+    }
+
+    pub fn set_deadline(&self, deadline: Option<Instant>) {
+        self.state.deadline.set(deadline);
+    }
+
+    /**
+        Returns `true` if the budget for the current step has been used up,
+        meaning that no more Lua threads should be resumed during it.
+    */
+    pub fn is_over_budget(&self) -> bool {
+        self.state
+            .deadline
+            .get()
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /**
+        Waits until the next step has been requested, if the scheduler is being stepped.
+
+        This is called once per iteration of the main loop, after the iteration has completed.
+        It does not register a waker, since the stepper polls the scheduler again for every step.
+    */
+    pub async fn wait_for_next(&self) {
+        if !self.is_stepping() {
+            return;
+        }
+        self.state.iterations.set(self.state.iterations.get() + 1);
+        let current = self.state.requested.get();
+        poll_fn(|_| {
+            if self.state.requested.get() > current {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+    }
+}
+
+/**
+    Drives a [`Scheduler`] forward one iteration at a time, see [`Scheduler::stepper`].
+
+    Dropping the stepper before the scheduler has completed stops the scheduler, dropping
+    any threads that are still running, the same as an exit code set using [`ExitMode::Force`].
+
+    [`Scheduler`]: crate::Scheduler
+    [`Scheduler::stepper`]: crate::Scheduler::stepper
+    [`ExitMode::Force`]: crate::ExitMode::Force
+*/
+#[must_use = "the scheduler only makes progress while it is being stepped"]
+pub struct SchedulerStepper<'sched, 'lua> {
+    sched: &'sched Scheduler<'lua>,
+    steps: Steps,
+    fut: Option<Pin<Box<dyn Future<Output = ()> + 'sched>>>,
+}
+
+impl<'sched, 'lua> SchedulerStepper<'sched, 'lua> {
+    pub(crate) fn new(sched: &'sched Scheduler<'lua>, steps: Steps) -> Self {
+        steps.set_stepping(true);
+        Self {
+            sched,
+            steps,
+            fut: Some(Box::pin(sched.run())),
+        }
+    }
+
+    /**
+        Performs a single iteration of the scheduler.
+
+        This handles the next action that is ready, such as resuming Lua threads or polling
+        futures, then drains all queues so that any threads picked up are resumed during the
+        next step. It never waits for sleeping threads or pending async work to become ready.
+
+        Returns `true` if more work remains, or `false` once the scheduler has completed or exited,
+        after which the stepper is done and any further calls also return `false`.
+    */
+    pub async fn step(&mut self) -> bool {
+        let Some(fut) = self.fut.as_mut() else {
+            return false;
+        };
+        self.steps.request();
+        if poll_once(fut.as_mut()).await.is_some() {
+            self.finish();
+            false
+        } else {
+            true
+        }
+    }
+
+    /**
+        Performs iterations of the scheduler until the given time budget has been used up,
+        or until nothing more is immediately ready, whichever happens first.
+
+        Once the budget has been used up, no more Lua threads are resumed, and any threads that
+        are ready but were not yet resumed are left queued for the next step. This is useful
+        for throttling the work done by the scheduler during a single frame of a game loop.
+
+        Note that a Lua thread can not be interrupted once it has been resumed, so a single
+        thread that runs for longer than the budget may still exceed it. At least one Lua
+        thread or future is always resumed per call, if any are ready, to guarantee progress.
+
+        Returns `true` if more work remains, or `false` once the scheduler has completed or exited,
+        the same as [`SchedulerStepper::step`].
+    */
+    pub async fn step_budgeted(&mut self, budget: Duration) -> bool {
+        self.steps.set_deadline(Some(instant_after(budget)));
+        let more = loop {
+            let iterations = self.steps.iterations();
+            if !self.step().await {
+                break false;
+            }
+            if self.steps.is_over_budget() || self.steps.iterations() == iterations {
+                break true;
+            }
+        };
+        self.steps.set_deadline(None);
+        more
+    }
+
+    /**
+        Returns `true` if the scheduler has completed or exited, and no more steps will be performed.
+    */
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        self.fut.is_none()
+    }
+
+    fn finish(&mut self) {
+        self.fut = None;
+        self.steps.set_stepping(false);
+        self.steps.set_deadline(None);
+    }
+}
+
+impl Drop for SchedulerStepper<'_, '_> {
+    fn drop(&mut self) {
+        if self.fut.is_some() {
+            self.finish();
+            self.sched.abandon_run();
+        }
+    }
+}
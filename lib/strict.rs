@@ -0,0 +1,112 @@
+use std::{
+    cell::Cell,
+    panic::{self, AssertUnwindSafe},
+    rc::{Rc, Weak as WeakRc},
+};
+
+use mlua::prelude::*;
+
+use crate::queue::FuturesQueue;
+
+/**
+    Strict mode for a scheduler, see [`Scheduler::set_strict`].
+
+    [`Scheduler::set_strict`]: crate::Scheduler::set_strict
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct StrictMode {
+    enabled: Rc<Cell<bool>>,
+}
+
+impl StrictMode {
+    pub fn new() -> Self {
+        Self {
+            enabled: Rc::new(Cell::new(false)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.set(enabled);
+    }
+
+    /**
+        Checks that the given thread was created by the given Lua state.
+    */
+    pub fn check_owned(&self, lua: &Lua, thread: &LuaThread) -> LuaResult<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        // NOTE: mlua panics when given values from a different Lua state, and does not
+        // offer any way to check this up front, so we catch that panic here instead
+        let owned = panic::catch_unwind(AssertUnwindSafe(|| {
+            lua.create_registry_value(thread.clone())
+        }));
+        match owned {
+            Ok(key) => lua.remove_registry_value(key?),
+            Err(_) => Err(misuse(
+                "the thread was created by a different Lua state",
+                "threads may only be pushed to the scheduler for the Lua state they were created in",
+            )),
+        }
+    }
+
+    /**
+        Explains an error from pushing a thread that could not be resumed, if enabled.
+    */
+    pub fn explain_push(&self, error: LuaError) -> LuaError {
+        match error {
+            LuaError::CoroutineInactive if self.is_enabled() => misuse(
+                "the thread has already completed, errored, or was cancelled",
+                "check the status of a thread before pushing it, and do not push cancelled threads",
+            ),
+            error => error,
+        }
+    }
+
+    /**
+        Checks that the given thread can be resumed, if enabled, for the given action.
+    */
+    pub fn check_resumable(&self, thread: &LuaThread, action: &str) -> LuaResult<()> {
+        if self.is_enabled() && thread.status() != LuaThreadStatus::Resumable {
+            return Err(misuse(
+                &format!("cannot {action} a thread that is not resumable, such as one that has completed or was cancelled"),
+                "use coroutine.status to check if a thread can be resumed first",
+            ));
+        }
+        Ok(())
+    }
+
+    /**
+        Checks that the scheduler is running, if enabled, before starting background work.
+    */
+    pub fn check_running(&self, lua: &Lua) -> LuaResult<()> {
+        if self.is_enabled() && lua.app_data_ref::<WeakRc<FuturesQueue>>().is_none() {
+            return Err(misuse(
+                "scheduler functions that start async work can only be called while the scheduler is running",
+                "push the thread calling this function to the scheduler instead of calling it directly",
+            ));
+        }
+        Ok(())
+    }
+
+    /**
+        Creates the error for getting the result of a thread that is not tracked.
+    */
+    pub fn untracked_result() -> LuaError {
+        misuse(
+            "the thread is not tracked by the scheduler, or its result was already taken",
+            "results are only tracked for threads pushed from Rust, and may only be taken once",
+        )
+    }
+}
+
+/**
+    Creates a descriptive error for misuse of the scheduler API, along with a hint to resolve it.
+*/
+fn misuse(problem: &str, hint: &str) -> LuaError {
+    LuaError::runtime(format!("scheduler misuse: {problem}\nhint: {hint}"))
+}
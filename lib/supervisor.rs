@@ -0,0 +1,255 @@
+#![allow(clippy::module_name_repetitions)]
+
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use concurrent_queue::ConcurrentQueue;
+use event_listener::Event;
+use mlua::prelude::*;
+use rustc_hash::FxHashMap;
+
+use crate::{
+    checkpoint::Checkpoints, clock::Clock, tags::ThreadTags, thread_id::ThreadId,
+    traits::spawn_local_unwatched, util::instant_after,
+};
+
+type RestartCallback = Box<dyn Fn(RestartEvent) + Send + 'static>;
+
+/**
+    When a supervised service thread should be restarted.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// Always restart the service, even if it completed successfully.
+    Always,
+    /// Only restart the service if it errored.
+    #[default]
+    OnFailure,
+}
+
+/**
+    Options for a supervised service thread, see [`Scheduler::supervise`].
+
+    [`Scheduler::supervise`]: crate::Scheduler::supervise
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RestartOptions {
+    /// When the service should be restarted.
+    pub policy: RestartPolicy,
+    /// How long to wait before restarting the service.
+    pub backoff: Duration,
+    /// The maximum number of times to restart the service, or `None` for no limit.
+    pub max_restarts: Option<usize>,
+}
+
+/**
+    Information about a supervised service thread that was restarted.
+*/
+#[derive(Debug, Clone)]
+pub struct RestartEvent {
+    /// The name of the service that was restarted.
+    pub name: String,
+    /// The total number of times this service has been restarted, including this restart.
+    pub restarts: usize,
+    /// The error that caused the restart, if the previous thread errored.
+    pub error: Option<LuaError>,
+    /// The id of the newly started thread for the service.
+    pub thread: ThreadId,
+}
+
+/**
+    A registered service, along with the factory used to start new threads for it.
+*/
+struct Service {
+    name: String,
+    factory: LuaRegistryKey,
+    options: RestartOptions,
+    restarts: usize,
+}
+
+/**
+    A pending restart for a service, waiting to be started by the scheduler.
+*/
+struct PendingRestart {
+    service: usize,
+    error: Option<LuaError>,
+}
+
+/**
+    Supervisor for long-running service threads.
+
+    Keeps track of which threads belong to which service, and restarts
+    services according to their [`RestartPolicy`] once their threads complete.
+
+    Service threads are tagged with the name of their service, so any checkpoints
+    they save outlive the thread, and restarted threads are passed the latest one.
+*/
+#[derive(Clone)]
+pub(crate) struct Supervisor {
+    services: Rc<RefCell<Vec<Service>>>,
+    running: Rc<RefCell<FxHashMap<ThreadId, usize>>>,
+    pending: Rc<ConcurrentQueue<PendingRestart>>,
+    event: Rc<Event>,
+    callback: Rc<RefCell<Option<RestartCallback>>>,
+    tags: ThreadTags,
+    checkpoints: Checkpoints,
+    clock: Clock,
+}
+
+impl Supervisor {
+    pub fn new(tags: ThreadTags, checkpoints: Checkpoints, clock: Clock) -> Self {
+        Self {
+            services: Rc::new(RefCell::new(Vec::new())),
+            running: Rc::new(RefCell::new(FxHashMap::default())),
+            pending: Rc::new(ConcurrentQueue::unbounded()),
+            event: Rc::new(Event::new()),
+            callback: Rc::new(RefCell::new(None)),
+            tags,
+            checkpoints,
+            clock,
+        }
+    }
+
+    pub fn replace_callback(&self, callback: impl Fn(RestartEvent) + Send + 'static) {
+        self.callback.borrow_mut().replace(Box::new(callback));
+    }
+
+    pub fn clear_callback(&self) {
+        self.callback.borrow_mut().take();
+    }
+
+    /**
+        Removes all services, along with any of their pending restarts.
+    */
+    pub fn clear(&self) {
+        self.services.borrow_mut().clear();
+        self.running.borrow_mut().clear();
+        while self.pending.pop().is_ok() {}
+    }
+
+    /**
+        Registers a new service, and creates its first thread.
+    */
+    pub fn add<'lua>(
+        &self,
+        lua: &'lua Lua,
+        name: String,
+        factory: LuaFunction<'lua>,
+        options: RestartOptions,
+    ) -> LuaResult<LuaThread<'lua>> {
+        let factory = lua.create_registry_value(factory)?;
+        let index = {
+            let mut services = self.services.borrow_mut();
+            services.push(Service {
+                name,
+                factory,
+                options,
+                restarts: 0,
+            });
+            services.len() - 1
+        };
+        self.start(lua, index)
+    }
+
+    /**
+        Creates a new thread for the service at the given index.
+    */
+    fn start<'lua>(&self, lua: &'lua Lua, index: usize) -> LuaResult<LuaThread<'lua>> {
+        let services = self.services.borrow();
+        let service = &services[index];
+        let thread = lua.create_thread(lua.registry_value(&service.factory)?)?;
+        self.running
+            .borrow_mut()
+            .insert(ThreadId::from(&thread), index);
+        self.tags.insert(lua, &thread, &service.name)?;
+        Ok(thread)
+    }
+
+    /**
+        Handles the final result of a thread, restarting its service if necessary.
+
+        Does nothing if the thread does not belong to a service.
+    */
+    pub fn handle_result(&self, lua: &Lua, id: ThreadId, result: &LuaResult<LuaMultiValue>) {
+        let Some(index) = self.running.borrow_mut().remove(&id) else {
+            return;
+        };
+
+        let backoff = {
+            let mut services = self.services.borrow_mut();
+            let service = &mut services[index];
+            let should_restart = match service.options.policy {
+                RestartPolicy::Always => true,
+                RestartPolicy::OnFailure => result.is_err(),
+            };
+            let can_restart = service
+                .options
+                .max_restarts
+                .is_none_or(|max| service.restarts < max);
+            if !should_restart || !can_restart {
+                return;
+            }
+            service.restarts += 1;
+            service.options.backoff
+        };
+
+        let restart = PendingRestart {
+            service: index,
+            error: result.as_ref().err().cloned(),
+        };
+        if backoff.is_zero() {
+            let _ = self.pending.push(restart);
+            self.event.notify(usize::MAX);
+        } else {
+            let pending = Rc::clone(&self.pending);
+            let event = Rc::clone(&self.event);
+            let clock = self.clock.clone();
+            spawn_local_unwatched(lua, async move {
+                clock.wait_until(instant_after(backoff)).await;
+                let _ = pending.push(restart);
+                event.notify(usize::MAX);
+            });
+        }
+    }
+
+    pub fn drain_items<'outer, 'lua>(
+        &'outer self,
+        lua: &'lua Lua,
+    ) -> impl Iterator<Item = (LuaThread<'lua>, LuaMultiValue<'lua>)> + 'outer
+    where
+        'lua: 'outer,
+    {
+        self.pending.try_iter().map(|restart| {
+            let thread = self.start(lua, restart.service).expect("out of memory");
+            let services = self.services.borrow();
+            let service = &services[restart.service];
+            if let Some(callback) = &*self.callback.borrow() {
+                callback(RestartEvent {
+                    name: service.name.clone(),
+                    restarts: service.restarts,
+                    error: restart.error,
+                    thread: ThreadId::from(&thread),
+                });
+            }
+            let args = match self.checkpoints.get_tag(&service.name) {
+                Some(checkpoint) => checkpoint.to_lua(lua).expect("out of memory"),
+                None => LuaValue::Nil,
+            };
+            (thread, LuaMultiValue::from_vec(vec![args]))
+        })
+    }
+
+    pub async fn wait_for_item(&self) {
+        if self.pending.is_empty() {
+            let listener = self.event.listen();
+            // NOTE: Need to check again, we could have gotten
+            // new queued items while creating our listener
+            if self.pending.is_empty() {
+                listener.await;
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
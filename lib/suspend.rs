@@ -0,0 +1,140 @@
+use std::{
+    cell::RefCell,
+    collections::hash_map::Entry,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll, Waker},
+};
+
+use mlua::prelude::*;
+use rustc_hash::FxHashMap;
+
+use crate::thread_id::ThreadId;
+
+/**
+    Lua threads that have been suspended from Rust, and may not be resumed until unsuspended.
+
+    Suspended threads keep their place in the scheduler - their resumptions are gated
+    instead of dropped, and continue as usual once the thread has been unsuspended.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct SuspendedThreads {
+    threads: Rc<RefCell<FxHashMap<ThreadId, Vec<Waker>>>>,
+}
+
+impl SuspendedThreads {
+    pub fn new() -> Self {
+        Self {
+            threads: Rc::new(RefCell::new(FxHashMap::default())),
+        }
+    }
+
+    /**
+        Suspends the given thread, returning `true` if it was not already suspended.
+    */
+    pub fn suspend(&self, id: ThreadId) -> bool {
+        match self.threads.borrow_mut().entry(id) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(Vec::new());
+                true
+            }
+        }
+    }
+
+    /**
+        Unsuspends the given thread, returning `true` if it was suspended.
+
+        Any resumptions of the thread that were gated while it was suspended will continue.
+    */
+    pub fn unsuspend(&self, id: ThreadId) -> bool {
+        // NOTE: Must not hold the borrow while waking, wakers may be arbitrary code
+        let wakers = self.threads.borrow_mut().remove(&id);
+        match wakers {
+            Some(wakers) => {
+                for waker in wakers {
+                    waker.wake();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[inline]
+    pub fn is_suspended(&self, id: ThreadId) -> bool {
+        self.threads.borrow().contains_key(&id)
+    }
+
+    #[cfg(feature = "unstable")]
+    pub fn ids(&self) -> Vec<ThreadId> {
+        self.threads.borrow().keys().copied().collect()
+    }
+
+    /**
+        Unsuspends all threads.
+    */
+    pub fn clear(&self) {
+        let ids = self.threads.borrow().keys().copied().collect::<Vec<_>>();
+        for id in ids {
+            self.unsuspend(id);
+        }
+    }
+
+    /**
+        Wraps the given future, which resumes the given thread, so that it
+        is not polled at all while the thread is suspended.
+
+        If the thread is no longer resumable once it has been unsuspended,
+        such as if it was cancelled, the future resolves to `None` instead.
+    */
+    pub fn gate<'lua, F, T>(&self, thread: LuaThread<'lua>, fut: F) -> SuspendGate<'lua, F>
+    where
+        F: Future<Output = Option<T>>,
+    {
+        SuspendGate {
+            id: ThreadId::from(&thread),
+            thread,
+            inner: Box::pin(fut),
+            threads: self.clone(),
+            gated: false,
+        }
+    }
+}
+
+/**
+    A future that only polls its inner future while its thread is not suspended.
+*/
+pub(crate) struct SuspendGate<'lua, F> {
+    id: ThreadId,
+    thread: LuaThread<'lua>,
+    inner: Pin<Box<F>>,
+    threads: SuspendedThreads,
+    gated: bool,
+}
+
+impl<F, T> Future for SuspendGate<'_, F>
+where
+    F: Future<Output = Option<T>>,
+{
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        {
+            let mut threads = self.threads.threads.borrow_mut();
+            if let Some(wakers) = threads.get_mut(&self.id) {
+                if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                    wakers.push(cx.waker().clone());
+                }
+                drop(threads);
+                self.gated = true;
+                return Poll::Pending;
+            }
+        }
+        if std::mem::take(&mut self.gated) && self.thread.status() != LuaThreadStatus::Resumable {
+            return Poll::Ready(None);
+        }
+        self.inner.as_mut().poll(cx)
+    }
+}
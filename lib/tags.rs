@@ -0,0 +1,73 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::prelude::*;
+
+use crate::thread_id::ThreadId;
+
+/**
+    Human-readable tags for Lua threads, such as the names of supervised services.
+
+    Tags are used to attribute checkpoints, tracing spans, and
+    error reports to something more meaningful than a thread id,
+    and to operate on logical groups of threads sharing a tag.
+
+    Tags are stored in a Lua table with weak keys, meaning tagging a
+    thread does not prevent it from being garbage collected.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct ThreadTags {
+    table: Rc<RefCell<Option<LuaRegistryKey>>>,
+}
+
+impl ThreadTags {
+    pub fn new() -> Self {
+        Self {
+            table: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    fn table<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaTable<'lua>> {
+        if let Some(key) = &*self.table.borrow() {
+            return lua.registry_value(key);
+        }
+        let table = lua.create_table()?;
+        let meta = lua.create_table_from([("__mode", "k")])?;
+        table.set_metatable(Some(meta));
+        self.table
+            .replace(Some(lua.create_registry_value(table.clone())?));
+        Ok(table)
+    }
+
+    pub fn clear(&self) {
+        self.table.borrow_mut().take();
+    }
+
+    pub fn insert(&self, lua: &Lua, thread: &LuaThread, tag: &str) -> LuaResult<()> {
+        self.table(lua)?.raw_set(thread.clone(), tag)
+    }
+
+    pub fn get(&self, lua: &Lua, thread: &LuaThread) -> Option<String> {
+        self.table(lua).ok()?.raw_get(thread.clone()).ok()?
+    }
+
+    /**
+        Finds all threads with the given tag that have not yet completed.
+    */
+    pub fn find<'lua>(&self, lua: &'lua Lua, tag: &str) -> LuaResult<Vec<LuaThread<'lua>>> {
+        let mut threads = Vec::new();
+        for pair in self.table(lua)?.pairs::<LuaThread, LuaString>() {
+            let (thread, thread_tag) = pair?;
+            if thread_tag.as_bytes() == tag.as_bytes()
+                && thread.status() == LuaThreadStatus::Resumable
+            {
+                threads.push(thread);
+            }
+        }
+        Ok(threads)
+    }
+
+    pub fn find_ids(&self, lua: &Lua, tag: &str) -> LuaResult<Vec<ThreadId>> {
+        let threads = self.find(lua, tag)?;
+        Ok(threads.iter().map(ThreadId::from).collect())
+    }
+}
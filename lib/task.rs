@@ -0,0 +1,184 @@
+use std::rc::Rc;
+
+use mlua::prelude::*;
+
+use crate::{
+    error_history::split_error, error_value::ThreadError, queue::DeferredThreadQueue,
+    result_map::ThreadResultMap, thread_id::ThreadId,
+};
+
+const ERR_AWAIT_SELF: &str = "task can not await itself";
+const ERR_NOT_DONE: &str = "task has not yet completed";
+const ERR_TAKEN: &str = "task result was already taken by the host";
+
+/**
+    Lua implementation of `Task:await`.
+
+    Parks the calling thread until the task completes, unless it already has,
+    and then returns the values of the task, or raises its error. Since the
+    scheduler is single-threaded, the task can not complete in between
+    checking if it is done and parking the calling thread.
+*/
+pub(crate) const AWAIT_IMPL_LUA: &str = r"
+local task = ...
+if not task:isDone() then
+    park(task)
+    yield()
+end
+local r = pack(result(task))
+if r[1] then
+    return unpack(r, 2, r.n)
+else
+    error(r[2], 0)
+end
+";
+
+/**
+    A handle to a spawned thread that can be used from Lua, see [`Functions::spawn_task`].
+
+    Threads awaiting the task are parked using completion callbacks in the result map,
+    and resumed through the deferred thread queue once the task has completed.
+
+    The result of the task is only ever observed, and not taken, meaning
+    that any number of threads may await the same task. Unless the host was
+    already tracking the thread, the task owns its result, and removes it once
+    the task is dropped - otherwise the result is left for the host to take
+    using [`Scheduler::get_thread_result`], after which awaiting the task errors.
+
+    [`Functions::spawn_task`]: crate::Functions::spawn_task
+    [`Scheduler::get_thread_result`]: crate::Scheduler::get_thread_result
+*/
+pub(crate) struct Task {
+    id: ThreadId,
+    thread: LuaRegistryKey,
+    result_map: ThreadResultMap,
+    queue: DeferredThreadQueue,
+    close: Rc<LuaRegistryKey>,
+    wait: Rc<LuaRegistryKey>,
+    owns_result: bool,
+}
+
+impl Task {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        lua: &Lua,
+        id: ThreadId,
+        thread: &LuaThread,
+        result_map: ThreadResultMap,
+        queue: DeferredThreadQueue,
+        close: Rc<LuaRegistryKey>,
+        wait: Rc<LuaRegistryKey>,
+        owns_result: bool,
+    ) -> LuaResult<Self> {
+        Ok(Self {
+            id,
+            thread: lua.create_registry_value(thread)?,
+            result_map,
+            queue,
+            close,
+            wait,
+            owns_result,
+        })
+    }
+
+    /**
+        Returns `true` if the task has completed, errored, or was cancelled.
+
+        Tasks with a result that was already taken by the host are also done.
+    */
+    pub fn is_done(&self) -> bool {
+        self.result_map.is_completed(self.id) || !self.result_map.is_tracked(self.id)
+    }
+
+    /**
+        Parks the currently running thread, until the task completes.
+
+        Note that this does not yield, that must be done separately.
+    */
+    pub fn park(&self, lua: &Lua) -> LuaResult<()> {
+        let current = lua.current_thread();
+        if ThreadId::from(&current) == self.id.base() {
+            return Err(LuaError::runtime(ERR_AWAIT_SELF));
+        }
+        let key = lua.create_registry_value(current)?;
+        let queue = self.queue.clone();
+        self.result_map.add_callback(
+            lua,
+            self.id,
+            Box::new(move |lua, _| {
+                let Ok(thread) = lua.registry_value::<LuaThread>(&key) else {
+                    return;
+                };
+                let _ = lua.remove_registry_value(key);
+                // NOTE: Waiting threads may have been cancelled while the task was running,
+                // and completions can not fail, so these wakeups bypass any queue capacity
+                if thread.status() == LuaThreadStatus::Resumable {
+                    let _ = queue.push_item_unbounded(lua, thread, ());
+                }
+            }),
+        );
+        Ok(())
+    }
+
+    /**
+        Returns `true` and the values of the completed task, or `false` and its error.
+
+        Errors raised with non-string values are returned as their original value,
+        and any other errors are returned as their message, without a traceback.
+    */
+    pub fn result<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaMultiValue<'lua>> {
+        match self.result_map.peek(lua, self.id) {
+            None if self.result_map.is_tracked(self.id) => Err(LuaError::runtime(ERR_NOT_DONE)),
+            None => Err(LuaError::runtime(ERR_TAKEN)),
+            Some(Ok(values)) => (true, values).into_lua_multi(lua),
+            Some(Err(e)) => {
+                let value = match ThreadError::from_error(&e) {
+                    Some(thread_error) => thread_error.value(lua)?,
+                    None => split_error(&e).0.into_lua(lua)?,
+                };
+                (false, value).into_lua_multi(lua)
+            }
+        }
+    }
+
+    /**
+        Cancels the task, if it has not yet completed, the same as [`Functions::close`].
+
+        Returns `true` if the task was cancelled, and `false` if it had already completed.
+
+        [`Functions::close`]: crate::Functions::close
+    */
+    pub fn cancel(&self, lua: &Lua) -> LuaResult<bool> {
+        if self.is_done() {
+            return Ok(false);
+        }
+        let thread: LuaThread = lua.registry_value(&self.thread)?;
+        let close: LuaFunction = lua.registry_value(&self.close)?;
+        close.call::<_, ()>(thread)?;
+        Ok(true)
+    }
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        // NOTE: Nothing else can observe the result of a thread that
+        // only the task was tracking, so it would otherwise stay forever
+        if self.owns_result {
+            self.result_map.untrack(self.id);
+        }
+    }
+}
+
+impl LuaUserData for Task {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_function_get("await", |lua, this| {
+            let this = this.borrow::<Self>()?;
+            lua.registry_value::<LuaFunction>(&this.wait)
+        });
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("isDone", |_, this, ()| Ok(this.is_done()));
+        methods.add_method("cancel", |lua, this, ()| this.cancel(lua));
+    }
+}
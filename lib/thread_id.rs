@@ -1,4 +1,7 @@
-use std::hash::{Hash, Hasher};
+use std::{
+    ffi::c_void,
+    hash::{Hash, Hasher},
+};
 
 use mlua::prelude::*;
 
@@ -9,16 +12,54 @@ use mlua::prelude::*;
 
     Note that holding a `ThreadId` does not prevent the thread from being garbage collected.
     The actual thread may or may not still exist and be active at any given point in time.
+
+    Ids are based on the address of the thread, along with a generation for that address.
+    When a tracked thread reuses the address of an old thread that the scheduler still holds
+    a result for, it is given the next free generation when pushed, so that the two threads
+    never share the same id, and their results can be told apart.
 */
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ThreadId {
     inner: usize,
+    generation: u32,
+}
+
+impl ThreadId {
+    pub(crate) fn generation(self) -> u32 {
+        self.generation
+    }
+
+    pub(crate) fn with_generation(self, generation: u32) -> Self {
+        Self {
+            inner: self.inner,
+            generation,
+        }
+    }
+
+    /**
+        Returns this id without its generation.
+
+        Generations only tell apart the results of threads, so any
+        other state for a thread is stored using its base id instead.
+    */
+    pub(crate) fn base(self) -> Self {
+        self.with_generation(0)
+    }
+
+    /**
+        Returns the address of the thread as a light userdata, for use as a key in Lua tables.
+    */
+    pub(crate) fn to_light_userdata(self) -> LuaLightUserData {
+        LuaLightUserData(self.inner as *mut c_void)
+    }
 }
 
 impl From<&LuaThread<'_>> for ThreadId {
     fn from(thread: &LuaThread) -> Self {
         Self {
             inner: thread.to_pointer() as usize,
+            generation: 0,
         }
     }
 }
@@ -26,5 +67,27 @@ impl From<&LuaThread<'_>> for ThreadId {
 impl Hash for ThreadId {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.inner.hash(state);
+        self.generation.hash(state);
     }
 }
+
+/**
+    A [`ThreadId`] scoped to the [`Scheduler`] it was created by.
+
+    Thread ids are only unique within a single Lua state, and may be reused once threads
+    are garbage collected, so the epoch of the scheduler is included alongside the id.
+    Epochs are unique per scheduler, and are very unlikely to ever repeat across processes,
+    making scoped ids suitable for logs and other tooling outside of the current process.
+
+    With the `serde` feature enabled, both this and [`ThreadId`] are serializable.
+
+    [`Scheduler`]: crate::Scheduler
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScopedThreadId {
+    /// The epoch of the scheduler that this thread id belongs to.
+    pub epoch: u64,
+    /// The id of the thread, only valid for schedulers with the same epoch.
+    pub id: ThreadId,
+}
@@ -0,0 +1,161 @@
+use std::{
+    cell::RefCell,
+    process,
+    rc::Rc,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use mlua::prelude::*;
+
+use crate::thread_id::{ScopedThreadId, ThreadId};
+
+static EPOCH_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/**
+    Information about a Lua thread, see [`Scheduler::describe_thread`].
+
+    [`Scheduler::describe_thread`]: crate::Scheduler::describe_thread
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadInfo {
+    /// The scoped id of the thread.
+    pub id: ScopedThreadId,
+    /// The tag of the thread, if it has one.
+    pub tag: Option<String>,
+    /// The name of the thread, if it has one.
+    pub name: Option<String>,
+    /// The current status of the thread.
+    pub status: LuaThreadStatus,
+    /// When the thread was first pushed to the scheduler, if it has been pushed.
+    pub pushed_at: Option<SystemTime>,
+}
+
+/**
+    Records for Lua threads pushed to a scheduler, along with the epoch of the scheduler.
+
+    Threads are stored by their id in a Lua table with weak values, so that they can be
+    found from their id directly, and push times are stored in a Lua table with weak keys,
+    meaning pushing a thread does not prevent it from being garbage collected. Push times
+    are stored as seconds since the unix epoch, so that they remain comparable to logs.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct ThreadRecords {
+    epoch: u64,
+    tables: Rc<RefCell<Option<RecordTables>>>,
+}
+
+#[derive(Debug)]
+struct RecordTables {
+    threads: LuaRegistryKey,
+    pushed_at: LuaRegistryKey,
+}
+
+impl ThreadRecords {
+    pub fn new() -> Self {
+        Self {
+            epoch: next_epoch(),
+            tables: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn clear(&self) {
+        self.tables.borrow_mut().take();
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn scope(&self, id: ThreadId) -> ScopedThreadId {
+        ScopedThreadId {
+            epoch: self.epoch,
+            id,
+        }
+    }
+
+    /**
+        Returns the tables of threads by their id, and of push times by thread.
+    */
+    fn tables<'lua>(&self, lua: &'lua Lua) -> LuaResult<(LuaTable<'lua>, LuaTable<'lua>)> {
+        if let Some(tables) = &*self.tables.borrow() {
+            return Ok((
+                lua.registry_value(&tables.threads)?,
+                lua.registry_value(&tables.pushed_at)?,
+            ));
+        }
+        let threads = lua.create_table()?;
+        threads.set_metatable(Some(lua.create_table_from([("__mode", "v")])?));
+        let pushed_at = lua.create_table()?;
+        pushed_at.set_metatable(Some(lua.create_table_from([("__mode", "k")])?));
+        self.tables.replace(Some(RecordTables {
+            threads: lua.create_registry_value(threads.clone())?,
+            pushed_at: lua.create_registry_value(pushed_at.clone())?,
+        }));
+        Ok((threads, pushed_at))
+    }
+
+    /**
+        Records the given thread, so that it can be found using its id, without recording a push.
+    */
+    pub fn record_thread(&self, lua: &Lua, thread: &LuaThread) -> LuaResult<()> {
+        let (threads, _) = self.tables(lua)?;
+        threads.raw_set(ThreadId::from(thread).to_light_userdata(), thread.clone())
+    }
+
+    /**
+        Records the push time for the given thread, unless it has been pushed before.
+    */
+    pub fn record_push(&self, lua: &Lua, thread: &LuaThread) -> LuaResult<()> {
+        let (threads, times) = self.tables(lua)?;
+        if times.raw_get::<_, Option<f64>>(thread.clone())?.is_none() {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            times.raw_set(thread.clone(), now.as_secs_f64())?;
+            threads.raw_set(ThreadId::from(thread).to_light_userdata(), thread.clone())?;
+        }
+        Ok(())
+    }
+
+    pub fn pushed_at(&self, lua: &Lua, thread: &LuaThread) -> Option<SystemTime> {
+        let (_, times) = self.tables(lua).ok()?;
+        let secs: Option<f64> = times.raw_get(thread.clone()).ok()?;
+        let secs = secs?;
+        Some(UNIX_EPOCH + Duration::from_secs_f64(secs))
+    }
+
+    /**
+        Returns all threads that have been pushed to the scheduler, and not yet garbage collected.
+    */
+    #[cfg(feature = "unstable")]
+    pub fn threads<'lua>(&self, lua: &'lua Lua) -> LuaResult<Vec<LuaThread<'lua>>> {
+        let (threads, _) = self.tables(lua)?;
+        threads
+            .pairs::<LuaValue, LuaThread>()
+            .map(|pair| pair.map(|(_, thread)| thread))
+            .collect()
+    }
+
+    /**
+        Finds a thread that has been pushed to the scheduler, using its id.
+    */
+    pub fn find<'lua>(&self, lua: &'lua Lua, id: ThreadId) -> LuaResult<Option<LuaThread<'lua>>> {
+        let (threads, _) = self.tables(lua)?;
+        threads.raw_get(id.base().to_light_userdata())
+    }
+}
+
+/**
+    Creates a new epoch, unique within this process and unlikely to repeat across processes.
+*/
+fn next_epoch() -> u64 {
+    let count = EPOCH_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    #[allow(clippy::cast_possible_truncation)]
+    let nanos = nanos as u64;
+    (nanos ^ (u64::from(process::id()) << 32)).wrapping_add(count)
+}
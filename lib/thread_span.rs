@@ -0,0 +1,113 @@
+#[cfg(feature = "debug-info")]
+use std::{future::Future, pin::pin};
+
+#[cfg(feature = "debug-info")]
+use futures_lite::future::poll_fn;
+use mlua::prelude::*;
+use tracing::{field, trace_span, Span};
+
+use crate::{names::ThreadNames, primitives::Primitives, thread_id::ThreadId};
+
+/**
+    Creator for the tracing spans that every resumption of a Lua thread runs within.
+
+    Spans record the id of the thread, as well as its name, if any, and with the
+    `debug-info` feature enabled, the Luau script name and line of the thread.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct ThreadSpans {
+    names: ThreadNames,
+    #[cfg_attr(not(feature = "debug-info"), allow(dead_code))]
+    primitives: Primitives,
+}
+
+impl ThreadSpans {
+    pub fn new(names: ThreadNames, primitives: Primitives) -> Self {
+        Self { names, primitives }
+    }
+
+    /**
+        Creates a span for a single resumption of the given thread.
+
+        With the `debug-info` feature enabled, the location that the thread is
+        being resumed from is also recorded, if the thread has run before.
+    */
+    pub fn span(&self, lua: &Lua, thread: &LuaThread) -> Span {
+        let span = trace_span!(
+            "Scheduler::thread",
+            thread = field::debug(ThreadId::from(thread)),
+            name = field::Empty,
+            source = field::Empty,
+            line = field::Empty,
+        );
+        if !span.is_disabled() {
+            if let Some(name) = self.names.get(lua, thread) {
+                span.record("name", name);
+            }
+            #[cfg(feature = "debug-info")]
+            self.record_location(lua, thread, &span);
+        }
+        span
+    }
+
+    /**
+        Runs the given future, which resumes the given thread, within the current span.
+
+        Threads that have not run before have no location when their span is created,
+        so the location is instead recorded once the thread first waits for async work,
+        which is the location that is needed to debug a thread that got stuck there.
+    */
+    #[cfg(feature = "debug-info")]
+    pub async fn located<F: Future>(&self, lua: &Lua, thread: LuaThread<'_>, fut: F) -> F::Output {
+        let span = Span::current();
+        if span.is_disabled() || self.location(lua, &thread).is_some() {
+            return fut.await;
+        }
+        let mut fut = pin!(fut);
+        let mut located = false;
+        poll_fn(|cx| {
+            let poll = fut.as_mut().poll(cx);
+            if poll.is_pending() && !located {
+                located = self.record_location(lua, &thread, &span);
+            }
+            poll
+        })
+        .await
+    }
+
+    #[cfg(feature = "debug-info")]
+    fn record_location(&self, lua: &Lua, thread: &LuaThread, span: &Span) -> bool {
+        match self.location(lua, thread) {
+            Some((source, line)) => {
+                span.record("source", source);
+                span.record("line", line);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /**
+        Finds the script name and line that the given thread is currently suspended at.
+
+        Skips any frames for native functions and the internal chunks of the
+        scheduler and `mlua`, such as the ones that implement async functions.
+    */
+    #[cfg(feature = "debug-info")]
+    fn location(&self, lua: &Lua, thread: &LuaThread) -> Option<(String, i64)> {
+        let info = self.primitives.get(lua, "info").ok()?;
+        for level in 0.. {
+            let (source, line) = info
+                .call::<_, (Option<String>, Option<i64>)>((thread.clone(), level, "sl"))
+                .ok()?;
+            let source = source?;
+            let internal = source == "[C]"
+                || source.starts_with("__scheduler")
+                || source.starts_with("__mlua");
+            if !internal {
+                return line.filter(|line| *line > 0).map(|line| (source, line));
+            }
+        }
+        None
+    }
+}
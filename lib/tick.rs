@@ -0,0 +1,139 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::prelude::*;
+
+use crate::{
+    location::{Location, ThreadLocations},
+    queue::ThreadQueue,
+    thread_id::ThreadId,
+};
+
+/**
+    Storage for threads waiting on host-driven ticks.
+
+    Waiting threads are parked until the next tick is fired, at which point
+    they are moved into the tick queue, along with the tick delta time.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct Ticks {
+    waiters: Rc<RefCell<Vec<LuaRegistryKey>>>,
+    queue: ThreadQueue,
+}
+
+impl Ticks {
+    pub fn new(locations: &ThreadLocations) -> Self {
+        Self {
+            waiters: Rc::new(RefCell::new(Vec::new())),
+            queue: ThreadQueue::new(locations, Location::Tick),
+        }
+    }
+
+    /**
+        Parks the currently running thread until the next tick.
+
+        Note that this does not yield, that must be done separately.
+    */
+    pub fn park(&self, lua: &Lua) -> LuaResult<()> {
+        let key = lua.create_registry_value(lua.current_thread())?;
+        self.waiters.borrow_mut().push(key);
+        Ok(())
+    }
+
+    /**
+        Fires a tick, moving all parked threads into the tick queue.
+    */
+    pub fn fire(&self, lua: &Lua, dt: f64) -> LuaResult<()> {
+        let keys = self.waiters.borrow_mut().drain(..).collect::<Vec<_>>();
+        for key in keys {
+            let thread: LuaThread = lua.registry_value(&key)?;
+            lua.remove_registry_value(key)?;
+            if thread.status() == LuaThreadStatus::Resumable {
+                self.queue.push_item(lua, thread, dt)?;
+            }
+        }
+        Ok(())
+    }
+
+    /**
+        Removes the given thread from the tick queue, so that it is never resumed.
+
+        Parked threads that were cancelled are removed once the next tick is fired.
+    */
+    pub fn cancel(&self, id: ThreadId) {
+        while self.queue.take_item(id).is_some() {}
+    }
+
+    /**
+        Removes all parked threads, and all threads in the tick queue.
+    */
+    pub fn clear(&self) {
+        self.waiters.borrow_mut().clear();
+        self.queue.clear();
+    }
+
+    /**
+        Checks if there are any threads still waiting for a tick.
+
+        Threads that are no longer resumable (such as cancelled threads) are removed.
+    */
+    pub fn has_waiters(&self, lua: &Lua) -> bool {
+        self.waiters.borrow_mut().retain(|key| {
+            lua.registry_value::<LuaThread>(key)
+                .is_ok_and(|t| t.status() == LuaThreadStatus::Resumable)
+        });
+        !self.waiters.borrow().is_empty()
+    }
+
+    /**
+        Returns the ids of all threads parked until the next tick, or already in the tick queue.
+    */
+    #[cfg(feature = "unstable")]
+    pub fn thread_ids(&self, lua: &Lua) -> Vec<ThreadId> {
+        let mut ids = self
+            .waiters
+            .borrow()
+            .iter()
+            .filter_map(|key| lua.registry_value::<LuaThread>(key).ok())
+            .map(|thread| ThreadId::from(&thread))
+            .collect::<Vec<_>>();
+        ids.extend(self.queue.thread_ids());
+        ids
+    }
+
+    /**
+        Returns the number of threads parked until the next tick, or already in the tick queue.
+    */
+    pub fn num_threads(&self, lua: &Lua) -> usize {
+        let parked = self
+            .waiters
+            .borrow()
+            .iter()
+            .filter(|key| {
+                lua.registry_value::<LuaThread>(key)
+                    .is_ok_and(|t| t.status() == LuaThreadStatus::Resumable)
+            })
+            .count();
+        parked + self.queue.len()
+    }
+
+    #[inline]
+    pub fn drain_items<'outer, 'lua>(
+        &'outer self,
+        lua: &'lua Lua,
+    ) -> impl Iterator<Item = (LuaThread<'lua>, LuaMultiValue<'lua>)> + 'outer
+    where
+        'lua: 'outer,
+    {
+        self.queue.drain_items(lua)
+    }
+
+    #[inline]
+    pub async fn wait_for_item(&self) {
+        self.queue.wait_for_item().await;
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
@@ -0,0 +1,484 @@
+use std::mem::size_of;
+
+use mlua::prelude::*;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{thread_id::ThreadId, util::ThreadResult};
+
+/**
+    The minimum capacity that tables are shrunk to in [`TrackingMode::Compact`].
+*/
+const MIN_COMPACT_CAPACITY: usize = 64;
+
+/**
+    How a scheduler stores tracked threads and their results, see [`Scheduler::set_tracking_mode`].
+
+    [`Scheduler::set_tracking_mode`]: crate::Scheduler::set_tracking_mode
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TrackingMode {
+    /// Store tracked threads and their results inline in hash tables,
+    /// and never release memory once it has been allocated.
+    #[default]
+    Default,
+    /// Store tracked threads in a slab, with a single hash table entry pointing at
+    /// the slot of each thread, a bitset of which slots are in use, and results
+    /// boxing any errors. Memory is released as tracked threads are removed,
+    /// at the cost of some rehashing as tracked threads come and go.
+    Compact,
+}
+
+/**
+    Statistics about the threads tracked by a scheduler, see [`Scheduler::tracking_stats`].
+
+    [`Scheduler::tracking_stats`]: crate::Scheduler::tracking_stats
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrackingStats {
+    /// The number of tracked threads, including ones that have completed.
+    pub tracked: usize,
+    /// The number of tracked threads that have completed, with results that have not yet been taken.
+    pub completed: usize,
+    /// The estimated number of bytes used to store tracked threads and their results.
+    ///
+    /// This does not include the values of results, which are stored in the Lua registry.
+    pub estimated_bytes: usize,
+}
+
+/**
+    A [`ThreadResult`] with its error boxed, making it a fraction of the size.
+*/
+struct CompactResult(Result<LuaRegistryKey, Box<LuaError>>);
+
+impl From<ThreadResult> for CompactResult {
+    fn from(result: ThreadResult) -> Self {
+        Self(result.into_inner().map_err(Box::new))
+    }
+}
+
+impl From<CompactResult> for ThreadResult {
+    fn from(result: CompactResult) -> Self {
+        ThreadResult::from_inner(result.0.map_err(|e| *e))
+    }
+}
+
+/**
+    Tracked threads stored in a slab, for [`TrackingMode::Compact`].
+
+    Each tracked thread is given the lowest free slot, so that slots stay densely packed
+    at the start of the slab, and trailing slots can be released as threads are removed.
+    A slot holds the result of its thread once completed, and threads that reuse the
+    address of an old thread are told apart by the generation in their [`ThreadId`].
+*/
+#[derive(Default)]
+struct Slab {
+    index: FxHashMap<ThreadId, u32>,
+    results: Vec<Option<CompactResult>>,
+    used: Vec<u64>,
+    /// All slots before this one are known to be in use.
+    first_free: usize,
+}
+
+impl Slab {
+    fn contains(&self, id: ThreadId) -> bool {
+        self.index.contains_key(&id)
+    }
+
+    fn get(&self, id: ThreadId) -> Option<&CompactResult> {
+        let slot = *self.index.get(&id)?;
+        self.results[slot as usize].as_ref()
+    }
+
+    fn track(&mut self, id: ThreadId) {
+        if !self.contains(id) {
+            let slot = self.alloc();
+            self.index.insert(id, slot);
+        }
+    }
+
+    fn insert(&mut self, id: ThreadId, result: CompactResult) {
+        if let Some(slot) = self.index.get(&id) {
+            self.results[*slot as usize] = Some(result);
+        }
+    }
+
+    /**
+        Removes the result of the given thread, freeing its slot, if it has completed.
+    */
+    fn remove(&mut self, id: ThreadId) -> Option<CompactResult> {
+        let slot = *self.index.get(&id)? as usize;
+        let result = self.results[slot].take()?;
+        self.index.remove(&id);
+        self.free(slot);
+        Some(result)
+    }
+
+    /**
+        Removes the given thread and its result, freeing its slot, if it is tracked.
+    */
+    fn untrack(&mut self, id: ThreadId) {
+        if let Some(slot) = self.index.remove(&id) {
+            self.results[slot as usize] = None;
+            self.free(slot as usize);
+        }
+    }
+
+    fn alloc(&mut self) -> u32 {
+        let mut word = self.first_free / 64;
+        while word < self.used.len() && self.used[word] == u64::MAX {
+            word += 1;
+        }
+        if word == self.used.len() {
+            self.used.push(0);
+        }
+        let slot = word * 64 + self.used[word].trailing_ones() as usize;
+        self.used[word] |= 1 << (slot % 64);
+        if slot >= self.results.len() {
+            self.results.resize_with(slot + 1, || None);
+        }
+        self.first_free = slot + 1;
+        u32::try_from(slot).expect("too many tracked threads")
+    }
+
+    fn free(&mut self, slot: usize) {
+        self.used[slot / 64] &= !(1 << (slot % 64));
+        self.first_free = self.first_free.min(slot);
+    }
+
+    fn clear(&mut self) {
+        self.index.clear();
+        self.results.clear();
+        self.used.clear();
+        self.first_free = 0;
+    }
+
+    fn completed(&self) -> impl Iterator<Item = &CompactResult> {
+        self.results.iter().flatten()
+    }
+
+    fn bytes(&self) -> usize {
+        let errors = self.completed().filter(|res| res.0.is_err()).count();
+        self.index.capacity() * (size_of::<(ThreadId, u32)>() + 1)
+            + self.results.capacity() * size_of::<Option<CompactResult>>()
+            + self.used.capacity() * size_of::<u64>()
+            + errors * size_of::<LuaError>()
+    }
+
+    /**
+        Releases any trailing slots that are no longer in use, and shrinks the index.
+    */
+    fn shrink(&mut self) {
+        while self.used.last() == Some(&0) {
+            self.used.pop();
+        }
+        let len = match self.used.last() {
+            Some(word) => self.used.len() * 64 - word.leading_zeros() as usize,
+            None => 0,
+        };
+        self.results.truncate(len);
+        if should_shrink(self.results.len(), self.results.capacity()) {
+            self.results.shrink_to(shrink_target(self.results.len()));
+            self.used.shrink_to(shrink_target(self.results.len()) / 64);
+        }
+        if should_shrink(self.index.len(), self.index.capacity()) {
+            self.index.shrink_to(shrink_target(self.index.len()));
+        }
+    }
+}
+
+fn should_shrink(len: usize, capacity: usize) -> bool {
+    capacity > MIN_COMPACT_CAPACITY && len < capacity / 4
+}
+
+fn shrink_target(len: usize) -> usize {
+    (len * 2).max(MIN_COMPACT_CAPACITY)
+}
+
+enum Store {
+    Default {
+        tracked: FxHashSet<ThreadId>,
+        results: FxHashMap<ThreadId, ThreadResult>,
+    },
+    Compact(Slab),
+}
+
+impl Store {
+    fn new_default() -> Self {
+        Self::Default {
+            tracked: FxHashSet::default(),
+            results: FxHashMap::default(),
+        }
+    }
+}
+
+/**
+    Tracked threads and their results, stored according to the current [`TrackingMode`].
+*/
+pub(crate) struct TrackedThreads {
+    store: Store,
+    /// Generations of tracked threads that have not yet completed, keyed by
+    /// their base id, for any that are not at the first generation.
+    generations: FxHashMap<ThreadId, u32>,
+}
+
+impl TrackedThreads {
+    pub fn new() -> Self {
+        Self {
+            store: Store::new_default(),
+            generations: FxHashMap::default(),
+        }
+    }
+
+    pub fn mode(&self) -> TrackingMode {
+        match self.store {
+            Store::Default { .. } => TrackingMode::Default,
+            Store::Compact(_) => TrackingMode::Compact,
+        }
+    }
+
+    /**
+        Switches to the given mode, converting any threads and results that are currently stored.
+    */
+    pub fn set_mode(&mut self, mode: TrackingMode) {
+        if self.mode() == mode {
+            return;
+        }
+        let store = std::mem::replace(&mut self.store, Store::new_default());
+        self.store = match store {
+            Store::Default { tracked, results } => {
+                let mut slab = Slab::default();
+                for id in tracked {
+                    slab.track(id);
+                }
+                for (id, res) in results {
+                    slab.insert(id, CompactResult::from(res));
+                }
+                Store::Compact(slab)
+            }
+            Store::Compact(mut slab) => {
+                let mut tracked = FxHashSet::default();
+                let mut results = FxHashMap::default();
+                for (id, slot) in slab.index.drain() {
+                    tracked.insert(id);
+                    if let Some(res) = slab.results[slot as usize].take() {
+                        results.insert(id, ThreadResult::from(res));
+                    }
+                }
+                Store::Default { tracked, results }
+            }
+        };
+        self.shrink();
+    }
+
+    /**
+        Starts tracking the thread with the given base id, and returns its id.
+
+        Threads that are already tracked, and have not yet completed, keep their id.
+        Otherwise, the thread is given the first generation that no other tracked thread
+        uses, so that a thread reusing the address of an old thread never aliases its result.
+    */
+    pub fn track_thread(&mut self, id: ThreadId) -> ThreadId {
+        let current = self.current(id);
+        if self.is_tracked(current) && !self.is_completed(current) {
+            return current;
+        }
+        let mut generation = 0;
+        while self.is_tracked(id.with_generation(generation)) {
+            generation = generation.wrapping_add(1);
+        }
+        let id = id.with_generation(generation);
+        match &mut self.store {
+            Store::Default { tracked, .. } => {
+                tracked.insert(id);
+            }
+            Store::Compact(slab) => slab.track(id),
+        }
+        if generation != 0 {
+            self.generations.insert(id.base(), generation);
+        }
+        id
+    }
+
+    /**
+        Returns the id of the tracked thread that is currently running at the given base id.
+
+        Ids that already have a generation, or that have no thread at a later
+        generation running, are returned as they are.
+    */
+    pub fn current(&self, id: ThreadId) -> ThreadId {
+        if id.generation() != 0 || self.generations.is_empty() {
+            return id;
+        }
+        match self.generations.get(&id) {
+            Some(generation) => id.with_generation(*generation),
+            None => id,
+        }
+    }
+
+    fn forget_generation(&mut self, id: ThreadId) {
+        if self.generations.get(&id.base()) == Some(&id.generation()) {
+            self.generations.remove(&id.base());
+        }
+    }
+
+    pub fn is_tracked(&self, id: ThreadId) -> bool {
+        match &self.store {
+            Store::Default { tracked, .. } => tracked.contains(&id),
+            Store::Compact(slab) => slab.contains(id),
+        }
+    }
+
+    pub fn is_completed(&self, id: ThreadId) -> bool {
+        match &self.store {
+            Store::Default { results, .. } => results.contains_key(&id),
+            Store::Compact(slab) => slab.get(id).is_some(),
+        }
+    }
+
+    pub fn insert(&mut self, id: ThreadId, result: ThreadResult) {
+        self.forget_generation(id);
+        match &mut self.store {
+            Store::Default { results, .. } => {
+                results.insert(id, result);
+            }
+            Store::Compact(slab) => slab.insert(id, CompactResult::from(result)),
+        }
+    }
+
+    /**
+        Returns a copy of the result of the given thread, if it has completed.
+    */
+    pub fn peek<'lua>(
+        &self,
+        lua: &'lua Lua,
+        id: ThreadId,
+    ) -> Option<LuaResult<LuaMultiValue<'lua>>> {
+        match &self.store {
+            Store::Default { results, .. } => results.get(&id).map(|res| res.peek(lua)),
+            Store::Compact(slab) => slab.get(id).map(|res| match &res.0 {
+                Ok(key) => Ok(LuaMultiValue::from_vec(lua.registry_value(key)?)),
+                Err(e) => Err(LuaError::clone(e)),
+            }),
+        }
+    }
+
+    /**
+        Removes the given thread and its result, if it has completed.
+
+        Threads that have not yet completed are left as they are.
+    */
+    pub fn remove(&mut self, id: ThreadId) -> Option<ThreadResult> {
+        let res = match &mut self.store {
+            Store::Default { tracked, results } => {
+                let res = results.remove(&id)?;
+                tracked.remove(&id);
+                res
+            }
+            Store::Compact(slab) => {
+                let res = ThreadResult::from(slab.remove(id)?);
+                slab.shrink();
+                res
+            }
+        };
+        self.forget_generation(id);
+        Some(res)
+    }
+
+    /**
+        Stops tracking the given thread, removing its result if it has completed.
+
+        Threads that have not yet completed will have their result discarded once they do.
+    */
+    pub fn untrack(&mut self, id: ThreadId) {
+        match &mut self.store {
+            Store::Default { tracked, results } => {
+                results.remove(&id);
+                tracked.remove(&id);
+            }
+            Store::Compact(slab) => {
+                slab.untrack(id);
+                slab.shrink();
+            }
+        }
+        self.forget_generation(id);
+    }
+
+    pub fn clear(&mut self) {
+        self.generations.clear();
+        match &mut self.store {
+            Store::Default { tracked, results } => {
+                tracked.clear();
+                results.clear();
+            }
+            Store::Compact(slab) => {
+                slab.clear();
+                slab.shrink();
+            }
+        }
+    }
+
+    /**
+        Returns all tracked threads that have not yet completed.
+    */
+    pub fn unfinished(&self) -> Vec<ThreadId> {
+        match &self.store {
+            Store::Default { tracked, results } => tracked
+                .iter()
+                .filter(|id| !results.contains_key(*id))
+                .copied()
+                .collect(),
+            Store::Compact(slab) => slab
+                .index
+                .iter()
+                .filter(|(_, slot)| slab.results[**slot as usize].is_none())
+                .map(|(id, _)| *id)
+                .collect(),
+        }
+    }
+
+    /**
+        Returns the number of results that hold a value in the Lua registry.
+    */
+    pub fn unclaimed(&self) -> usize {
+        match &self.store {
+            Store::Default { results, .. } => {
+                results.values().filter(|res| res.holds_value()).count()
+            }
+            Store::Compact(slab) => slab.completed().filter(|res| res.0.is_ok()).count(),
+        }
+    }
+
+    pub fn stats(&self) -> TrackingStats {
+        let generations_bytes = self.generations.capacity() * (size_of::<(ThreadId, u32)>() + 1);
+        match &self.store {
+            // NOTE: Hash tables store one control byte per slot, alongside the slot itself
+            Store::Default { tracked, results } => TrackingStats {
+                tracked: tracked.len(),
+                completed: results.len(),
+                estimated_bytes: generations_bytes
+                    + tracked.capacity() * (size_of::<ThreadId>() + 1)
+                    + results.capacity() * (size_of::<(ThreadId, ThreadResult)>() + 1),
+            },
+            Store::Compact(slab) => TrackingStats {
+                tracked: slab.index.len(),
+                completed: slab.completed().count(),
+                estimated_bytes: generations_bytes + slab.bytes(),
+            },
+        }
+    }
+
+    /**
+        Shrinks all tables once they are mostly empty, so that memory usage
+        follows the number of tracked threads, and not its peak.
+    */
+    fn shrink(&mut self) {
+        if should_shrink(self.generations.len(), self.generations.capacity()) {
+            self.generations
+                .shrink_to(shrink_target(self.generations.len()));
+        }
+        if let Store::Compact(slab) = &mut self.store {
+            slab.shrink();
+        }
+    }
+}
@@ -2,19 +2,39 @@
 #![allow(clippy::missing_errors_doc)]
 
 use std::{
-    cell::Cell, future::Future, process::ExitCode, rc::Weak as WeakRc, sync::Weak as WeakArc,
+    cell::Cell,
+    future::Future,
+    process::ExitCode,
+    rc::Weak as WeakRc,
+    time::{Duration, Instant},
 };
 
 use async_executor::{Executor, Task};
 use mlua::prelude::*;
-use tracing::trace;
+use tracing::{field, trace, trace_span, Instrument, Span};
 
+#[cfg(feature = "tokio")]
+use crate::companion::TokioCompanion;
 use crate::{
+    clock::Clock,
+    deadline::Deadlines,
+    diagnostics::Diagnostics,
+    drain::Drain,
+    executor::{self, MaybeSend},
     exit::Exit,
+    idle::IdleQueue,
+    lazy::create_lazy_async,
+    locals::TaskLocals,
+    names::ThreadNames,
+    native::create_native_async_function,
+    promise::create_promise_function,
     queue::{DeferredThreadQueue, FuturesQueue, SpawnedThreadQueue},
     result_map::ThreadResultMap,
     scheduler::Scheduler,
+    stats::Stats,
+    tags::ThreadTags,
     thread_id::ThreadId,
+    watchdog::Watchdog,
 };
 
 /**
@@ -34,6 +54,25 @@ pub trait IntoLuaThread<'lua> {
         Errors when out of memory.
     */
     fn into_lua_thread(self, lua: &'lua Lua) -> LuaResult<LuaThread<'lua>>;
+
+    /**
+        Converts the value into a Lua thread, along with the function it was created from, if any.
+
+        The function is used by the [`Scheduler`] to respawn threads, see [`Scheduler::respawn`].
+
+        # Errors
+
+        Errors when out of memory.
+    */
+    fn into_lua_thread_with_function(
+        self,
+        lua: &'lua Lua,
+    ) -> LuaResult<(LuaThread<'lua>, Option<LuaFunction<'lua>>)>
+    where
+        Self: Sized,
+    {
+        Ok((self.into_lua_thread(lua)?, None))
+    }
 }
 
 impl<'lua> IntoLuaThread<'lua> for LuaThread<'lua> {
@@ -46,12 +85,26 @@ impl<'lua> IntoLuaThread<'lua> for LuaFunction<'lua> {
     fn into_lua_thread(self, lua: &'lua Lua) -> LuaResult<LuaThread<'lua>> {
         lua.create_thread(self)
     }
+
+    fn into_lua_thread_with_function(
+        self,
+        lua: &'lua Lua,
+    ) -> LuaResult<(LuaThread<'lua>, Option<LuaFunction<'lua>>)> {
+        Ok((lua.create_thread(self.clone())?, Some(self)))
+    }
 }
 
 impl<'lua> IntoLuaThread<'lua> for LuaChunk<'lua, '_> {
     fn into_lua_thread(self, lua: &'lua Lua) -> LuaResult<LuaThread<'lua>> {
         lua.create_thread(self.into_function()?)
     }
+
+    fn into_lua_thread_with_function(
+        self,
+        lua: &'lua Lua,
+    ) -> LuaResult<(LuaThread<'lua>, Option<LuaFunction<'lua>>)> {
+        self.into_function()?.into_lua_thread_with_function(lua)
+    }
 }
 
 impl<'lua, T> IntoLuaThread<'lua> for &T
@@ -61,6 +114,13 @@ where
     fn into_lua_thread(self, lua: &'lua Lua) -> LuaResult<LuaThread<'lua>> {
         self.clone().into_lua_thread(lua)
     }
+
+    fn into_lua_thread_with_function(
+        self,
+        lua: &'lua Lua,
+    ) -> LuaResult<(LuaThread<'lua>, Option<LuaFunction<'lua>>)> {
+        self.clone().into_lua_thread_with_function(lua)
+    }
 }
 
 /**
@@ -114,12 +174,30 @@ pub trait LuaSchedulerExt<'lua> {
         args: impl IntoLuaMulti<'lua>,
     ) -> LuaResult<ThreadId>;
 
+    /**
+        Pushes a lua thread to the **idle** queue of the current scheduler.
+
+        See [`Scheduler::push_thread_idle`] for more information.
+
+        # Panics
+
+        Panics if called outside of a running [`Scheduler`].
+    */
+    fn push_thread_idle(
+        &'lua self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+    ) -> LuaResult<ThreadId>;
+
     /**
         Registers the given thread to be tracked within the current scheduler.
 
         Must be called before waiting for a thread to complete or getting its result.
+
+        Returns the id to use for the thread from now on, which only differs from the given id
+        if the thread reused the address of an old thread whose result has not yet been taken.
     */
-    fn track_thread(&'lua self, id: ThreadId);
+    fn track_thread(&'lua self, id: ThreadId) -> ThreadId;
 
     /**
         Gets the result of the given thread.
@@ -142,6 +220,298 @@ pub trait LuaSchedulerExt<'lua> {
         Panics if called outside of a running [`Scheduler`].
     */
     fn wait_for_thread(&'lua self, id: ThreadId) -> impl Future<Output = ()>;
+
+    /**
+        Sleeps for the given duration, using the clock of the current [`Scheduler`].
+
+        Returns the time that actually elapsed. Dropping the returned future cancels the sleep.
+
+        Builtins should prefer this over picking their own timer implementation,
+        so that all timing stays consistent with the rest of the scheduler, and
+        so that the sleep is visible through [`Scheduler::pending_sleeps`].
+
+        # Panics
+
+        Panics if called outside of a running [`Scheduler`].
+    */
+    fn sleep(&'lua self, duration: Duration) -> impl Future<Output = Duration> + 'static;
+
+    /**
+        Runs the given future with a timeout, using the clock of the current [`Scheduler`].
+
+        If the future does not complete within the given duration, it
+        is dropped, cancelling it, and an error is returned instead.
+
+        # Panics
+
+        Panics if called outside of a running [`Scheduler`].
+
+        # Example usage
+
+        ```rust
+        use std::time::Duration;
+
+        use async_io::block_on;
+
+        use mlua::prelude::*;
+        use mlua_luau_scheduler::*;
+
+        fn main() -> LuaResult<()> {
+            let lua = Lua::new();
+
+            let sched = Scheduler::new(&lua);
+            let fetch = lua.create_async_function(|lua, secs: f64| async move {
+                let response = async move {
+                    lua.sleep(Duration::from_secs_f64(secs)).await;
+                    "response"
+                };
+                lua.timeout(Duration::from_millis(50), response).await
+            })?;
+            lua.globals().set("fetch", fetch)?;
+
+            sched.push_thread_front(lua.load("assert(fetch(0.01) == 'response')"), ())?;
+            sched.push_thread_front(lua.load("assert(not pcall(fetch, 1))"), ())?;
+            block_on(sched.run());
+
+            Ok(())
+        }
+        ```
+    */
+    fn timeout<F: Future>(
+        &'lua self,
+        duration: Duration,
+        fut: F,
+    ) -> impl Future<Output = LuaResult<F::Output>>;
+
+    /**
+        Gets the deadline of the currently running Lua thread, if it has one.
+
+        Deadlines are set using [`Scheduler::push_thread_with_deadline`], and are inherited by
+        any threads spawned from a thread with a deadline. Note that this must be called while
+        the Lua thread is running, such as directly when an async function is called, and
+        not from within the future that it returns, which runs outside of the thread.
+
+        # Panics
+
+        Panics if called outside of a running [`Scheduler`].
+    */
+    fn current_deadline(&'lua self) -> Option<Instant>;
+
+    /**
+        Gets a clone of the task-local value of type `T` for the currently running Lua thread.
+
+        Task-local values are scoped to a single Lua thread, and are also available inside of
+        any futures spawned by the thread while they are being polled, making it possible for
+        builtins to share per-request state such as authentication tokens or database handles
+        without passing it through every Lua call. Note that futures share the values of the
+        thread that spawned them, so values should be set before spawning any such futures.
+
+        # Panics
+
+        Panics if called outside of a running [`Scheduler`].
+
+        # Example usage
+
+        ```rust
+        use async_io::block_on;
+
+        use mlua::prelude::*;
+        use mlua_luau_scheduler::*;
+
+        #[derive(Clone)]
+        struct AuthToken(String);
+
+        fn main() -> LuaResult<()> {
+            let lua = Lua::new();
+
+            lua.globals().set(
+                "whoami",
+                lua.create_function(|lua, ()| {
+                    Ok(lua.task_local::<AuthToken>().map(|token| token.0))
+                })?
+            )?;
+
+            let sched = Scheduler::new(&lua);
+            let thread = lua.create_thread(lua.load("assert(whoami() == 'admin')").into_function()?)?;
+            sched.set_task_local(&thread, AuthToken("admin".to_string()))?;
+            sched.push_thread_front(thread, ())?;
+            block_on(sched.run());
+
+            Ok(())
+        }
+        ```
+    */
+    fn task_local<T: Clone + 'static>(&'lua self) -> Option<T>;
+
+    /**
+        Sets the task-local value of type `T` for the currently running Lua thread, replacing any previous value.
+
+        See [`LuaSchedulerExt::task_local`] for more information.
+
+        # Panics
+
+        Panics if called outside of a running [`Scheduler`].
+    */
+    fn set_task_local<T: 'static>(&'lua self, value: T) -> LuaResult<()>;
+
+    /**
+        Removes the task-local value of type `T` for the currently running Lua thread, returning it.
+
+        See [`LuaSchedulerExt::task_local`] for more information.
+
+        # Panics
+
+        Panics if called outside of a running [`Scheduler`].
+    */
+    fn remove_task_local<T: 'static>(&'lua self) -> Option<T>;
+
+    /**
+        Creates a lazily loaded value, using the given async loader.
+
+        The returned proxy value will run the loader the first time it is called from Lua,
+        yielding the calling thread until the value has loaded, and then cache the value.
+        Any other threads calling the proxy while loading will also yield until it has loaded.
+
+        Once loaded, fields of the cached value may also be accessed directly on the proxy.
+        Note that Luau can not yield inside of metamethods such as `__index`, so
+        accessing fields on the proxy before it has been loaded will error.
+
+        # Example usage
+
+        ```rust
+        use async_io::block_on;
+
+        use mlua::prelude::*;
+        use mlua_luau_scheduler::*;
+
+        fn main() -> LuaResult<()> {
+            let lua = Lua::new();
+
+            let config = lua.create_lazy_async(|lua| async move {
+                lua.create_table_from([("name", "lazy")])
+            })?;
+            lua.globals().set("config", config)?;
+
+            let sched = Scheduler::new(&lua);
+            sched.push_thread_front(lua.load("assert(config().name == config.name)"), ())?;
+            block_on(sched.run());
+
+            Ok(())
+        }
+        ```
+    */
+    fn create_lazy_async<F, FR, R>(&'lua self, loader: F) -> LuaResult<LuaTable<'lua>>
+    where
+        F: Fn(&'lua Lua) -> FR + 'static,
+        FR: Future<Output = LuaResult<R>> + 'lua,
+        R: IntoLua<'lua>;
+
+    /**
+        Creates a native async function, which is driven directly by the scheduler.
+
+        Unlike [`Lua::create_async_function`], the calling thread will only yield and be resumed
+        a single time, once the returned future has completed, and will be resumed using the
+        returned values directly, without any additional polling in between.
+
+        Since the future runs on the scheduler independently from the calling thread,
+        it must be `'static`, and may not hold any references to the Lua state.
+
+        # Panics
+
+        Panics if called outside of a running [`Scheduler`].
+
+        # Example usage
+
+        ```rust
+        use std::time::Duration;
+
+        use async_io::{block_on, Timer};
+
+        use mlua::prelude::*;
+        use mlua_luau_scheduler::*;
+
+        fn main() -> LuaResult<()> {
+            let lua = Lua::new();
+
+            let sched = Scheduler::new(&lua);
+            let sleep = lua.create_native_async_function(|_, secs: f64| async move {
+                Timer::after(Duration::from_secs_f64(secs)).await;
+                Ok((secs, None::<f64>, "done"))
+            })?;
+            lua.globals().set("sleep", sleep)?;
+
+            sched.push_thread_front(lua.load("assert(select('#', sleep(0.01)) == 3)"), ())?;
+            block_on(sched.run());
+
+            Ok(())
+        }
+        ```
+    */
+    fn create_native_async_function<A, R, F, FR>(
+        &'lua self,
+        func: F,
+    ) -> LuaResult<LuaFunction<'lua>>
+    where
+        A: FromLuaMulti<'lua>,
+        R: for<'r> IntoLuaMulti<'r> + 'static,
+        F: Fn(&'lua Lua, A) -> FR + 'static,
+        FR: Future<Output = LuaResult<R>> + 'static;
+
+    /**
+        Creates a promise function, which returns a promise for the output of a future to Lua.
+
+        Unlike [`Lua::create_async_function`], calling the function does not yield the calling
+        thread. The returned future is instead spawned onto the scheduler right away, and a promise
+        is returned, which may be used to get the output of the future once it has completed:
+
+        - `promise:await()` yields until the future completes, and then returns its values, or raises its error
+        - `promise:andThen(onResolve, onReject?)` returns a new promise for the result of the given callbacks
+        - `promise:catch(onReject)` is the same as `andThen`, but only handles errors
+        - `promise:isSettled()` returns `true` if the future has completed
+
+        Callbacks given to `andThen` and `catch` run in threads of their own, once the promise has settled.
+        Values and errors without a matching callback are passed through to the returned promise as they are.
+
+        Since the future runs on the scheduler independently from the calling thread,
+        it must be `'static`, and may not hold any references to the Lua state.
+
+        # Panics
+
+        Panics if called outside of a running [`Scheduler`].
+
+        # Example usage
+
+        ```rust
+        use std::time::Duration;
+
+        use async_io::{block_on, Timer};
+
+        use mlua::prelude::*;
+        use mlua_luau_scheduler::*;
+
+        fn main() -> LuaResult<()> {
+            let lua = Lua::new();
+
+            let sched = Scheduler::new(&lua);
+            let fetch = lua.create_promise_function(|_, secs: f64| async move {
+                Timer::after(Duration::from_secs_f64(secs)).await;
+                Ok("done")
+            })?;
+            lua.globals().set("fetch", fetch)?;
+
+            sched.push_thread_front(lua.load("assert(fetch(0.01):await() == 'done')"), ())?;
+            block_on(sched.run());
+
+            Ok(())
+        }
+        ```
+    */
+    fn create_promise_function<A, R, F, FR>(&'lua self, func: F) -> LuaResult<LuaFunction<'lua>>
+    where
+        A: FromLuaMulti<'lua>,
+        R: for<'r> IntoLuaMulti<'r> + 'static,
+        F: Fn(&'lua Lua, A) -> FR + 'static,
+        FR: Future<Output = LuaResult<R>> + 'static;
 }
 
 /**
@@ -157,6 +527,9 @@ pub trait LuaSpawnExt<'lua> {
     /**
         Spawns the given future on the current executor and returns its [`Task`].
 
+        The future is instrumented with a tracing span that records the id and tag of the
+        Lua thread that spawned it, which also applies to all other spawn methods in this trait.
+
         # Panics
 
         Panics if called outside of a running [`Scheduler`].
@@ -192,8 +565,8 @@ pub trait LuaSpawnExt<'lua> {
     */
     fn spawn<F, T>(&self, fut: F) -> Task<T>
     where
-        F: Future<Output = T> + Send + 'static,
-        T: Send + 'static;
+        F: Future<Output = T> + MaybeSend + 'static,
+        T: MaybeSend + 'static;
 
     /**
         Spawns the given thread-local future on the current executor.
@@ -238,6 +611,23 @@ pub trait LuaSpawnExt<'lua> {
     where
         F: Future<Output = ()> + 'static;
 
+    /**
+        Spawns the given thread-local future on the idle queue of the current executor.
+
+        Idle futures are only spawned when there is no other work to be done, and will
+        run detached and always to completion, same as [`LuaSpawnExt::spawn_local`].
+
+        Note that once spawned, the future will be polled at the same priority as
+        any other future, only spawning the future is deferred until idle time.
+
+        # Panics
+
+        Panics if called outside of a running [`Scheduler`].
+    */
+    fn spawn_idle<F>(&self, fut: F)
+    where
+        F: Future<Output = ()> + 'static;
+
     /**
         Spawns the given blocking function and returns its [`Task`].
 
@@ -280,6 +670,130 @@ pub trait LuaSpawnExt<'lua> {
     where
         F: FnOnce() -> T + Send + 'static,
         T: Send + 'static;
+
+    /**
+        Spawns the given future on the companion Tokio runtime and returns its [`Task`].
+
+        This lets async functions use libraries that need a Tokio reactor, such as `reqwest`
+        or `sqlx`, while the output is forwarded back into the current executor, waking up
+        the [`Scheduler`] once ready. Dropping the returned task aborts the Tokio task,
+        and any panic in the Tokio task is resumed when awaiting the returned task.
+
+        See [`Scheduler::set_tokio_handle`] for which Tokio runtime is used.
+
+        # Panics
+
+        Panics if called outside of a running [`Scheduler`].
+
+        # Example usage
+
+        ```rust
+        use std::time::Duration;
+
+        use async_io::block_on;
+
+        use mlua::prelude::*;
+        use mlua_luau_scheduler::*;
+
+        fn main() -> LuaResult<()> {
+            let lua = Lua::new();
+
+            lua.globals().set(
+                "tokioSleep",
+                lua.create_async_function(|lua, secs: f64| async move {
+                    lua.spawn_tokio(async move {
+                        tokio::time::sleep(Duration::from_secs_f64(secs)).await;
+                    }).await;
+                    Ok(())
+                })?
+            )?;
+
+            let sched = Scheduler::new(&lua);
+            sched.push_thread_front(lua.load("tokioSleep(0.01)"), ());
+            block_on(sched.run());
+
+            Ok(())
+        }
+        ```
+    */
+    #[cfg(feature = "tokio")]
+    fn spawn_tokio<F, T>(&self, fut: F) -> Task<T>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static;
+}
+
+/**
+    Creates a tracing span for a future spawned by the currently running Lua thread.
+
+    The span records the id of the thread, as well as its tag, if any, so that
+    any events emitted while polling the future can be traced back to Lua.
+*/
+fn spawned_future_span(lua: &Lua) -> Span {
+    let span = trace_span!(
+        "Scheduler::spawned_future",
+        thread = field::Empty,
+        tag = field::Empty
+    );
+    if !span.is_disabled() {
+        let thread = lua.current_thread();
+        if let Some(tag) = lua
+            .app_data_ref::<ThreadTags>()
+            .and_then(|tags| tags.get(lua, &thread))
+        {
+            span.record("tag", tag);
+        }
+        span.record("thread", field::debug(ThreadId::from(&thread)));
+    }
+    span
+}
+
+/**
+    Counts a future spawned using [`LuaSpawnExt`], see [`Scheduler::stats`].
+*/
+fn record_spawned_future(lua: &Lua) {
+    if let Some(stats) = lua.app_data_ref::<Stats>() {
+        stats.record_future();
+    }
+}
+
+/**
+    Spawns the given thread-local future on the current executor, without the watchdog.
+
+    Used internally for futures that must never be cancelled by
+    the watchdog, such as those that handle their own timeouts.
+*/
+pub(crate) fn spawn_local_unwatched(lua: &Lua, fut: impl Future<Output = ()> + 'static) {
+    let queue = lua
+        .app_data_ref::<WeakRc<FuturesQueue>>()
+        .expect("tasks can only be spawned within an active scheduler")
+        .upgrade()
+        .expect("executor was dropped");
+    trace!("spawning local task on executor");
+    let fut = fut.instrument(spawned_future_span(lua));
+    queue.push_item(monitored_future(lua, fut));
+}
+
+/**
+    Wraps a future spawned by the currently running Lua thread, for
+    diagnostics, and so that it can access the task-local values of the thread.
+*/
+fn monitored_future<'fut>(
+    lua: &Lua,
+    fut: impl Future<Output = ()> + 'fut,
+) -> impl Future<Output = ()> + 'fut {
+    let diagnostics = lua
+        .app_data_ref::<Diagnostics>()
+        .expect("tasks can only be spawned within an active scheduler");
+    let thread = lua.current_thread();
+    let tag = lua
+        .app_data_ref::<ThreadTags>()
+        .and_then(|tags| diagnostics.tag_for(lua, &tags, &thread));
+    let fut = lua
+        .app_data_ref::<TaskLocals>()
+        .expect("tasks can only be spawned within an active scheduler")
+        .scoped(lua, fut);
+    diagnostics.monitor(fut, Some(ThreadId::from(&thread)), tag)
 }
 
 impl<'lua> LuaSchedulerExt<'lua> for Lua {
@@ -295,10 +809,24 @@ impl<'lua> LuaSchedulerExt<'lua> for Lua {
         thread: impl IntoLuaThread<'lua>,
         args: impl IntoLuaMulti<'lua>,
     ) -> LuaResult<ThreadId> {
+        self.app_data_ref::<Drain>()
+            .expect("lua threads can only be pushed from within an active scheduler")
+            .check()?;
         let queue = self
             .app_data_ref::<SpawnedThreadQueue>()
             .expect("lua threads can only be pushed from within an active scheduler");
-        queue.push_item(self, thread, args)
+        let thread = thread.into_lua_thread(self)?;
+        if let Some(deadlines) = self.app_data_ref::<Deadlines>() {
+            deadlines.inherit(self, &thread)?;
+        }
+        if let Some(names) = self.app_data_ref::<ThreadNames>() {
+            names.inherit(self, &thread)?;
+        }
+        let id = queue.push_item(self, thread, args)?;
+        if let Some(stats) = self.app_data_ref::<Stats>() {
+            stats.record_spawned();
+        }
+        Ok(id)
     }
 
     fn push_thread_back(
@@ -306,17 +834,49 @@ impl<'lua> LuaSchedulerExt<'lua> for Lua {
         thread: impl IntoLuaThread<'lua>,
         args: impl IntoLuaMulti<'lua>,
     ) -> LuaResult<ThreadId> {
+        self.app_data_ref::<Drain>()
+            .expect("lua threads can only be pushed from within an active scheduler")
+            .check()?;
         let queue = self
             .app_data_ref::<DeferredThreadQueue>()
             .expect("lua threads can only be pushed from within an active scheduler");
-        queue.push_item(self, thread, args)
+        let thread = thread.into_lua_thread(self)?;
+        if let Some(deadlines) = self.app_data_ref::<Deadlines>() {
+            deadlines.inherit(self, &thread)?;
+        }
+        if let Some(names) = self.app_data_ref::<ThreadNames>() {
+            names.inherit(self, &thread)?;
+        }
+        let id = queue.push_item(self, thread, args)?;
+        if let Some(stats) = self.app_data_ref::<Stats>() {
+            stats.record_deferred();
+        }
+        Ok(id)
     }
 
-    fn track_thread(&'lua self, id: ThreadId) {
+    fn push_thread_idle(
+        &'lua self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+    ) -> LuaResult<ThreadId> {
+        self.app_data_ref::<Drain>()
+            .expect("lua threads can only be pushed from within an active scheduler")
+            .check()?;
+        let queue = self
+            .app_data_ref::<IdleQueue>()
+            .expect("lua threads can only be pushed from within an active scheduler");
+        let id = queue.threads().push_item(self, thread, args)?;
+        if let Some(stats) = self.app_data_ref::<Stats>() {
+            stats.record_deferred();
+        }
+        Ok(id)
+    }
+
+    fn track_thread(&'lua self, id: ThreadId) -> ThreadId {
         let map = self
             .app_data_ref::<ThreadResultMap>()
             .expect("lua threads can only be tracked from within an active scheduler");
-        map.track(id);
+        map.track_thread(id)
     }
 
     fn get_thread_result(&'lua self, id: ThreadId) -> Option<LuaResult<LuaMultiValue<'lua>>> {
@@ -332,34 +892,130 @@ impl<'lua> LuaSchedulerExt<'lua> for Lua {
             .expect("lua threads results can only be retrieved from within an active scheduler");
         async move { map.listen(id).await }
     }
+
+    fn sleep(&'lua self, duration: Duration) -> impl Future<Output = Duration> + 'static {
+        let clock = self
+            .app_data_ref::<Clock>()
+            .expect("sleeping is only possible from within an active scheduler")
+            .clone();
+        clock.sleep(duration)
+    }
+
+    fn timeout<F: Future>(
+        &'lua self,
+        duration: Duration,
+        fut: F,
+    ) -> impl Future<Output = LuaResult<F::Output>> {
+        let clock = self
+            .app_data_ref::<Clock>()
+            .expect("timeouts are only possible from within an active scheduler")
+            .clone();
+        async move {
+            clock
+                .timeout(duration, fut)
+                .await
+                .ok_or_else(|| LuaError::runtime(format!("operation timed out after {duration:?}")))
+        }
+    }
+
+    fn current_deadline(&'lua self) -> Option<Instant> {
+        let deadlines = self
+            .app_data_ref::<Deadlines>()
+            .expect("deadlines are only available from within an active scheduler");
+        deadlines.get(ThreadId::from(&self.current_thread()))
+    }
+
+    fn task_local<T: Clone + 'static>(&'lua self) -> Option<T> {
+        let locals = self
+            .app_data_ref::<TaskLocals>()
+            .expect("task-local values are only available from within an active scheduler");
+        locals.get(self)
+    }
+
+    fn set_task_local<T: 'static>(&'lua self, value: T) -> LuaResult<()> {
+        let locals = self
+            .app_data_ref::<TaskLocals>()
+            .expect("task-local values are only available from within an active scheduler");
+        locals.set(self, value)
+    }
+
+    fn remove_task_local<T: 'static>(&'lua self) -> Option<T> {
+        let locals = self
+            .app_data_ref::<TaskLocals>()
+            .expect("task-local values are only available from within an active scheduler");
+        locals.remove(self)
+    }
+
+    fn create_lazy_async<F, FR, R>(&'lua self, loader: F) -> LuaResult<LuaTable<'lua>>
+    where
+        F: Fn(&'lua Lua) -> FR + 'static,
+        FR: Future<Output = LuaResult<R>> + 'lua,
+        R: IntoLua<'lua>,
+    {
+        create_lazy_async(self, loader)
+    }
+
+    fn create_native_async_function<A, R, F, FR>(
+        &'lua self,
+        func: F,
+    ) -> LuaResult<LuaFunction<'lua>>
+    where
+        A: FromLuaMulti<'lua>,
+        R: for<'r> IntoLuaMulti<'r> + 'static,
+        F: Fn(&'lua Lua, A) -> FR + 'static,
+        FR: Future<Output = LuaResult<R>> + 'static,
+    {
+        create_native_async_function(self, func)
+    }
+
+    fn create_promise_function<A, R, F, FR>(&'lua self, func: F) -> LuaResult<LuaFunction<'lua>>
+    where
+        A: FromLuaMulti<'lua>,
+        R: for<'r> IntoLuaMulti<'r> + 'static,
+        F: Fn(&'lua Lua, A) -> FR + 'static,
+        FR: Future<Output = LuaResult<R>> + 'static,
+    {
+        create_promise_function(self, func)
+    }
 }
 
-impl<'lua> LuaSpawnExt<'lua> for Lua {
+impl LuaSpawnExt<'_> for Lua {
     fn spawn<F, T>(&self, fut: F) -> Task<T>
     where
-        F: Future<Output = T> + Send + 'static,
-        T: Send + 'static,
+        F: Future<Output = T> + MaybeSend + 'static,
+        T: MaybeSend + 'static,
     {
-        let exec = self
-            .app_data_ref::<WeakArc<Executor>>()
-            .expect("tasks can only be spawned within an active scheduler")
-            .upgrade()
-            .expect("executor was dropped");
         trace!("spawning future on executor");
-        exec.spawn(fut)
+        record_spawned_future(self);
+        executor::spawn(self, fut.instrument(spawned_future_span(self)))
     }
 
     fn spawn_local<F>(&self, fut: F)
     where
         F: Future<Output = ()> + 'static,
     {
-        let queue = self
-            .app_data_ref::<WeakRc<FuturesQueue>>()
+        let watchdog = self
+            .app_data_ref::<Watchdog>()
             .expect("tasks can only be spawned within an active scheduler")
-            .upgrade()
-            .expect("executor was dropped");
-        trace!("spawning local task on executor");
-        queue.push_item(fut);
+            .clone();
+        let thread = ThreadId::from(&self.current_thread());
+        record_spawned_future(self);
+        spawn_local_unwatched(self, async move {
+            let _ = watchdog.watch(fut, Some(thread)).await;
+        });
+    }
+
+    fn spawn_idle<F>(&self, fut: F)
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        let queue = self
+            .app_data_ref::<IdleQueue>()
+            .expect("tasks can only be spawned within an active scheduler");
+        trace!("spawning idle task on executor");
+        record_spawned_future(self);
+        let fut = fut.instrument(spawned_future_span(self));
+        queue.futures().push_item(monitored_future(self, fut));
     }
 
     fn spawn_blocking<F, T>(&self, f: F) -> Task<T>
@@ -367,12 +1023,27 @@ impl<'lua> LuaSpawnExt<'lua> for Lua {
         F: FnOnce() -> T + Send + 'static,
         T: Send + 'static,
     {
-        let exec = self
-            .app_data_ref::<WeakArc<Executor>>()
-            .expect("tasks can only be spawned within an active scheduler")
-            .upgrade()
-            .expect("executor was dropped");
         trace!("spawning blocking task on executor");
-        exec.spawn(blocking::unblock(f))
+        record_spawned_future(self);
+        executor::spawn(
+            self,
+            blocking::unblock(f).instrument(spawned_future_span(self)),
+        )
+    }
+
+    #[cfg(feature = "tokio")]
+    fn spawn_tokio<F, T>(&self, fut: F) -> Task<T>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let companion = self
+            .app_data_ref::<TokioCompanion>()
+            .expect("tasks can only be spawned within an active scheduler")
+            .clone();
+        trace!("spawning future on companion tokio runtime");
+        record_spawned_future(self);
+        let fut = companion.spawn(fut.instrument(spawned_future_span(self)));
+        executor::spawn(self, fut)
     }
 }
@@ -0,0 +1,19 @@
+/*!
+    Experimental subsystems, available with the `unstable` feature.
+
+    Nothing in this module follows semantic versioning, and any of it may
+    change or be removed in any release, including patch releases. Subsystems
+    graduate to the [`prelude`](crate::prelude) once their APIs have settled.
+*/
+
+mod group;
+mod plugin;
+mod pool;
+mod snapshot;
+
+pub use group::SchedulerGroup;
+pub use plugin::{SchedulerPlugin, ThreadEvent};
+pub use pool::{PoolJobId, PoolStrategy, SchedulerPool};
+pub use snapshot::{ThreadSnapshot, ThreadState};
+
+pub(crate) use plugin::Plugins;
@@ -0,0 +1,181 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Wake, Waker},
+    time::{Duration, Instant},
+};
+
+use crate::scheduler::Scheduler;
+
+/**
+    Waker for a single member of a [`SchedulerGroup`].
+
+    Marks the member as woken, so that it gets polled during the next round,
+    and then wakes the group itself, which may be polled by any executor.
+*/
+struct MemberWaker {
+    woken: AtomicBool,
+    group: Mutex<Option<Waker>>,
+}
+
+impl Wake for MemberWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+        if let Some(waker) = &*self.group.lock().unwrap() {
+            waker.wake_by_ref();
+        }
+    }
+}
+
+/**
+    A single scheduler in a [`SchedulerGroup`], along with its budget and state.
+*/
+struct Member<'a> {
+    scheduler: &'a Scheduler<'a>,
+    budget: Duration,
+    debt: Duration,
+    waker: Arc<MemberWaker>,
+    run: Option<Pin<Box<dyn Future<Output = ()> + 'a>>>,
+}
+
+/**
+    A group of [`Scheduler`]s, for different Lua states, sharing a single OS thread.
+
+    Running the group interleaves all schedulers, polling them in round-robin order.
+    Each scheduler has a budget for how long it may run per round, and any scheduler
+    that exceeds its budget will skip rounds until the time it overran has been paid
+    back, letting other schedulers catch up, similar to deficit round-robin.
+
+    Note that a scheduler can not be interrupted in the middle of resuming a
+    Lua thread, so budgets are only enforced between polls of each scheduler.
+*/
+#[derive(Default)]
+pub struct SchedulerGroup<'a> {
+    members: Vec<Member<'a>>,
+}
+
+impl<'a> SchedulerGroup<'a> {
+    /**
+        Creates a new, empty, scheduler group.
+    */
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+        Adds the given [`Scheduler`] to this group, with the given budget per round.
+    */
+    pub fn add(&mut self, scheduler: &'a Scheduler<'a>, budget: Duration) {
+        self.members.push(Member {
+            scheduler,
+            budget,
+            debt: Duration::ZERO,
+            waker: Arc::new(MemberWaker {
+                woken: AtomicBool::new(true),
+                group: Mutex::new(None),
+            }),
+            run: None,
+        });
+    }
+
+    /**
+        Returns the number of schedulers in this group.
+    */
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    /**
+        Returns `true` if there are no schedulers in this group.
+    */
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /**
+        Runs all schedulers in this group until they have all completed.
+
+        See [`Scheduler::run`] for more information about running a single scheduler.
+
+        # Panics
+
+        Panics if any scheduler in this group is already running.
+    */
+    pub async fn run_all(&mut self) {
+        for member in &mut self.members {
+            let scheduler = member.scheduler;
+            member.run = Some(Box::pin(scheduler.run()));
+        }
+        RunAll {
+            members: &mut self.members,
+        }
+        .await;
+    }
+}
+
+/**
+    Future driving all members of a [`SchedulerGroup`], see [`SchedulerGroup::run_all`].
+*/
+struct RunAll<'g, 'a> {
+    members: &'g mut Vec<Member<'a>>,
+}
+
+impl Future for RunAll<'_, '_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut skipped = false;
+        for member in self.members.iter_mut() {
+            let Some(run) = member.run.as_mut() else {
+                continue;
+            };
+            member
+                .waker
+                .group
+                .lock()
+                .unwrap()
+                .replace(cx.waker().clone());
+
+            // Pay back some debt from previous overruns, skipping this round
+            if !member.debt.is_zero() {
+                member.debt = member.debt.saturating_sub(member.budget);
+                skipped = true;
+                continue;
+            }
+            if !member.waker.woken.swap(false, Ordering::AcqRel) {
+                continue;
+            }
+
+            let waker = Waker::from(Arc::clone(&member.waker));
+            let mut member_cx = Context::from_waker(&waker);
+            let start = Instant::now();
+            let poll = run.as_mut().poll(&mut member_cx);
+            member.debt = start.elapsed().saturating_sub(member.budget);
+            if poll.is_ready() {
+                member.run = None;
+            }
+        }
+
+        if self.members.iter().all(|member| member.run.is_none()) {
+            Poll::Ready(())
+        } else {
+            if skipped {
+                // NOTE: Skipped members may have been woken in the meantime,
+                // so we must make sure there is another round for them
+                cx.waker().wake_by_ref();
+            }
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::prelude::*;
+
+use crate::{exit::ExitReason, thread_id::ThreadId};
+
+/**
+    An event for a Lua thread resumed by a scheduler, see [`SchedulerPlugin::on_thread_event`].
+*/
+#[derive(Debug, Clone)]
+pub enum ThreadEvent {
+    /// The thread is about to be resumed.
+    Resumed(ThreadId),
+    /// The thread yielded, and may be resumed again later.
+    Yielded(ThreadId),
+    /// The thread ran to completion.
+    Completed(ThreadId),
+    /// The thread errored.
+    Errored(ThreadId, LuaError),
+}
+
+impl ThreadEvent {
+    /**
+        Returns the id of the thread that this event is for.
+    */
+    #[must_use]
+    pub fn thread_id(&self) -> ThreadId {
+        match self {
+            Self::Resumed(id) | Self::Yielded(id) | Self::Completed(id) | Self::Errored(id, _) => {
+                *id
+            }
+        }
+    }
+}
+
+/**
+    An extension for a [`Scheduler`], with hooks that are called throughout its lifetime.
+
+    Plugins make it possible to ship features such as stdio capture, metrics exporters,
+    or custom clocks, as separate units that can be composed together, and added to any
+    scheduler using [`Scheduler::with_plugin`]. All hooks do nothing by default.
+
+    Hooks are called in the order that plugins were added to the scheduler.
+
+    [`Scheduler`]: crate::Scheduler
+    [`Scheduler::with_plugin`]: crate::Scheduler::with_plugin
+*/
+pub trait SchedulerPlugin {
+    /**
+        Called once, when the plugin is added to a scheduler.
+
+        The scheduler is attached to the Lua state at this point, so this
+        is a good place to register any builtins the plugin provides,
+        including ones created using [`Functions`].
+
+        [`Functions`]: crate::Functions
+
+        # Errors
+
+        Any error returned here is returned from [`Scheduler::with_plugin`].
+
+        [`Scheduler::with_plugin`]: crate::Scheduler::with_plugin
+    */
+    fn on_init(&self, _lua: &Lua) -> LuaResult<()> {
+        Ok(())
+    }
+
+    /**
+        Called once per cycle of the scheduler, after all ready work has been processed.
+    */
+    fn on_tick(&self, _lua: &Lua) {}
+
+    /**
+        Called whenever a Lua thread is resumed by the scheduler, and when it yields, completes, or errors.
+
+        Note that threads resumed directly from Lua, such as using `spawn`
+        or `coroutine.resume`, are not reported until the scheduler resumes them.
+    */
+    fn on_thread_event(&self, _lua: &Lua, _event: &ThreadEvent) {}
+
+    /**
+        Called once the scheduler stops running, with the reason it stopped.
+    */
+    fn on_shutdown(&self, _lua: &Lua, _reason: ExitReason) {}
+}
+
+/**
+    The plugins added to a scheduler.
+*/
+#[derive(Clone)]
+pub(crate) struct Plugins {
+    inner: Rc<RefCell<Vec<Rc<dyn SchedulerPlugin>>>>,
+}
+
+impl Plugins {
+    pub fn new() -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    pub fn add(&self, lua: &Lua, plugin: impl SchedulerPlugin + 'static) -> LuaResult<()> {
+        plugin.on_init(lua)?;
+        self.inner.borrow_mut().push(Rc::new(plugin));
+        Ok(())
+    }
+
+    /**
+        Calls the given hook for all plugins, in order.
+
+        NOTE: We must not hold any borrows while calling into
+        plugins, since plugins may call back into the scheduler.
+    */
+    fn each(&self, f: impl Fn(&dyn SchedulerPlugin)) {
+        let plugins = self.inner.borrow().clone();
+        for plugin in plugins {
+            f(plugin.as_ref());
+        }
+    }
+
+    pub fn tick(&self, lua: &Lua) {
+        self.each(|plugin| plugin.on_tick(lua));
+    }
+
+    /**
+        Reports a thread event to all plugins, only creating the event if there are any plugins.
+    */
+    pub fn thread_event(&self, lua: &Lua, event: impl FnOnce() -> ThreadEvent) {
+        if !self.inner.borrow().is_empty() {
+            let event = event();
+            self.each(|plugin| plugin.on_thread_event(lua, &event));
+        }
+    }
+
+    pub fn shutdown(&self, lua: &Lua, reason: ExitReason) {
+        self.each(|plugin| plugin.on_shutdown(lua, reason));
+    }
+}
@@ -0,0 +1,437 @@
+use std::{
+    cell::RefCell,
+    pin::pin,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use async_io::block_on;
+use concurrent_queue::ConcurrentQueue;
+use event_listener::Event;
+use futures_lite::{future, prelude::*};
+use mlua::prelude::*;
+use rustc_hash::FxHashMap;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_value::Value;
+
+use crate::{
+    remote::{serialize_args, RemoteWork},
+    scheduler::Scheduler,
+    thread_id::ThreadId,
+};
+
+const ERR_POOL_EMPTY: &str = "scheduler pool must have at least one worker";
+const ERR_WORKER_GONE: &str = "scheduler pool worker has stopped";
+
+type PoolInit = dyn Fn(&Lua, &Scheduler) -> LuaResult<()> + Send + Sync;
+
+/**
+    Strategy for distributing jobs across the workers of a [`SchedulerPool`].
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PoolStrategy {
+    /**
+        Jobs are handed to each worker in turn.
+    */
+    #[default]
+    RoundRobin,
+    /**
+        Jobs are handed to the worker with the fewest unfinished jobs.
+    */
+    LeastLoaded,
+}
+
+/**
+    Opaque and unique identifier for a job pushed to a [`SchedulerPool`].
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PoolJobId(u64);
+
+/**
+    Results of finished jobs, shared between all workers and the pool itself.
+*/
+#[derive(Default)]
+struct PoolResults {
+    values: Mutex<FxHashMap<PoolJobId, LuaResult<Value>>>,
+    event: Event,
+}
+
+impl PoolResults {
+    fn insert(&self, id: PoolJobId, result: LuaResult<Value>) {
+        self.values.lock().unwrap().insert(id, result);
+        self.event.notify(usize::MAX);
+    }
+
+    fn take(&self, id: PoolJobId) -> Option<LuaResult<Value>> {
+        self.values.lock().unwrap().remove(&id)
+    }
+}
+
+/**
+    State shared between a single worker thread and the pool.
+*/
+struct WorkerShared {
+    queue: ConcurrentQueue<(PoolJobId, RemoteWork)>,
+    event: Event,
+    load: AtomicUsize,
+}
+
+struct Worker {
+    shared: Arc<WorkerShared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/**
+    A pool of Lua states, each with their own [`Scheduler`] running on a separate OS thread.
+
+    A single Luau VM can only ever use a single core, so CPU-bound workloads may be spread
+    across multiple VMs using a pool instead. Each worker is set up by calling the given
+    init function with its fresh Lua state and scheduler, which is typically used to set
+    globals and register functions that jobs may call, see [`Scheduler::register_function`].
+
+    Jobs are distributed across workers according to the [`PoolStrategy`] of the pool, and
+    their results are aggregated by the pool, keyed by the [`PoolJobId`] returned when pushing.
+    Much like with a [`SchedulerHandle`], arguments and results are serialized when crossing
+    between threads, and a job returning multiple values results in a sequence of values.
+
+    Dropping the pool waits for all pushed jobs to finish, and then stops all workers.
+
+    [`SchedulerHandle`]: crate::SchedulerHandle
+*/
+pub struct SchedulerPool {
+    workers: Vec<Worker>,
+    strategy: PoolStrategy,
+    next_worker: AtomicUsize,
+    next_id: AtomicU64,
+    results: Arc<PoolResults>,
+}
+
+impl SchedulerPool {
+    /**
+        Creates a new pool with the given number of workers, each set up using the given init function.
+
+        # Errors
+
+        Errors if the init function errors for any of the workers.
+
+        # Panics
+
+        Panics if the given number of workers is zero, or if a worker thread could not be spawned.
+    */
+    pub fn new<F>(size: usize, init: F) -> LuaResult<Self>
+    where
+        F: Fn(&Lua, &Scheduler) -> LuaResult<()> + Send + Sync + 'static,
+    {
+        assert!(size > 0, "{ERR_POOL_EMPTY}");
+
+        let init: Arc<PoolInit> = Arc::new(init);
+        let results = Arc::new(PoolResults::default());
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        let workers = (0..size)
+            .map(|index| {
+                let shared = Arc::new(WorkerShared {
+                    queue: ConcurrentQueue::unbounded(),
+                    event: Event::new(),
+                    load: AtomicUsize::new(0),
+                });
+                let thread = thread::Builder::new()
+                    .name(format!("lua-pool-{index}"))
+                    .spawn({
+                        let init = Arc::clone(&init);
+                        let shared = Arc::clone(&shared);
+                        let results = Arc::clone(&results);
+                        let ready = ready_tx.clone();
+                        move || run_worker(&*init, &shared, &results, &ready)
+                    })
+                    .expect("failed to spawn scheduler pool worker");
+                Worker {
+                    shared,
+                    thread: Some(thread),
+                }
+            })
+            .collect();
+
+        // NOTE: Dropping the pool on error stops any workers that did start
+        let pool = Self {
+            workers,
+            strategy: PoolStrategy::default(),
+            next_worker: AtomicUsize::new(0),
+            next_id: AtomicU64::new(0),
+            results,
+        };
+        drop(ready_tx);
+        for ready in ready_rx.iter().take(size) {
+            ready?;
+        }
+
+        Ok(pool)
+    }
+
+    /**
+        Sets the strategy used for distributing jobs across workers.
+
+        The default strategy is [`PoolStrategy::RoundRobin`].
+    */
+    #[must_use]
+    pub fn with_strategy(mut self, strategy: PoolStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /**
+        Returns the strategy used for distributing jobs across workers.
+    */
+    #[must_use]
+    pub fn strategy(&self) -> PoolStrategy {
+        self.strategy
+    }
+
+    /**
+        Returns the number of workers in the pool.
+    */
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    /**
+        Returns the number of unfinished jobs for each worker in the pool.
+    */
+    #[must_use]
+    pub fn loads(&self) -> Vec<usize> {
+        self.workers
+            .iter()
+            .map(|worker| worker.shared.load.load(Ordering::Acquire))
+            .collect()
+    }
+
+    /**
+        Pushes precompiled Luau bytecode to a worker, to be run with the given arguments.
+
+        The given name is used as the chunk name in error messages and tracebacks.
+
+        # Errors
+
+        Errors if the arguments could not be serialized, or if the chosen worker has stopped.
+    */
+    pub fn push_bytecode(
+        &self,
+        name: impl Into<String>,
+        bytecode: impl Into<Vec<u8>>,
+        args: impl Serialize,
+    ) -> LuaResult<PoolJobId> {
+        self.push(RemoteWork::Bytecode {
+            name: name.into(),
+            bytecode: bytecode.into(),
+            args: serialize_args(args)?,
+        })
+    }
+
+    /**
+        Pushes a call to a function registered in the init function of the pool
+        using [`Scheduler::register_function`], to be run with the given arguments.
+
+        # Errors
+
+        Errors if the arguments could not be serialized, or if the chosen worker has stopped.
+    */
+    pub fn push_registered(
+        &self,
+        name: impl Into<String>,
+        args: impl Serialize,
+    ) -> LuaResult<PoolJobId> {
+        self.push(RemoteWork::Registered {
+            name: name.into(),
+            args: serialize_args(args)?,
+        })
+    }
+
+    /**
+        Takes the result of the job with the given id out of the pool, if it has finished.
+
+        Results may only be taken once, any subsequent calls will return `None`.
+
+        # Errors
+
+        Errors if the job errored, or if its result could not be deserialized.
+    */
+    pub fn take_result<T: DeserializeOwned>(&self, id: PoolJobId) -> Option<LuaResult<T>> {
+        self.results.take(id).map(deserialize_result)
+    }
+
+    /**
+        Waits for the job with the given id to finish, and then takes its result out of the pool.
+
+        Note that this will wait forever if the result has already been taken.
+
+        # Errors
+
+        Errors if the job errored, or if its result could not be deserialized.
+    */
+    pub async fn wait_for_result<T: DeserializeOwned>(&self, id: PoolJobId) -> LuaResult<T> {
+        loop {
+            if let Some(result) = self.take_result(id) {
+                return result;
+            }
+            let listener = self.results.event.listen();
+            // NOTE: Need to check again, the job could have
+            // finished while we were creating our listener
+            if let Some(result) = self.take_result(id) {
+                return result;
+            }
+            listener.await;
+        }
+    }
+
+    fn push(&self, work: RemoteWork) -> LuaResult<PoolJobId> {
+        let id = PoolJobId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let worker = &self.workers[self.pick_worker()].shared;
+        worker.load.fetch_add(1, Ordering::AcqRel);
+        if worker.queue.push((id, work)).is_err() {
+            worker.load.fetch_sub(1, Ordering::AcqRel);
+            return Err(LuaError::runtime(ERR_WORKER_GONE));
+        }
+        worker.event.notify(usize::MAX);
+        Ok(id)
+    }
+
+    fn pick_worker(&self) -> usize {
+        match self.strategy {
+            PoolStrategy::RoundRobin => {
+                self.next_worker.fetch_add(1, Ordering::Relaxed) % self.workers.len()
+            }
+            PoolStrategy::LeastLoaded => self
+                .workers
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, worker)| worker.shared.load.load(Ordering::Acquire))
+                .map_or(0, |(index, _)| index),
+        }
+    }
+}
+
+impl Drop for SchedulerPool {
+    fn drop(&mut self) {
+        for worker in &self.workers {
+            worker.shared.queue.close();
+            worker.shared.event.notify(usize::MAX);
+        }
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for SchedulerPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SchedulerPool")
+            .field("strategy", &self.strategy)
+            .field("loads", &self.loads())
+            .finish_non_exhaustive()
+    }
+}
+
+/**
+    Runs a single worker of a pool, until the pool is dropped and all of its jobs have finished.
+*/
+fn run_worker(
+    init: &PoolInit,
+    shared: &WorkerShared,
+    results: &PoolResults,
+    ready: &mpsc::Sender<LuaResult<()>>,
+) {
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    if let Err(e) = init(&lua, &sched) {
+        shared.queue.close();
+        let _ = ready.send(Err(e));
+        return;
+    }
+    let _ = ready.send(Ok(()));
+
+    let jobs = Rc::new(RefCell::new(FxHashMap::<ThreadId, PoolJobId>::default()));
+    // NOTE: Load must be decremented before the result is visible,
+    // so that the pool never observes a finished job as unfinished
+    let finish = |job: PoolJobId, result: LuaResult<Value>| {
+        shared.load.fetch_sub(1, Ordering::AcqRel);
+        results.insert(job, result);
+    };
+    let mut completed = pin!(sched.results_stream());
+
+    // Keep the scheduler alive while taking in jobs, until the pool closes our queue
+    let keep_alive = sched.keep_alive();
+    let intake = async {
+        let _keep_alive = keep_alive;
+        loop {
+            while let Ok((job, work)) = shared.queue.pop() {
+                let pushed = sched
+                    .resolve_remote(work)
+                    .and_then(|(function, args)| sched.push_thread_back(function, args));
+                match pushed {
+                    Ok(id) => {
+                        jobs.borrow_mut().insert(id, job);
+                    }
+                    Err(e) => finish(job, Err(e)),
+                }
+            }
+            if shared.queue.is_closed() {
+                break;
+            }
+            let listener = shared.event.listen();
+            // NOTE: Need to check again, we could have gotten
+            // new jobs or been closed while creating our listener
+            if shared.queue.is_empty() && !shared.queue.is_closed() {
+                listener.await;
+            }
+        }
+    };
+    let outputs = async {
+        while let Some((id, result)) = completed.next().await {
+            if let Some(job) = jobs.borrow_mut().remove(&id) {
+                finish(
+                    job,
+                    result.and_then(|values| serialize_values(&lua, values)),
+                );
+            }
+        }
+    };
+    block_on(sched.run().or(async {
+        future::zip(intake, outputs).await;
+    }));
+
+    // The scheduler may complete before the last results were read from the stream
+    while let Some(Some((id, result))) = block_on(future::poll_once(completed.next())) {
+        if let Some(job) = jobs.borrow_mut().remove(&id) {
+            finish(
+                job,
+                result.and_then(|values| serialize_values(&lua, values)),
+            );
+        }
+    }
+}
+
+fn serialize_values(lua: &Lua, values: LuaMultiValue) -> LuaResult<Value> {
+    let mut values = values.into_vec();
+    match values.len() {
+        0 => Ok(Value::Unit),
+        1 => lua.from_value(values.pop().unwrap()),
+        _ => values
+            .into_iter()
+            .map(|value| lua.from_value(value))
+            .collect::<LuaResult<_>>()
+            .map(Value::Seq),
+    }
+}
+
+fn deserialize_result<T: DeserializeOwned>(result: LuaResult<Value>) -> LuaResult<T> {
+    result?
+        .deserialize_into()
+        .map_err(|e| LuaError::DeserializeError(e.to_string()))
+}
@@ -0,0 +1,41 @@
+use crate::thread_id::ThreadId;
+
+/**
+    What a Lua thread held by a scheduler is currently waiting for, see [`ThreadSnapshot`].
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ThreadState {
+    /// Queued with a high priority, to be resumed before any other queued threads.
+    Prioritized,
+    /// Spawned, and queued to be resumed.
+    Spawned,
+    /// Deferred, and queued to be resumed after all spawned threads.
+    Deferred,
+    /// Scheduled to be resumed once its delay has elapsed.
+    Delayed,
+    /// Queued to be resumed once the scheduler has no other work to do.
+    Idle,
+    /// Waiting for the next tick fired by the host.
+    WaitingForTick,
+    /// Waiting for a future, such as an async function, to complete.
+    WaitingOnFuture,
+    /// Suspended, and will not be resumed until it is unsuspended.
+    Suspended,
+}
+
+/**
+    A Lua thread currently held by a scheduler, see [`Scheduler::snapshot`].
+
+    [`Scheduler::snapshot`]: crate::Scheduler::snapshot
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadSnapshot {
+    /// The id of the thread.
+    pub id: ThreadId,
+    /// What the thread is currently waiting for.
+    pub state: ThreadState,
+    /// The name of the thread, if it has one.
+    pub name: Option<String>,
+    /// The tag of the thread, if it has one.
+    pub tag: Option<String>,
+}
@@ -1,7 +1,18 @@
+use std::{
+    fmt,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
 use futures_lite::StreamExt;
-use mlua::prelude::*;
+use mlua::{prelude::*, Compiler};
 use tracing::instrument;
 
+use crate::{
+    error_callback::ThreadErrorCallback, key_pool::RegistryKeyPool, result_map::ThreadResultMap,
+    stats::Stats, thread_id::ThreadId,
+};
+
 /**
     Runs a Lua thread until it manually yields (using coroutine.yield), errors, or completes.
 
@@ -37,8 +48,35 @@ pub(crate) async fn run_until_yield<'lua>(
 pub(crate) fn is_poll_pending(value: &LuaValue) -> bool {
     value
         .as_light_userdata()
-        .map(|l| l == Lua::poll_pending())
-        .unwrap_or_default()
+        .is_some_and(|l| l == Lua::poll_pending())
+}
+
+/**
+    How far into the future an instant is placed for durations too long to represent,
+    such as when waiting for `math.huge` seconds, which is long enough to never be reached.
+*/
+const FAR_FUTURE: Duration = Duration::from_secs(u32::MAX as u64);
+
+/**
+    Converts a number of seconds from Lua into a [`Duration`].
+
+    Negative and NaN values become zero, and values too large
+    to represent, such as `math.huge`, saturate instead of panicking.
+*/
+pub(crate) fn duration_from_secs(secs: f64) -> Duration {
+    Duration::try_from_secs_f64(secs.max(0.0)).unwrap_or(Duration::MAX)
+}
+
+/**
+    Returns the [`Instant`] that is the given duration from now.
+
+    Durations too long to represent as an instant saturate to an instant that is never reached.
+*/
+pub(crate) fn instant_after(duration: Duration) -> Instant {
+    let now = Instant::now();
+    now.checked_add(duration)
+        .or_else(|| now.checked_add(FAR_FUTURE))
+        .unwrap_or(now)
 }
 
 /**
@@ -62,7 +100,38 @@ impl ThreadResult {
         }
     }
 
-    pub fn value(self, lua: &Lua) -> LuaResult<LuaMultiValue> {
+    /**
+        Returns `true` if this result holds a value in the Lua registry.
+    */
+    pub fn holds_value(&self) -> bool {
+        self.inner.is_ok()
+    }
+
+    /**
+        Returns a copy of the value of this result, without taking it out of the Lua registry.
+    */
+    pub fn peek<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaMultiValue<'lua>> {
+        match &self.inner {
+            Ok(key) => Ok(LuaMultiValue::from_vec(lua.registry_value(key)?)),
+            Err(e) => Err(e.clone()),
+        }
+    }
+
+    /**
+        Creates a result from a value that was previously stored in the Lua registry.
+    */
+    pub fn from_inner(inner: LuaResult<LuaRegistryKey>) -> Self {
+        Self { inner }
+    }
+
+    /**
+        Returns the inner result, without taking its value out of the Lua registry.
+    */
+    pub fn into_inner(self) -> LuaResult<LuaRegistryKey> {
+        self.inner
+    }
+
+    pub fn value(self, lua: &Lua) -> LuaResult<LuaMultiValue<'_>> {
         match self.inner {
             Ok(key) => {
                 let vec = lua.registry_value(&key).unwrap();
@@ -74,42 +143,129 @@ impl ThreadResult {
     }
 }
 
+/**
+    Arguments for a [`LuaThread`] that are created lazily, right before the thread is resumed.
+*/
+pub(crate) type LazyArgs = Box<dyn for<'lua> FnOnce(&'lua Lua) -> LuaResult<LuaMultiValue<'lua>>>;
+
+/**
+    Arguments for a [`LuaThread`], either stored in the Lua registry or created lazily.
+*/
+enum StoredArgs {
+    Values(LuaRegistryKey),
+    Lazy(LazyArgs),
+}
+
+impl fmt::Debug for StoredArgs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Values(key) => f.debug_tuple("Values").field(key).finish(),
+            Self::Lazy(_) => f.debug_tuple("Lazy").finish(),
+        }
+    }
+}
+
 /**
     Representation of a [`LuaThread`] with its associated arguments currently stored in the Lua registry.
+
+    Registry keys are taken from, and returned to, the [`RegistryKeyPool`] of the queue storing the
+    thread, so that queueing lots of threads does not need to create lots of registry values.
 */
 #[derive(Debug)]
 pub(crate) struct ThreadWithArgs {
+    id: ThreadId,
     key_thread: LuaRegistryKey,
-    key_args: LuaRegistryKey,
+    args: StoredArgs,
 }
 
 impl ThreadWithArgs {
     pub fn new<'lua>(
         lua: &'lua Lua,
+        pool: &RegistryKeyPool,
         thread: LuaThread<'lua>,
         args: LuaMultiValue<'lua>,
     ) -> LuaResult<Self> {
         let argsv = args.into_vec();
 
-        let key_thread = lua.create_registry_value(thread)?;
-        let key_args = lua.create_registry_value(argsv)?;
+        let id = ThreadId::from(&thread);
+        let key_thread = pool.store(lua, thread)?;
+        let key_args = pool.store(lua, argsv)?;
+
+        Ok(Self {
+            id,
+            key_thread,
+            args: StoredArgs::Values(key_args),
+        })
+    }
+
+    pub fn new_lazy<'lua>(
+        lua: &'lua Lua,
+        pool: &RegistryKeyPool,
+        thread: LuaThread<'lua>,
+        args: LazyArgs,
+    ) -> LuaResult<Self> {
+        let id = ThreadId::from(&thread);
+        let key_thread = pool.store(lua, thread)?;
 
         Ok(Self {
+            id,
             key_thread,
-            key_args,
+            args: StoredArgs::Lazy(args),
         })
     }
 
-    pub fn into_inner(self, lua: &Lua) -> (LuaThread<'_>, LuaMultiValue<'_>) {
-        let thread = lua.registry_value(&self.key_thread).unwrap();
-        let argsv = lua.registry_value(&self.key_args).unwrap();
+    /**
+        Returns the id of the stored thread.
+    */
+    pub fn thread_id(&self) -> ThreadId {
+        self.id
+    }
 
-        let args = LuaMultiValue::from_vec(argsv);
+    /**
+        Takes the thread and its arguments back out of the Lua registry.
+
+        Lazy arguments are only created if the thread can still be resumed. If creating
+        them fails, the error is reported just like an error in the thread itself would
+        be, any tracked result for the thread is set to the error, and `None` is returned.
+    */
+    pub fn into_inner<'lua>(
+        self,
+        lua: &'lua Lua,
+        pool: &RegistryKeyPool,
+    ) -> Option<(LuaThread<'lua>, LuaMultiValue<'lua>)> {
+        let thread: LuaThread = pool.take(lua, self.key_thread).unwrap();
 
-        lua.remove_registry_value(self.key_thread).unwrap();
-        lua.remove_registry_value(self.key_args).unwrap();
+        let args = match self.args {
+            StoredArgs::Values(key_args) => {
+                let argsv = pool.take(lua, key_args).unwrap();
+                LuaMultiValue::from_vec(argsv)
+            }
+            StoredArgs::Lazy(_) if thread.status() != LuaThreadStatus::Resumable => {
+                // NOTE: Thread was cancelled before it could run, and will be
+                // skipped anyway, so there is no need to create its arguments
+                LuaMultiValue::new()
+            }
+            StoredArgs::Lazy(args) => match args(lua) {
+                Ok(args) => args,
+                Err(e) => {
+                    let error_callback =
+                        lua.app_data_ref::<ThreadErrorCallback>().map(|c| c.clone());
+                    if let Some(error_callback) = error_callback {
+                        error_callback.call_thread(lua, &thread, &e);
+                    }
+                    if let Some(stats) = lua.app_data_ref::<Stats>() {
+                        stats.record_errored();
+                    }
+                    let result_map = lua.app_data_ref::<ThreadResultMap>().map(|m| m.clone());
+                    if let Some(result_map) = result_map {
+                        result_map.complete(lua, ThreadId::from(&thread), Err(e));
+                    }
+                    return None;
+                }
+            },
+        };
 
-        (thread, args)
+        Some((thread, args))
     }
 }
 
@@ -146,3 +302,40 @@ impl<'lua> FromLua<'lua> for LuaThreadOrFunction<'lua> {
         }
     }
 }
+
+/**
+    A Lua chunk that is only ever compiled once, and then cached for the rest of the program.
+
+    Compiled bytecode does not depend on the Lua state, so this is safe to share
+    across many Lua states, and there is no need to invalidate the cache.
+*/
+pub(crate) struct CachedChunk {
+    name: &'static str,
+    source: &'static str,
+    bytecode: OnceLock<Vec<u8>>,
+}
+
+impl CachedChunk {
+    pub const fn new(name: &'static str, source: &'static str) -> Self {
+        Self {
+            name,
+            source,
+            bytecode: OnceLock::new(),
+        }
+    }
+
+    /**
+        Loads the chunk into the given Lua state as a function, using the given environment.
+
+        This will compile the chunk the first time it is loaded, and reuse the bytecode afterwards.
+    */
+    pub fn load<'lua>(&self, lua: &'lua Lua, env: LuaTable<'lua>) -> LuaResult<LuaFunction<'lua>> {
+        let bytecode = self
+            .bytecode
+            .get_or_init(|| Compiler::new().compile(self.source));
+        lua.load(bytecode.as_slice())
+            .set_name(self.name)
+            .set_environment(env)
+            .into_function()
+    }
+}
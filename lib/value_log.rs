@@ -0,0 +1,138 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    rc::Rc,
+    time::SystemTime,
+};
+
+use mlua::prelude::*;
+
+use crate::thread_id::ThreadId;
+
+/**
+    The maximum length of a single stringified value, in bytes.
+*/
+const MAX_VALUE_LEN: usize = 256;
+
+/**
+    How values were passed out of a Lua thread, see [`ValueRecord`].
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ValueKind {
+    /// The thread yielded the values, and may be resumed again.
+    Yielded,
+    /// The thread returned the values, and has completed.
+    Returned,
+}
+
+/**
+    A record of the values that a Lua thread yielded or returned, see [`Scheduler::recent_values`].
+
+    With the `serde` feature enabled, records are serializable, and may be dumped for post-mortem analysis.
+
+    [`Scheduler::recent_values`]: crate::Scheduler::recent_values
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ValueRecord {
+    /// The id of the thread that passed out the values.
+    pub thread: ThreadId,
+    /// Whether the values were yielded or returned.
+    pub kind: ValueKind,
+    /// The stringified values, each truncated to a maximum length.
+    pub values: Vec<String>,
+    /// When the values were passed out of the thread.
+    pub recorded_at: SystemTime,
+}
+
+/**
+    Bounded log of values passed out of tracked Lua threads.
+
+    Values are only recorded in debug builds, once a capacity has been set,
+    and the oldest records are discarded once the capacity has been reached.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct ValueLog {
+    capacity: Rc<Cell<usize>>,
+    records: Rc<RefCell<VecDeque<ValueRecord>>>,
+}
+
+impl ValueLog {
+    pub fn new() -> Self {
+        Self {
+            capacity: Rc::new(Cell::new(0)),
+            records: Rc::new(RefCell::new(VecDeque::new())),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity.get()
+    }
+
+    pub fn set_capacity(&self, capacity: usize) {
+        self.capacity.set(capacity);
+        let mut records = self.records.borrow_mut();
+        while records.len() > capacity {
+            records.pop_front();
+        }
+    }
+
+    pub fn clear(&self) {
+        self.records.borrow_mut().clear();
+    }
+
+    /**
+        Records the values passed out of the given thread, if values are being recorded.
+    */
+    pub fn record(&self, id: ThreadId, kind: ValueKind, values: &LuaMultiValue) {
+        let capacity = self.capacity.get();
+        if !cfg!(debug_assertions) || capacity == 0 {
+            return;
+        }
+
+        let record = ValueRecord {
+            thread: id,
+            kind,
+            values: values.iter().map(stringify).collect(),
+            recorded_at: SystemTime::now(),
+        };
+
+        let mut records = self.records.borrow_mut();
+        if records.len() >= capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /**
+        Returns up to `n` of the most recent records, newest first.
+    */
+    pub fn recent(&self, n: usize) -> Vec<ValueRecord> {
+        self.records
+            .borrow()
+            .iter()
+            .rev()
+            .take(n)
+            .cloned()
+            .collect()
+    }
+}
+
+/**
+    Stringifies a Lua value the same way `tostring` would, truncated to [`MAX_VALUE_LEN`].
+*/
+fn stringify(value: &LuaValue) -> String {
+    let mut s = value
+        .to_string()
+        .unwrap_or_else(|_| format!("<{}>", value.type_name()));
+    if s.len() > MAX_VALUE_LEN {
+        let mut end = MAX_VALUE_LEN;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        s.truncate(end);
+        s.push_str("...");
+    }
+    s
+}
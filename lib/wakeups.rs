@@ -0,0 +1,108 @@
+use std::{
+    cell::Cell,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+/**
+    Statistics about how often a scheduler woke up, and how long it spent parked.
+
+    While waiting for work, the scheduler parks on a single future that combines the events
+    of its queues, timers, and executors, which is only polled again once one of them wakes
+    it up - when run using a blocking executor, such as `async_io::block_on`, this parks the
+    OS thread until the nearest timer expires or an event is triggered, without spinning.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WakeupStats {
+    /// The number of times the scheduler woke up, including wakeups to exit or stop.
+    pub wakeups: usize,
+    /// The number of times the scheduler woke up without finding any work to do.
+    pub empty_wakeups: usize,
+    /// The total amount of time spent parked, waiting for work.
+    ///
+    /// This ends as soon as the scheduler wakes up, and does not include any time spent running threads.
+    pub time_parked: Duration,
+}
+
+/**
+    Tracker for scheduler wakeups, see [`WakeupStats`].
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct Wakeups {
+    stats: Rc<Cell<WakeupStats>>,
+    parked_at: Rc<Cell<Option<Instant>>>,
+}
+
+impl Wakeups {
+    pub fn new() -> Self {
+        Self {
+            stats: Rc::new(Cell::new(WakeupStats::default())),
+            parked_at: Rc::new(Cell::new(None)),
+        }
+    }
+
+    pub fn stats(&self) -> WakeupStats {
+        self.stats.get()
+    }
+
+    /**
+        Marks the scheduler as parked, waiting for work.
+    */
+    pub fn park(&self) {
+        self.parked_at.set(Some(Instant::now()));
+    }
+
+    /**
+        Records a wakeup, if the scheduler is currently parked.
+    */
+    pub fn wake(&self) {
+        if let Some(parked_at) = self.parked_at.take() {
+            let mut stats = self.stats.get();
+            stats.wakeups += 1;
+            stats.time_parked += parked_at.elapsed();
+            self.stats.set(stats);
+        }
+    }
+
+    /**
+        Records that the last wakeup did not find any work to do.
+    */
+    pub fn record_empty(&self) {
+        let mut stats = self.stats.get();
+        stats.empty_wakeups += 1;
+        self.stats.set(stats);
+    }
+
+    /**
+        Wraps the given future, recording a wakeup before it is polled while the scheduler is parked.
+
+        Futures on the executors of the scheduler run while it waits for work, so this is
+        what ends the time spent parked once they are woken, instead of when they are done.
+    */
+    pub fn waking<F: Future>(&self, fut: F) -> WakingFuture<F> {
+        WakingFuture {
+            inner: Box::pin(fut),
+            wakeups: self.clone(),
+        }
+    }
+}
+
+/**
+    A future that records a wakeup of the scheduler before it is polled, see [`Wakeups::waking`].
+*/
+pub(crate) struct WakingFuture<F> {
+    inner: Pin<Box<F>>,
+    wakeups: Wakeups,
+}
+
+impl<F: Future> Future for WakingFuture<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.wakeups.wake();
+        self.inner.as_mut().poll(cx)
+    }
+}
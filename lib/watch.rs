@@ -0,0 +1,125 @@
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    rc::Rc,
+};
+
+use event_listener::Event;
+use mlua::prelude::*;
+
+struct WatchState<T> {
+    value: RefCell<T>,
+    version: Cell<u64>,
+    changed: Event,
+}
+
+/**
+    An observable value, shared between Rust and Lua.
+
+    Rust sets the value using [`Watch::set`], and Lua threads may read the current value using
+    `watch:get()`, or wait for the next change using `watch:changed()`, which yields through the
+    scheduler without polling, and returns the new value. This is useful for configuration updates
+    and reactive game state, since scripts can simply wait for changes instead of polling for them.
+
+    Waiting threads read the value once they are resumed, meaning that multiple changes within a
+    single scheduler cycle are coalesced, and all threads that were waiting for a change observe the
+    same, latest value - intermediate values that were replaced before threads could resume are never seen.
+
+    Watches are passed to Lua as userdata, and cloning a watch creates another handle to the same value.
+    Note that `watch:changed()` may only be called from threads that are run by a [`Scheduler`].
+
+    [`Scheduler`]: crate::Scheduler
+*/
+pub struct Watch<T> {
+    state: Rc<WatchState<T>>,
+}
+
+impl<T: Clone> Watch<T> {
+    /**
+        Creates a new watch with the given initial value.
+    */
+    #[must_use]
+    pub fn new(value: T) -> Self {
+        Self {
+            state: Rc::new(WatchState {
+                value: RefCell::new(value),
+                version: Cell::new(0),
+                changed: Event::new(),
+            }),
+        }
+    }
+
+    /**
+        Returns a copy of the current value.
+    */
+    #[must_use]
+    pub fn get(&self) -> T {
+        self.state.value.borrow().clone()
+    }
+
+    /**
+        Sets the value, waking up any Lua threads waiting for a change.
+    */
+    pub fn set(&self, value: T) {
+        self.state.value.replace(value);
+        self.state.version.set(self.state.version.get() + 1);
+        self.state.changed.notify(usize::MAX);
+    }
+
+    /**
+        Returns the number of times that the value has been set.
+    */
+    #[must_use]
+    pub fn version(&self) -> u64 {
+        self.state.version.get()
+    }
+
+    /**
+        Waits until the value is set, and returns the latest value.
+    */
+    pub async fn changed(&self) -> T {
+        let version = self.version();
+        loop {
+            if self.version() != version {
+                return self.get();
+            }
+            // NOTE: We must listen before checking again, or we
+            // may miss a notification that happened in between
+            let listener = self.state.changed.listen();
+            if self.version() != version {
+                return self.get();
+            }
+            listener.await;
+        }
+    }
+}
+
+impl<T> Clone for Watch<T> {
+    fn clone(&self) -> Self {
+        Self {
+            state: Rc::clone(&self.state),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Watch<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watch")
+            .field("value", &*self.state.value.borrow())
+            .field("version", &self.state.version.get())
+            .finish()
+    }
+}
+
+impl<T> LuaUserData for Watch<T>
+where
+    T: for<'lua> IntoLua<'lua> + Clone + 'static,
+{
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("get", |_, this, ()| Ok(this.get()));
+        methods.add_async_function("changed", |_, this: LuaAnyUserData| {
+            let watch = this.borrow::<Self>().map(|this| this.clone());
+            async move { Ok(watch?.changed().await) }
+        });
+    }
+}
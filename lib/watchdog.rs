@@ -0,0 +1,142 @@
+#![allow(clippy::module_name_repetitions)]
+
+use std::{
+    cell::{Cell, RefCell},
+    future::Future,
+    rc::Rc,
+    time::Duration,
+};
+
+use futures_lite::FutureExt;
+use mlua::prelude::*;
+
+use crate::{clock::Clock, thread_id::ThreadId, util::instant_after};
+
+type StuckTaskCallback = Box<dyn Fn(StuckTask) + Send + 'static>;
+
+/**
+    What the watchdog should do with tasks that exceed their maximum lifetime.
+
+    See [`Scheduler::set_watchdog`] for more information.
+
+    [`Scheduler::set_watchdog`]: crate::Scheduler::set_watchdog
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WatchdogPolicy {
+    /// Only report the task, and let it keep running.
+    #[default]
+    Log,
+    /// Report the task and cancel it, resuming any waiting Lua thread with a timeout error.
+    Cancel,
+}
+
+/**
+    A task that exceeded the maximum lifetime configured for the watchdog.
+
+    See [`Scheduler::set_watchdog`] for more information.
+
+    [`Scheduler::set_watchdog`]: crate::Scheduler::set_watchdog
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StuckTask {
+    /// How long the task had been running for when it was reported.
+    pub lifetime: Duration,
+    /// The Lua thread waiting for the task, or that spawned it.
+    pub thread: Option<ThreadId>,
+    /// If the task was cancelled.
+    pub cancelled: bool,
+}
+
+/**
+    Watchdog for tasks that never complete, such as futures stuck on host I/O.
+
+    Tasks are only watched if a maximum lifetime was configured when they were started.
+*/
+#[derive(Clone)]
+pub(crate) struct Watchdog {
+    clock: Clock,
+    config: Rc<Cell<Option<(Duration, WatchdogPolicy)>>>,
+    callback: Rc<RefCell<Option<StuckTaskCallback>>>,
+}
+
+impl Watchdog {
+    pub fn new(clock: Clock) -> Self {
+        Self {
+            clock,
+            config: Rc::new(Cell::new(None)),
+            callback: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn replace(&self, max_lifetime: Duration, policy: WatchdogPolicy) {
+        self.config.set(Some((max_lifetime, policy)));
+    }
+
+    pub fn replace_callback(&self, callback: impl Fn(StuckTask) + Send + 'static) {
+        self.callback.borrow_mut().replace(Box::new(callback));
+    }
+
+    pub fn config(&self) -> Option<(Duration, WatchdogPolicy)> {
+        self.config.get()
+    }
+
+    pub fn clear(&self) {
+        self.config.set(None);
+        self.callback.borrow_mut().take();
+    }
+
+    /**
+        Creates the error that threads waiting on a cancelled task are resumed with.
+    */
+    pub fn timeout_error(lifetime: Duration) -> LuaError {
+        LuaError::runtime(format!("task timed out after {lifetime:?}"))
+    }
+
+    fn report(&self, task: StuckTask) {
+        tracing::warn!(
+            lifetime = ?task.lifetime,
+            thread = ?task.thread,
+            cancelled = task.cancelled,
+            "stuck task detected"
+        );
+        if let Some(callback) = &*self.callback.borrow() {
+            callback(task);
+        }
+    }
+
+    /**
+        Watches the given future, reporting it if it exceeds the maximum lifetime.
+
+        Returns `Err` with the lifetime of the future if it was cancelled.
+    */
+    pub async fn watch<F: Future>(
+        &self,
+        fut: F,
+        thread: Option<ThreadId>,
+    ) -> Result<F::Output, Duration> {
+        let Some((max_lifetime, policy)) = self.config.get() else {
+            return Ok(fut.await);
+        };
+
+        let mut fut = std::pin::pin!(fut);
+        let fut_expired = async {
+            self.clock.wait_until(instant_after(max_lifetime)).await;
+            None
+        };
+        if let Some(output) = async { Some(fut.as_mut().await) }.or(fut_expired).await {
+            return Ok(output);
+        }
+
+        let cancelled = policy == WatchdogPolicy::Cancel;
+        self.report(StuckTask {
+            lifetime: max_lifetime,
+            thread,
+            cancelled,
+        });
+        if cancelled {
+            Err(max_lifetime)
+        } else {
+            Ok(fut.await)
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::prelude::*;
+
+use crate::thread_id::ThreadId;
+
+pub(crate) type YieldHandler = Box<
+    dyn for<'lua> Fn(
+        &'lua Lua,
+        ThreadId,
+        LuaMultiValue<'lua>,
+    ) -> LuaResult<Option<LuaMultiValue<'lua>>>,
+>;
+
+#[derive(Clone)]
+pub(crate) struct ThreadYieldHandler {
+    inner: Rc<RefCell<Option<YieldHandler>>>,
+}
+
+impl ThreadYieldHandler {
+    pub fn new() -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn replace(
+        &self,
+        handler: impl for<'lua> Fn(
+                &'lua Lua,
+                ThreadId,
+                LuaMultiValue<'lua>,
+            ) -> LuaResult<Option<LuaMultiValue<'lua>>>
+            + 'static,
+    ) {
+        self.inner.borrow_mut().replace(Box::new(handler));
+    }
+
+    pub fn clear(&self) {
+        self.inner.borrow_mut().take();
+    }
+
+    /**
+        Calls the yield handler, if any, returning the arguments to resume the thread with.
+    */
+    pub fn call<'lua>(
+        &self,
+        lua: &'lua Lua,
+        id: ThreadId,
+        values: LuaMultiValue<'lua>,
+    ) -> LuaResult<Option<LuaMultiValue<'lua>>> {
+        match &*self.inner.borrow() {
+            Some(handler) => handler(lua, id, values),
+            None => Ok(None),
+        }
+    }
+}